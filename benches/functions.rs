@@ -17,9 +17,23 @@ fn church(c: &mut Criterion) {
     );
 }
 
+fn builtin_access(c: &mut Criterion) {
+    let expect = |term| matches!(term, Term::Bool(true));
+    bench_expect(
+        "builtin access 10000",
+        env!("CARGO_MANIFEST_DIR"),
+        "functions/builtins",
+        None,
+        10000,
+        EvalMode::Normal,
+        expect,
+        c,
+    );
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
-    targets = church
+    targets = church, builtin_access
 }
 criterion_main!(benches);
@@ -0,0 +1,25 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use nickel::term::Term;
+use pprof::criterion::{Output, PProfProfiler};
+use utilities::{bench_expect, EvalMode};
+
+fn regex_filter(c: &mut Criterion) {
+    let expect = |term| matches!(term, Term::Num(n) if n == 2000.0);
+    bench_expect(
+        "regex filter 10000",
+        env!("CARGO_MANIFEST_DIR"),
+        "strings/regex",
+        None,
+        10000,
+        EvalMode::Normal,
+        expect,
+        c,
+    );
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = regex_filter
+}
+criterion_main!(benches);
@@ -0,0 +1,268 @@
+//! Generate arbitrary well-scoped Nickel expressions, then parse, typecheck and fully evaluate
+//! them, which must result either in a value or in an error. The evaluation is bounded by a step
+//! limit, such that diverging programs are reported as errors.
+//!
+//! Expressions are generated structurally and printed as Nickel source, such that they go through
+//! the parser and the program transformations before being evaluated, as real programs do.
+#![no_main]
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+use nickel::cache::{Cache, GlobalEnv};
+use nickel::eval::{self, EvalConfig};
+use std::fmt::{self, Write};
+
+const MAX_STEPS: usize = 100_000;
+
+thread_local! {
+    // Preparing the standard library is costly: it is done once and for all, and each input is
+    // then evaluated in a fresh copy of the resulting cache, such that the files added by the
+    // previous inputs don't accumulate.
+    static STDLIB: (Cache, GlobalEnv) = {
+        let mut cache = Cache::new();
+        let global_env = cache
+            .prepare_stdlib()
+            .expect("fuzz: failed to load the standard library");
+        (cache, global_env)
+    };
+}
+
+/// A field name.
+#[derive(Arbitrary, Debug, Clone, Copy)]
+enum Field {
+    A,
+    B,
+    C,
+}
+
+/// A unary operator, or a builtin function of one argument.
+#[derive(Arbitrary, Debug, Clone, Copy)]
+enum UnOp {
+    Not,
+    Neg,
+    IsNum,
+    IsStr,
+    Length,
+    Head,
+    Tail,
+    Fields,
+    StrLength,
+    ToStr,
+    DeepSeq,
+}
+
+/// A binary operator.
+#[derive(Arbitrary, Debug, Clone, Copy)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Lt,
+    And,
+    Or,
+    ListConcat,
+    StrConcat,
+    Merge,
+    ElemAt,
+    Map,
+    Pipe,
+}
+
+/// An expression. Variables are represented by an index into the variables in scope, such that
+/// generated expressions are always well-scoped.
+#[derive(Arbitrary, Debug)]
+enum Expr {
+    Null,
+    Bool(bool),
+    Num(i8),
+    Str(Field),
+    Var(u8),
+    Let(Box<Expr>, Box<Expr>),
+    Fun(Box<Expr>),
+    App(Box<Expr>, Box<Expr>),
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    List(Vec<Expr>),
+    Record(Vec<(Field, Expr)>),
+    Access(Box<Expr>, Field),
+    Default(Box<Expr>),
+    Annot(Box<Expr>, bool),
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Field::A => write!(f, "a"),
+            Field::B => write!(f, "b"),
+            Field::C => write!(f, "c"),
+        }
+    }
+}
+
+impl UnOp {
+    fn name(self) -> &'static str {
+        match self {
+            UnOp::Not => "!",
+            UnOp::Neg => "-",
+            UnOp::IsNum => "builtins.is_num",
+            UnOp::IsStr => "builtins.is_str",
+            UnOp::Length => "lists.length",
+            UnOp::Head => "lists.head",
+            UnOp::Tail => "lists.tail",
+            UnOp::Fields => "records.fields",
+            UnOp::StrLength => "strings.length",
+            UnOp::ToStr => "strings.from_num",
+            UnOp::DeepSeq => "%deep_seq% null",
+        }
+    }
+}
+
+impl BinOp {
+    fn name(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::Eq => "==",
+            BinOp::Lt => "<",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+            BinOp::ListConcat => "@",
+            BinOp::StrConcat => "++",
+            BinOp::Merge => "&",
+            BinOp::ElemAt => "lists.elem_at",
+            BinOp::Map => "lists.map",
+            BinOp::Pipe => "|>",
+        }
+    }
+
+    fn is_infix(self) -> bool {
+        !matches!(self, BinOp::ElemAt | BinOp::Map)
+    }
+}
+
+impl Expr {
+    /// Print the expression as Nickel source, where `depth` is the number of variables in scope.
+    /// Variables are named `x0`, `x1`, etc. in binding order.
+    fn write(&self, out: &mut String, depth: usize) -> fmt::Result {
+        match self {
+            Expr::Null => write!(out, "null"),
+            Expr::Bool(b) => write!(out, "{}", b),
+            Expr::Num(n) => write!(out, "({})", n),
+            Expr::Str(field) => write!(out, "\"{}\"", field),
+            Expr::Var(_) if depth == 0 => write!(out, "null"),
+            Expr::Var(n) => write!(out, "x{}", *n as usize % depth),
+            Expr::Let(bound, body) => {
+                write!(out, "(let x{} = ", depth)?;
+                bound.write(out, depth)?;
+                write!(out, " in ")?;
+                body.write(out, depth + 1)?;
+                write!(out, ")")
+            }
+            Expr::Fun(body) => {
+                write!(out, "(fun x{} => ", depth)?;
+                body.write(out, depth + 1)?;
+                write!(out, ")")
+            }
+            Expr::App(t1, t2) => {
+                write!(out, "(")?;
+                t1.write(out, depth)?;
+                write!(out, " ")?;
+                t2.write(out, depth)?;
+                write!(out, ")")
+            }
+            Expr::If(cond, t1, t2) => {
+                write!(out, "(if ")?;
+                cond.write(out, depth)?;
+                write!(out, " then ")?;
+                t1.write(out, depth)?;
+                write!(out, " else ")?;
+                t2.write(out, depth)?;
+                write!(out, ")")
+            }
+            Expr::Unary(op, t) => {
+                write!(out, "({} ", op.name())?;
+                t.write(out, depth)?;
+                write!(out, ")")
+            }
+            Expr::Binary(op, t1, t2) if op.is_infix() => {
+                write!(out, "(")?;
+                t1.write(out, depth)?;
+                write!(out, " {} ", op.name())?;
+                t2.write(out, depth)?;
+                write!(out, ")")
+            }
+            Expr::Binary(op, t1, t2) => {
+                write!(out, "({} ", op.name())?;
+                t1.write(out, depth)?;
+                write!(out, " ")?;
+                t2.write(out, depth)?;
+                write!(out, ")")
+            }
+            Expr::List(ts) => {
+                write!(out, "[")?;
+                for t in ts {
+                    t.write(out, depth)?;
+                    write!(out, ", ")?;
+                }
+                write!(out, "]")
+            }
+            Expr::Record(fields) => {
+                write!(out, "{{")?;
+                for (field, t) in fields {
+                    write!(out, "{} = ", field)?;
+                    t.write(out, depth)?;
+                    write!(out, ", ")?;
+                }
+                write!(out, "}}")
+            }
+            Expr::Access(t, field) => {
+                write!(out, "(")?;
+                t.write(out, depth)?;
+                write!(out, ").{}", field)
+            }
+            Expr::Default(t) => {
+                write!(out, "(")?;
+                t.write(out, depth)?;
+                write!(out, " | default)")
+            }
+            Expr::Annot(t, is_num) => {
+                write!(out, "(")?;
+                t.write(out, depth)?;
+                write!(out, " | {})", if *is_num { "Num" } else { "Str" })
+            }
+        }
+    }
+}
+
+fuzz_target!(|expr: Expr| {
+    let mut source = String::new();
+    expr.write(&mut source, 0).unwrap();
+
+    let (mut cache, global_env) = STDLIB.with(|(cache, global_env)| {
+        (
+            cache.clone(),
+            GlobalEnv {
+                eval_env: global_env.eval_env.clone(),
+                type_env: global_env.type_env.clone(),
+            },
+        )
+    });
+    let file_id = cache.add_tmp("<fuzz>", source);
+
+    if cache.prepare(file_id, &global_env.type_env).is_err() {
+        return;
+    }
+
+    let config = EvalConfig {
+        max_steps: Some(MAX_STEPS),
+        ..EvalConfig::default()
+    };
+    let term = cache.get_owned(file_id).unwrap();
+    let _ = eval::eval_full_with_config(term, &global_env.eval_env, &mut cache, &config);
+});
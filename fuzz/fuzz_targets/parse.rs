@@ -0,0 +1,12 @@
+//! Feed arbitrary inputs to the parser, which must return either a term or a parse error.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use nickel::cache::Cache;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        let mut cache = Cache::new();
+        let file_id = cache.add_string("<fuzz>", String::from(s));
+        let _ = cache.parse_nocache(file_id);
+    }
+});
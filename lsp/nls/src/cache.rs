@@ -1,16 +1,26 @@
-use std::{collections::HashMap, ffi::OsString, io};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+    io,
+    path::Path,
+};
 
 use codespan::FileId;
+use lsp_types::Url;
 use nickel::{
-    cache::{Cache, CacheError, CacheOp, CachedTerm, EntryState},
+    cache::{normalize_path, Cache, CacheError, CacheOp, CachedTerm, EntryState},
     error::TypecheckError,
+    term::{RichTerm, Term, TraverseMethod},
     typecheck,
 };
 
 use crate::linearization::{completed::Completed, AnalysisHost};
 
 pub trait CacheExt {
+    fn id_of_uri(&self, uri: &Url) -> Option<FileId>;
+    fn uri_of(&self, file_id: FileId) -> Option<Url>;
     fn update_content(&mut self, path: impl Into<OsString>, s: String) -> io::Result<FileId>;
+    fn get_resolved_imports(&self, file_id: FileId) -> HashSet<FileId>;
     fn typecheck_with_analysis(
         &mut self,
         file_id: FileId,
@@ -19,7 +29,35 @@ pub trait CacheExt {
     ) -> Result<CacheOp<()>, CacheError<TypecheckError>>;
 }
 
+/// The name under which a document is stored in the cache. Local files are named after their
+/// normalized path, such that importing a document from another one resolves to the content of
+/// the document, instead of the file on disk. Other documents are named after their URI.
+pub fn name_of_uri(uri: &Url) -> OsString {
+    match uri.to_file_path() {
+        Ok(path) => normalize_path(&path).unwrap_or_else(|_| path.into_os_string()),
+        Err(()) => OsString::from(uri.as_str()),
+    }
+}
+
+fn uri_of_name(name: &OsStr) -> Option<Url> {
+    let path = Path::new(name);
+
+    if path.is_absolute() {
+        Url::from_file_path(path).ok()
+    } else {
+        Url::parse(&name.to_string_lossy()).ok()
+    }
+}
+
 impl CacheExt for Cache {
+    fn id_of_uri(&self, uri: &Url) -> Option<FileId> {
+        self.id_of(name_of_uri(uri))
+    }
+
+    fn uri_of(&self, file_id: FileId) -> Option<Url> {
+        uri_of_name(self.name(file_id))
+    }
+
     fn update_content(&mut self, path: impl Into<OsString>, source: String) -> io::Result<FileId> {
         let path: OsString = path.into();
         if let Some(file_id) = self.id_of(path.clone()) {
@@ -31,6 +69,27 @@ impl CacheExt for Cache {
             Ok(self.add_string(path, source))
         }
     }
+
+    /// The files imported by an entry of the cache whose imports have been resolved.
+    fn get_resolved_imports(&self, file_id: FileId) -> HashSet<FileId> {
+        let mut imports = HashSet::new();
+
+        if let Some(CachedTerm { term, .. }) = self.terms().get(&file_id) {
+            let _: Result<RichTerm, ()> = term.clone().traverse(
+                &mut |rt: RichTerm, imports: &mut HashSet<FileId>| {
+                    if let Term::ResolvedImport(id) = rt.as_ref() {
+                        imports.insert(*id);
+                    }
+                    Ok(rt)
+                },
+                &mut imports,
+                TraverseMethod::TopDown,
+            );
+        }
+
+        imports
+    }
+
     fn typecheck_with_analysis<'a>(
         &mut self,
         file_id: FileId,
@@ -1,14 +1,19 @@
 use std::ops::Range;
 
 use codespan::{FileId, Files};
-use codespan_reporting::diagnostic::{self, Diagnostic};
+use codespan_reporting::diagnostic::{self, Diagnostic, LabelStyle};
 use lsp_types::{NumberOrString, Position};
 
 /// Convert [codespan_reporting::diagnostic::Diagnostic] into a list of another type
 /// Diagnostics tend to contain a list of labels pointing to errors in the code which
-/// we want to extract, hence a list of `Self`
+/// we want to extract, hence a list of `Self`. Only the labels pointing to `file_id`, the
+/// document the diagnostics are published for, are located.
 pub trait DiagnosticCompat: Sized {
-    fn from_codespan(diagnostic: Diagnostic<FileId>, files: &mut Files<String>) -> Vec<Self>;
+    fn from_codespan(
+        file_id: FileId,
+        diagnostic: Diagnostic<FileId>,
+        files: &mut Files<String>,
+    ) -> Vec<Self>;
 }
 
 /// Determine the position of a [codespan_reporting::diagnostic::Label] by looking it up
@@ -46,7 +51,11 @@ impl LocationCompat for lsp_types::Range {
 }
 
 impl DiagnosticCompat for lsp_types::Diagnostic {
-    fn from_codespan(diagnostic: Diagnostic<FileId>, files: &mut Files<String>) -> Vec<Self> {
+    fn from_codespan(
+        file_id: FileId,
+        diagnostic: Diagnostic<FileId>,
+        files: &mut Files<String>,
+    ) -> Vec<Self> {
         let severity = Some(match diagnostic.severity {
             diagnostic::Severity::Bug => lsp_types::DiagnosticSeverity::Warning,
             diagnostic::Severity::Error => lsp_types::DiagnosticSeverity::Error,
@@ -55,19 +64,53 @@ impl DiagnosticCompat for lsp_types::Diagnostic {
             diagnostic::Severity::Help => lsp_types::DiagnosticSeverity::Hint,
         });
 
-        diagnostic
-            .labels
-            .iter()
+        let code = diagnostic.code.clone().map(NumberOrString::String);
+        let message = std::iter::once(&diagnostic.message)
+            .chain(diagnostic.notes.iter())
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        // Report the diagnostic at its primary labels, or at its other labels if none of the
+        // primary ones are in this document. Labels may also point to other documents or to
+        // generated snippets, in which case the diagnostic is reported at the start of the
+        // document.
+        let in_file = |style: LabelStyle| {
+            diagnostic
+                .labels
+                .iter()
+                .filter(|label| label.file_id == file_id && label.style == style)
+                .collect::<Vec<_>>()
+        };
+        let mut labels = in_file(LabelStyle::Primary);
+        if labels.is_empty() {
+            labels = in_file(LabelStyle::Secondary);
+        }
+
+        if labels.is_empty() {
+            return vec![lsp_types::Diagnostic {
+                range: lsp_types::Range::default(),
+                severity,
+                code,
+                message,
+                ..Default::default()
+            }];
+        }
+
+        labels
+            .into_iter()
             .map(|label| {
                 let range = lsp_types::Range::from_codespan(&label.file_id, &label.range, files);
-
-                let code = diagnostic.code.clone().map(NumberOrString::String);
-                let message = format!("{}\n{}", diagnostic.message, diagnostic.notes.join("\n"));
+                let message = if label.message.is_empty() {
+                    message.clone()
+                } else {
+                    format!("{}\n{}", message, label.message)
+                };
 
                 lsp_types::Diagnostic {
                     range,
                     severity,
-                    code,
+                    code: code.clone(),
                     message,
                     ..Default::default()
                 }
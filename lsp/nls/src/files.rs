@@ -8,13 +8,13 @@ use lsp_types::{
     DidChangeTextDocumentParams, DidOpenTextDocumentParams, PublishDiagnosticsParams, Url,
 };
 use nickel::{
-    cache::{CacheError, CacheOp},
+    cache::{CacheError, CacheOp, EntryState},
     error::ToDiagnostic,
 };
 
 use crate::trace::{param::FileUpdate, Enrich, Trace};
 
-use super::cache::CacheExt;
+use super::cache::{name_of_uri, CacheExt};
 use super::diagnostic::DiagnosticCompat;
 use super::server::Server;
 
@@ -32,9 +32,10 @@ pub fn handle_open(server: &mut Server, params: DidOpenTextDocumentParams) -> Re
             content: &params.text_document.text,
         },
     );
-    let file_id = server
-        .cache
-        .add_string(params.text_document.uri.as_str(), params.text_document.text);
+    let file_id = server.cache.update_content(
+        name_of_uri(&params.text_document.uri),
+        params.text_document.text,
+    )?;
 
     parse_and_typecheck(server, params.text_document.uri, file_id)?;
     Trace::reply(id);
@@ -57,7 +58,7 @@ pub fn handle_save(server: &mut Server, params: DidChangeTextDocumentParams) ->
     );
 
     let file_id = server.cache.update_content(
-        params.text_document.uri.as_str(),
+        name_of_uri(&params.text_document.uri),
         params.content_changes[0].text.to_owned(),
     )?;
 
@@ -65,10 +66,73 @@ pub fn handle_save(server: &mut Server, params: DidChangeTextDocumentParams) ->
     //       implement typecheck (at least) as part of a persistent AST representation
     //       for now execute the same as above for handling `open` notifications
     parse_and_typecheck(server, params.text_document.uri, file_id)?;
+
+    // The documents importing this one are typechecked again, as the type of the import may have
+    // changed. Other documents are left untouched. If the document couldn't be parsed, importers
+    // are kept as they are until it is fixed.
+    if server.cache.entry_state(file_id) >= Some(EntryState::ImportsResolved) {
+        for importer in importers(server, file_id) {
+            trace!("Typechecking importer {:?} again", importer);
+            server.lin_cache.remove(&importer);
+            server
+                .cache
+                .update_state(importer, EntryState::ImportsResolved);
+
+            if let Some(uri) = server.cache.uri_of(importer) {
+                parse_and_typecheck(server, uri, importer)?;
+            }
+        }
+    }
+
     Trace::reply(id);
     Ok(())
 }
 
+/// The documents importing `file_id`, directly or transitively.
+fn importers(server: &Server, file_id: FileId) -> Vec<FileId> {
+    let mut importers = Vec::new();
+    let mut pending = vec![file_id];
+
+    while let Some(imported) = pending.pop() {
+        for (importer, imports) in server.imports.iter() {
+            if imports.contains(&imported) && *importer != file_id && !importers.contains(importer)
+            {
+                importers.push(*importer);
+                pending.push(*importer);
+            }
+        }
+    }
+
+    importers
+}
+
+fn resolve_imports(server: &mut Server, file_id: FileId) -> Result<(), Vec<Diagnostic<FileId>>> {
+    let result = server
+        .cache
+        .resolve_imports(file_id)
+        .map(|_| ())
+        .map_err(|error| match error {
+            CacheError::Error(import_error) => {
+                import_error.to_diagnostic(server.cache.files_mut(), None)
+            }
+            CacheError::NotParsed => unreachable!(),
+        });
+
+    if result.is_err() {
+        // A failed resolution may drop the term: parse it again if needed, and leave the
+        // remaining imports unresolved, which are then typechecked as `Dyn`
+        let _ = server.cache.parse(file_id);
+        server
+            .cache
+            .update_state(file_id, EntryState::ImportsResolved);
+    }
+
+    let imports = server.cache.get_resolved_imports(file_id);
+    server.imports.insert(file_id, imports);
+
+    result
+}
+
 fn typecheck(server: &mut Server, file_id: FileId) -> Result<CacheOp<()>, Vec<Diagnostic<FileId>>> {
     server
         .cache
@@ -89,7 +153,9 @@ fn parse_and_typecheck(server: &mut Server, uri: Url, file_id: FileId) -> Result
             let mut d = parse_errs
                 .inner()
                 .to_diagnostic(server.cache.files_mut(), None);
-            trace!("Parsed, checking types");
+            trace!("Parsed, resolving imports");
+            let _ = resolve_imports(server, file_id).map_err(|mut imp_d| d.append(&mut imp_d));
+            trace!("Imports resolved, checking types");
             let _ = typecheck(server, file_id).map_err(|mut ty_d| d.append(&mut ty_d));
             d
         })
@@ -97,7 +163,7 @@ fn parse_and_typecheck(server: &mut Server, uri: Url, file_id: FileId) -> Result
 
     let diagnostics = diagnostics
         .into_iter()
-        .map(|d| lsp_types::Diagnostic::from_codespan(d, server.cache.files_mut()))
+        .map(|d| lsp_types::Diagnostic::from_codespan(file_id, d, server.cache.files_mut()))
         .flatten()
         .collect();
 
@@ -112,3 +178,91 @@ fn parse_and_typecheck(server: &mut Server, uri: Url, file_id: FileId) -> Result
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use lsp_types::{DiagnosticSeverity, Position, Range};
+
+    use super::*;
+    use crate::{linearization::interface::TermKind, testing::TestServer};
+
+    #[test]
+    fn no_diagnostics() {
+        let (_, diagnostics) =
+            TestServer::new().open_with_diagnostics("file:///test.ncl", "let x = 1 in x + 1");
+
+        assert_eq!(diagnostics, Vec::new());
+    }
+
+    #[test]
+    fn type_mismatch() {
+        let (_, diagnostics) = TestServer::new()
+            .open_with_diagnostics("file:///test.ncl", "let x : Num = \"a\" in\nx + 1");
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(0, 14), Position::new(0, 17))
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::Error));
+        assert!(diagnostics[0].message.starts_with("Incompatible types"));
+    }
+
+    #[test]
+    fn parse_error() {
+        let (_, diagnostics) =
+            TestServer::new().open_with_diagnostics("file:///test.ncl", "let x = in\nx");
+
+        assert!(!diagnostics.is_empty());
+        assert!(diagnostics.iter().all(|diagnostic| diagnostic.severity
+            == Some(DiagnosticSeverity::Error)
+            && diagnostic.range.start.line == 0));
+    }
+
+    /// The type of the declaration `name` in the linearization of a document.
+    fn declaration_type(server: &TestServer, uri: &Url, name: &str) -> String {
+        let file_id = server.server.cache.id_of_uri(uri).unwrap();
+        server.server.lin_cache[&file_id]
+            .linearization
+            .iter()
+            .find_map(|item| match &item.kind {
+                TermKind::Declaration(ident, _) if ident.label == name => Some(item.ty.to_string()),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn editing_import_typechecks_importers_only() {
+        let dir = std::env::temp_dir().join(format!("nls-imports-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let uri = |file: &str| -> Url {
+            let path: PathBuf = dir.join(file);
+            fs::write(&path, "null").unwrap();
+            Url::from_file_path(path).unwrap()
+        };
+        let (leaf, importer, unrelated) = (uri("leaf.ncl"), uri("importer.ncl"), uri("other.ncl"));
+
+        let mut server = TestServer::new();
+        server.open(leaf.as_str(), "1");
+        server.open(importer.as_str(), "let x = import \"leaf.ncl\" in x");
+        server.open(unrelated.as_str(), "let y = 2 in y");
+        assert_eq!(declaration_type(&server, &importer, "x"), "Num");
+
+        let published: Vec<_> = server
+            .change(&leaf, "\"one\"")
+            .into_iter()
+            .map(|params| params.uri)
+            .collect();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(published, vec![leaf, importer.clone()]);
+        assert_eq!(declaration_type(&server, &importer, "x"), "Str");
+
+        let unrelated = server.server.cache.id_of_uri(&unrelated).unwrap();
+        assert!(server.server.lin_cache.contains_key(&unrelated));
+    }
+}
@@ -3,7 +3,8 @@ use std::{collections::HashMap, mem};
 use log::debug;
 use nickel::{
     identifier::Ident,
-    term::{MetaValue, RichTerm, Term},
+    position::RawSpan,
+    term::{IndexMap, MetaValue, RichTerm, Term},
     typecheck::{
         linearization::{LinearizationState, ScopeId},
         TypeWrapper,
@@ -42,25 +43,27 @@ impl Building {
         self.linearization.push(item);
     }
 
+    /// Record `usage` as a usage of the declaration or record field `decl`. References to
+    /// another kind of item or to a missing item are ignored, such that an inconsistent
+    /// linearization degrades the analysis instead of crashing the server.
     pub(super) fn add_usage(&mut self, decl: usize, usage: usize) {
-        match self
-            .linearization
-            .get_mut(decl)
-            .expect("Could not find parent")
-            .kind
-        {
-            TermKind::Structure => unreachable!(),
-            TermKind::Usage(_) => unreachable!(),
-            TermKind::Record(_) => unreachable!(),
-            TermKind::Declaration(_, ref mut usages)
-            | TermKind::RecordField { ref mut usages, .. } => usages.push(usage),
+        match self.linearization.get_mut(decl).map(|item| &mut item.kind) {
+            Some(TermKind::Declaration(_, ref mut usages))
+            | Some(TermKind::RecordField { ref mut usages, .. }) => usages.push(usage),
+            kind => debug!(
+                "ignoring usage {} of {}, which is not a declaration: {:?}",
+                usage, decl, kind
+            ),
         };
     }
 
+    /// Record the fields of a record. Fields without a position are located at `record_pos`, the
+    /// position of the record.
     pub(super) fn register_fields(
         &mut self,
-        record_fields: &HashMap<Ident, RichTerm>,
+        record_fields: &IndexMap<Ident, RichTerm>,
         record: usize,
+        record_pos: RawSpan,
         scope: Vec<ScopeId>,
         env: &mut Environment,
     ) {
@@ -68,7 +71,7 @@ impl Building {
             let id = self.id_gen().get_and_advance();
             self.push(LinearizationItem {
                 id,
-                pos: ident.pos.unwrap(),
+                pos: ident.pos.into_opt().unwrap_or(record_pos),
                 // temporary, the actual type is resolved later and the item retyped
                 ty: TypeWrapper::Concrete(AbsType::Dyn()),
                 kind: TermKind::RecordField {
@@ -99,13 +102,15 @@ impl Building {
         match self
             .linearization
             .get_mut(record)
-            .expect("Could not find record")
-            .kind
+            .map(|item| &mut item.kind)
         {
-            TermKind::Record(ref mut fields) => {
+            Some(TermKind::Record(ref mut fields)) => {
                 fields.insert(field_ident, reference_id);
             }
-            _ => unreachable!(),
+            kind => debug!(
+                "ignoring field {} of {}, which is not a record: {:?}",
+                field_ident, record, kind
+            ),
         }
     }
 
@@ -224,9 +229,7 @@ impl Building {
                 child_ident, referenced_declaration
             );
 
-            {
-                let child: &mut LinearizationItem<TypeWrapper> =
-                    self.linearization.get_mut(*child_item).unwrap();
+            if let Some(child) = self.linearization.get_mut(*child_item) {
                 child.kind = TermKind::Usage(UsageState::Resolved(referenced_id));
             }
 
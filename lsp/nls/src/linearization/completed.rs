@@ -2,7 +2,8 @@ use std::collections::HashMap;
 
 use codespan::ByteIndex;
 use nickel::{
-    term::MetaValue,
+    identifier::Ident,
+    term::{MergePriority, MetaValue},
     typecheck::linearization::{LinearizationState, ScopeId},
 };
 
@@ -38,16 +39,14 @@ impl Completed {
             .and_then(|index| self.linearization.get(*index))
     }
 
+    /// Items recorded in the scope of `item` or in any of its enclosing scopes, outermost first.
     pub fn get_in_scope(
         &self,
         LinearizationItem { scope, .. }: &LinearizationItem<Resolved>,
     ) -> Vec<&LinearizationItem<Resolved>> {
         let empty = Vec::with_capacity(0);
-        (0..scope.len())
-            .flat_map(|end| {
-                eprintln!("in scope {:?}: {:?}", scope, self.scope.get(scope));
-                self.scope.get(&scope[..=end]).unwrap_or(&empty)
-            })
+        (0..=scope.len())
+            .flat_map(|end| self.scope.get(&scope[..end]).unwrap_or(&empty))
             .map(|id| self.get_item(*id))
             .flatten()
             .collect()
@@ -100,7 +99,49 @@ impl Completed {
         item
     }
 
-    /// Resolve type and meta information for a given item
+    /// The declaration or record field `item` refers to: `item` itself if it is one, or the target
+    /// of a resolved usage.
+    pub fn resolve_declaration<'a>(
+        &'a self,
+        item: &'a LinearizationItem<Resolved>,
+    ) -> Option<&'a LinearizationItem<Resolved>> {
+        let item = match item.kind {
+            TermKind::Usage(UsageState::Resolved(Some(pointed))) => self.get_item(pointed)?,
+            _ => item,
+        };
+
+        match item.kind {
+            TermKind::Declaration(..) | TermKind::RecordField { .. } => Some(item),
+            _ => None,
+        }
+    }
+
+    /// Find the fields of the record referred to by `item`, following usages to their
+    /// declaration, and declarations and record fields to their value.
+    pub fn resolve_record_fields<'a>(
+        &'a self,
+        item: &'a LinearizationItem<Resolved>,
+    ) -> Option<&'a HashMap<Ident, usize>> {
+        let mut item = item;
+
+        // Bound the number of steps, as a declaration may refer to itself
+        for _ in 0..self.linearization.len() {
+            item = match item.kind {
+                TermKind::Record(ref fields) => return Some(fields),
+                TermKind::Usage(UsageState::Resolved(Some(pointed))) => self.get_item(pointed)?,
+                // the value of a declaration is the item following it
+                TermKind::Declaration(_, _) => self.get_item(item.id + 1)?,
+                TermKind::RecordField {
+                    value: Some(value), ..
+                } => self.get_item(value)?,
+                _ => return None,
+            };
+        }
+
+        None
+    }
+
+    /// Resolve type and meta information for a given item. A usage is resolved to its declaration.
     pub fn resolve_item_type_meta(
         &self,
         item: &LinearizationItem<Resolved>,
@@ -111,17 +152,24 @@ impl Completed {
             TermKind::Usage(UsageState::Resolved(usage)) => {
                 usage.and_then(|u| self.get_item(u)).unwrap_or(item)
             }
-            TermKind::Declaration(_, _) => self.get_item(item.id).unwrap_or(item),
             _ => item,
         };
 
+        // The metadata of a let binding is recorded on its value, which follows the declaration
+        let meta = item.meta.as_ref().or_else(|| match item.kind {
+            TermKind::Declaration(_, _) => self
+                .get_item(item.id + 1)
+                .and_then(|value| value.meta.as_ref()),
+            _ => None,
+        });
+
         if let Some(MetaValue {
             ref doc,
             ref types,
             ref contracts,
             priority,
             ..
-        }) = item.meta.as_ref()
+        }) = meta
         {
             if let Some(doc) = doc {
                 extra.push(doc.to_owned());
@@ -139,7 +187,9 @@ impl Completed {
                 );
             }
 
-            extra.push(format!("Merge Priority: {:?}", priority));
+            if *priority != MergePriority::default() {
+                extra.push(format!("Merge Priority: {:?}", priority));
+            }
         }
 
         (item.ty.to_owned(), extra)
@@ -101,7 +101,7 @@ impl Linearizer for AnalysisHost {
             if let Some((record, (offset, Ident { pos: field_pos, .. }))) = self
                 .record_fields
                 .take()
-                .map(|(record, mut fields)| (record, fields.pop().unwrap()))
+                .and_then(|(record, mut fields)| Some(record).zip(fields.pop()))
             {
                 pos = field_pos.map(|mut pos| {
                     pos.start = ByteIndex(0);
@@ -126,16 +126,22 @@ impl Linearizer for AnalysisHost {
                             *value = Some(id_gen.get() + usage_offset);
                         }
                         // The linearization item of a record with n fields is expected to be
-                        // followed by n linearization items representing each field
-                        _ => unreachable!(),
+                        // followed by n linearization items representing each field. Otherwise,
+                        // the value of the field is not recorded.
+                        ref kind => debug!("expected a record field, got {:?}", kind),
                     }
                 }
             }
         }
 
-        if pos == TermPos::None {
-            return;
-        }
+        // Generated terms may lack a position, in which case they aren't recorded. Generated
+        // identifiers, such as the parameters of an eta-expanded operator `(+)`, may lack a
+        // position as well even if the enclosing term has one: they are then located at the
+        // position of this term.
+        let term_span = match pos.into_opt() {
+            Some(span) => span,
+            None => return,
+        };
 
         let id = id_gen.get();
         match term {
@@ -145,7 +151,7 @@ impl Linearizer for AnalysisHost {
                     lin.push(LinearizationItem {
                         id: id_gen.get_and_advance(),
                         ty,
-                        pos: ident.pos.unwrap(),
+                        pos: ident.pos.into_opt().unwrap_or(term_span),
                         scope: self.scope.clone(),
                         kind: TermKind::Declaration(ident.to_owned(), Vec::new()),
                         meta: self.meta.take(),
@@ -158,7 +164,7 @@ impl Linearizer for AnalysisHost {
                         id: id_gen.get_and_advance(),
                         // TODO: get type from pattern
                         ty: TypeWrapper::Concrete(AbsType::Dyn()),
-                        pos: ident.pos.unwrap(),
+                        pos: ident.pos.into_opt().unwrap_or(term_span),
                         scope: self.scope.clone(),
                         kind: TermKind::Declaration(ident.to_owned(), Vec::new()),
                         meta: match &*term.term {
@@ -176,7 +182,7 @@ impl Linearizer for AnalysisHost {
                 lin.push(LinearizationItem {
                     id,
                     ty,
-                    pos: ident.pos.unwrap(),
+                    pos: ident.pos.into_opt().unwrap_or(term_span),
                     scope: self.scope.clone(),
                     kind: TermKind::Declaration(ident.to_owned(), Vec::new()),
                     meta: self.meta.take(),
@@ -192,7 +198,7 @@ impl Linearizer for AnalysisHost {
 
                 lin.push(LinearizationItem {
                     id: root_id,
-                    pos: ident.pos.unwrap(),
+                    pos: ident.pos.into_opt().unwrap_or(term_span),
                     ty: TypeWrapper::Concrete(AbsType::Dyn()),
                     scope: self.scope.clone(),
                     kind: TermKind::Usage(UsageState::Resolved(self.env.get(ident))),
@@ -210,7 +216,7 @@ impl Linearizer for AnalysisHost {
                         let id = id_gen.get_and_advance();
                         lin.push(LinearizationItem {
                             id,
-                            pos: accessor.pos.unwrap(),
+                            pos: accessor.pos.into_opt().unwrap_or(term_span),
                             ty: TypeWrapper::Concrete(AbsType::Dyn()),
                             scope: self.scope.clone(),
                             kind: TermKind::Usage(UsageState::Deferred {
@@ -225,14 +231,14 @@ impl Linearizer for AnalysisHost {
             Term::Record(fields, _) | Term::RecRecord(fields, _, _) => {
                 lin.push(LinearizationItem {
                     id,
-                    pos: pos.unwrap(),
+                    pos: term_span,
                     ty,
                     kind: TermKind::Record(HashMap::new()),
                     scope: self.scope.clone(),
                     meta: self.meta.take(),
                 });
 
-                lin.register_fields(fields, id, self.scope.clone(), &mut self.env);
+                lin.register_fields(fields, id, term_span, self.scope.clone(), &mut self.env);
                 let mut field_names = fields.keys().cloned().collect::<Vec<_>>();
                 field_names.sort_unstable();
 
@@ -258,7 +264,7 @@ impl Linearizer for AnalysisHost {
                             let id = id_gen.get_and_advance();
                             lin.push(LinearizationItem {
                                 id,
-                                pos: ident.pos.unwrap(),
+                                pos: ident.pos.into_opt().unwrap_or(term_span),
                                 ty: TypeWrapper::Concrete(AbsType::Var(ident.to_owned())),
                                 scope: self.scope.clone(),
                                 // id = parent: full let binding including the body
@@ -285,7 +291,7 @@ impl Linearizer for AnalysisHost {
 
                 lin.push(LinearizationItem {
                     id,
-                    pos: pos.unwrap(),
+                    pos: term_span,
                     ty,
                     scope: self.scope.clone(),
                     kind: TermKind::Structure,
@@ -420,3 +426,118 @@ impl IdGen {
         current_id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use codespan::{ByteIndex, Files};
+    use nickel::{
+        identifier::Ident,
+        position::{RawSpan, TermPos},
+        term::Term,
+        typecheck::{linearization::Linearization, TypeWrapper},
+        types::AbsType,
+    };
+
+    use crate::{cache::CacheExt, testing::TestServer};
+
+    use super::{
+        building::Building,
+        interface::{TermKind, UsageState},
+        AnalysisHost, LinearizationItem, Linearizer,
+    };
+
+    #[test]
+    fn generated_identifiers_without_position() {
+        // Curried operators are eta-expanded into functions whose parameters have no position
+        let mut server = TestServer::new();
+        let (uri, diagnostics) = server.open_with_diagnostics(
+            "file:///test.ncl",
+            "let add = (+) in add 1 ((|>) 2 (fun x => x))",
+        );
+        assert!(diagnostics.is_empty());
+
+        let file_id = server.server.cache.id_of_uri(&uri).unwrap();
+        let completed = server.server.lin_cache.get(&file_id).unwrap();
+        let add = completed
+            .linearization
+            .iter()
+            .find(|item| matches!(&item.kind, TermKind::Declaration(ident, _) if ident.label == "add"))
+            .unwrap();
+
+        match &add.kind {
+            TermKind::Declaration(_, usages) => assert_eq!(usages.len(), 1),
+            _ => unreachable!(),
+        }
+    }
+
+    /// A linearization made of a single structure item.
+    fn structure_only() -> (Linearization<Building>, RawSpan) {
+        let span = RawSpan {
+            src_id: Files::new().add("<test>", String::from("1")),
+            start: ByteIndex(0),
+            end: ByteIndex(1),
+        };
+        let mut lin = Linearization::new(Building::default());
+        lin.push(LinearizationItem {
+            id: 0,
+            pos: span,
+            ty: TypeWrapper::Concrete(AbsType::Dyn()),
+            kind: TermKind::Structure,
+            scope: Vec::new(),
+            meta: None,
+        });
+
+        (lin, span)
+    }
+
+    #[test]
+    fn inconsistent_references() {
+        // References to items of the wrong kind, or to missing items, are ignored
+        let (mut lin, _) = structure_only();
+        lin.add_usage(0, 1);
+        lin.add_usage(42, 1);
+        lin.add_record_field(0, (Ident::from("a"), 1));
+        lin.add_record_field(42, (Ident::from("a"), 1));
+        lin.resolve_record_references(vec![(42, 0, Ident::from("a"))]);
+        assert_eq!(lin.linearization[0].kind, TermKind::Structure);
+
+        // A deferred usage whose parent isn't a record is resolved to nothing
+        let usage = LinearizationItem {
+            kind: TermKind::Usage(UsageState::Deferred {
+                parent: 0,
+                child: Ident::from("a"),
+            }),
+            id: 1,
+            ..lin.linearization[0].clone()
+        };
+        lin.push(usage);
+        lin.resolve_record_references(vec![(1, 0, Ident::from("a"))]);
+        assert_eq!(
+            lin.linearization[1].kind,
+            TermKind::Usage(UsageState::Resolved(None))
+        );
+    }
+
+    #[test]
+    fn inconsistent_record_fields() {
+        let (mut lin, span) = structure_only();
+        let ty = TypeWrapper::Concrete(AbsType::Dyn());
+
+        // The pending field is not followed by a field item
+        let mut host = AnalysisHost::new();
+        host.record_fields = Some((0, vec![(0, Ident::from("a"))]));
+        host.add_term(
+            &mut lin,
+            &Term::Num(1.0),
+            TermPos::Original(span),
+            ty.clone(),
+        );
+        assert_eq!(lin.linearization[0].kind, TermKind::Structure);
+
+        // There is no pending field left: the term is recorded as usual
+        let mut host = AnalysisHost::new();
+        host.record_fields = Some((0, Vec::new()));
+        host.add_term(&mut lin, &Term::Num(1.0), TermPos::Original(span), ty);
+        assert_eq!(lin.linearization.len(), 2);
+    }
+}
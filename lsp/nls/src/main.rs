@@ -17,6 +17,8 @@ use structopt::StructOpt;
 use crate::trace::Trace;
 
 mod term;
+#[cfg(test)]
+mod testing;
 mod trace;
 
 #[derive(StructOpt, Debug)]
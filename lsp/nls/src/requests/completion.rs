@@ -1,12 +1,23 @@
-use codespan::ByteIndex;
+use std::collections::{hash_map::Entry, HashMap};
+
+use codespan::{ByteIndex, FileId};
 use codespan_lsp::position_to_byte_index;
 use log::debug;
 use lsp_server::{RequestId, Response, ResponseError};
-use lsp_types::{CompletionItem, CompletionParams};
+use lsp_types::{CompletionItem, CompletionItemKind, CompletionParams};
+use nickel::{
+    identifier::Ident,
+    types::{AbsType, Types},
+};
 use serde_json::Value;
 
 use crate::{
-    linearization::interface::TermKind,
+    cache::CacheExt,
+    linearization::{
+        completed::Completed,
+        interface::{TermKind, UsageState},
+        LinearizationItem,
+    },
     server::Server,
     trace::{Enrich, Trace},
 };
@@ -18,7 +29,7 @@ pub fn handle_completion(
 ) -> Result<(), ResponseError> {
     let file_id = server
         .cache
-        .id_of(params.text_document_position.text_document.uri.as_str())
+        .id_of_uri(&params.text_document_position.text_document.uri)
         .unwrap();
 
     let start = position_to_byte_index(
@@ -28,35 +39,274 @@ pub fn handle_completion(
     )
     .unwrap();
 
-    let locator = (file_id, ByteIndex(start as u32));
     let linearization = server.lin_cache_get(&file_id)?;
 
     Trace::enrich(&id, linearization);
 
-    let item = linearization.item_at(&locator);
+    let source = server.cache.files().source(file_id);
+    let completions = match accessed_path(&source[..start]) {
+        Some((root_start, path)) => {
+            debug!("completing the fields of {}", path.join("."));
+            complete_fields(
+                linearization,
+                &(file_id, ByteIndex(root_start as u32)),
+                &path,
+            )
+        }
+        // The cursor usually sits right after the identifier being typed, that is at the end of
+        // its span, which doesn't belong to the span: look at the previous character instead.
+        None => complete_in_scope(
+            linearization,
+            &(file_id, ByteIndex(start.saturating_sub(1) as u32)),
+        ),
+    };
 
-    if item == None {
-        server.reply(Response::new_ok(id, Value::Null));
-        return Ok(());
+    match completions {
+        Some(completions) => server.reply(Response::new_ok(id, completions)),
+        None => server.reply(Response::new_ok(id, Value::Null)),
     }
 
-    let item = item.unwrap().to_owned();
+    Ok(())
+}
 
-    let in_scope: Vec<_> = linearization
-        .get_in_scope(&item)
-        .iter()
-        .filter_map(|i| match i.kind {
-            TermKind::Declaration(ref ident, _) => Some((ident.clone(), i.ty.clone())),
-            _ => None,
+fn complete_in_scope(
+    linearization: &Completed,
+    locator: &(FileId, ByteIndex),
+) -> Option<Vec<CompletionItem>> {
+    let item = linearization.item_at(locator)?;
+    debug!("found closest item: {:?}", item);
+
+    let completions = declarations_in_scope(linearization, item)
+        .into_iter()
+        .map(|(label, decl)| CompletionItem {
+            label: label.to_owned(),
+            kind: Some(match decl.ty {
+                Types(AbsType::Arrow(..)) => CompletionItemKind::Function,
+                _ => CompletionItemKind::Variable,
+            }),
+            detail: Some(decl.ty.to_string()),
+            ..Default::default()
         })
-        .map(|(ident, _)| CompletionItem {
-            label: ident.label,
+        .collect();
+
+    Some(completions)
+}
+
+/// Complete the fields of the record accessed by `path`, whose root identifier starts at
+/// `locator`. The root is resolved like a variable, and the rest of the path is followed through
+/// the fields of the successive records.
+fn complete_fields(
+    linearization: &Completed,
+    locator: &(FileId, ByteIndex),
+    path: &[&str],
+) -> Option<Vec<CompletionItem>> {
+    let (root, fields) = path.split_first()?;
+    let item = linearization.item_at(locator)?;
+
+    let mut record = match item.kind {
+        TermKind::Usage(UsageState::Resolved(Some(decl))) => linearization.get_item(decl)?,
+        _ => declarations_in_scope(linearization, item)
+            .into_iter()
+            .find_map(|(label, decl)| if label == *root { Some(decl) } else { None })?,
+    };
+
+    for field in fields {
+        let fields = linearization.resolve_record_fields(record)?;
+        record = linearization.get_item(*fields.get(&Ident::from(*field))?)?;
+    }
+
+    let mut completions: Vec<_> = linearization
+        .resolve_record_fields(record)?
+        .iter()
+        .filter_map(|(ident, id)| linearization.get_item(*id).map(|field| (ident, field)))
+        .map(|(ident, field)| CompletionItem {
+            label: ident.label.clone(),
+            kind: Some(CompletionItemKind::Field),
+            detail: Some(field.ty.to_string()),
             ..Default::default()
         })
         .collect();
+    completions.sort_unstable_by(|c1, c2| c1.label.cmp(&c2.label));
 
-    server.reply(Response::new_ok(id, in_scope));
+    Some(completions)
+}
 
-    debug!("found closest item: {:?}", item);
-    Ok(())
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '\'')
+}
+
+/// If `text` ends with a field access, that is a path `a.b.` possibly followed by the beginning
+/// of a field name, return the offset of the root of the path together with its identifiers,
+/// e.g. `["a", "b"]`.
+fn accessed_path(text: &str) -> Option<(usize, Vec<&str>)> {
+    let mut rest = text.trim_end_matches(is_ident_char).strip_suffix('.')?;
+    let mut path = Vec::new();
+
+    loop {
+        let start = rest.trim_end_matches(is_ident_char).len();
+        let ident = &rest[start..];
+
+        if ident.is_empty() {
+            return None;
+        }
+
+        path.push(ident);
+        match rest[..start].strip_suffix('.') {
+            Some(prefix) => rest = prefix,
+            None => {
+                path.reverse();
+                return Some((start, path));
+            }
+        }
+    }
+}
+
+/// The declarations visible from `item`, sorted by name. Only the declarations occurring before
+/// `item` are visible, and a shadowed declaration is hidden by the innermost one.
+fn declarations_in_scope<'a>(
+    linearization: &'a Completed,
+    item: &LinearizationItem<Types>,
+) -> Vec<(&'a str, &'a LinearizationItem<Types>)> {
+    let mut visible: HashMap<&str, &LinearizationItem<Types>> = HashMap::new();
+
+    for decl in linearization.get_in_scope(item) {
+        let label = match decl.kind {
+            TermKind::Declaration(ref ident, _) if decl.pos.start <= item.pos.start => {
+                ident.label.as_str()
+            }
+            _ => continue,
+        };
+
+        match visible.entry(label) {
+            Entry::Occupied(mut entry) if entry.get().pos.start < decl.pos.start => {
+                entry.insert(decl);
+            }
+            Entry::Occupied(_) => (),
+            Entry::Vacant(entry) => {
+                entry.insert(decl);
+            }
+        }
+    }
+
+    let mut visible: Vec<_> = visible.into_iter().collect();
+    visible.sort_unstable_by_key(|(label, _)| *label);
+    visible
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{
+        CompletionContext, CompletionTriggerKind, PartialResultParams, WorkDoneProgressParams,
+    };
+
+    use super::*;
+    use crate::testing::{position_params, TestServer};
+
+    fn complete(text: &str, line: u32, character: u32) -> Vec<CompletionItem> {
+        let mut server = TestServer::new();
+        let uri = server.open("file:///test.ncl", text);
+        let params = CompletionParams {
+            text_document_position: position_params(&uri, line, character),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+            context: Some(CompletionContext {
+                trigger_kind: CompletionTriggerKind::Invoked,
+                trigger_character: None,
+            }),
+        };
+
+        server
+            .request::<_, Option<Vec<CompletionItem>>>(handle_completion, params)
+            .unwrap()
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn in_scope_declarations_with_types() {
+        let text = "let x = 1 in\nlet f : Num -> Num = fun y => y + x in\nlet x = \"str\" in\nf";
+        let items: Vec<_> = complete(text, 3, 1)
+            .into_iter()
+            .map(|item| (item.label, item.kind, item.detail))
+            .collect();
+
+        assert_eq!(
+            items,
+            vec![
+                (
+                    String::from("f"),
+                    Some(CompletionItemKind::Function),
+                    Some(String::from("Num -> Num"))
+                ),
+                (
+                    String::from("x"),
+                    Some(CompletionItemKind::Variable),
+                    Some(String::from("Str"))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn declarations_after_the_cursor_are_not_visible() {
+        let text = "let a = 1 in\nlet b = a in\nlet c = 2 in\nb";
+        let labels: Vec<_> = complete(text, 1, 9)
+            .into_iter()
+            .map(|item| item.label)
+            .collect();
+
+        assert_eq!(labels, vec![String::from("a"), String::from("b")]);
+    }
+
+    #[test]
+    fn record_fields() {
+        let text =
+            "let r = { foo = 1, bar = { baz : Str = \"a\", qux = 2 } } in\nr.foo + r.bar.qux";
+        let fields = |character| -> Vec<_> {
+            complete(text, 1, character)
+                .into_iter()
+                .map(|item| (item.label, item.kind, item.detail))
+                .collect()
+        };
+
+        assert_eq!(
+            fields(2),
+            vec![
+                (
+                    String::from("bar"),
+                    Some(CompletionItemKind::Field),
+                    Some(String::from("Dyn"))
+                ),
+                (
+                    String::from("foo"),
+                    Some(CompletionItemKind::Field),
+                    Some(String::from("Num"))
+                ),
+            ]
+        );
+
+        let nested = vec![
+            (
+                String::from("baz"),
+                Some(CompletionItemKind::Field),
+                Some(String::from("Str")),
+            ),
+            (
+                String::from("qux"),
+                Some(CompletionItemKind::Field),
+                Some(String::from("Num")),
+            ),
+        ];
+        assert_eq!(fields(14), nested);
+        // with the beginning of a field name already typed
+        assert_eq!(fields(15), nested);
+    }
+
+    #[test]
+    fn accessed_paths() {
+        assert_eq!(accessed_path("let x = r."), Some((8, vec!["r"])));
+        assert_eq!(accessed_path("a.b-c.d"), Some((0, vec!["a", "b-c"])));
+        assert_eq!(accessed_path("f a.b.c"), Some((2, vec!["a", "b"])));
+        assert_eq!(accessed_path("f a"), None);
+        assert_eq!(accessed_path("{}."), None);
+    }
 }
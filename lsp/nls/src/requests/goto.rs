@@ -2,19 +2,30 @@ use codespan::ByteIndex;
 use codespan_lsp::position_to_byte_index;
 use log::debug;
 use lsp_server::{RequestId, Response, ResponseError};
-use lsp_types::{
-    GotoDefinitionParams, GotoDefinitionResponse, Location, Range, ReferenceParams, Url,
-};
-use nickel::position::RawSpan;
+use lsp_types::{GotoDefinitionParams, GotoDefinitionResponse, Location, Range, ReferenceParams};
+use nickel::{cache::Cache, position::RawSpan};
 use serde_json::Value;
 
 use crate::{
+    cache::CacheExt,
     diagnostic::LocationCompat,
     linearization::interface::{TermKind, UsageState},
     server::Server,
     trace::{Enrich, Trace},
 };
 
+/// The location of `span`, or `None` if its file has no URI.
+fn location(cache: &Cache, span: RawSpan) -> Option<Location> {
+    let uri = cache.uri_of(span.src_id)?;
+    let range = Range::from_codespan(
+        &span.src_id,
+        &(span.start.to_usize()..span.end.to_usize()),
+        cache.files(),
+    );
+
+    Some(Location { uri, range })
+}
+
 pub fn handle_to_definition(
     params: GotoDefinitionParams,
     id: RequestId,
@@ -22,13 +33,7 @@ pub fn handle_to_definition(
 ) -> Result<(), ResponseError> {
     let file_id = server
         .cache
-        .id_of(
-            params
-                .text_document_position_params
-                .text_document
-                .uri
-                .as_str(),
-        )
+        .id_of_uri(&params.text_document_position_params.text_document.uri)
         .unwrap();
 
     let start = position_to_byte_index(
@@ -54,25 +59,11 @@ pub fn handle_to_definition(
 
     debug!("found referencing item: {:?}", item);
 
+    // A usage that couldn't be resolved, such as an access to a missing field, has no definition
     let location = match item.kind {
-        TermKind::Usage(UsageState::Resolved(Some(usage_id))) => {
-            let definition = linearization.get_item(usage_id).unwrap();
-            let location = match definition.pos {
-                RawSpan {
-                    start: ByteIndex(start),
-                    end: ByteIndex(end),
-                    src_id,
-                } => Location {
-                    uri: Url::parse(&server.cache.name(src_id).to_string_lossy()).unwrap(),
-                    range: Range::from_codespan(
-                        &src_id,
-                        &(start as usize..end as usize),
-                        server.cache.files(),
-                    ),
-                },
-            };
-            Some(location)
-        }
+        TermKind::Usage(UsageState::Resolved(Some(usage_id))) => linearization
+            .get_item(usage_id)
+            .and_then(|definition| location(&server.cache, definition.pos)),
         _ => None,
     };
 
@@ -93,7 +84,7 @@ pub fn handle_to_usages(
 ) -> Result<(), ResponseError> {
     let file_id = server
         .cache
-        .id_of(params.text_document_position.text_document.uri.as_str())
+        .id_of_uri(&params.text_document_position.text_document.uri)
         .unwrap();
 
     let start = position_to_byte_index(
@@ -117,32 +108,29 @@ pub fn handle_to_usages(
 
     debug!("found referencing item: {:?}", item);
 
-    let locations = match &item.kind {
-        TermKind::Declaration(_, usages) | TermKind::RecordField { usages, .. } => {
-            let mut locations = Vec::new();
-
-            for reference_id in usages.iter() {
-                let reference = linearization.get_item(*reference_id).unwrap();
-                let location = match reference.pos {
-                    RawSpan {
-                        start: ByteIndex(start),
-                        end: ByteIndex(end),
-                        src_id,
-                    } => Location {
-                        uri: Url::parse(&server.cache.name(src_id).to_string_lossy()).unwrap(),
-                        range: Range::from_codespan(
-                            &src_id,
-                            &(start as usize..end as usize),
-                            server.cache.files(),
-                        ),
-                    },
-                };
-                locations.push(location);
+    let locations = linearization
+        .resolve_declaration(item)
+        .and_then(|declaration| match &declaration.kind {
+            TermKind::Declaration(_, usages) | TermKind::RecordField { usages, .. } => {
+                let mut spans: Vec<_> = usages
+                    .iter()
+                    .filter_map(|usage| linearization.get_item(*usage))
+                    .map(|usage| usage.pos)
+                    .collect();
+                if params.context.include_declaration {
+                    spans.push(declaration.pos);
+                }
+                spans.sort_by_key(|span| (span.src_id, span.start));
+
+                Some(
+                    spans
+                        .into_iter()
+                        .filter_map(|span| location(&server.cache, span))
+                        .collect::<Vec<_>>(),
+                )
             }
-            Some(locations)
-        }
-        _ => None,
-    };
+            _ => None,
+        });
 
     debug!("referencing locations: {:?}", locations);
 
@@ -153,3 +141,95 @@ pub fn handle_to_usages(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{PartialResultParams, Position, ReferenceContext, Url, WorkDoneProgressParams};
+
+    use super::*;
+    use crate::testing::{position_params, TestServer};
+
+    fn definition(text: &str, line: u32, character: u32) -> Option<GotoDefinitionResponse> {
+        let mut server = TestServer::new();
+        let uri = server.open("file:///test.ncl", text);
+
+        server
+            .request(
+                handle_to_definition,
+                GotoDefinitionParams {
+                    text_document_position_params: position_params(&uri, line, character),
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                    partial_result_params: PartialResultParams::default(),
+                },
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn usage_to_let_binding() {
+        assert_eq!(
+            definition("let x = 1 in\nlet y = 2 in\ny + x", 2, 4),
+            Some(GotoDefinitionResponse::Scalar(Location {
+                uri: Url::parse("file:///test.ncl").unwrap(),
+                range: Range::new(Position::new(0, 4), Position::new(0, 5)),
+            }))
+        );
+    }
+
+    fn references(
+        text: &str,
+        line: u32,
+        character: u32,
+        include_declaration: bool,
+    ) -> Option<Vec<Range>> {
+        let mut server = TestServer::new();
+        let uri = server.open("file:///test.ncl", text);
+
+        server
+            .request::<_, Option<Vec<Location>>>(
+                handle_to_usages,
+                ReferenceParams {
+                    text_document_position: position_params(&uri, line, character),
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                    partial_result_params: PartialResultParams::default(),
+                    context: ReferenceContext {
+                        include_declaration,
+                    },
+                },
+            )
+            .unwrap()
+            .map(|locations| locations.into_iter().map(|loc| loc.range).collect())
+    }
+
+    fn range(line: u32, start: u32, end: u32) -> Range {
+        Range::new(Position::new(line, start), Position::new(line, end))
+    }
+
+    #[test]
+    fn unresolved_usage() {
+        assert_eq!(definition("let r = { a = 1 } in\nr.b", 1, 2), None);
+    }
+
+    #[test]
+    fn references_from_declaration_and_usage() {
+        let text = "let x = 1 in\nx + x";
+        let usages = vec![range(1, 0, 1), range(1, 4, 5)];
+
+        assert_eq!(references(text, 0, 4, false), Some(usages.clone()));
+        assert_eq!(references(text, 1, 4, false), Some(usages.clone()));
+        assert_eq!(
+            references(text, 1, 0, true),
+            Some(vec![range(0, 4, 5), usages[0], usages[1]])
+        );
+    }
+
+    #[test]
+    fn record_field_references() {
+        let text = "let r = { a = 1, b = 2 } in\nr.a + r.b + r.a";
+
+        assert_eq!(
+            references(text, 0, 10, false),
+            Some(vec![range(1, 2, 3), range(1, 14, 15)])
+        );
+    }
+}
@@ -6,6 +6,7 @@ use lsp_types::{Hover, HoverContents, HoverParams, LanguageString, MarkedString,
 use serde_json::Value;
 
 use crate::{
+    cache::CacheExt,
     diagnostic::LocationCompat,
     server::Server,
     trace::{Enrich, Trace},
@@ -18,13 +19,7 @@ pub fn handle(
 ) -> Result<(), ResponseError> {
     let file_id = server
         .cache
-        .id_of(
-            params
-                .text_document_position_params
-                .text_document
-                .uri
-                .as_str(),
-        )
+        .id_of_uri(&params.text_document_position_params.text_document.uri)
         .unwrap();
 
     let start = position_to_byte_index(
@@ -60,26 +55,61 @@ pub fn handle(
         server.cache.files(),
     );
 
+    let contents = std::iter::once(MarkedString::LanguageString(LanguageString {
+        language: "nickel".into(),
+        value: ty.to_string(),
+    }))
+    .chain(meta.into_iter().map(MarkedString::String))
+    .collect();
+
     server.reply(Response::new_ok(
         id,
         Hover {
-            contents: HoverContents::Array(vec![
-                MarkedString::LanguageString(LanguageString {
-                    language: "nickel".into(),
-                    value: ty.to_string(),
-                }),
-                MarkedString::LanguageString(LanguageString {
-                    language: "plain".into(),
-                    value: meta.join("\n"),
-                }),
-                MarkedString::LanguageString(LanguageString {
-                    language: "plain".into(),
-                    value: format!("{:?} @ {:?}", item, range,),
-                }),
-            ]),
-
+            contents: HoverContents::Array(contents),
             range: Some(range),
         },
     ));
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{Position, WorkDoneProgressParams};
+
+    use super::*;
+    use crate::testing::{position_params, TestServer};
+
+    #[test]
+    fn usage_shows_declaration_type_and_doc() {
+        let mut server = TestServer::new();
+        let uri = server.open(
+            "file:///test.ncl",
+            "let answer | doc \"The answer\" = 42 in\nanswer + 1",
+        );
+        let hover = server
+            .request::<_, Option<Hover>>(
+                handle,
+                HoverParams {
+                    text_document_position_params: position_params(&uri, 1, 2),
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                },
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            hover.contents,
+            HoverContents::Array(vec![
+                MarkedString::LanguageString(LanguageString {
+                    language: String::from("nickel"),
+                    value: String::from("Num"),
+                }),
+                MarkedString::String(String::from("The answer")),
+            ])
+        );
+        assert_eq!(
+            hover.range,
+            Some(Range::new(Position::new(1, 0), Position::new(1, 6)))
+        );
+    }
+}
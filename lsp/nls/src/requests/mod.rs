@@ -1,4 +1,6 @@
 pub mod completion;
 pub mod goto;
 pub mod hover;
+pub mod rename;
+pub mod semantic_tokens;
 pub mod symbols;
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+
+use codespan::ByteIndex;
+use codespan_lsp::position_to_byte_index;
+use log::debug;
+use lsp_server::{ErrorCode, RequestId, Response, ResponseError};
+use lsp_types::{Range, RenameParams, TextEdit, Url, WorkspaceEdit};
+use nickel::parser::lexer::{Lexer, NormalToken, Token};
+use serde_json::Value;
+
+use crate::{
+    cache::CacheExt,
+    diagnostic::LocationCompat,
+    linearization::{
+        completed::Completed,
+        interface::{Resolved, TermKind},
+        LinearizationItem,
+    },
+    server::Server,
+    trace::{Enrich, Trace},
+};
+
+pub fn handle_rename(
+    params: RenameParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let file_id = server
+        .cache
+        .id_of_uri(&params.text_document_position.text_document.uri)
+        .unwrap();
+
+    let start = position_to_byte_index(
+        server.cache.files(),
+        file_id,
+        &params.text_document_position.position,
+    )
+    .unwrap();
+
+    let locator = (file_id, ByteIndex(start as u32));
+    let linearization = server.lin_cache_get(&file_id)?;
+
+    Trace::enrich(&id, linearization);
+
+    let declaration = linearization
+        .item_at(&locator)
+        .and_then(|item| linearization.resolve_declaration(item));
+
+    let declaration = match declaration {
+        Some(declaration) => declaration,
+        None => {
+            server.reply(Response::new_ok(id, Value::Null));
+            return Ok(());
+        }
+    };
+
+    debug!("renaming {:?} to {}", declaration, params.new_name);
+
+    if !is_identifier(&params.new_name) {
+        return Err(invalid_rename(format!(
+            "`{}` is not a valid identifier",
+            params.new_name
+        )));
+    }
+
+    if let Some(other) = find_collision(linearization, declaration, &params.new_name) {
+        return Err(invalid_rename(format!(
+            "renaming to `{}` would collide with the definition at {}",
+            params.new_name,
+            server
+                .cache
+                .files()
+                .location(other.pos.src_id, other.pos.start)
+                .map_or_else(
+                    |_| String::from("an unknown location"),
+                    |loc| format!("line {}, column {}", loc.line.number(), loc.column.number())
+                )
+        )));
+    }
+
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+
+    for span in std::iter::once(declaration.pos).chain(
+        usages(declaration)
+            .iter()
+            .filter_map(|usage| linearization.get_item(*usage))
+            .map(|usage| usage.pos),
+    ) {
+        let uri = match server.cache.uri_of(span.src_id) {
+            Some(uri) => uri,
+            None => continue,
+        };
+        let range = Range::from_codespan(
+            &span.src_id,
+            &(span.start.to_usize()..span.end.to_usize()),
+            server.cache.files(),
+        );
+
+        changes.entry(uri).or_default().push(TextEdit {
+            range,
+            new_text: params.new_name.clone(),
+        });
+    }
+
+    server.reply(Response::new_ok(
+        id,
+        WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        },
+    ));
+    Ok(())
+}
+
+fn invalid_rename(message: String) -> ResponseError {
+    ResponseError {
+        code: ErrorCode::InvalidParams as i32,
+        message,
+        data: None,
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    matches!(
+        Lexer::new(name).collect::<Vec<_>>().as_slice(),
+        [Ok((_, Token::Normal(NormalToken::Identifier(_)), _))]
+    )
+}
+
+fn usages(item: &LinearizationItem<Resolved>) -> &[usize] {
+    match &item.kind {
+        TermKind::Declaration(_, usages) | TermKind::RecordField { usages, .. } => usages,
+        _ => &[],
+    }
+}
+
+fn label(item: &LinearizationItem<Resolved>) -> Option<&str> {
+    match &item.kind {
+        TermKind::Declaration(ident, _) | TermKind::RecordField { ident, .. } => {
+            Some(ident.label.as_str())
+        }
+        _ => None,
+    }
+}
+
+/// Find a definition that would clash with `declaration` once renamed to `new_name`. That is the
+/// case if:
+/// - a sibling field of a record field is already named `new_name`,
+/// - a declaration of `new_name` would capture a usage of `declaration`, or
+/// - `declaration` would capture a usage of an enclosing declaration of `new_name`.
+fn find_collision<'a>(
+    linearization: &'a Completed,
+    declaration: &LinearizationItem<Resolved>,
+    new_name: &str,
+) -> Option<&'a LinearizationItem<Resolved>> {
+    if let TermKind::RecordField { record, .. } = declaration.kind {
+        let sibling = linearization
+            .get_item(record)
+            .and_then(|record| match &record.kind {
+                TermKind::Record(fields) => fields
+                    .iter()
+                    .find(|(ident, id)| ident.label == new_name && **id != declaration.id)
+                    .and_then(|(_, id)| linearization.get_item(*id)),
+                _ => None,
+            });
+
+        if sibling.is_some() {
+            return sibling;
+        }
+    }
+
+    let named = |item: &&LinearizationItem<Resolved>| {
+        matches!(item.kind, TermKind::Declaration(..)) && label(item) == Some(new_name)
+    };
+
+    let captured_by_inner = usages(declaration)
+        .iter()
+        .filter_map(|usage| linearization.get_item(*usage))
+        .find_map(|usage| {
+            linearization
+                .get_in_scope(usage)
+                .into_iter()
+                .filter(named)
+                .find(|other| {
+                    other.pos.start > declaration.pos.start && other.pos.start <= usage.pos.start
+                })
+        });
+
+    if captured_by_inner.is_some() {
+        return captured_by_inner;
+    }
+
+    linearization
+        .linearization
+        .iter()
+        .filter(named)
+        .filter(|other| other.pos.start < declaration.pos.start)
+        .find(|other| {
+            usages(other)
+                .iter()
+                .filter_map(|usage| linearization.get_item(*usage))
+                .any(|usage| {
+                    usage.scope.starts_with(&declaration.scope)
+                        && usage.pos.start > declaration.pos.start
+                })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{Position, WorkDoneProgressParams};
+
+    use super::*;
+    use crate::testing::{position_params, TestServer};
+
+    fn rename(
+        text: &str,
+        line: u32,
+        character: u32,
+        new_name: &str,
+    ) -> Result<Vec<Range>, ResponseError> {
+        let mut server = TestServer::new();
+        let uri = server.open("file:///test.ncl", text);
+
+        let edit: WorkspaceEdit = server.request(
+            handle_rename,
+            RenameParams {
+                text_document_position: position_params(&uri, line, character),
+                new_name: String::from(new_name),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            },
+        )?;
+
+        let mut changes = edit.changes.unwrap();
+        let edits = changes.remove(&uri).unwrap();
+        assert!(changes.is_empty());
+        assert!(edits.iter().all(|edit| edit.new_text == new_name));
+
+        let mut ranges: Vec<_> = edits.into_iter().map(|edit| edit.range).collect();
+        ranges.sort_by_key(|range| (range.start.line, range.start.character));
+        Ok(ranges)
+    }
+
+    fn range(line: u32, start: u32, end: u32) -> Range {
+        Range::new(Position::new(line, start), Position::new(line, end))
+    }
+
+    #[test]
+    fn let_binding_used_twice() {
+        let text = "let x = 1 in\nlet y = x in\nx + y";
+        let expected = vec![range(0, 4, 5), range(1, 8, 9), range(2, 0, 1)];
+
+        assert_eq!(rename(text, 0, 4, "foo").unwrap(), expected);
+        assert_eq!(rename(text, 2, 0, "foo").unwrap(), expected);
+    }
+
+    #[test]
+    fn shadowing_binding_is_untouched() {
+        let text = "let x = 1 in\nlet f = fun x => x in\nf x";
+
+        assert_eq!(
+            rename(text, 0, 4, "w").unwrap(),
+            vec![range(0, 4, 5), range(2, 2, 3)]
+        );
+    }
+
+    #[test]
+    fn collisions() {
+        // `y` would capture the usage of `x` in its body
+        assert!(rename("let x = 1 in\nlet y = 2 in\nx + y", 0, 4, "y").is_err());
+        // `x` would capture the usage of `y` in its body
+        assert!(rename("let y = 1 in\nlet x = 2 in\nx + y", 1, 4, "y").is_err());
+        // sibling fields
+        assert!(rename("{ a = 1, b = 2 }", 0, 2, "b").is_err());
+        assert!(rename("let x = 1 in x", 0, 4, "not valid").is_err());
+        // shadowing a binding that isn't used afterwards is fine
+        assert!(rename("let y = 1 in\ny + (let x = 2 in x)", 1, 9, "y").is_ok());
+    }
+}
@@ -0,0 +1,221 @@
+use codespan::{FileId, Files};
+use lsp_server::{RequestId, Response, ResponseError};
+use lsp_types::{
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens, SemanticTokensLegend,
+    SemanticTokensParams,
+};
+use nickel::types::{AbsType, Types};
+
+use crate::{
+    cache::CacheExt,
+    linearization::{
+        completed::Completed,
+        interface::{Resolved, TermKind, UsageState},
+        LinearizationItem,
+    },
+    server::Server,
+    trace::{Enrich, Trace},
+};
+
+/// The token types emitted by the server, in the order of their index in the legend.
+const TOKEN_TYPES: [SemanticTokenType; 3] = [
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::FUNCTION,
+];
+
+const VARIABLE: u32 = 0;
+const PROPERTY: u32 = 1;
+const FUNCTION: u32 = 2;
+
+/// The bit set for identifiers at their definition site.
+const DECLARATION: u32 = 1;
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: vec![SemanticTokenModifier::DECLARATION],
+    }
+}
+
+pub fn handle_semantic_tokens(
+    params: SemanticTokensParams,
+    id: RequestId,
+    server: &mut Server,
+) -> Result<(), ResponseError> {
+    let file_id = server.cache.id_of_uri(&params.text_document.uri).unwrap();
+    let linearization = server.lin_cache_get(&file_id)?;
+
+    Trace::enrich(&id, linearization);
+
+    let tokens = tokens(linearization, file_id, server.cache.files());
+
+    server.reply(Response::new_ok(
+        id,
+        SemanticTokens {
+            result_id: None,
+            data: tokens,
+        },
+    ));
+    Ok(())
+}
+
+/// The token type and modifiers of an identifier, or `None` if `item` isn't one. A usage is
+/// classified like the declaration it refers to.
+fn classify(
+    linearization: &Completed,
+    item: &LinearizationItem<Resolved>,
+) -> Option<(String, u32, u32)> {
+    let modifiers = match item.kind {
+        TermKind::Usage(_) => 0,
+        _ => DECLARATION,
+    };
+
+    let declaration = match item.kind {
+        TermKind::Usage(UsageState::Resolved(Some(pointed))) => linearization.get_item(pointed)?,
+        _ => item,
+    };
+
+    let (label, token_type) = match (&declaration.kind, &declaration.ty) {
+        (TermKind::Declaration(ident, _), Types(AbsType::Arrow(..)))
+        | (TermKind::RecordField { ident, .. }, Types(AbsType::Arrow(..))) => {
+            (ident.label.clone(), FUNCTION)
+        }
+        (TermKind::Declaration(ident, _), _) => (ident.label.clone(), VARIABLE),
+        (TermKind::RecordField { ident, .. }, _) => (ident.label.clone(), PROPERTY),
+        _ => return None,
+    };
+
+    Some((label, token_type, modifiers))
+}
+
+/// Compute the semantic tokens of the identifiers of `file_id`, encoded relatively to each other
+/// as required by the protocol.
+fn tokens(linearization: &Completed, file_id: FileId, files: &Files<String>) -> Vec<SemanticToken> {
+    let source = files.source(file_id);
+
+    let mut identifiers: Vec<_> = linearization
+        .linearization
+        .iter()
+        .filter(|item| item.pos.src_id == file_id)
+        .filter_map(|item| {
+            let (label, token_type, modifiers) = classify(linearization, item)?;
+            let span = item.pos.start.to_usize()..item.pos.end.to_usize();
+
+            // Skip identifiers that don't appear verbatim in the source, such as quoted field
+            // names or generated identifiers located at their enclosing term
+            if source.get(span.clone()) != Some(label.as_str()) {
+                return None;
+            }
+
+            let range = codespan_lsp::byte_span_to_range(files, file_id, span).ok()?;
+            if range.start.line == range.end.line {
+                Some((
+                    range.start,
+                    range.end.character - range.start.character,
+                    token_type,
+                    modifiers,
+                ))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    identifiers.sort_by_key(|(start, ..)| (start.line, start.character));
+    identifiers.dedup_by_key(|(start, ..)| *start);
+
+    let mut previous = lsp_types::Position::default();
+    identifiers
+        .into_iter()
+        .map(|(start, length, token_type, modifiers)| {
+            let delta_line = start.line - previous.line;
+            let delta_start = if delta_line == 0 {
+                start.character - previous.character
+            } else {
+                start.character
+            };
+            previous = start;
+
+            SemanticToken {
+                delta_line,
+                delta_start,
+                length,
+                token_type,
+                token_modifiers_bitset: modifiers,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{PartialResultParams, TextDocumentIdentifier, WorkDoneProgressParams};
+
+    use super::*;
+    use crate::testing::TestServer;
+
+    /// The tokens of `text`, with absolute positions.
+    fn tokens(text: &str) -> Vec<(u32, u32, u32, u32, u32)> {
+        let mut server = TestServer::new();
+        let uri = server.open("file:///test.ncl", text);
+
+        let tokens: SemanticTokens = server
+            .request(
+                handle_semantic_tokens,
+                SemanticTokensParams {
+                    text_document: TextDocumentIdentifier { uri },
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                    partial_result_params: PartialResultParams::default(),
+                },
+            )
+            .unwrap();
+
+        let (mut line, mut character) = (0, 0);
+        tokens
+            .data
+            .into_iter()
+            .map(|token| {
+                if token.delta_line > 0 {
+                    character = 0;
+                }
+                line += token.delta_line;
+                character += token.delta_start;
+                (
+                    line,
+                    character,
+                    token.length,
+                    token.token_type,
+                    token.token_modifiers_bitset,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn variables_fields_and_functions() {
+        let text = "let r = { foo = 1 } in\nlet f : Num -> Num = fun x => x in\nf r.foo";
+
+        assert_eq!(
+            tokens(text),
+            vec![
+                (0, 4, 1, VARIABLE, DECLARATION),
+                (0, 10, 3, PROPERTY, DECLARATION),
+                (1, 4, 1, FUNCTION, DECLARATION),
+                (1, 25, 1, VARIABLE, DECLARATION),
+                (1, 30, 1, VARIABLE, 0),
+                (2, 0, 1, FUNCTION, 0),
+                (2, 2, 1, VARIABLE, 0),
+                (2, 4, 3, PROPERTY, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn generated_identifiers_are_skipped() {
+        assert_eq!(
+            tokens("let add = (+) in add 1 2"),
+            vec![(0, 4, 3, VARIABLE, DECLARATION), (0, 17, 3, VARIABLE, 0)]
+        );
+    }
+}
@@ -1,10 +1,19 @@
+use std::collections::{HashMap, HashSet};
+
 use crate::{
-    linearization::interface::TermKind,
+    cache::CacheExt,
+    linearization::{
+        completed::Completed,
+        interface::{Resolved, TermKind},
+        LinearizationItem,
+    },
     term::RawSpanExt,
     trace::{Enrich, Trace},
 };
+use codespan::Files;
 use lsp_server::{RequestId, Response, ResponseError};
 use lsp_types::{DocumentSymbol, DocumentSymbolParams, SymbolKind};
+use nickel::types::{AbsType, Types};
 use serde_json::Value;
 
 use crate::server::Server;
@@ -14,39 +23,25 @@ pub fn handle_document_symbols(
     id: RequestId,
     server: &mut Server,
 ) -> Result<(), ResponseError> {
-    let file_id = server
-        .cache
-        .id_of(params.text_document.uri.to_string())
-        .unwrap();
+    let file_id = server.cache.id_of_uri(&params.text_document.uri).unwrap();
 
     if let Some(completed) = server.lin_cache.get(&file_id) {
         Trace::enrich(&id, completed);
+
+        let records = owned_records(completed);
+        let nested: HashSet<usize> = records.values().copied().collect();
+
+        // Fields of records defined by a declaration or another field are nested under them,
+        // the other ones are at the top-level together with the declarations
         let symbols = completed
             .linearization
             .iter()
-            .filter_map(|item| match &item.kind {
-                TermKind::Declaration(name, _) => {
-                    let (file_id, span) = item.pos.to_range();
-
-                    let range =
-                        codespan_lsp::byte_span_to_range(server.cache.files(), file_id, span)
-                            .unwrap();
-
-                    // `deprecated` is a required field but causes a warning although we are not using it
-                    #[allow(deprecated)]
-                    Some(DocumentSymbol {
-                        name: name.to_string(),
-                        detail: Some(format!("{}", item.ty)),
-                        kind: SymbolKind::Variable,
-                        tags: None,
-                        range,
-                        selection_range: range,
-                        children: None,
-                        deprecated: None,
-                    })
-                }
-                _ => None,
+            .filter(|item| match item.kind {
+                TermKind::Declaration(..) => true,
+                TermKind::RecordField { record, .. } => !nested.contains(&record),
+                _ => false,
             })
+            .filter_map(|item| symbol(completed, &records, server.cache.files(), item))
             .collect::<Vec<_>>();
 
         server.reply(Response::new_ok(id, symbols));
@@ -56,3 +51,177 @@ pub fn handle_document_symbols(
 
     Ok(())
 }
+
+/// Map the declarations and record fields whose value is a record literal to the id of this
+/// record.
+fn owned_records(completed: &Completed) -> HashMap<usize, usize> {
+    completed
+        .linearization
+        .iter()
+        .filter_map(|item| {
+            let value = match item.kind {
+                // the value of a declaration is the item following it
+                TermKind::Declaration(..) => completed.get_item(item.id + 1),
+                TermKind::RecordField {
+                    value: Some(value), ..
+                } => completed.get_item(value),
+                _ => None,
+            }?;
+
+            matches!(value.kind, TermKind::Record(_)).then(|| (item.id, value.id))
+        })
+        .collect()
+}
+
+fn symbol(
+    completed: &Completed,
+    records: &HashMap<usize, usize>,
+    files: &Files<String>,
+    item: &LinearizationItem<Resolved>,
+) -> Option<DocumentSymbol> {
+    let name = match &item.kind {
+        TermKind::Declaration(ident, _) | TermKind::RecordField { ident, .. } => ident.to_string(),
+        _ => return None,
+    };
+
+    let (file_id, span) = item.pos.to_range();
+    let selection_range = codespan_lsp::byte_span_to_range(files, file_id, span.clone()).ok()?;
+
+    let record = records.get(&item.id).and_then(|id| completed.get_item(*id));
+
+    // The symbol of a record extends up to the end of the record literal
+    let range = match record {
+        Some(record) if record.pos.src_id == file_id => {
+            let end = span.end.max(record.pos.end.to_usize());
+            codespan_lsp::byte_span_to_range(files, file_id, span.start..end).ok()?
+        }
+        _ => selection_range,
+    };
+
+    let children = match record.map(|record| &record.kind) {
+        Some(TermKind::Record(fields)) => {
+            let mut fields: Vec<_> = fields
+                .values()
+                .filter_map(|id| completed.get_item(*id))
+                .collect();
+            fields.sort_by_key(|field| field.pos.start);
+
+            Some(
+                fields
+                    .into_iter()
+                    .filter_map(|field| symbol(completed, records, files, field))
+                    .collect(),
+            )
+        }
+        _ => None,
+    };
+
+    let kind = match (&item.kind, &item.ty) {
+        _ if record.is_some() => SymbolKind::Object,
+        (_, Types(AbsType::Arrow(..))) => SymbolKind::Function,
+        (TermKind::RecordField { .. }, _) => SymbolKind::Field,
+        _ => SymbolKind::Variable,
+    };
+
+    // `deprecated` is a required field but causes a warning although we are not using it
+    #[allow(deprecated)]
+    Some(DocumentSymbol {
+        name,
+        detail: Some(format!("{}", item.ty)),
+        kind,
+        tags: None,
+        range,
+        selection_range,
+        children,
+        deprecated: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{PartialResultParams, TextDocumentIdentifier, WorkDoneProgressParams};
+
+    use super::*;
+    use crate::testing::TestServer;
+
+    #[derive(Debug, PartialEq)]
+    struct Outline(String, SymbolKind, Vec<Outline>);
+
+    impl From<DocumentSymbol> for Outline {
+        fn from(symbol: DocumentSymbol) -> Self {
+            Outline(
+                symbol.name,
+                symbol.kind,
+                symbol
+                    .children
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(Outline::from)
+                    .collect(),
+            )
+        }
+    }
+
+    fn outline(text: &str) -> Vec<Outline> {
+        let mut server = TestServer::new();
+        let uri = server.open("file:///test.ncl", text);
+
+        server
+            .request::<_, Vec<DocumentSymbol>>(
+                handle_document_symbols,
+                DocumentSymbolParams {
+                    text_document: TextDocumentIdentifier { uri },
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                    partial_result_params: PartialResultParams::default(),
+                },
+            )
+            .unwrap()
+            .into_iter()
+            .map(Outline::from)
+            .collect()
+    }
+
+    fn leaf(name: &str, kind: SymbolKind) -> Outline {
+        Outline(String::from(name), kind, Vec::new())
+    }
+
+    #[test]
+    fn nested_records() {
+        let text = "let r = { a = 1, b = { c = 2 } } in\nlet f : Num -> Num = fun x => x in\nr";
+
+        assert_eq!(
+            outline(text),
+            vec![
+                Outline(
+                    String::from("r"),
+                    SymbolKind::Object,
+                    vec![
+                        leaf("a", SymbolKind::Field),
+                        Outline(
+                            String::from("b"),
+                            SymbolKind::Object,
+                            vec![leaf("c", SymbolKind::Field)]
+                        ),
+                    ]
+                ),
+                leaf("f", SymbolKind::Function),
+                leaf("x", SymbolKind::Variable),
+            ]
+        );
+    }
+
+    #[test]
+    fn top_level_record() {
+        assert_eq!(
+            outline("{ foo = { bar = 1 }, baz = 2 }"),
+            vec![
+                Outline(
+                    String::from("foo"),
+                    SymbolKind::Object,
+                    vec![leaf("bar", SymbolKind::Field)]
+                ),
+                leaf("baz", SymbolKind::Field),
+            ]
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use anyhow::Result;
 use codespan::FileId;
@@ -12,7 +12,8 @@ use lsp_types::{
     request::{Request as RequestTrait, *},
     CompletionOptions, CompletionParams, DidChangeTextDocumentParams, DidOpenTextDocumentParams,
     DocumentSymbolParams, GotoDefinitionParams, HoverOptions, HoverParams, HoverProviderCapability,
-    OneOf, ReferenceParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+    OneOf, ReferenceParams, RenameParams, SemanticTokensFullOptions, SemanticTokensOptions,
+    SemanticTokensParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
     TextDocumentSyncOptions, WorkDoneProgressOptions,
 };
 
@@ -21,7 +22,7 @@ use nickel::typecheck::Environment;
 
 use crate::{
     linearization::completed::Completed,
-    requests::{completion, goto, hover, symbols},
+    requests::{completion, goto, hover, rename, semantic_tokens, symbols},
     trace::Trace,
 };
 
@@ -30,6 +31,9 @@ pub struct Server {
     pub cache: Cache,
     pub lin_cache: HashMap<FileId, Completed>,
     pub global_env: Environment,
+    /// The files imported by each document, used to typecheck a document again when one of its
+    /// imports changes.
+    pub imports: HashMap<FileId, HashSet<FileId>>,
 }
 
 impl Server {
@@ -49,11 +53,20 @@ impl Server {
             })),
             definition_provider: Some(OneOf::Left(true)),
             references_provider: Some(OneOf::Left(true)),
+            rename_provider: Some(OneOf::Left(true)),
             completion_provider: Some(CompletionOptions {
-                trigger_characters: Some(vec![]),
+                trigger_characters: Some(vec![String::from(".")]),
                 ..Default::default()
             }),
             document_symbol_provider: Some(OneOf::Left(true)),
+            semantic_tokens_provider: Some(
+                SemanticTokensOptions {
+                    legend: semantic_tokens::legend(),
+                    full: Some(SemanticTokensFullOptions::Bool(true)),
+                    ..Default::default()
+                }
+                .into(),
+            ),
             ..ServerCapabilities::default()
         }
     }
@@ -68,6 +81,7 @@ impl Server {
             cache,
             lin_cache,
             global_env,
+            imports: HashMap::new(),
         }
     }
 
@@ -176,6 +190,12 @@ impl Server {
                 goto::handle_to_usages(params, req.id.clone(), self)
             }
 
+            Rename::METHOD => {
+                debug!("handle rename");
+                let params: RenameParams = serde_json::from_value(req.params).unwrap();
+                rename::handle_rename(params, req.id.clone(), self)
+            }
+
             Completion::METHOD => {
                 debug!("handle completion");
                 let params: CompletionParams = serde_json::from_value(req.params).unwrap();
@@ -188,6 +208,12 @@ impl Server {
                 symbols::handle_document_symbols(params, req.id.clone(), self)
             }
 
+            SemanticTokensFullRequest::METHOD => {
+                debug!("handle semantic tokens");
+                let params: SemanticTokensParams = serde_json::from_value(req.params).unwrap();
+                semantic_tokens::handle_semantic_tokens(params, req.id.clone(), self)
+            }
+
             _ => Ok(()),
         };
 
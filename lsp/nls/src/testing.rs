@@ -0,0 +1,126 @@
+//! Helpers to drive a [Server] from tests, using an in-memory connection in place of a client.
+use lsp_server::{Connection, Message, RequestId, ResponseError};
+use lsp_types::{
+    Diagnostic, DidChangeTextDocumentParams, DidOpenTextDocumentParams, Position,
+    PublishDiagnosticsParams, TextDocumentContentChangeEvent, TextDocumentIdentifier,
+    TextDocumentItem, TextDocumentPositionParams, Url, VersionedTextDocumentIdentifier,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{files, server::Server};
+
+/// The signature shared by the request handlers.
+pub type Handler<P> = fn(P, RequestId, &mut Server) -> Result<(), ResponseError>;
+
+pub struct TestServer {
+    pub server: Server,
+    client: Connection,
+    next_id: i32,
+}
+
+impl TestServer {
+    pub fn new() -> Self {
+        let (connection, client) = Connection::memory();
+        TestServer {
+            server: Server::new(connection),
+            client,
+            next_id: 0,
+        }
+    }
+
+    /// Open a document, discarding the diagnostics published in response.
+    pub fn open(&mut self, uri: &str, text: &str) -> Url {
+        self.open_with_diagnostics(uri, text).0
+    }
+
+    /// Open a document and return the diagnostics published in response.
+    pub fn open_with_diagnostics(&mut self, uri: &str, text: &str) -> (Url, Vec<Diagnostic>) {
+        let uri = Url::parse(uri).unwrap();
+        files::handle_open(
+            &mut self.server,
+            DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: String::from("nickel"),
+                    version: 0,
+                    text: String::from(text),
+                },
+            },
+        )
+        .unwrap();
+
+        match self.receive() {
+            Message::Notification(notification) => {
+                let params: PublishDiagnosticsParams =
+                    serde_json::from_value(notification.params).unwrap();
+                assert_eq!(params.uri, uri);
+                (uri, params.diagnostics)
+            }
+            msg => panic!("expected diagnostics, got {:?}", msg),
+        }
+    }
+
+    /// Change the content of a document, and return the diagnostics published in response.
+    pub fn change(&mut self, uri: &Url, text: &str) -> Vec<PublishDiagnosticsParams> {
+        files::handle_save(
+            &mut self.server,
+            DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: uri.clone(),
+                    version: 1,
+                },
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: String::from(text),
+                }],
+            },
+        )
+        .unwrap();
+
+        let mut published = Vec::new();
+        while let Ok(msg) = self.client.receiver.try_recv() {
+            match msg {
+                Message::Notification(notification) => {
+                    published.push(serde_json::from_value(notification.params).unwrap())
+                }
+                msg => panic!("expected diagnostics, got {:?}", msg),
+            }
+        }
+        published
+    }
+
+    /// Call a request handler and deserialize the response sent to the client.
+    pub fn request<P, R: DeserializeOwned>(
+        &mut self,
+        handler: Handler<P>,
+        params: P,
+    ) -> Result<R, ResponseError> {
+        self.next_id += 1;
+        handler(params, RequestId::from(self.next_id), &mut self.server)?;
+
+        match self.receive() {
+            Message::Response(response) => match response.error {
+                Some(err) => Err(err),
+                None => Ok(serde_json::from_value(response.result.unwrap_or_default()).unwrap()),
+            },
+            msg => panic!("expected a response, got {:?}", msg),
+        }
+    }
+
+    /// Take the next message sent by the server.
+    pub fn receive(&mut self) -> Message {
+        self.client
+            .receiver
+            .try_recv()
+            .expect("the server didn't send any message")
+    }
+}
+
+/// Build the parameters of a request pointing at `position` in the document `uri`.
+pub fn position_params(uri: &Url, line: u32, character: u32) -> TextDocumentPositionParams {
+    TextDocumentPositionParams {
+        text_document: TextDocumentIdentifier { uri: uri.clone() },
+        position: Position { line, character },
+    }
+}
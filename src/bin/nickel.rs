@@ -1,5 +1,5 @@
 //! Entry point of the program.
-use nickel::error::{Error, IOError};
+use nickel::error::{explain, Error, IOError};
 use nickel::program::Program;
 use nickel::repl::query_print;
 #[cfg(feature = "repl")]
@@ -20,11 +20,21 @@ struct Opt {
     #[structopt(parse(from_os_str))]
     file: Option<PathBuf>,
 
+    /// Add a directory to the import search path. Relative imports not found next to the
+    /// importing file are searched in these directories, in order
+    #[structopt(short = "I", long = "import-path", value_name = "DIR")]
+    #[structopt(parse(from_os_str))]
+    import_paths: Vec<PathBuf>,
+
     #[cfg(debug_assertions)]
     /// Skip the standard library import, for debugging only, does not affect REPL
     #[structopt(long)]
     nostdlib: bool,
 
+    /// Print the explanation of an error code, such as `E0001`
+    #[structopt(long, value_name = "CODE")]
+    explain: Option<String>,
+
     #[structopt(subcommand)]
     command: Option<Command>,
 }
@@ -34,7 +44,7 @@ struct Opt {
 enum Command {
     /// Export the result to a different format
     Export {
-        /// Available formats: `raw, json, yaml, toml`. Default format: `json`.
+        /// Available formats: `raw, json, yaml, toml, xml, ini`. Default format: `json`.
         #[structopt(long)]
         format: Option<ExportFormat>,
         /// Output file. Standard output by default
@@ -68,6 +78,18 @@ enum Command {
 fn main() {
     let opts = Opt::from_args();
 
+    if let Some(code) = opts.explain {
+        match explain::explain(&code) {
+            Some(explanation) => println!("{}", explanation),
+            None => {
+                eprintln!("error: unknown error code `{}`", code);
+                process::exit(1)
+            }
+        }
+
+        return;
+    }
+
     if let Some(Command::Repl { history_file }) = opts.command {
         let histfile = if let Some(h) = history_file {
             h
@@ -94,6 +116,8 @@ fn main() {
                 process::exit(1)
             });
 
+        program.add_import_paths(opts.import_paths);
+
         #[cfg(debug_assertions)]
         if opts.nostdlib {
             program.set_skip_stdlib();
@@ -1,15 +1,20 @@
 //! Source cache.
 
-use crate::error::{Error, ImportError, ParseError, ParseErrors, TypecheckError};
+use crate::error::{Error, ImportError, ParseError, ParseErrors, ToDiagnostic, TypecheckError};
+use crate::eval::{lazy::Thunk, Closure, IdentKind};
+use crate::identifier::Ident;
+use crate::mk_app;
 use crate::parser::lexer::Lexer;
 use crate::position::TermPos;
 use crate::stdlib as nickel_stdlib;
-use crate::term::{RichTerm, SharedTerm, Term};
+use crate::term::{make as mk_term, NativeFn, RichTerm, SharedTerm, Term, UnaryOp};
 use crate::transform::import_resolution;
 use crate::typecheck;
-use crate::typecheck::{linearization::StubHost, type_check};
-use crate::{eval, parser, transform};
+use crate::typecheck::{linearization::StubHost, type_check, TypeWrapper};
+use crate::types::{AbsType, Types};
+use crate::{eval, parser, serialize, transform};
 use codespan::{FileId, Files};
+use codespan_reporting::term::termcolor::NoColor;
 use io::Read;
 use std::collections::hash_map;
 use std::collections::{HashMap, HashSet};
@@ -28,6 +33,8 @@ pub enum InputFormat {
     Json,
     Yaml,
     Toml,
+    Xml,
+    Ini,
 }
 
 impl InputFormat {
@@ -37,6 +44,8 @@ impl InputFormat {
             Some("json") => Some(InputFormat::Json),
             Some("yaml") | Some("yml") => Some(InputFormat::Yaml),
             Some("toml") => Some(InputFormat::Toml),
+            Some("xml") => Some(InputFormat::Xml),
+            Some("ini") => Some(InputFormat::Ini),
             _ => None,
         }
     }
@@ -68,6 +77,9 @@ pub struct Cache {
     terms: HashMap<FileId, CachedTerm>,
     /// The list of ids corresponding to the stdlib modules
     stdlib_ids: Option<Vec<FileId>>,
+    /// The directories where relative imports are searched, in order, when they are not found
+    /// relatively to the importing file.
+    import_paths: Vec<PathBuf>,
 
     #[cfg(debug_assertions)]
     /// Skip loading the stdlib, used for debugging purpose
@@ -92,6 +104,40 @@ impl GlobalEnv {
             type_env: typecheck::Environment::new(),
         }
     }
+
+    /// Add a builtin implemented in Rust to the environment, under the name `name`.
+    ///
+    /// The builtin is a curried function which forces its arguments, and then calls the native
+    /// function on their values. Its type is `Dyn -> .. -> Dyn`, with one `Dyn` per argument, or
+    /// `Dyn` if the native function doesn't take any argument.
+    pub fn with_builtin(mut self, name: impl Into<Ident>, native: NativeFn) -> Self {
+        let id = name.into();
+        let params: Vec<Ident> = (0..native.arity())
+            .map(|_| transform::fresh_var())
+            .collect();
+
+        let ty = params.iter().fold(Types(AbsType::Dyn()), |acc, _| {
+            Types(AbsType::Arrow(
+                Box::new(Types(AbsType::Dyn())),
+                Box::new(acc),
+            ))
+        });
+
+        let args: RichTerm = Term::List(params.iter().cloned().map(mk_term::var).collect()).into();
+        let body = mk_app!(
+            mk_term::op1(UnaryOp::DeepSeq(), args.clone()),
+            mk_term::op1(UnaryOp::Native(native), args)
+        );
+        let fun = params
+            .into_iter()
+            .rev()
+            .fold(body, |acc, param| Term::Fun(param, acc).into());
+
+        self.type_env.insert(id.clone(), TypeWrapper::from(ty));
+        self.eval_env
+            .insert(id, Thunk::new(Closure::atomic_closure(fun), IdentKind::Let));
+        self
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -207,12 +253,82 @@ impl Cache {
             terms: HashMap::new(),
             imports: HashMap::new(),
             stdlib_ids: None,
+            import_paths: Vec::new(),
 
             #[cfg(debug_assertions)]
             skip_stdlib: false,
         }
     }
 
+    /// Add directories to the import search path. A relative import which is not found next to
+    /// the importing file is looked up in each directory of the search path, in the order they
+    /// were added.
+    pub fn add_import_paths<P>(&mut self, paths: impl IntoIterator<Item = P>)
+    where
+        P: Into<PathBuf>,
+    {
+        self.import_paths.extend(paths.into_iter().map(Into::into));
+    }
+
+    /// Locate an imported file and load it in the file database if needed, without parsing it.
+    /// Return the path where the file has been found.
+    ///
+    /// The import is first looked up relatively to the importing file and then, if it is
+    /// relative, in each directory of the search path.
+    fn find_import(
+        &mut self,
+        path: &OsStr,
+        parent: &Option<PathBuf>,
+        pos: &TermPos,
+    ) -> Result<(PathBuf, CacheOp<FileId>), ImportError> {
+        let candidates: Vec<PathBuf> = std::iter::once(with_parent(path, parent.clone()))
+            .chain(
+                self.import_paths
+                    .iter()
+                    .filter(|_| Path::new(path).is_relative())
+                    .map(|dir| dir.join(path)),
+            )
+            .collect();
+
+        let mut found = None;
+        let mut first_err = None;
+        for candidate in candidates.iter() {
+            match self.get_or_add_file(candidate) {
+                Ok(id_op) => {
+                    found = Some((candidate.clone(), id_op));
+                    break;
+                }
+                // Only a missing file makes us try the next location: other errors, such as a
+                // lack of permission, are reported right away.
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    first_err.get_or_insert(err);
+                }
+                Err(err) => {
+                    return Err(ImportError::IOError(
+                        path.to_string_lossy().into_owned(),
+                        format!("{}", err),
+                        *pos,
+                    ))
+                }
+            }
+        }
+
+        found.ok_or_else(|| {
+            let err = first_err.map(|err| format!("{}", err)).unwrap_or_default();
+            let msg = if candidates.len() > 1 {
+                let searched: Vec<_> = candidates
+                    .iter()
+                    .map(|candidate| candidate.to_string_lossy())
+                    .collect();
+                format!("{} (searched in: {})", err, searched.join(", "))
+            } else {
+                err
+            };
+
+            ImportError::IOError(path.to_string_lossy().into_owned(), msg, *pos)
+        })
+    }
+
     /// Load a file in the file database. Do not insert an entry in the name-id table.
     fn load_file(&mut self, path: impl Into<OsString>) -> io::Result<FileId> {
         let path = path.into();
@@ -271,6 +387,9 @@ impl Cache {
 
     /// Try to retrieve the id of a file from the cache, using the normalized path and comparing
     /// timestamps. If it was not in cache, add it as a new entry.
+    ///
+    /// A source added as a string under the normalized path of the file, such as the unsaved
+    /// content of a file opened in an editor, takes precedence over the file itself.
     pub fn get_or_add_file(&mut self, path: impl Into<OsString>) -> io::Result<CacheOp<FileId>> {
         let path = path.into();
         let timestamp = timestamp(&path)?;
@@ -387,25 +506,7 @@ impl Cache {
         file_id: FileId,
         format: InputFormat,
     ) -> Result<(RichTerm, ParseErrors), ParseError> {
-        let buf = self.files.source(file_id);
-
-        match format {
-            InputFormat::Nickel => {
-                let (t, parse_errs) = parser::grammar::TermParser::new()
-                    .parse_term_tolerant(file_id, Lexer::new(buf))?;
-
-                Ok((t, parse_errs))
-            }
-            InputFormat::Json => serde_json::from_str(self.files.source(file_id))
-                .map(|t| (t, ParseErrors::default()))
-                .map_err(|err| ParseError::from_serde_json(err, file_id, &self.files)),
-            InputFormat::Yaml => serde_yaml::from_str(self.files.source(file_id))
-                .map(|t| (t, ParseErrors::default()))
-                .map_err(|err| (ParseError::from_serde_yaml(err, file_id))),
-            InputFormat::Toml => toml::from_str(self.files.source(file_id))
-                .map(|t| (t, ParseErrors::default()))
-                .map_err(|err| (ParseError::from_toml(err, file_id, &self.files))),
-        }
+        parse_source(&self.files, file_id, format)
     }
 
     /// Typecheck an entry of the cache and update its state accordingly, or do nothing if the
@@ -681,9 +782,9 @@ impl Cache {
     /// Retrieve the id of a file given a path.
     ///
     /// This function normalizes the given path, search it in the name-id table, and check that the
-    /// stored timestamp is the same as the current timestamp of the file. If normalization or
-    /// metadata retrieval fails, or if the stored entry has no timestamps (it was added as a
-    /// stand-alone source), `None` is returned.
+    /// stored timestamp is the same as the current timestamp of the file. If the stored entry has
+    /// no timestamp, it was added as a stand-alone source under this path and is returned as is.
+    /// If normalization or metadata retrieval fails, `None` is returned.
     pub fn id_of_file(&self, path: impl AsRef<OsStr>) -> io::Result<Option<FileId>> {
         let normalized = normalize_path(PathBuf::from(path.as_ref()).as_path())?;
         let timestamp = timestamp(path)?;
@@ -697,7 +798,8 @@ impl Cache {
             .get(path.as_ref())
             .and_then(|entry| match entry.timestamp {
                 Some(ts) if ts == timestamp => Some(entry.id),
-                _ => None,
+                Some(_) => None,
+                None => Some(entry.id),
             })
     }
 
@@ -753,6 +855,19 @@ impl Cache {
         self.terms.get(&file_id).map(|CachedTerm { term, .. }| term)
     }
 
+    /// Render an error as a diagnostic, including the snippets of the source code it refers to,
+    /// and return it as an uncolored string.
+    ///
+    /// Building a diagnostic may require adding new snippets to the file database (see
+    /// [`label_alt`](../error/fn.label_alt.html)), which is why the rendering operates on a
+    /// copy of the files of the cache.
+    pub fn render_error<E>(&self, error: E) -> String
+    where
+        E: ToDiagnostic<FileId>,
+    {
+        render_error(&self.files, self.id_of("<stdlib/contracts.ncl>"), error)
+    }
+
     /// Load and parse the standard library in the cache.
     pub fn load_stdlib(&mut self) -> Result<CacheOp<()>, Error> {
         if self.stdlib_ids.is_some() {
@@ -805,7 +920,8 @@ impl Cache {
     /// for performance reason: this is done in the test suite.
     /// Return a global environment containing both eval and type environment. If you need only the
     /// type environment, use `load_stdlib()` then `mk_global_type` to avoid
-    /// transformations and evaluation preparation.
+    /// transformations and evaluation preparation. The entries of the eval environment are
+    /// evaluated upfront (see [`eval::eval_global_env`](../eval/fn.eval_global_env.html)).
     pub fn prepare_stdlib(&mut self) -> Result<GlobalEnv, Error> {
         #[cfg(debug_assertions)]
         if self.skip_stdlib {
@@ -825,6 +941,8 @@ impl Cache {
                     .unwrap_error("cache::prepare_stdlib(): expected standard library to be parsed")
             })?;
         let eval_env = self.mk_eval_env().unwrap();
+        // The standard library doesn't contain any import.
+        let eval_env = eval::eval_global_env(&eval_env, &mut resolvers::DummyResolver {})?;
         Ok(GlobalEnv { eval_env, type_env })
     }
 
@@ -905,6 +1023,17 @@ pub trait ImportResolver {
         pos: &TermPos,
     ) -> Result<(ResolvedTerm, FileId), ImportError>;
 
+    /// Resolve an import of the raw content of a file, as done by `importStr`.
+    ///
+    /// The file is looked up and stored in the file cache as for [`resolve`](#method.resolve), but
+    /// it is not parsed: its content is returned as is.
+    fn resolve_str(
+        &mut self,
+        path: &OsStr,
+        parent: Option<PathBuf>,
+        pos: &TermPos,
+    ) -> Result<String, ImportError>;
+
     /// Get a resolved import from the term cache.
     fn get(&self, file_id: FileId) -> Option<RichTerm>;
 
@@ -918,18 +1047,15 @@ impl ImportResolver for Cache {
         parent: Option<PathBuf>,
         pos: &TermPos,
     ) -> Result<(ResolvedTerm, FileId), ImportError> {
-        let path_buf = with_parent(path, parent.clone());
+        let (path_buf, id_op) = self.find_import(path, &parent, pos)?;
         let format = InputFormat::from_path_buf(&path_buf).unwrap_or(InputFormat::Nickel);
-        let id_op = self.get_or_add_file(&path_buf).map_err(|err| {
-            ImportError::IOError(
-                path.to_string_lossy().into_owned(),
-                format!("{}", err),
-                *pos,
-            )
-        })?;
         let file_id = match id_op {
-            CacheOp::Cached(id) => return Ok((ResolvedTerm::FromCache(), id)),
-            CacheOp::Done(id) => {
+            CacheOp::Cached(id) if self.terms.contains_key(&id) => {
+                return Ok((ResolvedTerm::FromCache(), id))
+            }
+            // The file may be in the cache but not parsed yet, if it has only been imported as a
+            // string before.
+            CacheOp::Cached(id) | CacheOp::Done(id) => {
                 if let Some(parent) = parent {
                     let parent_id = self.id_of(parent).unwrap();
                     if let Some(imports) = self.imports.get_mut(&parent_id) {
@@ -951,6 +1077,16 @@ impl ImportResolver for Cache {
         Ok((ResolvedTerm::FromFile { path: path_buf }, file_id))
     }
 
+    fn resolve_str(
+        &mut self,
+        path: &OsStr,
+        parent: Option<PathBuf>,
+        pos: &TermPos,
+    ) -> Result<String, ImportError> {
+        let (_, id_op) = self.find_import(path, &parent, pos)?;
+        Ok(self.files.source(id_op.inner()).clone())
+    }
+
     fn get(&self, file_id: FileId) -> Option<RichTerm> {
         self.terms
             .get(&file_id)
@@ -965,6 +1101,59 @@ impl ImportResolver for Cache {
     }
 }
 
+/// Render an error as an uncolored string, using the sources of a file database. See
+/// [`Cache::render_error`](struct.Cache.html#method.render_error).
+pub fn render_error<E>(files: &Files<String>, contracts_id: Option<FileId>, error: E) -> String
+where
+    E: ToDiagnostic<FileId>,
+{
+    let mut files = files.clone();
+    let mut buffer = NoColor::new(Vec::new());
+    let config = codespan_reporting::term::Config::default();
+    let diagnostics = error.to_diagnostic(&mut files, contracts_id);
+
+    diagnostics
+        .iter()
+        .try_for_each(|d| codespan_reporting::term::emit(&mut buffer, &config, &files, d))
+        .expect("cache::render_error(): could not write to an in-memory buffer");
+
+    String::from_utf8_lossy(&buffer.into_inner()).into_owned()
+}
+
+/// Parse a source of a file database in the given format. Data formats are decoded as by the
+/// `deserialize` primitive operator.
+fn parse_source(
+    files: &Files<String>,
+    file_id: FileId,
+    format: InputFormat,
+) -> Result<(RichTerm, ParseErrors), ParseError> {
+    let buf = files.source(file_id);
+
+    match format {
+        InputFormat::Nickel => {
+            let (t, parse_errs) =
+                parser::grammar::TermParser::new().parse_term_tolerant(file_id, Lexer::new(buf))?;
+
+            Ok((t, parse_errs))
+        }
+        InputFormat::Json => serde_json::from_str(buf)
+            .map(|t| (t, ParseErrors::default()))
+            .map_err(|err| ParseError::from_serde_json(err, file_id, files)),
+        InputFormat::Yaml => serde_yaml::from_str(buf)
+            .map(|t| (t, ParseErrors::default()))
+            .map_err(|err| (ParseError::from_serde_yaml(err, file_id))),
+        InputFormat::Toml => toml::from_str(buf)
+            .map(|t| (t, ParseErrors::default()))
+            .map_err(|err| (ParseError::from_toml(err, file_id, files))),
+        InputFormat::Xml => serialize::xml::from_str(buf)
+            .map(|t| (t, ParseErrors::default()))
+            .map_err(|err| ParseError::from_xml(err, file_id)),
+        InputFormat::Ini => serialize::ini::from_str(buf)
+            .map(|t| (t, ParseErrors::default()))
+            .map_err(|err| ParseError::from_ini(err, file_id, files)),
+    }
+}
+
 /// Compute the path of a file relatively to a parent.
 fn with_parent(path: &OsStr, parent: Option<PathBuf>) -> PathBuf {
     let mut path_buf = parent.unwrap_or_default();
@@ -985,9 +1174,11 @@ pub fn timestamp(path: impl AsRef<OsStr>) -> io::Result<SystemTime> {
     fs::metadata(path.as_ref())?.modified()
 }
 
-/// Provide mockup import resolvers for testing purpose.
+/// Provide alternative import resolvers, which don't access the file system. Used for testing
+/// purpose, or in environments without a file system such as WebAssembly.
 pub mod resolvers {
     use super::*;
+    use std::path::Component;
 
     /// A dummy resolver that panics when asked to do something. Used to test code that contains no
     /// import.
@@ -1003,6 +1194,15 @@ pub mod resolvers {
             panic!("cache::resolvers: dummy resolver should not have been invoked");
         }
 
+        fn resolve_str(
+            &mut self,
+            _path: &OsStr,
+            _parent: Option<PathBuf>,
+            _pos: &TermPos,
+        ) -> Result<String, ImportError> {
+            panic!("cache::resolvers: dummy resolver should not have been invoked");
+        }
+
         fn get(&self, _file_id: FileId) -> Option<RichTerm> {
             panic!("cache::resolvers: dummy resolver should not have been invoked");
         }
@@ -1037,15 +1237,9 @@ pub mod resolvers {
         }
     }
 
-    impl ImportResolver for SimpleResolver {
-        fn resolve(
-            &mut self,
-            path: &OsStr,
-            _parent: Option<PathBuf>,
-            pos: &TermPos,
-        ) -> Result<(ResolvedTerm, FileId), ImportError> {
-            let file_id = self
-                .file_cache
+    impl SimpleResolver {
+        fn file_id(&self, path: &OsStr, pos: &TermPos) -> Result<FileId, ImportError> {
+            self.file_cache
                 .get(path.to_string_lossy().as_ref())
                 .copied()
                 .ok_or_else(|| {
@@ -1054,13 +1248,31 @@ pub mod resolvers {
                         String::from("Import not found by the mockup resolver."),
                         *pos,
                     )
-                })?;
+                })
+        }
+    }
+
+    impl ImportResolver for SimpleResolver {
+        fn resolve(
+            &mut self,
+            path: &OsStr,
+            _parent: Option<PathBuf>,
+            pos: &TermPos,
+        ) -> Result<(ResolvedTerm, FileId), ImportError> {
+            let file_id = self.file_id(path, pos)?;
 
             if let hash_map::Entry::Vacant(e) = self.term_cache.entry(file_id) {
                 let buf = self.files.source(file_id);
-                let term = parser::grammar::TermParser::new()
-                    .parse_term(file_id, Lexer::new(buf))
-                    .map_err(|e| ImportError::ParseErrors(e, *pos))?;
+                let format =
+                    InputFormat::from_path_buf(Path::new(path)).unwrap_or(InputFormat::Nickel);
+                let term = match format {
+                    InputFormat::Nickel => parser::grammar::TermParser::new()
+                        .parse_term(file_id, Lexer::new(buf))
+                        .map_err(|e| ImportError::ParseErrors(e, *pos))?,
+                    format => parse_source(&self.files, file_id, format)
+                        .map(|(term, _)| term)
+                        .map_err(|e| ImportError::ParseErrors(e.into(), *pos))?,
+                };
                 e.insert(term);
                 Ok((
                     ResolvedTerm::FromFile {
@@ -1073,6 +1285,219 @@ pub mod resolvers {
             }
         }
 
+        fn resolve_str(
+            &mut self,
+            path: &OsStr,
+            _parent: Option<PathBuf>,
+            pos: &TermPos,
+        ) -> Result<String, ImportError> {
+            let file_id = self.file_id(path, pos)?;
+            Ok(self.files.source(file_id).clone())
+        }
+
+        fn get(&self, file_id: FileId) -> Option<RichTerm> {
+            self.term_cache.get(&file_id).cloned()
+        }
+
+        fn get_path(&self, file_id: FileId) -> &OsStr {
+            self.files.name(file_id)
+        }
+    }
+
+    /// Resolve imports from an in-memory virtual file system, mapping paths to the content of the
+    /// corresponding files.
+    ///
+    /// Contrary to [`SimpleResolver`], imports are resolved relatively to the path of the
+    /// importing file, as done by [`Cache`]. Paths are normalized lexically: `.` components are
+    /// removed and `..` components remove the preceding one. A path with a `..` component going
+    /// above the root of the virtual file system is an error. As for [`Cache`], the format of an
+    /// imported file is determined by its extension, and defaults to Nickel source code. When an
+    /// import is resolved for the first time, its own imports are resolved recursively and the
+    /// program transformations are applied, such that the term returned by [`get`](#method.get)
+    /// is ready to be evaluated.
+    pub struct VirtualFsResolver {
+        sources: HashMap<PathBuf, String>,
+        files: Files<String>,
+        file_ids: HashMap<PathBuf, FileId>,
+        term_cache: HashMap<FileId, RichTerm>,
+    }
+
+    impl VirtualFsResolver {
+        pub fn new() -> VirtualFsResolver {
+            VirtualFsResolver::with_files(Files::new())
+        }
+
+        /// Create a resolver adding imported files to an existing file database, for example the
+        /// one of a [`Cache`] holding the standard library, such that the ids of both don't clash.
+        pub fn with_files(files: Files<String>) -> VirtualFsResolver {
+            VirtualFsResolver {
+                sources: HashMap::new(),
+                files,
+                file_ids: HashMap::new(),
+                term_cache: HashMap::new(),
+            }
+        }
+
+        /// Get a reference to the underlying files, used to report errors.
+        pub fn files(&self) -> &Files<String> {
+            &self.files
+        }
+
+        /// Get a mutable reference to the underlying files, used to add the main program.
+        pub fn files_mut(&mut self) -> &mut Files<String> {
+            &mut self.files
+        }
+
+        /// Add a file to the virtual file system. Replace the previous content of the file, if
+        /// any, unless it has already been imported. Fail if the path goes above the root.
+        pub fn add_file(
+            &mut self,
+            path: impl AsRef<Path>,
+            source: String,
+        ) -> Result<(), ImportError> {
+            let path = path.as_ref();
+            let path_buf = normalize_import(path.as_os_str(), path, &TermPos::None)?;
+            self.sources.insert(path_buf, source);
+            Ok(())
+        }
+
+        /// Get the content of a file from its normalized path. `path` is the original path of the
+        /// import, used in the error message.
+        fn source(
+            &self,
+            path: &OsStr,
+            path_buf: &Path,
+            pos: &TermPos,
+        ) -> Result<String, ImportError> {
+            self.sources.get(path_buf).cloned().ok_or_else(|| {
+                ImportError::IOError(
+                    path.to_string_lossy().into_owned(),
+                    format!(
+                        "No such file in the virtual file system: {}",
+                        path_buf.to_string_lossy()
+                    ),
+                    *pos,
+                )
+            })
+        }
+
+        /// Parse an imported file, resolve its imports and transform it. See
+        /// [`resolve`](#method.resolve).
+        fn load(
+            &mut self,
+            file_id: FileId,
+            path_buf: &Path,
+            pos: &TermPos,
+        ) -> Result<RichTerm, ImportError> {
+            let format = InputFormat::from_path_buf(path_buf).unwrap_or(InputFormat::Nickel);
+            let term = match format {
+                InputFormat::Nickel => parser::grammar::TermParser::new()
+                    .parse_term(file_id, Lexer::new(self.files.source(file_id)))
+                    .map_err(|e| ImportError::ParseErrors(e, *pos))?,
+                format => parse_source(&self.files, file_id, format)
+                    .map(|(term, _)| term)
+                    .map_err(|e| ImportError::ParseErrors(e.into(), *pos))?,
+            };
+            // The untransformed term is put in the cache before its own imports are resolved, for
+            // the same reason as in `resolve`.
+            self.term_cache.insert(file_id, term.clone());
+
+            // The pending imports don't need to be processed: they have already been resolved
+            // recursively by the nested calls to `resolve`.
+            let (term, _) = import_resolution::resolve_imports(term, self)?;
+            Ok(transform::transform(term))
+        }
+    }
+
+    impl Default for VirtualFsResolver {
+        fn default() -> Self {
+            VirtualFsResolver::new()
+        }
+    }
+
+    /// Normalize a path without accessing the file system, by removing `.` components and
+    /// resolving `..` components against the preceding one. Return `None` if a `..` component has
+    /// no preceding one to remove, that is if the path goes above the root.
+    fn normalize_lexically(path: &Path) -> Option<PathBuf> {
+        let mut result = PathBuf::new();
+
+        for component in path.components() {
+            match component {
+                Component::CurDir => (),
+                Component::ParentDir => {
+                    if !result.pop() {
+                        return None;
+                    }
+                }
+                component => result.push(component),
+            }
+        }
+
+        Some(result)
+    }
+
+    /// Normalize the path `path_buf` of an import with [`normalize_lexically`]. `path` is the
+    /// original path of the import, used in the error message.
+    fn normalize_import(
+        path: &OsStr,
+        path_buf: &Path,
+        pos: &TermPos,
+    ) -> Result<PathBuf, ImportError> {
+        normalize_lexically(path_buf).ok_or_else(|| {
+            ImportError::IOError(
+                path.to_string_lossy().into_owned(),
+                format!(
+                    "The path goes above the root of the virtual file system: {}",
+                    path_buf.to_string_lossy()
+                ),
+                *pos,
+            )
+        })
+    }
+
+    impl ImportResolver for VirtualFsResolver {
+        fn resolve(
+            &mut self,
+            path: &OsStr,
+            parent: Option<PathBuf>,
+            pos: &TermPos,
+        ) -> Result<(ResolvedTerm, FileId), ImportError> {
+            let path_buf = normalize_import(path, &with_parent(path, parent), pos)?;
+
+            if let Some(file_id) = self.file_ids.get(&path_buf) {
+                return Ok((ResolvedTerm::FromCache(), *file_id));
+            }
+
+            let source = self.source(path, &path_buf, pos)?;
+            let file_id = self.files.add(path_buf.clone(), source);
+            // The file is registered before its own imports are resolved, such that cyclic imports
+            // terminate. The registration is undone if the import fails, as there is then no term
+            // to be served by a later import of the same path.
+            self.file_ids.insert(path_buf.clone(), file_id);
+
+            match self.load(file_id, &path_buf, pos) {
+                Ok(term) => {
+                    self.term_cache.insert(file_id, term);
+                    Ok((ResolvedTerm::FromFile { path: path_buf }, file_id))
+                }
+                Err(err) => {
+                    self.file_ids.remove(&path_buf);
+                    self.term_cache.remove(&file_id);
+                    Err(err)
+                }
+            }
+        }
+
+        fn resolve_str(
+            &mut self,
+            path: &OsStr,
+            parent: Option<PathBuf>,
+            pos: &TermPos,
+        ) -> Result<String, ImportError> {
+            let path_buf = normalize_import(path, &with_parent(path, parent), pos)?;
+            self.source(path, &path_buf, pos)
+        }
+
         fn get(&self, file_id: FileId) -> Option<RichTerm> {
             self.term_cache.get(&file_id).cloned()
         }
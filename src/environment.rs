@@ -124,6 +124,32 @@ impl<K: Hash + Eq, V: PartialEq> Environment<K, V> {
         }
     }
 
+    /// Consume the environment and push the values of the layers which are not shared with other
+    /// environments to `values`. Shared layers are released without being traversed.
+    ///
+    /// Dropping a value may drop other environments in turn. This method makes it possible to drop
+    /// long chains of environments and values iteratively rather than recursively.
+    pub fn into_unshared_values(self, values: &mut Vec<V>) {
+        let mut layers = vec![self.current];
+        let mut previous = self.previous.into_inner();
+
+        while let Some(env) = previous {
+            match Rc::try_unwrap(env) {
+                Ok(env) => {
+                    layers.push(env.current);
+                    previous = env.previous.into_inner();
+                }
+                Err(_) => break,
+            }
+        }
+
+        for layer in layers {
+            if let Ok(map) = Rc::try_unwrap(layer) {
+                values.extend(map.into_values());
+            }
+        }
+    }
+
     /// Checks if `current` has been cloned. If it has, it is present both in current and in
     /// previous, making it Rc strong count bigger than 1.
     fn was_cloned(&self) -> bool {
@@ -0,0 +1,478 @@
+//! Long explanations of error codes.
+//!
+//! Each variant of [`EvalError`](super::EvalError) and [`TypecheckError`](super::TypecheckError)
+//! has a stable code, returned by their respective `code()` methods, which is shown in
+//! diagnostics. The explanations are displayed by `nickel --explain <code>`.
+//!
+//! Codes are never reused: when a variant is removed, its code is retired together with it.
+
+/// The codes of errors, together with their explanation.
+const EXPLANATIONS: &[(&str, &str)] = &[
+    (
+        "E0001",
+        "\
+A contract was broken.
+
+A value was checked against a contract, either a type annotation that is checked at run-time
+or an explicit contract annotation `value | Contract`, and the contract failed. The error points
+to the contract and to the value that broke it. For function contracts, the error also tells if
+the function itself returned a wrong value, or if it was called with a wrong argument.
+
+    let f | Num -> Num = fun x => \"a\" in f 1",
+    ),
+    (
+        "E0002",
+        "\
+A field required by a record contract has no definition.
+
+A record contract may declare fields without giving them a value. Such fields must be defined
+by the records the contract is applied to, or merged with. Accessing one of them before it has
+been defined is an error.
+
+    let C = {name | Str} in ({} | #C).name",
+    ),
+    (
+        "E0003",
+        "\
+A value has the wrong type for an operation.
+
+A primitive operation, such as an arithmetic operation, a field access or a builtin function,
+was applied to a value of an unexpected type. Nickel is dynamically typed by default: this is
+only detected when the operation is evaluated. Typed code, between a type annotation `: Type`,
+catches such errors before evaluation.
+
+    1 + \"a\"",
+    ),
+    (
+        "E0004",
+        "\
+A value which is not a function was applied to an argument.
+
+Only functions can be applied. This usually happens when a function is given too many
+arguments, or when a record field or a variable doesn't hold the expected function.
+
+    let x = 1 in x 2",
+    ),
+    (
+        "E0005",
+        "\
+A required field is missing.
+
+A field access, or another record operation requiring a specific field, was performed on a
+record which doesn't have this field. Use `records.has_field` to test for the existence of a
+field, or provide a default value.
+
+    {foo = 1}.bar",
+    ),
+    (
+        "E0006",
+        "\
+A field is already defined.
+
+A record operation tried to add a field which is already present in the record. To replace
+the value of a field, remove it first, or use merging with default values.
+
+    {a = 1}$[\"a\" = 2]",
+    ),
+    (
+        "E0007",
+        "\
+An operation requiring a non-empty list was applied to an empty list.
+
+Operations such as `lists.head` or `lists.tail` don't have a meaningful result on an empty
+list. Test if the list is empty beforehand.
+
+    lists.head []",
+    ),
+    (
+        "E0008",
+        "\
+An integer was expected, but the number has a fractional part.
+
+Some operations, such as indexing a list or converting a code point to a character, only make
+sense for integers. Numbers are floating-point numbers, which can be rounded with
+`nums.floor` or similar functions.
+
+    lists.elem_at 0.5 [1, 2]",
+    ),
+    (
+        "E0009",
+        "\
+An index is out of bounds.
+
+An operation accessed an element of a list, or a position in a string, outside of its range of
+valid indices. Indices start at 0.
+
+    lists.elem_at 2 [1, 2]",
+    ),
+    (
+        "E0010",
+        "\
+A number was divided by zero.
+
+Division and modulo by zero are errors rather than returning infinite or undefined numbers.
+
+    1 / 0",
+    ),
+    (
+        "E0011",
+        "\
+A builtin function was applied to too few arguments.
+
+Primitive operators must be fully applied. This usually indicates a bug in the standard
+library or in code using primitive operators directly.",
+    ),
+    (
+        "E0012",
+        "\
+Two values can't be merged.
+
+Merging combines records recursively, but other values can only be merged if they are equal.
+Two different values for the same field, which are not default values, are a conflict. Use
+`| default` to define a value which can be overridden.
+
+    {a = 1} & {a = 2}",
+    ),
+    (
+        "E0013",
+        "\
+An identifier is not bound.
+
+A variable was used without being defined by a `let` binding, a function parameter or a field of
+a recursive record in scope. This may be a typo.
+
+Unbound identifiers are usually detected before evaluation, see E0101.",
+    ),
+    (
+        "E0014",
+        "\
+An infinite recursion was detected.
+
+A value was needed to compute itself, for example a field whose definition directly depends on
+its own value.
+
+    {a = a + 1}.a",
+    ),
+    (
+        "E0015",
+        "\
+The evaluation exceeded the maximum number of steps.
+
+The evaluation was configured with a limit on its number of steps, and was stopped after
+reaching it. This is typically caused by a non-terminating program.",
+    ),
+    (
+        "E0016",
+        "\
+A value can't be serialized.
+
+Serialization requires a fully evaluated value made of records, lists and base values.
+Functions and other special values can't be serialized, and some formats have additional
+restrictions, such as not supporting `null` or requiring a record at the top-level.
+
+    builtins.serialize `Json {f = fun x => x}",
+    ),
+    (
+        "E0017",
+        "\
+A string can't be deserialized.
+
+The string given to `deserialize` is not valid in the requested format. The error reports the
+position of the problem in the string when available.
+
+    builtins.deserialize `Json \"{\"",
+    ),
+    (
+        "E0018",
+        "\
+An unexpected internal error occurred.
+
+This is a bug in the Nickel interpreter. Please report it, together with the program causing
+the error if possible.",
+    ),
+    (
+        "E0019",
+        "\
+An evaluation error occurred.
+
+This code is used by the errors which are too rare to have a dedicated code. The error message
+describes what went wrong.",
+    ),
+    (
+        "E0020",
+        "\
+An import was needed to compute its own value.
+
+The evaluation of an imported file required the value of another imported file, which, directly or
+through other imports, required the value of the first one. Cyclic imports are only allowed when
+the cycle goes through lazy positions, such as record fields which are not needed to compute the
+value of the file itself.
+
+    // a.ncl
+    (import \"b.ncl\") + 1
+    // b.ncl
+    (import \"a.ncl\") + 1",
+    ),
+    (
+        "E0021",
+        "\
+An integer is too large to be represented exactly.
+
+Numbers are double-precision floating-point numbers, which only represent all the integers up to
+2^53 in absolute value. Operations which compute exact integer results, such as the sum of a list
+of integers, fail rather than silently rounding a result or an operand above this limit.
+
+    lists.sum [9007199254740992, 1]",
+    ),
+    (
+        "E0101",
+        "\
+An identifier is not bound.
+
+A variable was used without being defined by a `let` binding, a function parameter or a field of
+a recursive record in scope. This may be a typo.
+
+    let value = 1 in valeu",
+    ),
+    (
+        "E0102",
+        "\
+A type is ill-formed.
+
+For example, a type which is not a row appears where a row type is expected, such as in the
+tail of a record type.",
+    ),
+    (
+        "E0103",
+        "\
+A record or enum type is missing a field or a tag.
+
+The type of an expression was expected to contain a specific field or tag, but its inferred or
+annotated type doesn't.
+
+    ({a = 1} : {a: Num, b: Num})",
+    ),
+    (
+        "E0104",
+        "\
+A type is missing a dynamic tail.
+
+The type of an expression was expected to be a record type with a dynamic tail, written
+`{a: Num | Dyn}`, but the inferred or annotated type doesn't have one.",
+    ),
+    (
+        "E0105",
+        "\
+A record or enum type has an unexpected field or tag.
+
+The inferred or annotated type of an expression contains a field or a tag which its expected
+type doesn't allow.
+
+    ({a = 1, b = 2} : {a: Num})",
+    ),
+    (
+        "E0106",
+        "\
+A type has an unexpected dynamic tail.
+
+The inferred or annotated type of an expression is a record type with a dynamic tail, written
+`{a: Num | Dyn}`, while the expected type doesn't have one.",
+    ),
+    (
+        "E0107",
+        "\
+A type variable is not bound.
+
+A type variable was used without being introduced by a `forall` in scope.",
+    ),
+    (
+        "E0108",
+        "\
+The type of an expression doesn't match the expected type.
+
+In typed code, the inferred or annotated type of an expression is incompatible with the type
+expected by its context.
+
+    (1 + \"a\" : Num)",
+    ),
+    (
+        "E0109",
+        "\
+A field or tag is used both as a record field and as an enum tag.
+
+The same identifier of a row type was inferred to be both a field of a record type and a tag of
+an enum type, which are incompatible.",
+    ),
+    (
+        "E0110",
+        "\
+The types of a field or tag don't match.
+
+Two record types are incompatible because a field common to both has incompatible types. The
+error also reports why these types are incompatible.
+
+    ({a = 1} : {a: Str})",
+    ),
+    (
+        "E0111",
+        "\
+A row type has conflicting definitions of a field.
+
+The tail of a record type was inferred to be extended with a field that the record type already
+defines, which would define the field twice.",
+    ),
+    (
+        "E0112",
+        "\
+The types of functions don't match.
+
+Two function types are incompatible because their domains, their codomains, or a part of them
+recursively, are incompatible. The error reports which part of the function types is
+problematic. This often happens when a function is applied to an argument of a wrong type.
+
+    let f : Num -> Num = fun x => x in (f : Str -> Num)",
+    ),
+];
+
+/// Return the explanation of an error code, or `None` if the code is unknown. Codes are
+/// case-insensitive.
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|(other, _)| other.eq_ignore_ascii_case(code))
+        .map(|(_, explanation)| *explanation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{EvalError, SerializationError, TypecheckError};
+    use crate::eval::callstack::CallStack;
+    use crate::identifier::Ident;
+    use crate::label::Label;
+    use crate::position::TermPos;
+    use crate::term::{RichTerm, Term};
+    use crate::types::{AbsType, Types};
+    use std::collections::HashSet;
+
+    fn all_eval_errors() -> Vec<EvalError> {
+        let t = RichTerm::from(Term::Null);
+        let pos = TermPos::None;
+        let msg = String::new;
+
+        // Listing the variants in an exhaustive match ensures that new variants are added here.
+        let check_exhaustive = |err: &EvalError| match err {
+            EvalError::BlameError(..)
+            | EvalError::MissingFieldDef(..)
+            | EvalError::TypeError(..)
+            | EvalError::NotAFunc(..)
+            | EvalError::FieldMissing(..)
+            | EvalError::FieldAlreadyDefined(..)
+            | EvalError::EmptyList(..)
+            | EvalError::NotAnInteger(..)
+            | EvalError::IntegerOverflow(..)
+            | EvalError::IndexOutOfBounds(..)
+            | EvalError::DivisionByZero(..)
+            | EvalError::NotEnoughArgs(..)
+            | EvalError::MergeIncompatibleArgs(..)
+            | EvalError::UnboundIdentifier(..)
+            | EvalError::InfiniteRecursion(..)
+            | EvalError::CyclicImport(..)
+            | EvalError::StepLimitExceeded(..)
+            | EvalError::SerializationError(..)
+            | EvalError::DeserializationError(..)
+            | EvalError::InternalError(..)
+            | EvalError::Other(..) => (),
+        };
+        check_exhaustive(&EvalError::DivisionByZero(pos));
+
+        vec![
+            EvalError::BlameError(Label::dummy(), CallStack::new()),
+            EvalError::MissingFieldDef(None, CallStack::new()),
+            EvalError::TypeError(msg(), msg(), pos, t.clone()),
+            EvalError::NotAFunc(t.clone(), t.clone(), 1, pos, None),
+            EvalError::FieldMissing(msg(), msg(), t.clone(), pos),
+            EvalError::FieldAlreadyDefined(msg(), msg(), pos),
+            EvalError::EmptyList(msg(), pos),
+            EvalError::NotAnInteger(msg(), 0.5, pos),
+            EvalError::IntegerOverflow(msg(), pos),
+            EvalError::IndexOutOfBounds(msg(), 0.0, 0..0, pos),
+            EvalError::DivisionByZero(pos),
+            EvalError::NotEnoughArgs(0, msg(), pos),
+            EvalError::MergeIncompatibleArgs(t.clone(), t, pos, Vec::new()),
+            EvalError::UnboundIdentifier(Ident::from("x"), Vec::new(), pos, None),
+            EvalError::InfiniteRecursion(CallStack::new(), pos),
+            EvalError::CyclicImport(Vec::new(), pos),
+            EvalError::StepLimitExceeded(0, pos),
+            EvalError::SerializationError(SerializationError::Other(msg())),
+            EvalError::DeserializationError(msg(), msg(), pos),
+            EvalError::InternalError(msg(), pos),
+            EvalError::Other(msg(), pos, None),
+        ]
+    }
+
+    fn all_typecheck_errors() -> Vec<TypecheckError> {
+        let ty = Types(AbsType::Dyn());
+        let id = Ident::from("x");
+        let pos = TermPos::None;
+        let sub_err = || Box::new(TypecheckError::TypeMismatch(ty.clone(), ty.clone(), pos));
+
+        let check_exhaustive = |err: &TypecheckError| match err {
+            TypecheckError::UnboundIdentifier(..)
+            | TypecheckError::IllformedType(..)
+            | TypecheckError::MissingRow(..)
+            | TypecheckError::MissingDynTail(..)
+            | TypecheckError::ExtraRow(..)
+            | TypecheckError::ExtraDynTail(..)
+            | TypecheckError::UnboundTypeVariable(..)
+            | TypecheckError::TypeMismatch(..)
+            | TypecheckError::RowKindMismatch(..)
+            | TypecheckError::RowMismatch(..)
+            | TypecheckError::RowConflict(..)
+            | TypecheckError::ArrowTypeMismatch(..) => (),
+        };
+        check_exhaustive(&TypecheckError::IllformedType(ty.clone()));
+
+        vec![
+            TypecheckError::UnboundIdentifier(id.clone(), Vec::new(), pos),
+            TypecheckError::IllformedType(ty.clone()),
+            TypecheckError::MissingRow(id.clone(), ty.clone(), ty.clone(), pos),
+            TypecheckError::MissingDynTail(ty.clone(), ty.clone(), pos),
+            TypecheckError::ExtraRow(id.clone(), ty.clone(), ty.clone(), pos),
+            TypecheckError::ExtraDynTail(ty.clone(), ty.clone(), pos),
+            TypecheckError::UnboundTypeVariable(id.clone(), pos),
+            TypecheckError::TypeMismatch(ty.clone(), ty.clone(), pos),
+            TypecheckError::RowKindMismatch(id.clone(), None, None, pos),
+            TypecheckError::RowMismatch(id.clone(), ty.clone(), ty.clone(), sub_err(), pos),
+            TypecheckError::RowConflict(id, None, ty.clone(), ty.clone(), pos),
+            TypecheckError::ArrowTypeMismatch(ty.clone(), ty.clone(), Vec::new(), sub_err(), pos),
+        ]
+    }
+
+    #[test]
+    fn codes_are_unique_and_explained() {
+        let codes: Vec<&str> = all_eval_errors()
+            .iter()
+            .map(EvalError::code)
+            .chain(all_typecheck_errors().iter().map(TypecheckError::code))
+            .collect();
+        let unique: HashSet<&str> = codes.iter().cloned().collect();
+        assert_eq!(codes.len(), unique.len());
+
+        for code in codes {
+            assert!(
+                explain(code).map_or(false, |expl| !expl.trim().is_empty()),
+                "missing explanation for {}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn explanations_are_unique() {
+        let codes: HashSet<&str> = EXPLANATIONS.iter().map(|(code, _)| *code).collect();
+        assert_eq!(codes.len(), EXPLANATIONS.len());
+        assert_eq!(explain("e0001"), explain("E0001"));
+        assert_eq!(explain("E9999"), None);
+    }
+}
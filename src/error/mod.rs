@@ -26,6 +26,8 @@ use crate::{
     types::Types,
 };
 
+pub mod explain;
+
 /// A general error occurring during either parsing or evaluation.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Error {
@@ -52,11 +54,13 @@ pub enum EvalError {
         /* position of the original unevaluated expression */ TermPos,
         /* evaluated expression */ RichTerm,
     ),
-    /// A term which is not a function has been applied to an argument.
+    /// A term which is not a function has been applied to one or several arguments.
     NotAFunc(
         /* term */ RichTerm,
-        /* arg */ RichTerm,
-        /* app position */ TermPos,
+        /* first arg */ RichTerm,
+        /* number of args */ usize,
+        /* position of the whole application */ TermPos,
+        /* call stack, if available */ Option<CallStack>,
     ),
     /// A field access, or another record operation requiring the existence of a specific field,
     /// has been performed on a record missing that field.
@@ -66,6 +70,32 @@ pub enum EvalError {
         RichTerm,
         TermPos,
     ),
+    /// A record operation tried to add a field which is already defined.
+    FieldAlreadyDefined(
+        /* field identifier */ String,
+        /* operator */ String,
+        TermPos,
+    ),
+    /// An operation requiring a non-empty list has been applied to an empty list.
+    EmptyList(/* operator */ String, TermPos),
+    /// An operation expected an integer, but got a number with a fractional part.
+    NotAnInteger(
+        /* operation and argument */ String,
+        /* actual value */ f64,
+        TermPos,
+    ),
+    /// The result of an integer operation, or one of its operands, is too large to be represented
+    /// exactly by a number.
+    IntegerOverflow(/* operation and argument */ String, TermPos),
+    /// An index is outside of the range of valid indices of a list or a string.
+    IndexOutOfBounds(
+        /* operation and argument */ String,
+        /* index */ f64,
+        /* valid indices */ std::ops::Range<usize>,
+        TermPos,
+    ),
+    /// A number has been divided by zero.
+    DivisionByZero(TermPos),
     /// Too few arguments were provided to a builtin function.
     NotEnoughArgs(
         /* required arg count */ usize,
@@ -78,30 +108,97 @@ pub enum EvalError {
         /* left operand */ RichTerm,
         /* right operand */ RichTerm,
         /* original merge */ TermPos,
+        /* path of the merged values, when they are fields of merged records */ Vec<Ident>,
     ),
     /// An unbound identifier was referenced.
-    UnboundIdentifier(Ident, TermPos),
+    UnboundIdentifier(
+        Ident,
+        /* similar identifiers in scope, to suggest */ Vec<Ident>,
+        TermPos,
+        /* call stack, if available */ Option<CallStack>,
+    ),
     /// A thunk was entered during its own update.
     InfiniteRecursion(CallStack, TermPos),
+    /// An import was needed to compute its own value, through a cycle of imports.
+    CyclicImport(
+        /* paths of the imports of the cycle */ Vec<String>,
+        TermPos,
+    ),
+    /// The evaluation exceeded the maximum number of steps of the abstract machine.
+    StepLimitExceeded(/* maximum number of steps */ usize, TermPos),
     /// A serialization error occurred during a call to the builtin `serialize`.
     SerializationError(SerializationError),
     /// A parse error occurred during a call to the builtin `deserialize`.
     DeserializationError(
         String,  /* format */
         String,  /* error message */
-        TermPos, /* position of the call to deserialize */
+        TermPos, /* position of the error in the deserialized string, or of the call to deserialize */
     ),
     /// An unexpected internal error.
     InternalError(String, TermPos),
     /// Errors occurring rarely enough to not deserve a dedicated variant.
-    Other(String, TermPos),
+    Other(
+        String,
+        TermPos,
+        /* call stack, if available */ Option<CallStack>,
+    ),
+}
+
+impl EvalError {
+    /// Return the stable code of this error, whose explanation is given by
+    /// [`explain::explain`].
+    pub fn code(&self) -> &'static str {
+        match self {
+            EvalError::BlameError(..) => "E0001",
+            EvalError::MissingFieldDef(..) => "E0002",
+            EvalError::TypeError(..) => "E0003",
+            EvalError::NotAFunc(..) => "E0004",
+            EvalError::FieldMissing(..) => "E0005",
+            EvalError::FieldAlreadyDefined(..) => "E0006",
+            EvalError::EmptyList(..) => "E0007",
+            EvalError::NotAnInteger(..) => "E0008",
+            EvalError::IndexOutOfBounds(..) => "E0009",
+            EvalError::DivisionByZero(..) => "E0010",
+            EvalError::NotEnoughArgs(..) => "E0011",
+            EvalError::MergeIncompatibleArgs(..) => "E0012",
+            EvalError::UnboundIdentifier(..) => "E0013",
+            EvalError::InfiniteRecursion(..) => "E0014",
+            EvalError::StepLimitExceeded(..) => "E0015",
+            EvalError::SerializationError(..) => "E0016",
+            EvalError::DeserializationError(..) => "E0017",
+            EvalError::InternalError(..) => "E0018",
+            EvalError::Other(..) => "E0019",
+            EvalError::CyclicImport(..) => "E0020",
+            EvalError::IntegerOverflow(..) => "E0021",
+        }
+    }
+
+    /// Attach a call stack to the errors which can carry one but don't have one yet. The
+    /// evaluation loop uses it to record where the error occurred, whatever the place the error
+    /// was raised from.
+    pub fn with_call_stack(self, call_stack: CallStack) -> Self {
+        match self {
+            EvalError::NotAFunc(t, arg, arg_count, pos, None) => {
+                EvalError::NotAFunc(t, arg, arg_count, pos, Some(call_stack))
+            }
+            EvalError::UnboundIdentifier(id, suggestions, pos, None) => {
+                EvalError::UnboundIdentifier(id, suggestions, pos, Some(call_stack))
+            }
+            EvalError::Other(msg, pos, None) => EvalError::Other(msg, pos, Some(call_stack)),
+            err => err,
+        }
+    }
 }
 
 /// An error occurring during the static typechecking phase.
 #[derive(Debug, PartialEq, Clone)]
 pub enum TypecheckError {
     /// An unbound identifier was referenced.
-    UnboundIdentifier(Ident, TermPos),
+    UnboundIdentifier(
+        Ident,
+        /* similar identifiers in scope, to suggest */ Vec<Ident>,
+        TermPos,
+    ),
     /// An ill-formed type, such as a non-row type appearing in a row.
     IllformedType(Types),
     /// A specific row was expected to be in the type of an expression, but was not.
@@ -199,6 +296,27 @@ pub enum TypecheckError {
     ),
 }
 
+impl TypecheckError {
+    /// Return the stable code of this error, whose explanation is given by
+    /// [`explain::explain`].
+    pub fn code(&self) -> &'static str {
+        match self {
+            TypecheckError::UnboundIdentifier(..) => "E0101",
+            TypecheckError::IllformedType(..) => "E0102",
+            TypecheckError::MissingRow(..) => "E0103",
+            TypecheckError::MissingDynTail(..) => "E0104",
+            TypecheckError::ExtraRow(..) => "E0105",
+            TypecheckError::ExtraDynTail(..) => "E0106",
+            TypecheckError::UnboundTypeVariable(..) => "E0107",
+            TypecheckError::TypeMismatch(..) => "E0108",
+            TypecheckError::RowKindMismatch(..) => "E0109",
+            TypecheckError::RowMismatch(..) => "E0110",
+            TypecheckError::RowConflict(..) => "E0111",
+            TypecheckError::ArrowTypeMismatch(..) => "E0112",
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Default)]
 pub struct ParseErrors {
     pub errors: Vec<ParseError>,
@@ -283,6 +401,8 @@ pub enum ParseError {
     InvalidEscapeSequence(RawSpan),
     /// Invalid ASCII escape code in a string literal.
     InvalidAsciiEscapeCode(RawSpan),
+    /// An integer literal which can't be represented exactly as a number, and would be rounded.
+    InexactIntegerLiteral(RawSpan),
     /// Error when parsing an external format such as JSON, YAML, etc.
     ExternalFormatError(
         String, /* format */
@@ -441,6 +561,9 @@ impl ParseError {
                 InternalParseError::Lexical(LexicalError::InvalidAsciiEscapeCode(location)) => {
                     ParseError::InvalidAsciiEscapeCode(mk_span(file_id, location, location + 2))
                 }
+                InternalParseError::Lexical(LexicalError::InexactIntegerLiteral(start, end)) => {
+                    ParseError::InexactIntegerLiteral(mk_span(file_id, start, end))
+                }
                 InternalParseError::UnboundTypeVariables(idents, span) => {
                     ParseError::UnboundTypeVariables(idents, span)
                 }
@@ -511,6 +634,40 @@ impl ParseError {
             }),
         )
     }
+
+    pub fn from_xml(error: crate::serialize::xml::XmlError, file_id: FileId) -> Self {
+        use codespan::{ByteIndex, ByteOffset};
+
+        let start = ByteIndex::from(error.offset as u32);
+        ParseError::ExternalFormatError(
+            String::from("xml"),
+            error.msg,
+            Some(RawSpan {
+                src_id: file_id,
+                start,
+                end: start + ByteOffset::from(1),
+            }),
+        )
+    }
+
+    pub fn from_ini(
+        error: crate::serialize::ini::IniError,
+        file_id: FileId,
+        files: &Files<String>,
+    ) -> Self {
+        use codespan::ByteOffset;
+
+        let span = files.line_span(file_id, error.line as u32).ok();
+        ParseError::ExternalFormatError(
+            String::from("ini"),
+            error.msg,
+            span.map(|span| RawSpan {
+                src_id: file_id,
+                start: span.start(),
+                end: span.start() + ByteOffset::from(1),
+            }),
+        )
+    }
 }
 
 pub const INTERNAL_ERROR_MSG: &str =
@@ -758,6 +915,43 @@ fn report_ty_path(l: &label::Label, files: &mut Files<String>) -> (Label<FileId>
 }
 
 /// Return a note diagnostic showing where a contract was bound.
+/// Generate notes describing the function calls of a call stack, innermost first. The calls
+/// located in the builtin contracts, given by `contract_id`, are skipped. If `contract_id` is
+/// `None`, no note is generated.
+fn call_stack_notes(
+    call_stack: &CallStack,
+    contract_id: Option<FileId>,
+) -> Vec<Diagnostic<FileId>> {
+    let id = match contract_id {
+        Some(id) => id,
+        None => return Vec::new(),
+    };
+
+    let (calls, curr_call) = call_stack.group_by_calls(id);
+    let diag_curr_call = curr_call.map(|cdescr| {
+        let name = cdescr
+            .head
+            .map(|ident| ident.to_string())
+            .unwrap_or_else(|| String::from("<func>"));
+        Diagnostic::note().with_labels(vec![
+            primary(&cdescr.span).with_message(format!("While calling to {}", name))
+        ])
+    });
+    let diags = calls.into_iter().enumerate().map(|(i, cdescr)| {
+        let name = cdescr
+            .head
+            .map(|ident| ident.to_string())
+            .unwrap_or_else(|| String::from("<func>"));
+        Diagnostic::note().with_labels(vec![secondary(&cdescr.span).with_message(format!(
+            "({}) calling {}",
+            i + 1,
+            name
+        ))])
+    });
+
+    diag_curr_call.into_iter().chain(diags).collect()
+}
+
 fn blame_label_note(l: &label::Label) -> Diagnostic<FileId> {
     Diagnostic::note().with_labels(vec![Label::primary(
         l.span.src_id,
@@ -795,7 +989,7 @@ impl ToDiagnostic<FileId> for EvalError {
         files: &mut Files<String>,
         contract_id: Option<FileId>,
     ) -> Vec<Diagnostic<FileId>> {
-        match self {
+        let mut diagnostics = match self {
             EvalError::BlameError(l, call_stack) => {
                 let mut msg = String::from("Blame error: ");
 
@@ -811,6 +1005,10 @@ impl ToDiagnostic<FileId> for EvalError {
                     write!(&mut msg, "contract broken by the caller").unwrap();
                 }
 
+                if let Some(ref field) = l.field_name {
+                    write!(&mut msg, " at field `{}`", field).unwrap();
+                }
+
                 if !l.tag.is_empty() {
                     write!(&mut msg, " [{}].", &escape(&l.tag)).unwrap();
                 } else {
@@ -891,28 +1089,8 @@ impl ToDiagnostic<FileId> for EvalError {
 
                 diagnostics.push(blame_label_note(&l));
 
-                if ty_path::is_only_codom(&l.path) {
-                } else if let Some(id) = contract_id {
-                    let (calls, curr_call) = call_stack.group_by_calls(id);
-                    let diag_curr_call = curr_call.map(|cdescr| {
-                        let name = cdescr
-                            .head
-                            .map(|ident| ident.to_string())
-                            .unwrap_or_else(|| String::from("<func>"));
-                        Diagnostic::note().with_labels(vec![primary(&cdescr.span)
-                            .with_message(format!("While calling to {}", name))])
-                    });
-                    let diags = calls.into_iter().enumerate().map(|(i, cdescr)| {
-                        let name = cdescr
-                            .head
-                            .map(|ident| ident.to_string())
-                            .unwrap_or_else(|| String::from("<func>"));
-                        Diagnostic::note().with_labels(vec![secondary(&cdescr.span)
-                            .with_message(format!("({}) calling {}", i + 1, name))])
-                    });
-
-                    diagnostics.extend(diag_curr_call);
-                    diagnostics.extend(diags);
+                if !ty_path::is_only_codom(&l.path) {
+                    diagnostics.extend(call_stack_notes(call_stack, contract_id));
                 }
 
                 diagnostics
@@ -1008,22 +1186,43 @@ impl ToDiagnostic<FileId> for EvalError {
                     .with_labels(labels)
                     .with_notes(vec![msg.clone()])]
             }
-            EvalError::NotAFunc(t, arg, pos_opt) => vec![Diagnostic::error()
-                .with_message("Not a function")
-                .with_labels(vec![
-                    primary_term(t, files)
-                        .with_message("this term is applied, but it is not a function"),
-                    secondary_alt(
-                        *pos_opt,
-                        format!(
-                            "({}) ({})",
-                            (*t.term).shallow_repr(),
-                            (*arg.term).shallow_repr()
-                        ),
-                        files,
-                    )
-                    .with_message("applied here"),
-                ])],
+            EvalError::NotAFunc(t, arg, arg_count, pos_opt, call_stack) => {
+                let value = match t.term.type_of() {
+                    Some(ty) if ty.starts_with(['A', 'E', 'I', 'O', 'U']) => format!("an {}", ty),
+                    Some(ty) => format!("a {}", ty),
+                    None => String::from("a value"),
+                };
+                let args = if *arg_count > 1 {
+                    format!("{} arguments", arg_count)
+                } else {
+                    String::from("1 argument")
+                };
+
+                let mut diagnostics = vec![Diagnostic::error()
+                    .with_message("Not a function")
+                    .with_labels(vec![
+                        primary_term(t, files)
+                            .with_message("this term is applied, but it is not a function"),
+                        secondary_alt(
+                            *pos_opt,
+                            format!(
+                                "({}) ({}){}",
+                                (*t.term).shallow_repr(),
+                                (*arg.term).shallow_repr(),
+                                if *arg_count > 1 { " ..." } else { "" }
+                            ),
+                            files,
+                        )
+                        .with_message("applied here"),
+                    ])
+                    .with_notes(vec![format!("attempted to apply {} to {}", value, args)])];
+
+                if let Some(call_stack) = call_stack {
+                    diagnostics.extend(call_stack_notes(call_stack, contract_id));
+                }
+
+                diagnostics
+            }
             EvalError::FieldMissing(field, op, t, span_opt) => {
                 let mut labels = Vec::new();
                 let mut notes = Vec::new();
@@ -1051,6 +1250,88 @@ impl ToDiagnostic<FileId> for EvalError {
                     .with_message("Missing field")
                     .with_labels(labels)]
             }
+            EvalError::FieldAlreadyDefined(field, op, span_opt) => {
+                let field = escape(field);
+                let msg = format!("{} tried to add the field {}", op, field);
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message(msg)])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!("Field {} already defined", field))
+                    .with_labels(labels)]
+            }
+            EvalError::EmptyList(op, span_opt) => {
+                let labels =
+                    span_opt
+                        .as_opt_ref()
+                        .map(|span| {
+                            vec![primary(span)
+                                .with_message(format!("{} requires a non-empty list", op))]
+                        })
+                        .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message("Empty list")
+                    .with_labels(labels)]
+            }
+            EvalError::NotAnInteger(op, n, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message(format!("in {}", op))])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!(
+                        "Expected an integer, got the floating-point value {}",
+                        n
+                    ))
+                    .with_labels(labels)]
+            }
+            EvalError::IntegerOverflow(op, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message(format!("in {}", op))])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message("Integer too large to be represented exactly")
+                    .with_labels(labels)
+                    .with_notes(vec![String::from(
+                        "Numbers only represent integers exactly up to 2^53 in absolute value",
+                    )])]
+            }
+            EvalError::IndexOutOfBounds(op, index, valid, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message(format!("in {}", op))])
+                    .unwrap_or_default();
+                let note = if valid.is_empty() {
+                    String::from("There is no valid index")
+                } else {
+                    format!(
+                        "Expected a value between {} and {}",
+                        valid.start,
+                        valid.end - 1
+                    )
+                };
+
+                vec![Diagnostic::error()
+                    .with_message(format!("Index {} out of bounds", index))
+                    .with_labels(labels)
+                    .with_notes(vec![note])]
+            }
+            EvalError::DivisionByZero(span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message("Division by zero")
+                    .with_labels(labels)]
+            }
             EvalError::NotEnoughArgs(count, op, span_opt) => {
                 let mut labels = Vec::new();
                 let mut notes = Vec::new();
@@ -1073,7 +1354,7 @@ impl ToDiagnostic<FileId> for EvalError {
                     .with_labels(labels)
                     .with_notes(notes)]
             }
-            EvalError::MergeIncompatibleArgs(t1, t2, span_opt) => {
+            EvalError::MergeIncompatibleArgs(t1, t2, span_opt, path) => {
                 let mut labels = vec![
                     primary_term(t1, files).with_message("cannot merge this expression"),
                     primary_term(t2, files).with_message("with this expression"),
@@ -1083,18 +1364,43 @@ impl ToDiagnostic<FileId> for EvalError {
                     labels.push(secondary(span).with_message("merged here"));
                 }
 
-                vec![Diagnostic::error()
-                    .with_message("Non mergeable terms")
-                    .with_labels(labels)]
+                let mut msg = String::from("Non mergeable terms");
+                if !path.is_empty() {
+                    let path: Vec<String> = path.iter().map(Ident::to_string).collect();
+                    write!(&mut msg, " at field `{}`", path.join(".")).unwrap();
+                }
+
+                vec![Diagnostic::error().with_message(msg).with_labels(labels)]
+            }
+            EvalError::UnboundIdentifier(ident, suggestions, span_opt, call_stack) => {
+                let mut diagnostic = Diagnostic::error()
+                    .with_message("Unbound identifier")
+                    .with_labels(vec![primary_alt(
+                        span_opt.into_opt(),
+                        ident.to_string(),
+                        files,
+                    )
+                    .with_message("this identifier is unbound")]);
+
+                let quoted: Vec<String> =
+                    suggestions.iter().map(|id| format!("`{}`", id)).collect();
+                if let Some((last, init)) = quoted.split_last() {
+                    let alternatives = if init.is_empty() {
+                        last.clone()
+                    } else {
+                        format!("{} or {}", init.join(", "), last)
+                    };
+                    diagnostic =
+                        diagnostic.with_notes(vec![format!("did you mean {}?", alternatives)]);
+                }
+
+                let mut diagnostics = vec![diagnostic];
+                if let Some(call_stack) = call_stack {
+                    diagnostics.extend(call_stack_notes(call_stack, contract_id));
+                }
+
+                diagnostics
             }
-            EvalError::UnboundIdentifier(ident, span_opt) => vec![Diagnostic::error()
-                .with_message("Unbound identifier")
-                .with_labels(vec![primary_alt(
-                    span_opt.into_opt(),
-                    ident.to_string(),
-                    files,
-                )
-                .with_message("this identifier is unbound")])],
             EvalError::InfiniteRecursion(_call_stack, span_opt) => {
                 let labels = span_opt
                     .as_opt_ref()
@@ -1105,13 +1411,50 @@ impl ToDiagnostic<FileId> for EvalError {
                     .with_message("infinite recursion")
                     .with_labels(labels)]
             }
-            EvalError::Other(msg, span_opt) => {
+            EvalError::CyclicImport(paths, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("cyclic import")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message("cyclic imports")
+                    .with_labels(labels)
+                    .with_notes(vec![
+                        format!("Import cycle: {}", paths.join(" -> ")),
+                        String::from(
+                            "The value of an imported file can't depend on itself. Cyclic imports \
+                            are only allowed when they are accessed lazily, e.g. inside record \
+                            fields.",
+                        ),
+                    ])]
+            }
+            EvalError::StepLimitExceeded(max_steps, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("evaluation stopped here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!(
+                        "Step limit exceeded: the evaluation took more than {} steps",
+                        max_steps
+                    ))
+                    .with_labels(labels)]
+            }
+            EvalError::Other(msg, span_opt, call_stack) => {
                 let labels = span_opt
                     .as_opt_ref()
                     .map(|span| vec![primary(span).with_message("here")])
                     .unwrap_or_default();
 
-                vec![Diagnostic::error().with_message(msg).with_labels(labels)]
+                let mut diagnostics =
+                    vec![Diagnostic::error().with_message(msg).with_labels(labels)];
+                if let Some(call_stack) = call_stack {
+                    diagnostics.extend(call_stack_notes(call_stack, contract_id));
+                }
+
+                diagnostics
             }
             EvalError::InternalError(msg, span_opt) => {
                 let labels = span_opt
@@ -1133,10 +1476,14 @@ impl ToDiagnostic<FileId> for EvalError {
 
                 vec![Diagnostic::error()
                     .with_message(format!("{} parse error: {}", format, msg))
-                    .with_labels(labels)
-                    .with_notes(vec![String::from(INTERNAL_ERROR_MSG)])]
+                    .with_labels(labels)]
             }
+        };
+
+        if let Some(diagnostic) = diagnostics.first_mut() {
+            diagnostic.code = Some(String::from(self.code()));
         }
+        diagnostics
     }
 }
 
@@ -1175,6 +1522,16 @@ impl ToDiagnostic<FileId> for ParseError {
             ParseError::InvalidAsciiEscapeCode(span) => Diagnostic::error()
                 .with_message("Invalid ascii escape code")
                 .with_labels(vec![primary(span)]),
+            ParseError::InexactIntegerLiteral(span) => Diagnostic::error()
+                .with_message("Integer literal can't be represented exactly")
+                .with_labels(vec![primary(span)])
+                .with_notes(vec![
+                    String::from(
+                        "Numbers are 64-bit floating point numbers, which can't represent all the \
+                        integers above 2^53 in absolute value.",
+                    ),
+                    String::from("Use a string to store large identifiers."),
+                ]),
             ParseError::ExternalFormatError(format, msg, span_opt) => {
                 let labels = span_opt
                     .as_ref()
@@ -1214,11 +1571,16 @@ impl ToDiagnostic<FileId> for TypecheckError {
                 .unwrap_or_default()
         }
 
-        match self {
-            TypecheckError::UnboundIdentifier(ident, pos_opt) =>
+        let mut diagnostics = match self {
+            TypecheckError::UnboundIdentifier(ident, suggestions, pos_opt) =>
             // Use the same diagnostic as `EvalError::UnboundIdentifier` for consistency.
                 {
-                    EvalError::UnboundIdentifier(ident.clone(), *pos_opt)
+                    EvalError::UnboundIdentifier(
+                        ident.clone(),
+                        suggestions.clone(),
+                        *pos_opt,
+                        None,
+                    )
                         .to_diagnostic(files, contract_id)
                 }
             TypecheckError::IllformedType(ty) => {
@@ -1409,7 +1771,12 @@ impl ToDiagnostic<FileId> for TypecheckError {
 
                 diags
             }
+        };
+
+        if let Some(diagnostic) = diagnostics.first_mut() {
+            diagnostic.code = Some(String::from(self.code()));
         }
+        diagnostics
     }
 }
 
@@ -1,5 +1,5 @@
 //! Thunks and associated devices used to implement lazy evaluation.
-use super::{Closure, IdentKind};
+use super::{Closure, Environment, IdentKind};
 use std::cell::{Ref, RefCell, RefMut};
 use std::rc::{Rc, Weak};
 
@@ -89,6 +89,26 @@ impl ThunkData {
         }
     }
 
+    /// Take the environments of the closures which are not shared with other thunk data, leaving
+    /// empty environments in place.
+    fn take_envs(&mut self, envs: &mut Vec<Environment>) {
+        match self.inner {
+            InnerThunkData::Standard(ref mut closure) => {
+                envs.push(std::mem::replace(&mut closure.env, Environment::new()))
+            }
+            InnerThunkData::Revertible {
+                ref mut orig,
+                ref mut cached,
+            } => {
+                for closure in [orig, cached] {
+                    if let Some(closure) = Rc::get_mut(closure) {
+                        envs.push(std::mem::replace(&mut closure.env, Environment::new()));
+                    }
+                }
+            }
+        }
+    }
+
     /// Update the cached closure.
     pub fn update(&mut self, new: Closure) {
         match self.inner {
@@ -200,7 +220,11 @@ impl Thunk {
     /// Consume the thunk and return an owned closure. Avoid cloning if this thunk is the only
     /// reference to the inner closure.
     pub fn into_closure(self) -> Closure {
-        match Rc::try_unwrap(self.data) {
+        // `Thunk` implements `Drop`, so its data can't be moved out directly.
+        let data = Rc::clone(&self.data);
+        std::mem::drop(self);
+
+        match Rc::try_unwrap(data) {
             Ok(inner) => inner.into_inner().into_closure(),
             Err(rc) => rc.borrow().closure().clone(),
         }
@@ -226,6 +250,38 @@ impl Thunk {
     }
 }
 
+impl Drop for Thunk {
+    /// Dropping the last reference to a thunk drops its environment, which may hold the last
+    /// reference to other thunks, and so on. Such chains can be arbitrarily long, for example when
+    /// an accumulator is never forced, and dropping them recursively would overflow the stack.
+    /// Instead, the environments of the thunks being freed are dismantled iteratively.
+    fn drop(&mut self) {
+        if Rc::strong_count(&self.data) > 1 {
+            return;
+        }
+
+        let mut envs = Vec::new();
+        if let Ok(mut data) = self.data.try_borrow_mut() {
+            data.take_envs(&mut envs);
+        }
+
+        let mut thunks = Vec::new();
+        while let Some(env) = envs.pop() {
+            env.into_unshared_values(&mut thunks);
+
+            // The environments of the thunks are taken before the thunks are dropped, such that
+            // this function is never called recursively on a non-empty environment.
+            for thunk in thunks.drain(..) {
+                if Rc::strong_count(&thunk.data) == 1 {
+                    if let Ok(mut data) = thunk.data.try_borrow_mut() {
+                        data.take_envs(&mut envs);
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// A thunk update frame.
 ///
 /// A thunk update frame is put on the stack whenever a variable is entered, such that once this
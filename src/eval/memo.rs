@@ -0,0 +1,154 @@
+//! Memoization of the evaluation of pure terms.
+//!
+//! Structurally identical but unshared expressions are evaluated independently by the abstract
+//! machine, as they live in distinct thunks. When enabled through
+//! [`EvalConfig`](../struct.EvalConfig.html), the machine records the thunks holding pure terms in
+//! a [`MemoTable`](struct.MemoTable.html), indexed by the [content
+//! hash](../../term/struct.RichTerm.html#method.content_hash) of their body. A later thunk with
+//! the same content, as checked by a [structural
+//! comparison](../../term/struct.RichTerm.html#method.content_eq), is then directly updated with
+//! the result of the first one.
+//!
+//! A term is pure, in this sense, if its value only depends on its content. This excludes terms
+//! referring to variables of the local environment, whose value is not captured by the content
+//! hash, as well as imports. Terms with metadata or labels are excluded as well: the content hash
+//! ignores the positions and the messages stored in labels, which are part of error reporting.
+use super::lazy::{Thunk, ThunkState};
+use super::{Closure, Environment};
+use crate::identifier::Ident;
+use crate::term::{RichTerm, StrChunk, Term};
+use std::collections::HashMap;
+
+/// A table mapping pure terms to the thunk of their first occurrence during one evaluation.
+///
+/// Terms are bucketed by their content hash, and compared structurally inside a bucket, such that
+/// a collision of the 64 bits hash can't return the value of another term. The environment of a
+/// pure term doesn't need to be compared: its free variables all refer to the global environment,
+/// which is the same for all the terms of one evaluation. A table must thus not be shared between
+/// evaluations in different global environments.
+#[derive(Debug, Default)]
+pub struct MemoTable {
+    entries: HashMap<u64, Vec<MemoEntry>>,
+    hits: usize,
+}
+
+/// A pure term, as it was before its evaluation, together with the thunk being updated with its
+/// result.
+#[derive(Debug)]
+struct MemoEntry {
+    body: RichTerm,
+    thunk: Thunk,
+}
+
+impl MemoTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Look up the result of a term with the same content as the thunk about to be evaluated.
+    ///
+    /// Return the closure of the first thunk with the same content if it has already been
+    /// evaluated. Otherwise, if the content of the thunk is pure and hasn't been seen before,
+    /// record the thunk such that its result can be reused once it is updated.
+    pub fn lookup_or_insert(&mut self, thunk: &Thunk) -> Option<Closure> {
+        let body = {
+            let closure = thunk.borrow();
+            if !is_pure(&closure.body, &closure.env) {
+                return None;
+            }
+            closure.body.clone()
+        };
+
+        let bucket = self.entries.entry(body.content_hash()).or_default();
+
+        match bucket.iter().find(|entry| entry.body.content_eq(&body)) {
+            Some(entry) if entry.thunk.state() == ThunkState::Evaluated => {
+                self.hits += 1;
+                Some(entry.thunk.get_owned())
+            }
+            Some(_) => None,
+            None => {
+                bucket.push(MemoEntry {
+                    body,
+                    thunk: thunk.clone(),
+                });
+                None
+            }
+        }
+    }
+
+    /// The number of thunks whose evaluation has been avoided thanks to the table.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+}
+
+/// Determine if a term is pure, that is, if it has no free variable bound in the local
+/// environment, no import and no metadata. Variables which are not bound in the local environment
+/// refer to the global environment, which doesn't change during an evaluation.
+fn is_pure(rt: &RichTerm, env: &Environment) -> bool {
+    // Maintain the list of the variables bound inside the term. This list may be smaller than the
+    // actual set of bound variables, which only makes the check more conservative.
+    fn is_pure_(rt: &RichTerm, env: &Environment, bound: &mut Vec<Ident>) -> bool {
+        match rt.as_ref() {
+            Term::Null
+            | Term::Bool(_)
+            | Term::Num(_)
+            | Term::Str(_)
+            | Term::Enum(_)
+            | Term::Sym(_) => true,
+            Term::Var(id) => bound.contains(id) || env.get(id).is_none(),
+            Term::Lbl(_)
+            | Term::MetaValue(_)
+            | Term::Import(_)
+            | Term::ImportStr(_)
+            | Term::ResolvedImport(_)
+            | Term::LetPattern(..)
+            | Term::FunPattern(..)
+            | Term::ParseError => false,
+            Term::Fun(id, t) => {
+                bound.push(id.clone());
+                let result = is_pure_(t, env, bound);
+                bound.pop();
+                result
+            }
+            Term::Let(id, t1, t2, _) => {
+                is_pure_(t1, env, bound) && {
+                    bound.push(id.clone());
+                    let result = is_pure_(t2, env, bound);
+                    bound.pop();
+                    result
+                }
+            }
+            Term::App(t1, t2) | Term::Op2(_, t1, t2) => {
+                is_pure_(t1, env, bound) && is_pure_(t2, env, bound)
+            }
+            Term::Op1(_, t) | Term::Wrapped(_, t) => is_pure_(t, env, bound),
+            Term::OpN(_, ts) | Term::List(ts) => ts.iter().all(|t| is_pure_(t, env, bound)),
+            Term::Switch(t, cases, default) => {
+                is_pure_(t, env, bound)
+                    && cases.values().all(|t| is_pure_(t, env, bound))
+                    && default.iter().all(|t| is_pure_(t, env, bound))
+            }
+            Term::Record(map, _) => map.values().all(|t| is_pure_(t, env, bound)),
+            Term::RecRecord(map, dyn_fields, _) => {
+                dyn_fields
+                    .iter()
+                    .all(|(id_t, t)| is_pure_(id_t, env, bound) && is_pure_(t, env, bound))
+                    && {
+                        let len = bound.len();
+                        bound.extend(map.keys().cloned());
+                        let result = map.values().all(|t| is_pure_(t, env, bound));
+                        bound.truncate(len);
+                        result
+                    }
+            }
+            Term::StrChunks(chunks) => chunks.iter().all(|chunk| match chunk {
+                StrChunk::Literal(_) => true,
+                StrChunk::Expr(t, _) => is_pure_(t, env, bound),
+            }),
+        }
+    }
+
+    is_pure_(rt, env, &mut Vec::new())
+}
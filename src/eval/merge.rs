@@ -23,7 +23,10 @@
 //! - *Values*: merging any other values succeeds if and only if these two values are equals, in which case it evaluates to
 //! this common value.
 //!
-//! Note that merging of lists is not yet implemented.
+//! Merging lists fails in standard mode. In the opt-in mode [`MergeMode::ListsByKey`], lists of
+//! records are merged element-wise: elements with the same value for a given key field are
+//! recursively merged, and the elements of the right list which don't match any element of the
+//! left list are appended to it.
 //!
 //! ## On enriched values
 //!
@@ -52,14 +55,18 @@
 //! - *Contract check*: merging a `Contract` or a `ContractDefault` with a simple value `t`
 //! evaluates to a contract check, that is an `Assume(..., t)`
 use crate::error::EvalError;
-use crate::eval::{CallStack, Closure, Environment};
+use crate::eval::{subst, CallStack, Closure, Environment};
+use crate::identifier::Ident;
 use crate::label::Label;
+use crate::mk_app;
 use crate::position::TermPos;
 use crate::term::{
-    make as mk_term, BinaryOp, Contract, MetaValue, RecordAttrs, RichTerm, SharedTerm, Term,
+    make as mk_term, BinaryOp, Contract, MergePath, MetaValue, NAryOp, RecordAttrs, RichTerm,
+    SharedTerm, Term, UnaryOp,
 };
 use crate::transform::Closurizable;
-use std::collections::HashMap;
+use indexmap::IndexMap;
+use std::collections::{HashMap, VecDeque};
 
 /// Merging mode. Merging is used both to combine standard data and to apply contracts defined as
 /// records.
@@ -69,6 +76,9 @@ pub enum MergeMode {
     Standard,
     /// Merging to apply a record contract to a value, with the associated label.
     Contract(Label),
+    /// Standard merging, where lists of records are additionally merged element-wise, by the
+    /// value of the given key field.
+    ListsByKey(Ident),
 }
 
 impl Default for MergeMode {
@@ -84,6 +94,12 @@ impl Default for MergeMode {
 ///
 /// In `Contract` mode (see [`MergingMode`]()), `t1` must be the value and `t2` must be the
 /// contract. It is important as `merge` is not commutative in this mode.
+///
+/// # Path
+///
+/// `path` is the list of the fields leading to the merged values, when the merge comes from the
+/// merge of two records. It is extended with the name of the fields merged recursively, and
+/// reported if the values can't be merged.
 pub fn merge(
     t1: RichTerm,
     mut env1: Environment,
@@ -91,6 +107,7 @@ pub fn merge(
     mut env2: Environment,
     pos_op: TermPos,
     mode: MergeMode,
+    path: MergePath,
 ) -> Result<Closure, EvalError> {
     // Merging a simple value and a metavalue is equivalent to first wrapping the simple value in a
     // new metavalue (with no attribute set excepted the value), and then merging the two
@@ -119,6 +136,10 @@ pub fn merge(
 
     match (t1.into_owned(), t2.into_owned()) {
         // Merge is idempotent on basic terms
+        (Term::Null, Term::Null) => Ok(Closure::atomic_closure(RichTerm::new(
+            Term::Null,
+            pos_op.into_inherited(),
+        ))),
         (Term::Bool(b1), Term::Bool(b2)) => {
             if b1 == b2 {
                 Ok(Closure::atomic_closure(RichTerm::new(
@@ -136,11 +157,14 @@ pub fn merge(
                         pos: pos2,
                     },
                     pos_op,
+                    path.to_vec(),
                 ))
             }
         }
         (Term::Num(n1), Term::Num(n2)) => {
-            if (n1 - n2).abs() < f64::EPSILON {
+            // Numbers are compared exactly, as with `==`. Comparing up to some epsilon would
+            // make merge non associative.
+            if n1 == n2 {
                 Ok(Closure::atomic_closure(RichTerm::new(
                     Term::Num(n1),
                     pos_op.into_inherited(),
@@ -156,6 +180,7 @@ pub fn merge(
                         pos: pos2,
                     },
                     pos_op,
+                    path.to_vec(),
                 ))
             }
         }
@@ -176,6 +201,28 @@ pub fn merge(
                         pos: pos2,
                     },
                     pos_op,
+                    path.to_vec(),
+                ))
+            }
+        }
+        (Term::Enum(id1), Term::Enum(id2)) => {
+            if id1 == id2 {
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Enum(id1),
+                    pos_op.into_inherited(),
+                )))
+            } else {
+                Err(EvalError::MergeIncompatibleArgs(
+                    RichTerm {
+                        term: SharedTerm::new(Term::Enum(id1)),
+                        pos: pos1,
+                    },
+                    RichTerm {
+                        term: SharedTerm::new(Term::Enum(id2)),
+                        pos: pos2,
+                    },
+                    pos_op,
+                    path.to_vec(),
                 ))
             }
         }
@@ -196,6 +243,7 @@ pub fn merge(
                         pos: pos2,
                     },
                     pos_op,
+                    path.to_vec(),
                 ))
             }
         }
@@ -267,7 +315,9 @@ pub fn merge(
                 (Some(t1), Some(t2)) if priority1 == priority2 => {
                     let mut env = Environment::new();
                     (
-                        Some(merge_closurize(&mut env, t1, val_env1, t2, val_env2)),
+                        Some(merge_closurize(
+                            &mut env, t1, val_env1, t2, val_env2, &mode, path,
+                        )),
                         priority1,
                         env,
                     )
@@ -331,35 +381,48 @@ pub fn merge(
              */
             rev_thunks(m1.values_mut(), &mut env1);
             rev_thunks(m2.values_mut(), &mut env2);
-            let (left, center, right) = hashmap::split(m1, m2);
 
-            match mode {
-                MergeMode::Contract(mut lbl) if !attrs2.open && !left.is_empty() => {
+            // The fields of the result are in the order of the left operand, followed by the
+            // fields only defined by the right operand
+            let order: Vec<Ident> = m1.keys().chain(m2.keys()).cloned().collect();
+            let (mut left, mut center, mut right) = hashmap::split(m1, m2);
+
+            match &mode {
+                MergeMode::Contract(lbl) if !attrs2.open && !left.is_empty() => {
                     let fields: Vec<String> =
                         left.keys().map(|field| format!("`{}`", field)).collect();
                     let plural = if fields.len() == 1 { "" } else { "s" };
+                    let mut lbl = lbl.clone();
                     lbl.tag = format!("extra field{} {}", plural, fields.join(","));
                     return Err(EvalError::BlameError(lbl, CallStack::new()));
                 }
                 _ => (),
             };
 
-            let mut m = HashMap::with_capacity(left.len() + center.len() + right.len());
+            let mut m = IndexMap::with_capacity(left.len() + center.len() + right.len());
             let mut env = Environment::new();
 
-            for (field, t) in left.into_iter() {
-                m.insert(field, t.closurize(&mut env, env1.clone()));
-            }
-
-            for (field, t) in right.into_iter() {
-                m.insert(field, t.closurize(&mut env, env2.clone()));
-            }
-
-            for (field, (t1, t2)) in center.into_iter() {
-                m.insert(
-                    field,
-                    merge_closurize(&mut env, t1, env1.clone(), t2, env2.clone()),
-                );
+            for field in order {
+                let t = if let Some(t) = left.remove(&field) {
+                    t.closurize(&mut env, env1.clone())
+                } else if let Some((t1, t2)) = center.remove(&field) {
+                    merge_closurize(
+                        &mut env,
+                        t1,
+                        env1.clone(),
+                        t2,
+                        env2.clone(),
+                        &mode,
+                        path.with_field(field.clone()),
+                    )
+                } else if let Some(t) = right.remove(&field) {
+                    t.closurize(&mut env, env2.clone())
+                } else {
+                    // The fields defined by both operands appear twice in `order`
+                    continue;
+                };
+
+                m.insert(field, t);
             }
 
             Ok(Closure {
@@ -370,6 +433,14 @@ pub fn merge(
                 env,
             })
         }
+        (Term::List(ts1), Term::List(ts2)) if matches!(mode, MergeMode::ListsByKey(_)) => {
+            let key = match mode {
+                MergeMode::ListsByKey(key) => key,
+                _ => unreachable!(),
+            };
+
+            Ok(prepare_merge_lists(key, ts1, env1, ts2, env2, pos_op))
+        }
         //The following cases are either errors or not yet implemented
         (t1_, t2_) => Err(EvalError::MergeIncompatibleArgs(
             RichTerm {
@@ -381,6 +452,7 @@ pub fn merge(
                 pos: pos2,
             },
             pos_op,
+            path.to_vec(),
         )),
     }
 }
@@ -420,22 +492,184 @@ fn merge_doc(doc1: Option<String>, doc2: Option<String>) -> Option<String> {
 
 /// Take the current environment, two terms with their local environment, and return a term which
 /// is the closurized merge of the two.
+///
+/// Nested merges are standard merges, excepted in `ListsByKey` mode which is propagated. `path`
+/// is the path of the merged values, which is lost in `ListsByKey` mode.
 fn merge_closurize(
     env: &mut Environment,
     t1: RichTerm,
     env1: Environment,
     t2: RichTerm,
     env2: Environment,
+    mode: &MergeMode,
+    path: MergePath,
 ) -> RichTerm {
     let mut local_env = Environment::new();
-    let body = RichTerm::from(Term::Op2(
-        BinaryOp::Merge(),
-        t1.closurize(&mut local_env, env1),
-        t2.closurize(&mut local_env, env2),
-    ));
+    let t1 = t1.closurize(&mut local_env, env1);
+    let t2 = t2.closurize(&mut local_env, env2);
+
+    let body = match mode {
+        MergeMode::ListsByKey(key) => mk_term::opn(
+            NAryOp::MergeByKey(),
+            vec![mk_term::string(key.to_string()), t1, t2],
+        ),
+        _ => RichTerm::from(Term::Op2(BinaryOp::Merge(path), t1, t2)),
+    };
     body.closurize(env, local_env)
 }
 
+/// First step of the merge of two lists by key. The elements of both lists are closurized in a
+/// common environment, and the value of the key field of each element (or `null`, if the element
+/// is not a record with this field) is bound to a fresh thunk.
+///
+/// Return the closure `MergeListsByKey key l1 l2 (%deep_seq% keys keys)`, which forces the
+/// evaluation of the keys before combining the elements in [`merge_lists_by_key`].
+fn prepare_merge_lists(
+    key: Ident,
+    ts1: Vec<RichTerm>,
+    env1: Environment,
+    ts2: Vec<RichTerm>,
+    env2: Environment,
+    pos_op: TermPos,
+) -> Closure {
+    let mut env = Environment::new();
+    let ts1: Vec<RichTerm> = ts1
+        .into_iter()
+        .map(|t| t.closurize(&mut env, env1.clone()))
+        .collect();
+    let ts2: Vec<RichTerm> = ts2
+        .into_iter()
+        .map(|t| t.closurize(&mut env, env2.clone()))
+        .collect();
+
+    let keys: Vec<RichTerm> = ts1
+        .iter()
+        .chain(ts2.iter())
+        .map(|t| {
+            let key_of = mk_term::if_then_else(
+                mk_term::op1(UnaryOp::IsRecord(), t.clone()),
+                mk_term::if_then_else(
+                    mk_term::op2(
+                        BinaryOp::HasField(),
+                        mk_term::string(key.to_string()),
+                        t.clone(),
+                    ),
+                    mk_term::op1(UnaryOp::StaticAccess(key.clone()), t.clone()),
+                    Term::Null,
+                ),
+                Term::Null,
+            );
+            let local_env = env.clone();
+            key_of.closurize(&mut env, local_env)
+        })
+        .collect();
+    let keys = RichTerm::from(Term::List(keys));
+
+    let body = mk_term::opn(
+        NAryOp::MergeListsByKey(),
+        vec![
+            mk_term::string(key.to_string()),
+            RichTerm::from(Term::List(ts1)),
+            RichTerm::from(Term::List(ts2)),
+            mk_app!(mk_term::op1(UnaryOp::DeepSeq(), keys.clone()), keys),
+        ],
+    )
+    .with_pos(pos_op.into_inherited());
+
+    Closure { body, env }
+}
+
+/// Combine two lists whose key values have been evaluated. The elements of `ts1` which match an
+/// element of `ts2`, that is which have the same key value, are merged with it. The remaining
+/// elements of `ts2` are appended to the result. If several elements have the same key, they are
+/// matched in order of appearance.
+///
+/// The elements of `ts1` and `ts2` live respectively in `env1` and `env2`, and the keys `keys`
+/// live in `env_keys` and are given for the elements of `ts1` first, then for the elements of
+/// `ts2`.
+#[allow(clippy::too_many_arguments)]
+pub fn merge_lists_by_key(
+    key: Ident,
+    ts1: Vec<RichTerm>,
+    env1: Environment,
+    ts2: Vec<RichTerm>,
+    env2: Environment,
+    keys: Vec<RichTerm>,
+    env_keys: Environment,
+    pos_op: TermPos,
+) -> Result<Closure, EvalError> {
+    let mut key_values: Vec<Option<String>> = Vec::with_capacity(keys.len());
+    for t in keys {
+        match subst(t, &Environment::new(), &env_keys).as_ref() {
+            Term::Str(s) => key_values.push(Some(s.clone())),
+            Term::Null => key_values.push(None),
+            t => {
+                return Err(EvalError::Other(
+                    format!(
+                        "merge by key: expected the key field `{}` to be a string, got {}",
+                        key,
+                        t.type_of().unwrap_or_else(|| String::from("<unevaluated>"))
+                    ),
+                    pos_op,
+                    None,
+                ))
+            }
+        }
+    }
+    let (keys1, keys2) = key_values.split_at(ts1.len());
+
+    let mut unmatched: HashMap<&String, VecDeque<usize>> = HashMap::new();
+    for (i, k) in keys2.iter().enumerate() {
+        if let Some(k) = k {
+            unmatched.entry(k).or_default().push_back(i);
+        }
+    }
+
+    let mut ts2: Vec<Option<RichTerm>> = ts2.into_iter().map(Some).collect();
+    let mut result_env = Environment::new();
+    let mode = MergeMode::ListsByKey(key);
+    let mut result: Vec<RichTerm> = ts1
+        .into_iter()
+        .zip(keys1.iter())
+        .map(|(t1, k)| {
+            let matched = k
+                .as_ref()
+                .and_then(|k| unmatched.get_mut(k))
+                .and_then(|indices| indices.pop_front());
+
+            match matched {
+                Some(j) => merge_closurize(
+                    &mut result_env,
+                    t1,
+                    env1.clone(),
+                    ts2[j].take().unwrap(),
+                    env2.clone(),
+                    &mode,
+                    MergePath::default(),
+                ),
+                None => t1.closurize(&mut result_env, env1.clone()),
+            }
+        })
+        .collect();
+
+    result.extend(
+        ts2.into_iter()
+            .flatten()
+            .map(|t2| t2.closurize(&mut result_env, env2.clone())),
+    );
+
+    Ok(Closure {
+        body: RichTerm::new(Term::List(result), pos_op.into_inherited()),
+        env: result_env,
+    })
+}
+
+/// Replace the fields of a record by fresh variables bound to reverted copies of their thunks.
+///
+/// The fields of a recursive record are bound to revertible thunks, whose original expression may
+/// refer to the other fields. Reverting them before computing the fixpoint of the merged record
+/// makes these references point to the fields of the merged record, such that overriding `b` in
+/// `{a = b + 1, b | default = 1}` also updates `a`, even if it had already been evaluated.
 fn rev_thunks<'a, I: Iterator<Item = &'a mut RichTerm>>(map: I, env: &mut Environment) {
     use crate::transform::fresh_var;
 
@@ -453,24 +687,36 @@ fn rev_thunks<'a, I: Iterator<Item = &'a mut RichTerm>>(map: I, env: &mut Enviro
 }
 
 pub mod hashmap {
-    use std::collections::HashMap;
+    use indexmap::IndexMap;
 
-    /// Split two hashmaps m1 and m2 in three parts (left,center,right), where left holds bindings
+    /// Split two maps m1 and m2 in three parts (left,center,right), where left holds bindings
     /// `(key,value)` where key is not in `m2.keys()`, right is the dual (keys of m2 that are not
-    /// in m1), and center holds bindings for keys that are both in m1 and m2.
+    /// in m1), and center holds bindings for keys that are both in m1 and m2. Left and center are
+    /// in the order of m1, and right in the order of m2.
     pub fn split<K, V1, V2>(
-        m1: HashMap<K, V1>,
-        m2: HashMap<K, V2>,
-    ) -> (HashMap<K, V1>, HashMap<K, (V1, V2)>, HashMap<K, V2>)
+        m1: IndexMap<K, V1>,
+        m2: IndexMap<K, V2>,
+    ) -> (IndexMap<K, V1>, IndexMap<K, (V1, V2)>, IndexMap<K, V2>)
     where
         K: std::hash::Hash + Eq,
     {
-        let mut left = HashMap::new();
-        let mut center = HashMap::new();
-        let mut right = m2;
+        let mut left = IndexMap::new();
+        let mut center = IndexMap::new();
+        let mut right = IndexMap::new();
+        // The values of m2 whose key is also in m1, to be paired with the values of m1 in the
+        // order of m1.
+        let mut common = IndexMap::new();
+
+        for (key, value) in m2 {
+            if m1.contains_key(&key) {
+                common.insert(key, value);
+            } else {
+                right.insert(key, value);
+            }
+        }
 
         for (key, value) in m1 {
-            if let Some(v2) = right.remove(&key) {
+            if let Some(v2) = common.swap_remove(&key) {
                 center.insert(key, (value, v2));
             } else {
                 left.insert(key, value);
@@ -486,8 +732,8 @@ pub mod hashmap {
 
         #[test]
         fn all_left() -> Result<(), String> {
-            let mut m1 = HashMap::new();
-            let m2 = HashMap::<isize, isize>::new();
+            let mut m1 = IndexMap::new();
+            let m2 = IndexMap::<isize, isize>::new();
 
             m1.insert(1, 1);
             let (mut left, center, right) = split(m1, m2);
@@ -505,8 +751,8 @@ pub mod hashmap {
 
         #[test]
         fn all_right() -> Result<(), String> {
-            let m1 = HashMap::<isize, isize>::new();
-            let mut m2 = HashMap::new();
+            let m1 = IndexMap::<isize, isize>::new();
+            let mut m2 = IndexMap::new();
 
             m2.insert(1, 1);
             let (left, center, mut right) = split(m1, m2);
@@ -526,8 +772,8 @@ pub mod hashmap {
 
         #[test]
         fn all_center() -> Result<(), String> {
-            let mut m1 = HashMap::new();
-            let mut m2 = HashMap::new();
+            let mut m1 = IndexMap::new();
+            let mut m2 = IndexMap::new();
 
             m1.insert(1, 1);
             m2.insert(1, 2);
@@ -548,8 +794,8 @@ pub mod hashmap {
 
         #[test]
         fn mixed() -> Result<(), String> {
-            let mut m1 = HashMap::new();
-            let mut m2 = HashMap::new();
+            let mut m1 = IndexMap::new();
+            let mut m2 = IndexMap::new();
 
             m1.insert(1, 1);
             m1.insert(2, 1);
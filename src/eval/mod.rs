@@ -79,6 +79,10 @@
 //!  If `enriched_strict` is set to false, as it is when evaluating `merge`, the machine does not
 //!  evaluate enriched values further, and consider the term evaluated.
 //!
+//!  Raising an error on a missing definition can be relaxed through an
+//!  [`EvalConfig`](struct.EvalConfig.html), for example to preview a partially defined
+//!  configuration.
+//!
 //! # Garbage collection
 //!
 //! Currently the machine relies on Rust's reference counting to manage memory. Precisely, the
@@ -92,7 +96,7 @@ use crate::{
     cache::ImportResolver,
     environment::Environment as GenericEnvironment,
     error::EvalError,
-    identifier::Ident,
+    identifier::{closest_idents, Ident},
     match_sharedterm, mk_app,
     term::{
         make as mk_term, BinaryOp, BindingType, MetaValue, RichTerm, SharedTerm, StrChunk, Term,
@@ -102,14 +106,20 @@ use crate::{
 
 pub mod callstack;
 pub mod lazy;
+pub mod memo;
 pub mod merge;
 pub mod operation;
+pub mod profile;
 pub mod stack;
 
 use callstack::*;
+use codespan::FileId;
 use lazy::*;
+use memo::MemoTable;
 use operation::{continuate_operation, OperationCont};
+use profile::Profile;
 use stack::Stack;
+use std::collections::HashMap;
 
 impl AsRef<Vec<StackElem>> for CallStack {
     fn as_ref(&self) -> &Vec<StackElem> {
@@ -176,6 +186,73 @@ pub fn env_add(env: &mut Environment, id: Ident, rt: RichTerm, local_env: Enviro
     env.insert(id, Thunk::new(closure, IdentKind::Let));
 }
 
+/// Evaluate the entries of a global environment once and for all.
+///
+/// The global environment is shared by all evaluations, and its thunks are accessed each time a
+/// builtin is used. Return an environment where each entry of `env` is replaced by an evaluated
+/// thunk holding its weak head normal form. Accessing such a builtin is then a mere clone of a
+/// stable closure: no update frame is pushed, and the shared thunk is never updated afterwards.
+/// In particular, an evaluation aborted by an error can't leave a black-holed builtin behind in
+/// a global environment which is reused later, as in the REPL.
+pub fn eval_global_env<R>(env: &Environment, resolver: &mut R) -> Result<Environment, EvalError>
+where
+    R: ImportResolver,
+{
+    let mut evaluated = Environment::new();
+
+    for (id, thunk) in env.iter_elems() {
+        let (body, local_env) = eval_closure(
+            Closure::atomic_closure(Term::Var(id.clone()).into()),
+            env,
+            resolver,
+            true,
+        )?;
+        let mut thunk = Thunk::new(
+            Closure {
+                body,
+                env: local_env,
+            },
+            thunk.ident_kind(),
+        );
+        thunk.set_evaluated();
+        evaluated.insert(id.clone(), thunk);
+    }
+
+    Ok(evaluated)
+}
+
+/// What to do when forcing a field which has a type or contracts attached, but no definition.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MissingFieldDefMode {
+    /// Raise a [`EvalError::MissingFieldDef`](../error/enum.EvalError.html#variant.MissingFieldDef).
+    Error,
+    /// Evaluate the field to `null` instead.
+    Null,
+}
+
+/// Options controlling the behavior of the abstract machine.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct EvalConfig {
+    pub missing_field_def: MissingFieldDefMode,
+    /// Share the results of structurally identical pure terms during an evaluation. See
+    /// [`memo`](memo/index.html).
+    pub memoize: bool,
+    /// The maximum number of steps of the abstract machine, or `None` for no limit. Exceeding it
+    /// aborts the evaluation with an
+    /// [`EvalError::StepLimitExceeded`](../error/enum.EvalError.html#variant.StepLimitExceeded).
+    pub max_steps: Option<usize>,
+}
+
+impl Default for EvalConfig {
+    fn default() -> Self {
+        EvalConfig {
+            missing_field_def: MissingFieldDefMode::Error,
+            memoize: false,
+            max_steps: None,
+        }
+    }
+}
+
 /// Evaluate a Nickel term. Wrapper around [eval_closure](fn.eval_closure.html) that starts from an
 /// empty local environment and drops the final environment.
 pub fn eval<R>(
@@ -186,7 +263,27 @@ pub fn eval<R>(
 where
     R: ImportResolver,
 {
-    eval_closure(Closure::atomic_closure(t0), global_env, resolver, true).map(|(term, _)| term)
+    eval_with_config(t0, global_env, resolver, &EvalConfig::default())
+}
+
+/// Same as [eval](fn.eval.html), but with a custom configuration of the abstract machine.
+pub fn eval_with_config<R>(
+    t0: RichTerm,
+    global_env: &Environment,
+    resolver: &mut R,
+    config: &EvalConfig,
+) -> Result<RichTerm, EvalError>
+where
+    R: ImportResolver,
+{
+    eval_closure_with_config(
+        Closure::atomic_closure(t0),
+        global_env,
+        resolver,
+        true,
+        config,
+    )
+    .map(|(term, _)| term)
 }
 
 /// Fully evaluate a Nickel term: the result is not a WHNF but to a value with all variables substituted.
@@ -198,7 +295,22 @@ pub fn eval_full<R>(
 where
     R: ImportResolver,
 {
-    eval_deep_closure(t0, global_env, resolver).map(|(term, env)| subst(term, global_env, &env))
+    eval_full_with_config(t0, global_env, resolver, &EvalConfig::default())
+}
+
+/// Same as [eval_full](fn.eval_full.html), but with a custom configuration of the abstract
+/// machine.
+pub fn eval_full_with_config<R>(
+    t0: RichTerm,
+    global_env: &Environment,
+    resolver: &mut R,
+    config: &EvalConfig,
+) -> Result<RichTerm, EvalError>
+where
+    R: ImportResolver,
+{
+    eval_deep_closure(t0, global_env, resolver, config)
+        .map(|(term, env)| subst(term, global_env, &env))
 }
 
 /// Fully evaluates a Nickel term like `eval_full`, but does not substitute all variables.
@@ -210,13 +322,14 @@ pub fn eval_deep<R>(
 where
     R: ImportResolver,
 {
-    eval_deep_closure(t0, global_env, resolver).map(|(term, _)| term)
+    eval_deep_closure(t0, global_env, resolver, &EvalConfig::default()).map(|(term, _)| term)
 }
 
 fn eval_deep_closure<R>(
     t0: RichTerm,
     global_env: &Environment,
     resolver: &mut R,
+    config: &EvalConfig,
 ) -> Result<(RichTerm, Environment), EvalError>
 where
     R: ImportResolver,
@@ -233,7 +346,13 @@ where
             Term::Var(var)
         ),
     );
-    eval_closure(Closure::atomic_closure(wrapper), global_env, resolver, true)
+    eval_closure_with_config(
+        Closure::atomic_closure(wrapper),
+        global_env,
+        resolver,
+        true,
+        config,
+    )
 }
 
 /// Evaluate a Nickel Term, stopping when a meta value is encountered at the top-level without
@@ -267,6 +386,25 @@ where
     Ok(rt)
 }
 
+/// Compute the cycle of imports leading to the import `id`, whose evaluation has been started but
+/// is not finished, that is which is needed to compute its own value. The imports being currently
+/// evaluated are the ones of `imports_started` whose thunk is still blackholed. Return the paths
+/// of the imports of the cycle, starting and ending with `id`.
+fn import_cycle<R: ImportResolver>(
+    imports_started: &[(FileId, Thunk)],
+    id: FileId,
+    resolver: &R,
+) -> Vec<String> {
+    imports_started
+        .iter()
+        .filter(|(_, thunk)| thunk.state() == ThunkState::Blackholed)
+        .map(|(other, _)| *other)
+        .skip_while(|other| *other != id)
+        .chain(std::iter::once(id))
+        .map(|id| resolver.get_path(id).to_string_lossy().into_owned())
+        .collect()
+}
+
 /// The main loop of evaluation.
 ///
 /// Implement the evaluation of the core language, which includes application, thunk update,
@@ -289,18 +427,145 @@ where
 ///  - an evaluation error
 ///  - the evaluated term with its final environment
 pub fn eval_closure<R>(
+    clos: Closure,
+    global_env: &Environment,
+    resolver: &mut R,
+    enriched_strict: bool,
+) -> Result<(RichTerm, Environment), EvalError>
+where
+    R: ImportResolver,
+{
+    eval_closure_with_config(
+        clos,
+        global_env,
+        resolver,
+        enriched_strict,
+        &EvalConfig::default(),
+    )
+}
+
+/// Same as [eval_closure](fn.eval_closure.html), but with a custom configuration of the abstract
+/// machine.
+pub fn eval_closure_with_config<R>(
+    clos: Closure,
+    global_env: &Environment,
+    resolver: &mut R,
+    enriched_strict: bool,
+    config: &EvalConfig,
+) -> Result<(RichTerm, Environment), EvalError>
+where
+    R: ImportResolver,
+{
+    let mut memo = if config.memoize {
+        Some(MemoTable::new())
+    } else {
+        None
+    };
+
+    eval_closure_with_memo(
+        clos,
+        global_env,
+        resolver,
+        enriched_strict,
+        config,
+        memo.as_mut(),
+    )
+}
+
+/// Same as [eval_closure_with_config](fn.eval_closure_with_config.html), but record and reuse the
+/// results of pure terms in the given memoization table, if any, independently of the value of
+/// `config.memoize`.
+pub fn eval_closure_with_memo<R>(
+    clos: Closure,
+    global_env: &Environment,
+    resolver: &mut R,
+    enriched_strict: bool,
+    config: &EvalConfig,
+    memo: Option<&mut MemoTable>,
+) -> Result<(RichTerm, Environment), EvalError>
+where
+    R: ImportResolver,
+{
+    let mut call_stack = CallStack::new();
+
+    eval_closure_with_memo_(
+        clos,
+        global_env,
+        resolver,
+        enriched_strict,
+        config,
+        memo,
+        None,
+        &mut call_stack,
+    )
+    .map_err(|err| err.with_call_stack(call_stack))
+}
+
+/// Same as [eval_with_config](fn.eval_with_config.html), but also measure the applications of
+/// primitive operators. See [`profile`](profile/index.html).
+pub fn eval_profiled<R>(
+    t0: RichTerm,
+    global_env: &Environment,
+    resolver: &mut R,
+    config: &EvalConfig,
+) -> Result<(RichTerm, Profile), EvalError>
+where
+    R: ImportResolver,
+{
+    let mut memo = if config.memoize {
+        Some(MemoTable::new())
+    } else {
+        None
+    };
+    let mut profile = Profile::new();
+    let mut call_stack = CallStack::new();
+
+    eval_closure_with_memo_(
+        Closure::atomic_closure(t0),
+        global_env,
+        resolver,
+        true,
+        config,
+        memo.as_mut(),
+        Some(&mut profile),
+        &mut call_stack,
+    )
+    .map(|(term, _)| (term, profile))
+    .map_err(|err| err.with_call_stack(call_stack))
+}
+
+/// Same as [eval_closure_with_memo](fn.eval_closure_with_memo.html), but measure the operators in
+/// the given profile, if any, and use the given call stack. The call stack is left as it was when
+/// the evaluation ended, such that the caller can attach it to the error in case of failure.
+#[allow(clippy::too_many_arguments)]
+fn eval_closure_with_memo_<R>(
     mut clos: Closure,
     global_env: &Environment,
     resolver: &mut R,
     mut enriched_strict: bool,
+    config: &EvalConfig,
+    mut memo: Option<&mut MemoTable>,
+    mut profile: Option<&mut Profile>,
+    call_stack: &mut CallStack,
 ) -> Result<(RichTerm, Environment), EvalError>
 where
     R: ImportResolver,
 {
-    let mut call_stack = CallStack::new();
     let mut stack = Stack::new();
+    let mut steps: usize = 0;
+    // The thunks of the imports, such that each import is evaluated at most once, together with
+    // the list of the imports whose evaluation has been started, in order, to report cycles.
+    let mut import_thunks: HashMap<FileId, Thunk> = HashMap::new();
+    let mut imports_started: Vec<(FileId, Thunk)> = Vec::new();
 
     loop {
+        if let Some(max_steps) = config.max_steps {
+            if steps >= max_steps {
+                return Err(EvalError::StepLimitExceeded(max_steps, clos.body.pos));
+            }
+            steps += 1;
+        }
+
         let Closure {
             body: RichTerm {
                 term: shared_term,
@@ -315,20 +580,32 @@ where
 
         clos = match &*shared_term {
             Term::Var(x) => {
-                let mut thunk = env
-                    .get(x)
-                    .or_else(|| global_env.get(x))
-                    .ok_or_else(|| EvalError::UnboundIdentifier(x.clone(), pos))?;
+                let mut thunk = env.get(x).or_else(|| global_env.get(x)).ok_or_else(|| {
+                    let in_scope = env.iter_elems().chain(global_env.iter_elems());
+                    EvalError::UnboundIdentifier(
+                        x.clone(),
+                        closest_idents(x, in_scope.map(|(id, _)| id)),
+                        pos,
+                        None,
+                    )
+                })?;
                 std::mem::drop(env); // thunk may be a 1RC pointer
+                let mut memoized = None;
 
                 if thunk.state() != ThunkState::Evaluated {
                     if thunk.should_update() {
                         match thunk.mk_update_frame() {
                             Ok(thunk_upd) => stack.push_thunk(thunk_upd),
                             Err(BlackholedError) => {
-                                return Err(EvalError::InfiniteRecursion(call_stack, pos))
+                                return Err(EvalError::InfiniteRecursion(call_stack.clone(), pos))
                             }
                         }
+
+                        // The result of a previous evaluation of the same term is already a
+                        // WHNF, which will be used to update the thunk right away.
+                        memoized = memo
+                            .as_deref_mut()
+                            .and_then(|memo| memo.lookup_or_insert(&thunk));
                     }
                     // If the thunk isn't to be updated, directly set the evaluated flag.
                     else {
@@ -336,7 +613,7 @@ where
                     }
                 }
                 call_stack.enter_var(thunk.ident_kind(), x.clone(), pos);
-                thunk.into_closure()
+                memoized.unwrap_or_else(|| thunk.into_closure())
             }
             Term::App(t1, t2) => {
                 call_stack.enter_app(pos);
@@ -389,7 +666,10 @@ where
 
                 stack.push_arg(
                     Closure {
-                        body: RichTerm::new(Term::Record(cases.clone(), Default::default()), pos),
+                        body: RichTerm::new(
+                            Term::Record(cases.clone().into_iter().collect(), Default::default()),
+                            pos,
+                        ),
                         env: env.clone(),
                     },
                     pos,
@@ -482,16 +762,19 @@ where
                             StrChunk::Literal(s) => (Term::Str(s.clone()).into(), 0),
                             StrChunk::Expr(e, indent) => (e.clone(), *indent),
                         };
+                        // The concatenation is positioned at the chunk being evaluated, such that
+                        // errors point to the faulty interpolated expression.
+                        let pos_chunk = if arg.pos.is_def() { arg.pos } else { pos };
 
                         if !enriched_strict {
                             stack.push_strictness(enriched_strict);
                         }
                         enriched_strict = true;
                         stack.push_str_chunks(chunks_iter.cloned());
-                        stack.push_str_acc(String::new(), indent, env.clone());
+                        stack.push_str_acc(String::new(), indent, env.clone(), pos);
 
                         Closure {
-                            body: RichTerm::new(Term::Op1(UnaryOp::ChunksConcat(), arg), pos),
+                            body: RichTerm::new(Term::Op1(UnaryOp::ChunksConcat(), arg), pos_chunk),
                             env,
                         }
                     }
@@ -505,7 +788,12 @@ where
                     |mut rec_env, (id, rt)| match rt.as_ref() {
                         Term::Var(ref var_id) => {
                             let thunk = env.get(var_id).ok_or_else(|| {
-                                EvalError::UnboundIdentifier(var_id.clone(), rt.pos)
+                                EvalError::UnboundIdentifier(
+                                    var_id.clone(),
+                                    Vec::new(),
+                                    rt.pos,
+                                    None,
+                                )
                             })?;
                             rec_env.insert(id.clone(), thunk);
                             Ok(rec_env)
@@ -565,7 +853,12 @@ where
                             match &*t.term {
                                 Term::Var(var_id) => {
                                     let mut thunk = env.get(var_id).ok_or_else(|| {
-                                        EvalError::UnboundIdentifier(var_id.clone(), pos)
+                                        EvalError::UnboundIdentifier(
+                                            var_id.clone(),
+                                            Vec::new(),
+                                            pos,
+                                            None,
+                                        )
                                     })?;
 
                                     thunk.borrow_mut().env.extend(
@@ -622,26 +915,62 @@ where
                         }) => Closure { body: inner, env },
                         _ => unreachable!(),
                     }
+                } else if config.missing_field_def == MissingFieldDefMode::Null {
+                    // As above, keep the content of potential thunks a meta value.
+                    update_thunks(
+                        &mut stack,
+                        &Closure {
+                            body: RichTerm {
+                                term: shared_term.clone(),
+                                pos,
+                            },
+                            env,
+                        },
+                    );
+                    Closure::atomic_closure(RichTerm::new(Term::Null, pos))
                 } else {
                     let label = meta
                         .contracts
                         .last()
                         .or(meta.types.as_ref())
                         .map(|ctr| ctr.label.clone());
-                    return Err(EvalError::MissingFieldDef(label, call_stack));
+                    return Err(EvalError::MissingFieldDef(label, call_stack.clone()));
                 }
             }
             Term::ResolvedImport(id) => {
-                if let Some(t) = resolver.get(*id) {
-                    Closure::atomic_closure(t)
+                let mut thunk = if let Some(thunk) = import_thunks.get(id) {
+                    thunk.clone()
+                } else if let Some(t) = resolver.get(*id) {
+                    let thunk = Thunk::new(Closure::atomic_closure(t), IdentKind::Let);
+                    import_thunks.insert(*id, thunk.clone());
+                    thunk
                 } else {
                     return Err(EvalError::InternalError(
                         format!("Resolved import not found ({:?})", id),
                         pos,
                     ));
+                };
+
+                if thunk.state() != ThunkState::Evaluated {
+                    if thunk.should_update() {
+                        match thunk.mk_update_frame() {
+                            Ok(thunk_upd) => stack.push_thunk(thunk_upd),
+                            Err(BlackholedError) => {
+                                return Err(EvalError::CyclicImport(
+                                    import_cycle(&imports_started, *id, resolver),
+                                    pos,
+                                ))
+                            }
+                        }
+                        imports_started.push((*id, thunk.clone()));
+                    } else {
+                        thunk.set_evaluated();
+                    }
                 }
+
+                thunk.into_closure()
             }
-            Term::Import(path) => {
+            Term::Import(path) | Term::ImportStr(path) => {
                 return Err(EvalError::InternalError(
                     format!("Unresolved import ({})", path.to_string_lossy()),
                     pos,
@@ -660,7 +989,7 @@ where
                     update_thunks(&mut stack, &clos);
                     clos
                 } else {
-                    continuate_operation(clos, &mut stack, &mut call_stack)?
+                    continuate_operation(clos, &mut stack, call_stack, profile.as_deref_mut())?
                 }
             }
             // Function call
@@ -678,14 +1007,25 @@ where
             }
             // Otherwise, this is either an ill-formed application, or we are done
             t => {
-                if let Some((arg, pos_app)) = stack.pop_arg() {
+                let arg_count = stack.count_args();
+                if let Some((arg, mut pos_app)) = stack.pop_arg() {
+                    // The remaining arguments come from the enclosing applications of the same
+                    // spine: the outermost one spans the whole application.
+                    while let Some((_, pos_outer)) = stack.pop_arg() {
+                        if pos_outer.is_def() {
+                            pos_app = pos_outer;
+                        }
+                    }
+
                     return Err(EvalError::NotAFunc(
                         RichTerm {
                             term: shared_term.clone(),
                             pos,
                         },
                         arg.body,
+                        arg_count,
                         pos_app,
+                        None,
                     ));
                 } else {
                     return Ok((RichTerm::new(t.clone(), pos), env));
@@ -738,6 +1078,7 @@ pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichT
             | v @ Term::Var(_)
             | v @ Term::Enum(_)
             | v @ Term::Import(_)
+            | v @ Term::ImportStr(_)
             | v @ Term::ResolvedImport(_) => RichTerm::new(v, pos),
             Term::Let(id, t1, t2, btype) => {
                 let t1 = subst_(t1, global_env, env, Cow::Borrowed(bound.as_ref()));
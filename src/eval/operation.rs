@@ -8,23 +8,48 @@
 //! implement the actual semantics of operators.
 use super::merge;
 use super::merge::{merge, MergeMode};
+use super::profile::Profile;
 use super::stack::Stack;
 use crate::error::EvalError;
 use crate::eval::{subst, CallStack, Closure, Environment};
 use crate::identifier::Ident;
 use crate::label::ty_path;
-use crate::position::TermPos;
+use crate::position::{RawSpan, TermPos};
 use crate::term::make as mk_term;
-use crate::term::{BinaryOp, NAryOp, RichTerm, StrChunk, Term, UnaryOp};
+use crate::term::{
+    BinaryOp, IndexMap, MergePath, NAryOp, RecordAttrs, RichTerm, StrChunk, Term, UnaryOp,
+};
 use crate::transform::Closurizable;
 use crate::{match_sharedterm, mk_record};
 use crate::{mk_app, mk_fun, mk_opn};
 use crate::{serialize, serialize::ExportFormat};
+use codespan::ByteIndex;
 use md5::digest::Digest;
-use simple_counter::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::iter::Extend;
 
-generate_counter!(FreshVariableCounter, usize);
+/// The maximum number of compiled regexes kept in [`REGEX_CACHE`].
+const REGEX_CACHE_CAPACITY: usize = 64;
+
+/// The maximum length, in bytes, of a string built by repeating another one. A longer string is
+/// most likely the result of a mistake, and would exhaust the memory.
+const MAX_REPEATED_STR_LEN: usize = 1 << 30;
+
+/// The maximum number of elements of a list generated by a range. A longer list is most likely the
+/// result of a mistake, and would exhaust the memory.
+const MAX_RANGE_LEN: usize = 1 << 24;
+
+/// The maximum width, in characters, of a padded string. A larger width is most likely the result
+/// of a mistake, and would exhaust the memory.
+const MAX_PADDED_STR_LEN: usize = 1 << 30;
+
+thread_local! {
+    /// The regexes compiled by the regex primitive operations, indexed by their pattern. Compiling
+    /// a regex is much more expensive than matching it, and a program typically uses a few
+    /// patterns many times, e.g. when testing all the elements of a list.
+    static REGEX_CACHE: RefCell<HashMap<String, regex::Regex>> = RefCell::new(HashMap::new());
+}
 
 /// Result of the equality of two terms.
 ///
@@ -84,16 +109,19 @@ impl std::fmt::Debug for OperationCont {
 ///
 /// Depending on the content of the stack, it either starts the evaluation of the first argument,
 /// starts the evaluation of the second argument, or finally process with the operation if both
-/// arguments are evaluated (for binary operators).
+/// arguments are evaluated (for binary operators). In the latter case, the operation is measured
+/// in `profile`, if any.
 pub fn continuate_operation(
     mut clos: Closure,
     stack: &mut Stack,
     call_stack: &mut CallStack,
+    profile: Option<&mut Profile>,
 ) -> Result<Closure, EvalError> {
     let (cont, cs_len, pos) = stack.pop_op_cont().expect("Condition already checked");
     call_stack.truncate(cs_len);
     match cont {
         OperationCont::Op1(u_op, arg_pos) => {
+            let _timer = profile.map(|profile| profile.start(&u_op));
             process_unary_operation(u_op, clos, arg_pos, stack, call_stack, pos)
         }
         OperationCont::Op2First(b_op, mut snd_clos, fst_pos) => {
@@ -105,9 +133,12 @@ pub fn continuate_operation(
             );
             Ok(clos)
         }
-        OperationCont::Op2Second(b_op, fst_clos, fst_pos, snd_pos) => process_binary_operation(
-            b_op, fst_clos, fst_pos, clos, snd_pos, stack, call_stack, pos,
-        ),
+        OperationCont::Op2Second(b_op, fst_clos, fst_pos, snd_pos) => {
+            let _timer = profile.map(|profile| profile.start(&b_op));
+            process_binary_operation(
+                b_op, fst_clos, fst_pos, clos, snd_pos, stack, call_stack, pos,
+            )
+        }
         OperationCont::OpN {
             op,
             mut evaluated,
@@ -116,7 +147,10 @@ pub fn continuate_operation(
         } => {
             evaluated.push((clos, current_pos));
 
-            if let Some(next) = pending.pop() {
+            if evaluated.len() < op.strict_arity() {
+                let next = pending
+                    .pop()
+                    .expect("missing argument of an n-ary operation");
                 let current_pos = next.body.pos;
                 stack.push_op_cont(
                     OperationCont::OpN {
@@ -131,7 +165,14 @@ pub fn continuate_operation(
 
                 Ok(next)
             } else {
-                process_nary_operation(op, evaluated, stack, pos)
+                // The remaining arguments are passed unevaluated
+                evaluated.extend(pending.into_iter().rev().map(|clos| {
+                    let pos = clos.body.pos;
+                    (clos, pos)
+                }));
+
+                let _timer = profile.map(|profile| profile.start(&op));
+                process_nary_operation(op, evaluated, stack, call_stack, pos)
             }
         }
     }
@@ -199,17 +240,37 @@ fn process_unary_operation(
             Term::Bool(matches!(*t, Term::Record(..) | Term::RecRecord(..))),
             pos_op_inh,
         ))),
+        UnaryOp::IsNull() => Ok(Closure::atomic_closure(RichTerm::new(
+            Term::Bool(matches!(*t, Term::Null)),
+            pos_op_inh,
+        ))),
+        UnaryOp::TypeOf() => {
+            let type_name = match *t {
+                Term::Num(..) => "Num",
+                Term::Bool(..) => "Bool",
+                Term::Str(..) => "Str",
+                Term::Record(..) | Term::RecRecord(..) => "Record",
+                Term::List(..) => "List",
+                Term::Fun(..) => "Fun",
+                Term::Enum(..) => "Enum",
+                Term::Null => "Null",
+                _ => "Other",
+            };
+
+            Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Str(String::from(type_name)),
+                pos_op_inh,
+            )))
+        }
         UnaryOp::BoolAnd() =>
         // The syntax should not allow partially applied boolean operators.
         {
             if let Some((next, ..)) = stack.pop_arg() {
                 match &*t {
-                    Term::Bool(true) => Ok(next),
-                    // FIXME: this does not check that the second argument is actually a boolean.
-                    // This means `true && 2` silently evaluates to `2`. This is simpler and more
-                    // efficient, but can make debugging harder. In any case, it should be solved
-                    // only once primary operators have better support for laziness in some
-                    // arguments.
+                    Term::Bool(true) => {
+                        push_bool_guard(stack, "&&", next.body.pos, call_stack.len(), pos_op);
+                        Ok(next)
+                    }
                     Term::Bool(false) => Ok(Closure::atomic_closure(RichTerm {
                         term: t,
                         pos: pos_op_inh,
@@ -232,12 +293,10 @@ fn process_unary_operation(
                         term: t,
                         pos: pos_op_inh,
                     })),
-                    // FIXME: this does not check that the second argument is actually a boolean.
-                    // This means `false || 2` silently evaluates to `2`. This is simpler and more
-                    // efficient, but can make debugging harder. In any case, it should be solved
-                    // only once primary operators have better support for laziness in some
-                    // arguments.
-                    Term::Bool(false) => Ok(next),
+                    Term::Bool(false) => {
+                        push_bool_guard(stack, "||", next.body.pos, call_stack.len(), pos_op);
+                        Ok(next)
+                    }
                     _ => Err(EvalError::TypeError(
                         String::from("Bool"),
                         String::from("||"),
@@ -264,6 +323,18 @@ fn process_unary_operation(
                 ))
             }
         }
+        UnaryOp::BoolGuard(op_name) => {
+            if let Term::Bool(_) = *t {
+                Ok(Closure::atomic_closure(RichTerm { term: t, pos }))
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Bool"),
+                    String::from(op_name),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
         UnaryOp::Blame() => match_sharedterm! { t, with {
                 Term::Lbl(label) => Err(
                     EvalError::BlameError(
@@ -450,6 +521,34 @@ fn process_unary_operation(
                 ))
             }
         }
+        UnaryOp::RecordInsert(id) => {
+            let (clos, _) = stack
+                .pop_arg()
+                .ok_or_else(|| EvalError::NotEnoughArgs(2, String::from("recordInsert"), pos_op))?;
+
+            match_sharedterm! {t, with {
+                    Term::Record(static_map, attrs) => {
+                        let mut static_map = static_map;
+                        let mut env = env;
+                        let as_var = clos.body.closurize(&mut env, clos.env);
+                        match static_map.insert(id.clone(), as_var) {
+                            Some(_) => Err(EvalError::FieldAlreadyDefined(id.to_string(), String::from("recordInsert"), pos_op)),
+                            None => Ok(Closure {
+                                body: RichTerm::new(Term::Record(static_map, attrs), pos_op_inh),
+                                env,
+                            }),
+                        }
+                    }
+                } else {
+                    Err(EvalError::TypeError(
+                        String::from("Record"),
+                        String::from("recordInsert"),
+                        arg_pos,
+                        RichTerm { term: t, pos },
+                    ))
+                }
+            }
+        }
         UnaryOp::StaticAccess(id) => {
             if let Term::Record(static_map, ..) = &*t {
                 match static_map.get(&id) {
@@ -478,9 +577,10 @@ fn process_unary_operation(
         }
         UnaryOp::FieldsOf() => match_sharedterm! {t, with {
                 Term::Record(map, ..) => {
-                    let mut fields: Vec<String> = map.into_keys().map(|id| id.to_string()).collect();
-                    fields.sort();
-                    let terms = fields.into_iter().map(mk_term::string).collect();
+                    let terms = map
+                        .into_iter()
+                        .map(|(id, _)| mk_term::string(id.to_string()))
+                        .collect();
                     Ok(Closure::atomic_closure(RichTerm::new(
                         Term::List(terms),
                         pos_op_inh,
@@ -497,12 +597,7 @@ fn process_unary_operation(
         },
         UnaryOp::ValuesOf() => match_sharedterm! {t, with {
                 Term::Record(map, ..) => {
-                    let mut values: Vec<_> = map.into_iter().collect();
-                    // Although it seems that sort_by_key would be easier here, it would actually
-                    // require to copy the identifiers because of the lack of HKT. See
-                    // https://github.com/rust-lang/rust/issues/34162.
-                    values.sort_by(|(id1, _), (id2, _)| id1.cmp(id2));
-                    let terms = values.into_iter().map(|(_, t)| t).collect();
+                    let terms = map.into_iter().map(|(_, t)| t).collect();
                     Ok(Closure {
                         body: RichTerm::new(Term::List(terms), pos_op_inh),
                         env,
@@ -517,6 +612,30 @@ fn process_unary_operation(
                 ))
             }
         },
+        UnaryOp::RecordToList() => match_sharedterm! {t, with {
+                Term::Record(map, ..) => {
+                    let mut shared_env = Environment::new();
+                    let terms = map
+                        .into_iter()
+                        .map(|(id, t)| {
+                            mk_record!(("field", mk_term::string(id.label)), ("value", t))
+                                .closurize(&mut shared_env, env.clone())
+                        })
+                        .collect();
+                    Ok(Closure {
+                        body: RichTerm::new(Term::List(terms), pos_op_inh),
+                        env: shared_env,
+                    })
+                }
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Record"),
+                    String::from("recordToList"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        },
         UnaryOp::ListMap() => {
             let (f, ..) = stack
                 .pop_arg()
@@ -552,6 +671,214 @@ fn process_unary_operation(
                 }
             }
         }
+        UnaryOp::ListSort() => {
+            let (f, ..) = stack
+                .pop_arg()
+                .ok_or_else(|| EvalError::NotEnoughArgs(2, String::from("sort"), pos_op))?;
+            match_sharedterm! {t, with {
+                    Term::List(ts) if ts.len() <= 1 => Ok(Closure {
+                        body: RichTerm::new(Term::List(ts), pos_op_inh),
+                        env,
+                    }),
+                    Term::List(ts) => {
+                        // The sorted elements, the remaining ones and the comparison function
+                        // all live in the same environment, which is the one of the final result.
+                        let mut shared_env = Environment::new();
+                        let f_as_var = f.body.closurize(&mut shared_env, f.env);
+                        let mut ts: Vec<RichTerm> = ts
+                            .into_iter()
+                            .map(|t| t.closurize(&mut shared_env, env.clone()))
+                            .collect();
+                        let f = Closure {
+                            body: f_as_var,
+                            env: shared_env,
+                        };
+
+                        // The remaining elements are stored in reverse order, such that the next
+                        // one to insert is at the end.
+                        let mut remaining = ts.split_off(1);
+                        remaining.reverse();
+
+                        Ok(sort_step(ts, remaining, (0, 1), f, stack, call_stack, pos_op))
+                    }
+                } else {
+                    Err(EvalError::TypeError(
+                        String::from("List"),
+                        String::from("sort, 2nd argument"),
+                        arg_pos,
+                        RichTerm { term: t, pos },
+                    ))
+                }
+            }
+        }
+        UnaryOp::ListSortStep(mut lo, mut hi) => {
+            let mut sorted = pop_list_state(stack);
+            let mut remaining = pop_list_state(stack);
+            let (f, ..) = stack
+                .pop_arg()
+                .expect("sort: missing comparison function on the stack");
+
+            let mid = (lo + hi) / 2;
+            match &*t {
+                Term::Enum(id) if id.label == "Less" => hi = mid,
+                // Inserting equal elements after the ones already sorted makes the sort stable.
+                Term::Enum(id) if id.label == "Equal" || id.label == "Greater" => lo = mid + 1,
+                _ => {
+                    return Err(EvalError::TypeError(
+                        String::from("<Less, Equal, Greater>"),
+                        String::from("sort, result of the comparison function"),
+                        arg_pos,
+                        RichTerm { term: t, pos },
+                    ))
+                }
+            }
+
+            if lo < hi {
+                return Ok(sort_step(
+                    sorted,
+                    remaining,
+                    (lo, hi),
+                    f,
+                    stack,
+                    call_stack,
+                    pos_op,
+                ));
+            }
+
+            sorted.insert(lo, remaining.pop().unwrap());
+
+            if remaining.is_empty() {
+                Ok(Closure {
+                    body: RichTerm::new(Term::List(sorted), pos_op_inh),
+                    env: f.env,
+                })
+            } else {
+                let len = sorted.len();
+                Ok(sort_step(
+                    sorted,
+                    remaining,
+                    (0, len),
+                    f,
+                    stack,
+                    call_stack,
+                    pos_op,
+                ))
+            }
+        }
+        UnaryOp::ListFilterStep() => {
+            let mut kept = pop_list_state(stack);
+            let mut remaining = pop_list_state(stack);
+            let (pred, ..) = stack
+                .pop_arg()
+                .expect("filter: missing predicate on the stack");
+
+            let next = remaining.pop().unwrap();
+            match *t {
+                Term::Bool(true) => kept.push(next),
+                Term::Bool(false) => (),
+                _ => {
+                    return Err(EvalError::TypeError(
+                        String::from("Bool"),
+                        String::from("filter, result of the predicate"),
+                        arg_pos,
+                        RichTerm { term: t, pos },
+                    ))
+                }
+            }
+
+            if remaining.is_empty() {
+                Ok(Closure {
+                    body: RichTerm::new(Term::List(kept), pos_op_inh),
+                    env: pred.env,
+                })
+            } else {
+                Ok(filter_step(
+                    kept, remaining, pred, stack, call_stack, pos_op,
+                ))
+            }
+        }
+        UnaryOp::RecordFilterStep() => {
+            let (mut kept, attrs) = pop_record_state(stack);
+            let mut names = pop_list_state(stack);
+            let mut remaining = pop_list_state(stack);
+            let (pred, ..) = stack
+                .pop_arg()
+                .expect("recordFilter: missing predicate on the stack");
+
+            let id = match names.pop().unwrap().term.into_owned() {
+                Term::Str(s) => Ident::from(s),
+                _ => panic!("recordFilter: expected the name of a field to be a string"),
+            };
+            let next = remaining.pop().unwrap();
+            match *t {
+                Term::Bool(true) => {
+                    kept.insert(id, next);
+                }
+                Term::Bool(false) => (),
+                _ => {
+                    return Err(EvalError::TypeError(
+                        String::from("Bool"),
+                        String::from("recordFilter, result of the predicate"),
+                        arg_pos,
+                        RichTerm { term: t, pos },
+                    ))
+                }
+            }
+
+            if remaining.is_empty() {
+                Ok(Closure {
+                    body: RichTerm::new(Term::Record(kept, attrs), pos_op_inh),
+                    env: pred.env,
+                })
+            } else {
+                Ok(record_filter_step(
+                    (kept, attrs),
+                    names,
+                    remaining,
+                    pred,
+                    stack,
+                    call_stack,
+                    pos_op,
+                ))
+            }
+        }
+        UnaryOp::ListFoldlStep() => {
+            let remaining = pop_list_state(stack);
+            let (f, ..) = stack
+                .pop_arg()
+                .expect("foldl: missing function on the stack");
+            let acc = Closure {
+                body: RichTerm { term: t, pos },
+                env,
+            };
+
+            Ok(foldl_step(acc, remaining, f, stack, call_stack, pos_op))
+        }
+        UnaryOp::ListQuantifierStep(stop_on) => {
+            let mut remaining = pop_list_state(stack);
+            let (pred, ..) = stack
+                .pop_arg()
+                .expect("all/any: missing predicate on the stack");
+
+            remaining.pop();
+            match *t {
+                Term::Bool(b) if b == stop_on || remaining.is_empty() => Ok(
+                    Closure::atomic_closure(RichTerm::new(Term::Bool(b), pos_op_inh)),
+                ),
+                Term::Bool(_) => Ok(quantifier_step(
+                    remaining, pred, stop_on, stack, call_stack, pos_op,
+                )),
+                _ => Err(EvalError::TypeError(
+                    String::from("Bool"),
+                    format!(
+                        "{}, result of the predicate",
+                        if stop_on { "any" } else { "all" }
+                    ),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                )),
+            }
+        }
         UnaryOp::ListGen() => {
             let (f, _) = stack
                 .pop_arg()
@@ -566,6 +893,7 @@ fn process_unary_operation(
                             n
                         ),
                         pos_op,
+                        None,
                     ))
                 } else {
                     let mut shared_env = Environment::new();
@@ -678,6 +1006,24 @@ fn process_unary_operation(
                 }
             }
         }
+        UnaryOp::RecordElideNulls(in_lists) => {
+            if let Term::Record(..) = &*t {
+                // The record needs to be fully substituted to inspect the value of its fields
+                let global_env = Environment::new();
+                let rt = subst(RichTerm { term: t, pos }, &global_env, &env);
+
+                Ok(Closure::atomic_closure(
+                    elide_nulls(rt, in_lists).with_pos(pos_op_inh),
+                ))
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Record"),
+                    String::from("elideNulls"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
         UnaryOp::ListHead() => {
             if let Term::List(ts) = &*t {
                 if let Some(head) = ts.first() {
@@ -686,7 +1032,7 @@ fn process_unary_operation(
                         env,
                     })
                 } else {
-                    Err(EvalError::Other(String::from("head: empty list"), pos_op))
+                    Err(EvalError::EmptyList(String::from("head"), pos_op))
                 }
             } else {
                 Err(EvalError::TypeError(
@@ -706,7 +1052,7 @@ fn process_unary_operation(
                                 env,
                             })
                         } else {
-                            Err(EvalError::Other(String::from("tail: empty list"), pos_op))
+                            Err(EvalError::EmptyList(String::from("tail"), pos_op))
                         }
                     }
                 } else {
@@ -718,6 +1064,102 @@ fn process_unary_operation(
                     ))
                 }
         },
+        UnaryOp::ListLast() => {
+            if let Term::List(ts) = &*t {
+                if let Some(last) = ts.last() {
+                    Ok(Closure {
+                        body: last.clone(),
+                        env,
+                    })
+                } else {
+                    Err(EvalError::EmptyList(String::from("last"), pos_op))
+                }
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("List"),
+                    String::from("last"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
+        UnaryOp::ListInit() => match_sharedterm! {t, with {
+                    Term::List(ts) => {
+                        let mut ts = ts;
+                        if ts.pop().is_some() {
+                            Ok(Closure {
+                                body: RichTerm::new(Term::List(ts), pos_op_inh),
+                                env,
+                            })
+                        } else {
+                            Err(EvalError::EmptyList(String::from("init"), pos_op))
+                        }
+                    }
+                } else {
+                    Err(EvalError::TypeError(
+                        String::from("List"),
+                        String::from("init"),
+                        arg_pos,
+                        RichTerm { term: t, pos },
+                    ))
+                }
+        },
+        UnaryOp::ListReverse() => match_sharedterm! {t, with {
+                    Term::List(ts) => {
+                        let mut ts = ts;
+                        ts.reverse();
+                        Ok(Closure {
+                            body: RichTerm::new(Term::List(ts), pos_op_inh),
+                            env,
+                        })
+                    }
+                } else {
+                    Err(EvalError::TypeError(
+                        String::from("List"),
+                        String::from("reverse"),
+                        arg_pos,
+                        RichTerm { term: t, pos },
+                    ))
+                }
+        },
+        UnaryOp::ListFlatten() => {
+            if let Term::List(ts) = &*t {
+                // The inner lists are concatenated along a balanced tree, such that each element
+                // is only copied a logarithmic number of times.
+                fn concat_tree(ts: &[RichTerm], pos: TermPos) -> RichTerm {
+                    match ts {
+                        [] => RichTerm::new(Term::List(Vec::new()), pos),
+                        [t] => mk_term::op2(
+                            BinaryOp::ListConcat(),
+                            t.clone(),
+                            RichTerm::new(Term::List(Vec::new()), pos),
+                        )
+                        .with_pos(pos),
+                        _ => {
+                            let (left, right) = ts.split_at(ts.len() / 2);
+                            mk_term::op2(
+                                BinaryOp::ListConcat(),
+                                concat_tree(left, pos),
+                                concat_tree(right, pos),
+                            )
+                            .with_pos(pos)
+                        }
+                    }
+                }
+
+                Ok(Closure {
+                    body: concat_tree(ts, pos_op_inh),
+                    env,
+                })
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("List"),
+                    String::from("flatten"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
         UnaryOp::ListLength() => {
             if let Term::List(ts) = &*t {
                 // A num does not have any free variable so we can drop the environment
@@ -734,40 +1176,107 @@ fn process_unary_operation(
                 ))
             }
         }
-        UnaryOp::ChunksConcat() => {
-            let (mut acc, indent, env_chunks) = stack.pop_str_acc().unwrap();
+        UnaryOp::ListSum() => {
+            // Above 2^53, consecutive integers can't all be represented by a number.
+            const MAX_EXACT_INT: f64 = 9007199254740992.0;
 
-            if let Term::Str(s) = &*t {
-                let s = if indent != 0 {
-                    let indent_str: String = std::iter::once('\n')
-                        .chain((0..indent).map(|_| ' '))
-                        .collect();
-                    s.replace("\n", &indent_str)
-                } else {
-                    s.clone()
+            if let Term::List(ts) = &*t {
+                let nums: Vec<(f64, TermPos)> = match list_values(ts, &env, |t| match t {
+                    Term::Num(n) => Some(*n),
+                    _ => None,
+                }) {
+                    Ok(Some(nums)) => nums.into_iter().zip(ts.iter().map(|t| t.pos)).collect(),
+                    Ok(None) => {
+                        return Ok(seq_list_elements(
+                            ts,
+                            &env,
+                            pos,
+                            Environment::new(),
+                            |list| mk_term::op1(UnaryOp::ListSum(), list),
+                            pos_op_inh,
+                        ))
+                    }
+                    Err(t) => {
+                        return Err(EvalError::TypeError(
+                            String::from("Num"),
+                            String::from("sum, element of the argument"),
+                            t.pos,
+                            t.clone(),
+                        ))
+                    }
                 };
 
-                acc.push_str(&s);
+                let sum = if nums.iter().all(|(n, _)| n.fract() == 0.0) {
+                    // The elements are bounded by 2^53, so their sum can't overflow an `i128`.
+                    let mut sum = 0i128;
 
-                let mut next_opt = stack.pop_str_chunk();
+                    for (n, elt_pos) in nums {
+                        if n.abs() > MAX_EXACT_INT {
+                            return Err(EvalError::IntegerOverflow(
+                                String::from("sum, element of the argument"),
+                                elt_pos,
+                            ));
+                        }
+                        sum += n as i128;
+                    }
 
-                // Pop consecutive string literals to find the next expression to evaluate
-                while let Some(StrChunk::Literal(s)) = next_opt {
-                    acc.push_str(&s);
-                    next_opt = stack.pop_str_chunk();
-                }
+                    if sum.abs() > MAX_EXACT_INT as i128 {
+                        return Err(EvalError::IntegerOverflow(String::from("sum"), pos_op));
+                    }
 
-                if let Some(StrChunk::Expr(e, indent)) = next_opt {
-                    stack.push_str_acc(acc, indent, env_chunks.clone());
+                    sum as f64
+                } else {
+                    nums.iter().map(|(n, _)| n).sum()
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(sum),
+                    pos_op_inh,
+                )))
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("List"),
+                    String::from("sum"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
+        UnaryOp::ChunksConcat() => {
+            let (mut acc, indent, env_chunks, pos_str) = stack.pop_str_acc().unwrap();
+
+            if let Term::Str(s) = &*t {
+                let s = if indent != 0 {
+                    let indent_str: String = std::iter::once('\n')
+                        .chain((0..indent).map(|_| ' '))
+                        .collect();
+                    s.replace("\n", &indent_str)
+                } else {
+                    s.clone()
+                };
+
+                acc.push_str(&s);
+
+                let mut next_opt = stack.pop_str_chunk();
+
+                // Pop consecutive string literals to find the next expression to evaluate
+                while let Some(StrChunk::Literal(s)) = next_opt {
+                    acc.push_str(&s);
+                    next_opt = stack.pop_str_chunk();
+                }
+
+                if let Some(StrChunk::Expr(e, indent)) = next_opt {
+                    stack.push_str_acc(acc, indent, env_chunks.clone(), pos_str);
+                    let pos_chunk = if e.pos.is_def() { e.pos } else { pos_str };
 
                     Ok(Closure {
-                        body: RichTerm::new(Term::Op1(UnaryOp::ChunksConcat(), e), pos_op_inh),
+                        body: RichTerm::new(Term::Op1(UnaryOp::ChunksConcat(), e), pos_chunk),
                         env: env_chunks,
                     })
                 } else {
                     Ok(Closure::atomic_closure(RichTerm::new(
                         Term::Str(acc),
-                        pos_op_inh,
+                        pos_str.into_inherited(),
                     )))
                 }
             } else {
@@ -796,6 +1305,36 @@ fn process_unary_operation(
                 ))
             }
         }
+        UnaryOp::StrTrimStart() => {
+            if let Term::Str(s) = &*t {
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(String::from(s.trim_start())),
+                    pos_op_inh,
+                )))
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Str"),
+                    String::from("trimStart"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
+        UnaryOp::StrTrimEnd() => {
+            if let Term::Str(s) = &*t {
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(String::from(s.trim_end())),
+                    pos_op_inh,
+                )))
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Str"),
+                    String::from("trimEnd"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
         UnaryOp::StrChars() => {
             if let Term::Str(s) = &*t {
                 let ts = s
@@ -815,6 +1354,54 @@ fn process_unary_operation(
                 ))
             }
         }
+        UnaryOp::StrLines() => {
+            if let Term::Str(s) = &*t {
+                // Splitting on line feeds gives one last empty part if the string ends with a line
+                // feed, which is not a line but is recorded in `trailing_newline`.
+                let (content, trailing_newline) = match s.strip_suffix('\n') {
+                    Some(content) => (content, true),
+                    None => (s.as_str(), false),
+                };
+                let lines = if s.is_empty() {
+                    Vec::new()
+                } else {
+                    content
+                        .split('\n')
+                        .map(|line| RichTerm::from(Term::Str(String::from(line))))
+                        .collect()
+                };
+
+                Ok(Closure::atomic_closure(
+                    mk_record!(
+                        ("lines", Term::List(lines)),
+                        ("trailing_newline", Term::Bool(trailing_newline))
+                    )
+                    .with_pos(pos_op_inh),
+                ))
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Str"),
+                    String::from("strLines"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
+        UnaryOp::StrDedent() => {
+            if let Term::Str(s) = &*t {
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(dedent(s)),
+                    pos_op_inh,
+                )))
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Str"),
+                    String::from("strDedent"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
         UnaryOp::CharCode() => {
             if let Term::Str(s) = &*t {
                 if s.len() == 1 {
@@ -827,6 +1414,7 @@ fn process_unary_operation(
                     Err(EvalError::Other(
                         format!("charCode: expected 1-char string, got `{}`", s.len()),
                         pos,
+                        None,
                     ))
                 }
             } else {
@@ -841,9 +1429,13 @@ fn process_unary_operation(
         UnaryOp::CharFromCode() => {
             if let Term::Num(code) = *t {
                 if code.fract() != 0.0 {
-                    Err(EvalError::Other(format!("charFromCode: expected the agument to be an integer, got the floating-point value {}", code), pos_op))
+                    Err(EvalError::NotAnInteger(
+                        String::from("charFromCode"),
+                        code,
+                        pos_op,
+                    ))
                 } else if code < 0.0 || code > (u32::MAX as f64) {
-                    Err(EvalError::Other(format!("charFromCode: code out of bounds. Expected a value between 0 and {}, got {}", u32::MAX, code), pos_op))
+                    Err(EvalError::Other(format!("charFromCode: code out of bounds. Expected a value between 0 and {}, got {}", u32::MAX, code), pos_op, None))
                 } else if let Some(car) = std::char::from_u32(code as u32) {
                     Ok(Closure::atomic_closure(RichTerm::new(
                         Term::Str(String::from(car)),
@@ -853,6 +1445,7 @@ fn process_unary_operation(
                     Err(EvalError::Other(
                         format!("charFromCode: invalid character code {}", code),
                         pos_op,
+                        None,
                     ))
                 }
             } else {
@@ -879,6 +1472,39 @@ fn process_unary_operation(
                 ))
             }
         }
+        UnaryOp::Base64Encode()
+        | UnaryOp::Base64Decode()
+        | UnaryOp::HexEncode()
+        | UnaryOp::HexDecode() => {
+            let op_name = match u_op {
+                UnaryOp::Base64Encode() => "base64Encode",
+                UnaryOp::Base64Decode() => "base64Decode",
+                UnaryOp::HexEncode() => "hexEncode",
+                _ => "hexDecode",
+            };
+
+            if let Term::Str(s) = &*t {
+                let result = match u_op {
+                    UnaryOp::Base64Encode() => Ok(base64_encode(s.as_bytes())),
+                    UnaryOp::HexEncode() => Ok(hex_encode(s.as_bytes())),
+                    UnaryOp::Base64Decode() => base64_decode(s).and_then(decoded_to_string),
+                    _ => hex_decode(s).and_then(decoded_to_string),
+                }
+                .map_err(|msg| EvalError::Other(format!("{}: {}", op_name, msg), arg_pos, None))?;
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(result),
+                    pos_op_inh,
+                )))
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Str"),
+                    String::from(op_name),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
         UnaryOp::StrLowercase() => {
             if let Term::Str(s) = &*t {
                 Ok(Closure::atomic_closure(RichTerm::new(
@@ -921,6 +1547,7 @@ fn process_unary_operation(
                         t.type_of().unwrap()
                     ),
                     pos,
+                    None,
                 )),
             }?;
             Ok(Closure::atomic_closure(RichTerm::new(result, pos_op_inh)))
@@ -928,7 +1555,7 @@ fn process_unary_operation(
         UnaryOp::NumFromStr() => {
             if let Term::Str(s) = &*t {
                 let n = s.parse::<f64>().map_err(|_| {
-                    EvalError::Other(format!("numFrom: invalid num literal `{}`", s), pos)
+                    EvalError::Other(format!("numFrom: invalid num literal `{}`", s), pos, None)
                 })?;
                 Ok(Closure::atomic_closure(RichTerm::new(
                     Term::Num(n),
@@ -958,145 +1585,508 @@ fn process_unary_operation(
                 ))
             }
         }
-    }
-}
-
-/// Evaluate a binary operation.
-///
-/// Both arguments are expected to be evaluated (in WHNF). `pos_op` corresponds to the whole
-/// operation position, that may be needed for error reporting.
-fn process_binary_operation(
-    b_op: BinaryOp,
-    fst_clos: Closure,
-    fst_pos: TermPos,
-    clos: Closure,
-    snd_pos: TermPos,
-    stack: &mut Stack,
-    call_stack: &mut CallStack,
-    pos_op: TermPos,
-) -> Result<Closure, EvalError> {
-    let Closure {
-        body: RichTerm {
-            term: t1,
-            pos: pos1,
-        },
-        env: env1,
-    } = fst_clos;
-    let Closure {
-        body: RichTerm {
-            term: t2,
-            pos: pos2,
-        },
-        env: mut env2,
-    } = clos;
-    let pos_op_inh = pos_op.into_inherited();
+        UnaryOp::EnumTag() => {
+            if let Term::Enum(id) = &*t {
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(id.to_string()),
+                    pos_op_inh,
+                )))
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Enum"),
+                    String::from("enumTag"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
+        UnaryOp::Floor() | UnaryOp::Ceil() | UnaryOp::Round() => {
+            if let Term::Num(n) = *t {
+                let result = match u_op {
+                    UnaryOp::Floor() => n.floor(),
+                    UnaryOp::Ceil() => n.ceil(),
+                    // `f64::round` rounds half-way cases away from zero
+                    _ => n.round(),
+                };
 
-    match b_op {
-        BinaryOp::Plus() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
-                    Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Num(n1 + n2),
-                        pos_op_inh,
-                    )))
-                } else {
-                    Err(EvalError::TypeError(
-                        String::from("Num"),
-                        String::from("+, 2nd argument"),
-                        snd_pos,
-                        RichTerm {
-                            term: t2,
-                            pos: pos2,
-                        },
-                    ))
-                }
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(result),
+                    pos_op_inh,
+                )))
             } else {
+                let op_name = match u_op {
+                    UnaryOp::Floor() => "floor",
+                    UnaryOp::Ceil() => "ceil",
+                    _ => "round",
+                };
+
                 Err(EvalError::TypeError(
                     String::from("Num"),
-                    String::from("+, 1st argument"),
-                    fst_pos,
-                    RichTerm {
-                        term: t1,
-                        pos: pos1,
-                    },
+                    String::from(op_name),
+                    arg_pos,
+                    RichTerm { term: t, pos },
                 ))
             }
         }
-        BinaryOp::Sub() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
-                    Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Num(n1 - n2),
-                        pos_op_inh,
-                    )))
-                } else {
-                    Err(EvalError::TypeError(
-                        String::from("Num"),
-                        String::from("-, 2nd argument"),
-                        snd_pos,
-                        RichTerm {
-                            term: t2,
-                            pos: pos2,
-                        },
-                    ))
-                }
+        UnaryOp::Abs() => {
+            if let Term::Num(n) = *t {
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(n.abs()),
+                    pos_op_inh,
+                )))
             } else {
                 Err(EvalError::TypeError(
                     String::from("Num"),
-                    String::from("-, 1st argument"),
-                    fst_pos,
-                    RichTerm {
-                        term: t1,
-                        pos: pos1,
-                    },
+                    String::from("abs"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
                 ))
             }
         }
-        BinaryOp::Mult() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
-                    Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Num(n1 * n2),
-                        pos_op_inh,
-                    )))
+        UnaryOp::Signum() => {
+            if let Term::Num(n) = *t {
+                // Contrary to `f64::signum`, the sign of both `0.0` and `-0.0` is `0`
+                let result = if n > 0.0 {
+                    1.0
+                } else if n < 0.0 {
+                    -1.0
                 } else {
-                    Err(EvalError::TypeError(
-                        String::from("Num"),
-                        String::from("*, 2nd argument"),
-                        snd_pos,
-                        RichTerm {
-                            term: t2,
-                            pos: pos2,
-                        },
-                    ))
-                }
+                    0.0
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(result),
+                    pos_op_inh,
+                )))
             } else {
                 Err(EvalError::TypeError(
                     String::from("Num"),
-                    String::from("*, 1st argument"),
-                    fst_pos,
-                    RichTerm {
-                        term: t1,
-                        pos: pos1,
-                    },
+                    String::from("signum"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
                 ))
             }
         }
-        BinaryOp::Div() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
-                    if n2 == 0.0 {
-                        Err(EvalError::Other(String::from("division by zero"), pos_op))
-                    } else {
-                        Ok(Closure::atomic_closure(RichTerm::new(
-                            Term::Num(n1 / n2),
-                            pos_op_inh,
-                        )))
-                    }
+        UnaryOp::Sqrt() | UnaryOp::Exp() | UnaryOp::Ln() | UnaryOp::Log10() => {
+            let op_name = match u_op {
+                UnaryOp::Sqrt() => "sqrt",
+                UnaryOp::Exp() => "exp",
+                UnaryOp::Ln() => "ln",
+                _ => "log10",
+            };
+
+            if let Term::Num(n) = *t {
+                let result = match u_op {
+                    UnaryOp::Sqrt() if n < 0.0 => Err(format!(
+                        "expected the argument to be a positive number, got {}",
+                        n
+                    )),
+                    UnaryOp::Ln() | UnaryOp::Log10() if n <= 0.0 => Err(format!(
+                        "expected the argument to be a strictly positive number, got {}",
+                        n
+                    )),
+                    UnaryOp::Sqrt() => Ok(n.sqrt()),
+                    UnaryOp::Exp() => Ok(n.exp()),
+                    UnaryOp::Ln() => Ok(n.ln()),
+                    _ => Ok(n.log10()),
+                };
+
+                match result {
+                    Ok(result) => Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Num(result),
+                        pos_op_inh,
+                    ))),
+                    Err(msg) => Err(EvalError::Other(
+                        format!("{}: {}", op_name, msg),
+                        arg_pos,
+                        None,
+                    )),
+                }
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Num"),
+                    String::from(op_name),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
+        UnaryOp::Native(native) => {
+            if let Term::List(ts) = &*t {
+                // The arguments have already been forced, but they need to be substituted to be
+                // handed over as closed terms
+                let args: Vec<Term> = ts
+                    .iter()
+                    .map(|t| subst(t.clone(), &Environment::new(), &env).into())
+                    .collect();
+                let result = native.body()(&args)?;
+
+                Ok(Closure::atomic_closure(RichTerm::new(result, pos_op_inh)))
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("List"),
+                    String::from("native"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
+    }
+}
+
+/// Push the state of a list operation implemented by successive steps, such as `ListSort`, on the
+/// stack. The elements are expected to live in the environment `env`.
+fn push_list_state(stack: &mut Stack, ts: Vec<RichTerm>, env: &Environment, pos_op: TermPos) {
+    stack.push_arg(
+        Closure {
+            body: RichTerm::from(Term::List(ts)),
+            env: env.clone(),
+        },
+        pos_op,
+    );
+}
+
+/// Pop a list pushed by [`push_list_state`](fn.push_list_state.html) from the stack.
+fn pop_list_state(stack: &mut Stack) -> Vec<RichTerm> {
+    let (clos, ..) = stack
+        .pop_arg()
+        .expect("missing list operation state on the stack");
+
+    match clos.body.term.into_owned() {
+        Term::List(ts) => ts,
+        _ => panic!("expected the list operation state to be a list"),
+    }
+}
+
+/// Perform one step of `ListFilter`.
+///
+/// Push the state of the filter on the stack, together with a `ListFilterStep` continuation, and
+/// return the application of the predicate to the next element to test, which is the last one of
+/// `remaining`. All the terms are expected to live in the environment of the predicate `pred`.
+fn filter_step(
+    kept: Vec<RichTerm>,
+    remaining: Vec<RichTerm>,
+    pred: Closure,
+    stack: &mut Stack,
+    call_stack: &CallStack,
+    pos_op: TermPos,
+) -> Closure {
+    let Closure { body: pred, env } = pred;
+    let app =
+        mk_app!(pred.clone(), remaining.last().unwrap().clone()).with_pos(pos_op.into_inherited());
+
+    stack.push_arg(
+        Closure {
+            body: pred,
+            env: env.clone(),
+        },
+        pos_op,
+    );
+    push_list_state(stack, remaining, &env, pos_op);
+    push_list_state(stack, kept, &env, pos_op);
+    stack.push_op_cont(
+        OperationCont::Op1(UnaryOp::ListFilterStep(), app.pos),
+        call_stack.len(),
+        pos_op,
+    );
+
+    Closure { body: app, env }
+}
+
+/// Push the state of a record operation implemented by successive steps, such as `RecordFilter`,
+/// on the stack. The content of the fields is expected to live in the environment `env`.
+fn push_record_state(
+    stack: &mut Stack,
+    fields: IndexMap<Ident, RichTerm>,
+    attrs: RecordAttrs,
+    env: &Environment,
+    pos_op: TermPos,
+) {
+    stack.push_arg(
+        Closure {
+            body: RichTerm::from(Term::Record(fields, attrs)),
+            env: env.clone(),
+        },
+        pos_op,
+    );
+}
+
+/// Pop a record pushed by [`push_record_state`](fn.push_record_state.html) from the stack.
+fn pop_record_state(stack: &mut Stack) -> (IndexMap<Ident, RichTerm>, RecordAttrs) {
+    let (clos, ..) = stack
+        .pop_arg()
+        .expect("missing record operation state on the stack");
+
+    match clos.body.term.into_owned() {
+        Term::Record(fields, attrs) => (fields, attrs),
+        _ => panic!("expected the record operation state to be a record"),
+    }
+}
+
+/// Perform one step of `RecordFilter`.
+///
+/// Push the state of the filter on the stack, together with a `RecordFilterStep` continuation, and
+/// return the application of the predicate to the next field to test, whose name and content are
+/// the last ones of `names` and `remaining`. All the terms are expected to live in the environment
+/// of the predicate `pred`.
+fn record_filter_step(
+    kept: (IndexMap<Ident, RichTerm>, RecordAttrs),
+    names: Vec<RichTerm>,
+    remaining: Vec<RichTerm>,
+    pred: Closure,
+    stack: &mut Stack,
+    call_stack: &CallStack,
+    pos_op: TermPos,
+) -> Closure {
+    let Closure { body: pred, env } = pred;
+    let app = mk_app!(
+        pred.clone(),
+        names.last().unwrap().clone(),
+        remaining.last().unwrap().clone()
+    )
+    .with_pos(pos_op.into_inherited());
+
+    stack.push_arg(
+        Closure {
+            body: pred,
+            env: env.clone(),
+        },
+        pos_op,
+    );
+    push_list_state(stack, remaining, &env, pos_op);
+    push_list_state(stack, names, &env, pos_op);
+    let (fields, attrs) = kept;
+    push_record_state(stack, fields, attrs, &env, pos_op);
+    stack.push_op_cont(
+        OperationCont::Op1(UnaryOp::RecordFilterStep(), app.pos),
+        call_stack.len(),
+        pos_op,
+    );
+
+    Closure { body: app, env }
+}
+
+/// Perform one step of `ListAll` or `ListAny`.
+///
+/// Push the state of the operation on the stack, together with a `ListQuantifierStep`
+/// continuation, and return the application of the predicate to the next element to test, which is
+/// the last one of `remaining`. All the terms are expected to live in the environment of the
+/// predicate `pred`.
+fn quantifier_step(
+    remaining: Vec<RichTerm>,
+    pred: Closure,
+    stop_on: bool,
+    stack: &mut Stack,
+    call_stack: &CallStack,
+    pos_op: TermPos,
+) -> Closure {
+    let Closure { body: pred, env } = pred;
+    let app =
+        mk_app!(pred.clone(), remaining.last().unwrap().clone()).with_pos(pos_op.into_inherited());
+
+    stack.push_arg(
+        Closure {
+            body: pred,
+            env: env.clone(),
+        },
+        pos_op,
+    );
+    push_list_state(stack, remaining, &env, pos_op);
+    stack.push_op_cont(
+        OperationCont::Op1(UnaryOp::ListQuantifierStep(stop_on), app.pos),
+        call_stack.len(),
+        pos_op,
+    );
+
+    Closure { body: app, env }
+}
+
+/// Perform one step of `ListFoldl`.
+///
+/// Apply the folded function `f` to the accumulator `acc` and to the next element to fold, which
+/// is the last one of `remaining`. The arguments are pushed on the stack directly, such that the
+/// accumulator doesn't have to be bound in the environment of the elements. If there are elements
+/// left to fold, push them on the stack beforehand together with `f` and a `ListFoldlStep`
+/// continuation, such that the next accumulator is evaluated before proceeding. The elements are
+/// expected to live in the environment of `f`.
+fn foldl_step(
+    acc: Closure,
+    mut remaining: Vec<RichTerm>,
+    f: Closure,
+    stack: &mut Stack,
+    call_stack: &CallStack,
+    pos_op: TermPos,
+) -> Closure {
+    let Closure { body: f, env } = f;
+    let next = remaining.pop().unwrap();
+
+    if !remaining.is_empty() {
+        stack.push_arg(
+            Closure {
+                body: f.clone(),
+                env: env.clone(),
+            },
+            pos_op,
+        );
+        push_list_state(stack, remaining, &env, pos_op);
+        stack.push_op_cont(
+            OperationCont::Op1(UnaryOp::ListFoldlStep(), pos_op),
+            call_stack.len(),
+            pos_op,
+        );
+    }
+
+    stack.push_arg(
+        Closure {
+            body: next,
+            env: env.clone(),
+        },
+        pos_op,
+    );
+    stack.push_arg(acc, pos_op);
+
+    Closure { body: f, env }
+}
+
+/// Perform one step of the binary insertion done by `ListSort`.
+///
+/// Push the state of the sort on the stack, together with a `ListSortStep` continuation, and
+/// return the comparison of the next element to insert with the middle of the range `[lo, hi)` of
+/// `sorted`. The next element to insert is the last one of `remaining`. All the terms are expected
+/// to live in the environment of the comparison function `f`.
+fn sort_step(
+    sorted: Vec<RichTerm>,
+    remaining: Vec<RichTerm>,
+    (lo, hi): (usize, usize),
+    f: Closure,
+    stack: &mut Stack,
+    call_stack: &CallStack,
+    pos_op: TermPos,
+) -> Closure {
+    let Closure { body: f, env } = f;
+    let mid = (lo + hi) / 2;
+    let cmp = mk_app!(
+        f.clone(),
+        remaining.last().unwrap().clone(),
+        sorted[mid].clone()
+    )
+    .with_pos(pos_op.into_inherited());
+
+    stack.push_arg(
+        Closure {
+            body: f,
+            env: env.clone(),
+        },
+        pos_op,
+    );
+    push_list_state(stack, remaining, &env, pos_op);
+    push_list_state(stack, sorted, &env, pos_op);
+    stack.push_op_cont(
+        OperationCont::Op1(UnaryOp::ListSortStep(lo, hi), cmp.pos),
+        call_stack.len(),
+        pos_op,
+    );
+
+    Closure { body: cmp, env }
+}
+
+/// Return the byte offset of the start of the 0-based line `line` of `s`, or the length of `s` if it
+/// has fewer lines.
+fn line_offset(s: &str, line: usize) -> usize {
+    let offset: usize = s.split('\n').take(line).map(|l| l.len() + 1).sum();
+    offset.min(s.len())
+}
+
+/// Return the byte offset of the 0-based character column `col` of the 0-based line `line` of `s`.
+fn char_column_offset(s: &str, line: usize, col: usize) -> usize {
+    let line_start = line_offset(s, line);
+    let line_str = s[line_start..].split('\n').next().unwrap_or("");
+
+    line_start
+        + line_str
+            .char_indices()
+            .nth(col)
+            .map_or(line_str.len(), |(i, _)| i)
+}
+
+/// Compute the position of an error which occurred at the byte offset `offset` while deserializing
+/// the string `s`, whose position is `pos_str`. The offset is derived from the location reported
+/// by the deserializer.
+///
+/// If `s` comes from a string literal without escape sequences, the position can be mapped exactly
+/// inside the literal. Otherwise, the position of the whole string is returned, or the position
+/// `pos_op` of the call to deserialize if the string has no position either.
+fn deserialization_error_pos(
+    s: &str,
+    offset: Option<usize>,
+    pos_str: TermPos,
+    pos_op: TermPos,
+) -> TermPos {
+    match (offset, pos_str.into_opt()) {
+        // The span of a string literal includes the delimiting double quotes
+        (Some(offset), Some(span))
+            if span.end.to_usize() - span.start.to_usize() == s.len() + 2 =>
+        {
+            let mut offset = offset.min(s.len());
+            while !s.is_char_boundary(offset) {
+                offset -= 1;
+            }
+            let width = s[offset..].chars().next().map_or(0, char::len_utf8);
+            let start = span.start.to_usize() + 1 + offset;
+
+            TermPos::Original(RawSpan {
+                src_id: span.src_id,
+                start: ByteIndex::from(start as u32),
+                end: ByteIndex::from((start + width) as u32),
+            })
+        }
+        _ if pos_str.is_def() => pos_str,
+        _ => pos_op,
+    }
+}
+
+/// Evaluate a binary operation.
+///
+/// Both arguments are expected to be evaluated (in WHNF). `pos_op` corresponds to the whole
+/// operation position, that may be needed for error reporting.
+fn process_binary_operation(
+    b_op: BinaryOp,
+    fst_clos: Closure,
+    fst_pos: TermPos,
+    clos: Closure,
+    snd_pos: TermPos,
+    stack: &mut Stack,
+    call_stack: &mut CallStack,
+    pos_op: TermPos,
+) -> Result<Closure, EvalError> {
+    let Closure {
+        body: RichTerm {
+            term: t1,
+            pos: pos1,
+        },
+        env: env1,
+    } = fst_clos;
+    let Closure {
+        body: RichTerm {
+            term: t2,
+            pos: pos2,
+        },
+        env: mut env2,
+    } = clos;
+    let pos_op_inh = pos_op.into_inherited();
+
+    match b_op {
+        BinaryOp::Plus() => {
+            if let Term::Num(n1) = *t1 {
+                if let Term::Num(n2) = *t2 {
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Num(n1 + n2),
+                        pos_op_inh,
+                    )))
                 } else {
                     Err(EvalError::TypeError(
                         String::from("Num"),
-                        String::from("/, 2nd argument"),
+                        String::from("+, 2nd argument"),
                         snd_pos,
                         RichTerm {
                             term: t2,
@@ -1107,7 +2097,7 @@ fn process_binary_operation(
             } else {
                 Err(EvalError::TypeError(
                     String::from("Num"),
-                    String::from("/, 1st argument"),
+                    String::from("+, 1st argument"),
                     fst_pos,
                     RichTerm {
                         term: t1,
@@ -1116,17 +2106,17 @@ fn process_binary_operation(
                 ))
             }
         }
-        BinaryOp::Modulo() => {
+        BinaryOp::Sub() => {
             if let Term::Num(n1) = *t1 {
                 if let Term::Num(n2) = *t2 {
                     Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Num(n1 % n2),
+                        Term::Num(n1 - n2),
                         pos_op_inh,
                     )))
                 } else {
                     Err(EvalError::TypeError(
                         String::from("Num"),
-                        String::from("%, 2nd argument"),
+                        String::from("-, 2nd argument"),
                         snd_pos,
                         RichTerm {
                             term: t2,
@@ -1137,7 +2127,7 @@ fn process_binary_operation(
             } else {
                 Err(EvalError::TypeError(
                     String::from("Num"),
-                    String::from("%, 1st argument"),
+                    String::from("-, 1st argument"),
                     fst_pos,
                     RichTerm {
                         term: t1,
@@ -1146,17 +2136,17 @@ fn process_binary_operation(
                 ))
             }
         }
-        BinaryOp::Pow() => {
+        BinaryOp::Mult() => {
             if let Term::Num(n1) = *t1 {
                 if let Term::Num(n2) = *t2 {
                     Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Num(n1.powf(n2)),
+                        Term::Num(n1 * n2),
                         pos_op_inh,
                     )))
                 } else {
                     Err(EvalError::TypeError(
                         String::from("Num"),
-                        String::from("pow, 2nd argument"),
+                        String::from("*, 2nd argument"),
                         snd_pos,
                         RichTerm {
                             term: t2,
@@ -1167,7 +2157,7 @@ fn process_binary_operation(
             } else {
                 Err(EvalError::TypeError(
                     String::from("Num"),
-                    String::from("pow, 1st argument"),
+                    String::from("*, 1st argument"),
                     fst_pos,
                     RichTerm {
                         term: t1,
@@ -1176,18 +2166,21 @@ fn process_binary_operation(
                 ))
             }
         }
-        BinaryOp::StrConcat() => {
-            if let Term::Str(s1) = &*t1 {
-                if let Term::Str(s2) = &*t2 {
-                    let ss: [&str; 2] = [s1, s2];
-                    Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Str(ss.concat()),
-                        pos_op_inh,
-                    )))
+        BinaryOp::Div() => {
+            if let Term::Num(n1) = *t1 {
+                if let Term::Num(n2) = *t2 {
+                    if n2 == 0.0 {
+                        Err(EvalError::DivisionByZero(snd_pos))
+                    } else {
+                        Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::Num(n1 / n2),
+                            pos_op_inh,
+                        )))
+                    }
                 } else {
                     Err(EvalError::TypeError(
-                        String::from("Str"),
-                        String::from("++, 2nd argument"),
+                        String::from("Num"),
+                        String::from("/, 2nd argument"),
                         snd_pos,
                         RichTerm {
                             term: t2,
@@ -1197,8 +2190,8 @@ fn process_binary_operation(
                 }
             } else {
                 Err(EvalError::TypeError(
-                    String::from("Str"),
-                    String::from("++, 1st argument"),
+                    String::from("Num"),
+                    String::from("/, 1st argument"),
                     fst_pos,
                     RichTerm {
                         term: t1,
@@ -1207,32 +2200,216 @@ fn process_binary_operation(
                 ))
             }
         }
-        BinaryOp::Assume() => {
-            if let Term::Lbl(l) = &*t2 {
-                // Track the contract argument for better error reporting, and push back the label
-                // on the stack, so that it becomes the first argument of the contract.
-                let thunk = stack
-                    .track_arg()
-                    .ok_or_else(|| EvalError::NotEnoughArgs(3, String::from("assume"), pos_op))?;
-                let mut l = l.clone();
-                l.arg_pos = thunk.borrow().body.pos;
-                l.arg_thunk = Some(thunk);
+        BinaryOp::Modulo() => {
+            if let Term::Num(n1) = *t1 {
+                if let Term::Num(n2) = *t2 {
+                    if n2 == 0.0 {
+                        Err(EvalError::DivisionByZero(snd_pos))
+                    } else {
+                        Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::Num(n1 % n2),
+                            pos_op_inh,
+                        )))
+                    }
+                } else {
+                    Err(EvalError::TypeError(
+                        String::from("Num"),
+                        String::from("%, 2nd argument"),
+                        snd_pos,
+                        RichTerm {
+                            term: t2,
+                            pos: pos2,
+                        },
+                    ))
+                }
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Num"),
+                    String::from("%, 1st argument"),
+                    fst_pos,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                ))
+            }
+        }
+        BinaryOp::IntDiv() | BinaryOp::DivMod() => {
+            let op_name = if let BinaryOp::IntDiv() = b_op {
+                "intDiv"
+            } else {
+                "divMod"
+            };
 
-                stack.push_arg(
-                    Closure::atomic_closure(RichTerm::new(Term::Lbl(l), pos2.into_inherited())),
-                    pos2.into_inherited(),
-                );
+            match (&*t1, &*t2) {
+                (Term::Num(_), Term::Num(n2)) if *n2 == 0.0 => {
+                    Err(EvalError::DivisionByZero(snd_pos))
+                }
+                (Term::Num(n1), Term::Num(n2)) => {
+                    let (quot, rem) = floor_div_mod(*n1, *n2);
 
-                match *t1 {
-                    Term::Fun(..) => Ok(Closure {
-                        body: RichTerm {
-                            term: t1,
-                            pos: pos1,
-                        },
-                        env: env1,
-                    }),
-                    Term::Record(..) => {
-                        let mut new_env = Environment::new();
+                    let result = if let BinaryOp::IntDiv() = b_op {
+                        RichTerm::new(Term::Num(quot), pos_op_inh)
+                    } else {
+                        mk_record!(("quot", Term::Num(quot)), ("rem", Term::Num(rem)))
+                            .with_pos(pos_op_inh)
+                    };
+
+                    Ok(Closure::atomic_closure(result))
+                }
+                (Term::Num(_), _) => Err(EvalError::TypeError(
+                    String::from("Num"),
+                    format!("{}, 2nd argument", op_name),
+                    snd_pos,
+                    RichTerm {
+                        term: t2,
+                        pos: pos2,
+                    },
+                )),
+                (_, _) => Err(EvalError::TypeError(
+                    String::from("Num"),
+                    format!("{}, 1st argument", op_name),
+                    fst_pos,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                )),
+            }
+        }
+        BinaryOp::Pow() => {
+            if let Term::Num(n1) = *t1 {
+                if let Term::Num(n2) = *t2 {
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Num(n1.powf(n2)),
+                        pos_op_inh,
+                    )))
+                } else {
+                    Err(EvalError::TypeError(
+                        String::from("Num"),
+                        String::from("pow, 2nd argument"),
+                        snd_pos,
+                        RichTerm {
+                            term: t2,
+                            pos: pos2,
+                        },
+                    ))
+                }
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Num"),
+                    String::from("pow, 1st argument"),
+                    fst_pos,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                ))
+            }
+        }
+        BinaryOp::Min() | BinaryOp::Max() => {
+            let op_name = if let BinaryOp::Min() = b_op {
+                "min"
+            } else {
+                "max"
+            };
+
+            if let Term::Num(n1) = *t1 {
+                if let Term::Num(n2) = *t2 {
+                    // `f64::min` and `f64::max` ignore `NaN` operands. We rather propagate it, as
+                    // other arithmetic operations do.
+                    let result = if n1.is_nan() || n2.is_nan() {
+                        f64::NAN
+                    } else if let BinaryOp::Min() = b_op {
+                        n1.min(n2)
+                    } else {
+                        n1.max(n2)
+                    };
+
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Num(result),
+                        pos_op_inh,
+                    )))
+                } else {
+                    Err(EvalError::TypeError(
+                        String::from("Num"),
+                        format!("{}, 2nd argument", op_name),
+                        snd_pos,
+                        RichTerm {
+                            term: t2,
+                            pos: pos2,
+                        },
+                    ))
+                }
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Num"),
+                    format!("{}, 1st argument", op_name),
+                    fst_pos,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                ))
+            }
+        }
+        BinaryOp::StrConcat() => {
+            if let Term::Str(s1) = &*t1 {
+                if let Term::Str(s2) = &*t2 {
+                    let ss: [&str; 2] = [s1, s2];
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Str(ss.concat()),
+                        pos_op_inh,
+                    )))
+                } else {
+                    Err(EvalError::TypeError(
+                        String::from("Str"),
+                        String::from("++, 2nd argument"),
+                        snd_pos,
+                        RichTerm {
+                            term: t2,
+                            pos: pos2,
+                        },
+                    ))
+                }
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Str"),
+                    String::from("++, 1st argument"),
+                    fst_pos,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                ))
+            }
+        }
+        BinaryOp::Assume() => {
+            if let Term::Lbl(l) = &*t2 {
+                // Track the contract argument for better error reporting, and push back the label
+                // on the stack, so that it becomes the first argument of the contract.
+                let thunk = stack
+                    .track_arg()
+                    .ok_or_else(|| EvalError::NotEnoughArgs(3, String::from("assume"), pos_op))?;
+                let mut l = l.clone();
+                l.arg_pos = thunk.borrow().body.pos;
+                l.arg_thunk = Some(thunk);
+
+                stack.push_arg(
+                    Closure::atomic_closure(RichTerm::new(Term::Lbl(l), pos2.into_inherited())),
+                    pos2.into_inherited(),
+                );
+
+                match *t1 {
+                    Term::Fun(..) => Ok(Closure {
+                        body: RichTerm {
+                            term: t1,
+                            pos: pos1,
+                        },
+                        env: env1,
+                    }),
+                    Term::Record(..) => {
+                        let mut new_env = Environment::new();
                         let closurized = RichTerm {
                             term: t1,
                             pos: pos1,
@@ -1601,7 +2778,7 @@ fn process_binary_operation(
                             let mut static_map = static_map;
                             let as_var = clos.body.closurize(&mut env2, clos.env);
                             match static_map.insert(Ident::from(id), as_var) {
-                                Some(_) => Err(EvalError::Other(format!("$[ .. ]: tried to extend record with the field {}, but it already exists", id), pos_op)),
+                                Some(_) => Err(EvalError::FieldAlreadyDefined(id.to_string(), String::from("$[ .. ]"), pos_op)),
                                 None => Ok(Closure {
                                     body: Term::Record(static_map, attrs).into(),
                                     env: env2,
@@ -1636,7 +2813,7 @@ fn process_binary_operation(
                 Term::Str(id) => match_sharedterm! {t2, with {
                         Term::Record(static_map, attrs) => {
                             let mut static_map = static_map;
-                            match static_map.remove(&Ident::from(&id)) {
+                            match static_map.shift_remove(&Ident::from(&id)) {
                                 None => Err(EvalError::FieldMissing(
                                     id,
                                     String::from("(-$)"),
@@ -1707,42 +2884,232 @@ fn process_binary_operation(
                 ))
             }
         },
-        BinaryOp::ListConcat() => match_sharedterm! {t1, with {
-                Term::List(ts1) => match_sharedterm! {t2, with {
-                        Term::List(ts2) => {
-                            let mut env = Environment::new();
-                            let mut ts: Vec<RichTerm> = Vec::with_capacity(ts1.len() + ts2.len());
-                            ts.extend(
-                                ts1.into_iter()
-                                    .map(|t| t.closurize(&mut env, env1.clone())),
-                            );
-                            ts.extend(
-                                ts2.into_iter()
-                                    .map(|t| t.closurize(&mut env, env2.clone())),
-                            );
+        BinaryOp::MatchField() => match (&*t1, &*t2) {
+            (Term::Record(fields, _), Term::Record(handlers, _)) => {
+                let (id, value) = fields.iter().next().ok_or_else(|| {
+                    EvalError::Other(
+                        String::from("matchField: cannot dispatch on an empty record"),
+                        fst_pos,
+                        None,
+                    )
+                })?;
+                let handler = handlers.get(id).ok_or_else(|| {
+                    EvalError::Other(
+                        format!("matchField: no handler for the field `{}`", id),
+                        pos_op,
+                        None,
+                    )
+                })?;
+
+                let mut shared_env = Environment::new();
+                let value = value.clone().closurize(&mut shared_env, env1);
+                let handler = handler.clone().closurize(&mut shared_env, env2);
+
+                Ok(Closure {
+                    body: mk_app!(handler, value).with_pos(pos_op_inh),
+                    env: shared_env,
+                })
+            }
+            (Term::Record(..), _) => Err(EvalError::TypeError(
+                String::from("Record"),
+                String::from("matchField, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Record"),
+                String::from("matchField, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::RecordFieldsDiff() => match (&*t1, &*t2) {
+            (Term::Record(fields1, _), Term::Record(fields2, _)) => {
+                let added = fields2
+                    .keys()
+                    .filter(|id| !fields1.contains_key(*id))
+                    .map(|id| id.label.as_str())
+                    .collect();
+                let mut removed = Vec::new();
+                let mut common = Vec::new();
+
+                for id in fields1.keys() {
+                    if fields2.contains_key(id) {
+                        common.push(id.label.as_str());
+                    } else {
+                        removed.push(id.label.as_str());
+                    }
+                }
+
+                let to_list = |mut names: Vec<&str>| {
+                    names.sort_unstable();
+                    Term::List(names.into_iter().map(mk_term::string).collect())
+                };
+
+                Ok(Closure::atomic_closure(
+                    mk_record!(
+                        ("added", to_list(added)),
+                        ("removed", to_list(removed)),
+                        ("common", to_list(common))
+                    )
+                    .with_pos(pos_op_inh),
+                ))
+            }
+            (Term::Record(..), _) => Err(EvalError::TypeError(
+                String::from("Record"),
+                String::from("recordFieldsDiff, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Record"),
+                String::from("recordFieldsDiff, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::RecordFilter() => match_sharedterm! {t1, with {
+                Term::Record(fields, attrs) if fields.is_empty() => Ok(Closure::atomic_closure(
+                    RichTerm::new(Term::Record(fields, attrs), pos_op_inh),
+                )),
+                Term::Record(fields, attrs) => {
+                    let mut shared_env = Environment::new();
+                    let pred_as_var = RichTerm {
+                        term: t2,
+                        pos: pos2,
+                    }
+                    .closurize(&mut shared_env, env2);
+
+                    // The fields are tested in their definition order. As for `ListFilter`, the
+                    // remaining ones are stored in reverse order, such that the next one to test
+                    // is at the end.
+                    let (names, remaining): (Vec<RichTerm>, Vec<RichTerm>) = fields
+                        .into_iter()
+                        .rev()
+                        .map(|(id, t)| {
+                            (
+                                mk_term::string(id.label),
+                                t.closurize(&mut shared_env, env1.clone()),
+                            )
+                        })
+                        .unzip();
+                    let pred = Closure {
+                        body: pred_as_var,
+                        env: shared_env,
+                    };
+
+                    Ok(record_filter_step(
+                        (IndexMap::new(), attrs),
+                        names,
+                        remaining,
+                        pred,
+                        stack,
+                        call_stack,
+                        pos_op,
+                    ))
+                }
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Record"),
+                    String::from("recordFilter, 1st argument"),
+                    fst_pos,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                ))
+            }
+        },
+        BinaryOp::RecordProject() => match_sharedterm! {t1, with {
+                Term::Record(static_map, attrs) => match_sharedterm! {t2, with {
+                        Term::List(ts) => {
+                            let ids = match str_list(&ts, &env2) {
+                                Ok(Some(ids)) => ids,
+                                Ok(None) => {
+                                    let mut env = Environment::new();
+                                    let record = RichTerm::new(Term::Record(static_map, attrs), pos1)
+                                        .closurize(&mut env, env1);
+
+                                    return Ok(seq_list_elements(
+                                        &ts,
+                                        &env2,
+                                        pos2,
+                                        env,
+                                        |list| mk_term::op2(BinaryOp::RecordProject(), record, list),
+                                        pos_op_inh,
+                                    ));
+                                }
+                                Err(t) => {
+                                    return Err(EvalError::TypeError(
+                                        String::from("Str"),
+                                        String::from("recordProject, element of the 2nd argument"),
+                                        t.pos,
+                                        t.clone(),
+                                    ))
+                                }
+                            };
+
+                            let mut static_map = static_map;
+                            let mut projected = IndexMap::with_capacity(ids.len());
+
+                            for id in ids {
+                                let id = Ident::from(id);
+
+                                // A field may be listed several times, in which case it has
+                                // already been moved to the projection.
+                                if projected.contains_key(&id) {
+                                    continue;
+                                }
+
+                                match static_map.shift_remove(&id) {
+                                    Some(field) => {
+                                        projected.insert(id, field);
+                                    }
+                                    None => {
+                                        static_map.extend(projected);
+                                        return Err(EvalError::FieldMissing(
+                                            id.label,
+                                            String::from("recordProject"),
+                                            RichTerm::new(Term::Record(static_map, attrs), pos1),
+                                            pos_op,
+                                        ))
+                                    }
+                                }
+                            }
 
                             Ok(Closure {
-                                body: RichTerm::new(Term::List(ts), pos_op_inh),
-                                env,
+                                body: RichTerm::new(Term::Record(projected, attrs), pos_op_inh),
+                                env: env1,
                             })
                         }
                     } else {
                         Err(EvalError::TypeError(
                             String::from("List"),
-                            String::from("@, 2nd operand"),
+                            String::from("recordProject, 2nd argument"),
                             snd_pos,
                             RichTerm {
                                 term: t2,
                                 pos: pos2,
                             },
                         ))
-
                     }
                 },
             } else {
                 Err(EvalError::TypeError(
-                    String::from("List"),
-                    String::from("@, 1st operand"),
+                    String::from("Record"),
+                    String::from("recordProject, 1st argument"),
                     fst_pos,
                     RichTerm {
                         term: t1,
@@ -1751,209 +3118,681 @@ fn process_binary_operation(
                 ))
             }
         },
-        BinaryOp::ListElemAt() => match (&*t1, &*t2) {
-            (Term::List(ts), Term::Num(n)) => {
-                let n_int = *n as usize;
-                if n.fract() != 0.0 {
-                    Err(EvalError::Other(format!("elemAt: expected the 2nd agument to be an integer, got the floating-point value {}", n), pos_op))
-                } else if *n < 0.0 || n_int >= ts.len() {
-                    Err(EvalError::Other(format!("elemAt: index out of bounds. Expected a value between 0 and {}, got {}", ts.len(), n), pos_op))
-                } else {
-                    Ok(Closure {
-                        body: ts[n_int].clone(),
-                        env: env1,
-                    })
-                }
-            }
-            (Term::List(_), _) => Err(EvalError::TypeError(
-                String::from("Num"),
-                String::from("elemAt, 2nd argument"),
-                snd_pos,
-                RichTerm {
-                    term: t2,
-                    pos: pos2,
-                },
-            )),
-            (_, _) => Err(EvalError::TypeError(
-                String::from("List"),
-                String::from("elemAt, 1st argument"),
-                fst_pos,
-                RichTerm {
-                    term: t1,
-                    pos: pos1,
-                },
-            )),
-        },
-        BinaryOp::Merge() => merge(
-            RichTerm {
-                term: t1,
-                pos: pos1,
-            },
-            env1,
-            RichTerm {
-                term: t2,
-                pos: pos2,
-            },
-            env2,
-            pos_op,
-            MergeMode::Standard,
-        ),
+        BinaryOp::RecordMergeDeep() => match_sharedterm! {t1, with {
+                Term::Record(m1, attrs1) => match_sharedterm! {t2, with {
+                        Term::Record(m2, attrs2) => {
+                            let mut env = Environment::new();
+                            let mut m: IndexMap<Ident, RichTerm> = m1
+                                .into_iter()
+                                .map(|(id, t)| (id, t.closurize(&mut env, env1.clone())))
+                                .collect();
 
-        BinaryOp::Hash() => {
-            let mk_err_fst = |t1| {
+                            let mut common = Vec::new();
+                            for (id, t2) in m2.into_iter() {
+                                let t2 = t2.closurize(&mut env, env2.clone());
+                                match m.get(&id) {
+                                    Some(t1) => common.push((id, t1.clone(), t2)),
+                                    None => {
+                                        m.insert(id, t2);
+                                    }
+                                }
+                            }
+
+                            // The first value is only needed if the second one is a record, hence
+                            // the conditional rather than a direct recursive call, which would
+                            // force both values.
+                            let fields_env = env.clone();
+                            for (id, t1, t2) in common.into_iter() {
+                                let field = mk_term::if_then_else(
+                                    mk_term::op1(UnaryOp::IsRecord(), t2.clone()),
+                                    mk_term::if_then_else(
+                                        mk_term::op1(UnaryOp::IsRecord(), t1.clone()),
+                                        mk_term::op2(BinaryOp::RecordMergeDeep(), t1, t2.clone()),
+                                        t2.clone(),
+                                    ),
+                                    t2,
+                                )
+                                .closurize(&mut env, fields_env.clone());
+                                m.insert(id, field);
+                            }
+
+                            Ok(Closure {
+                                body: RichTerm::new(
+                                    Term::Record(m, RecordAttrs::merge(attrs1, attrs2)),
+                                    pos_op_inh,
+                                ),
+                                env,
+                            })
+                        }
+                    } else {
+                        Err(EvalError::TypeError(
+                            String::from("Record"),
+                            String::from("recordMergeDeep, 2nd argument"),
+                            snd_pos,
+                            RichTerm {
+                                term: t2,
+                                pos: pos2,
+                            },
+                        ))
+                    }
+                },
+            } else {
                 Err(EvalError::TypeError(
-                    String::from("Enum <Md5, Sha1, Sha256, Sha512>"),
-                    String::from("hash, 1st argument"),
+                    String::from("Record"),
+                    String::from("recordMergeDeep, 1st argument"),
                     fst_pos,
                     RichTerm {
                         term: t1,
                         pos: pos1,
                     },
                 ))
+            }
+        },
+        BinaryOp::RecordMergePreferLeft() | BinaryOp::RecordMergePreferRight() => {
+            let (op_name, prefer_left) = if let BinaryOp::RecordMergePreferLeft() = b_op {
+                ("recordMergePreferLeft", true)
+            } else {
+                ("recordMergePreferRight", false)
             };
 
-            if let Term::Enum(id) = &*t1 {
-                if let Term::Str(s) = &*t2 {
-                    let result = match id.as_ref() {
-                        "Md5" => {
-                            let mut hasher = md5::Md5::new();
-                            hasher.update(s);
-                            format!("{:x}", hasher.finalize())
-                        }
-                        "Sha1" => {
-                            let mut hasher = sha1::Sha1::new();
-                            hasher.update(s);
-                            format!("{:x}", hasher.finalize())
-                        }
-                        "Sha256" => {
-                            let mut hasher = sha2::Sha256::new();
-                            hasher.update(s);
-                            format!("{:x}", hasher.finalize())
-                        }
-                        "Sha512" => {
-                            let mut hasher = sha2::Sha512::new();
-                            hasher.update(s);
-                            format!("{:x}", hasher.finalize())
-                        }
-                        _ => return mk_err_fst(t1),
-                    };
+            match_sharedterm! {t1, with {
+                    Term::Record(m1, attrs1) => match_sharedterm! {t2, with {
+                            Term::Record(m2, attrs2) => {
+                                let mut env = Environment::new();
+                                let mut m: IndexMap<Ident, RichTerm> = m1
+                                    .into_iter()
+                                    .map(|(id, t)| (id, t.closurize(&mut env, env1.clone())))
+                                    .collect();
 
-                    Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Str(result),
-                        pos_op_inh,
-                    )))
+                                for (id, t2) in m2.into_iter() {
+                                    if !(prefer_left && m.contains_key(&id)) {
+                                        m.insert(id, t2.closurize(&mut env, env2.clone()));
+                                    }
+                                }
+
+                                Ok(Closure {
+                                    body: RichTerm::new(
+                                        Term::Record(m, RecordAttrs::merge(attrs1, attrs2)),
+                                        pos_op_inh,
+                                    ),
+                                    env,
+                                })
+                            }
+                        } else {
+                            Err(EvalError::TypeError(
+                                String::from("Record"),
+                                format!("{}, 2nd argument", op_name),
+                                snd_pos,
+                                RichTerm {
+                                    term: t2,
+                                    pos: pos2,
+                                },
+                            ))
+                        }
+                    },
                 } else {
                     Err(EvalError::TypeError(
-                        String::from("Str"),
-                        String::from("hash, 2nd argument"),
-                        snd_pos,
+                        String::from("Record"),
+                        format!("{}, 1st argument", op_name),
+                        fst_pos,
                         RichTerm {
-                            term: t2,
-                            pos: pos2,
+                            term: t1,
+                            pos: pos1,
                         },
                     ))
                 }
-            } else {
-                mk_err_fst(t1)
             }
         }
-        BinaryOp::Serialize() => {
-            let mk_err_fst = |t1| {
+        BinaryOp::ListConcat() => match_sharedterm! {t1, with {
+                Term::List(ts1) => match_sharedterm! {t2, with {
+                        Term::List(ts2) => {
+                            let mut env = Environment::new();
+                            let mut ts: Vec<RichTerm> = Vec::with_capacity(ts1.len() + ts2.len());
+                            ts.extend(
+                                ts1.into_iter()
+                                    .map(|t| t.closurize(&mut env, env1.clone())),
+                            );
+                            ts.extend(
+                                ts2.into_iter()
+                                    .map(|t| t.closurize(&mut env, env2.clone())),
+                            );
+
+                            Ok(Closure {
+                                body: RichTerm::new(Term::List(ts), pos_op_inh),
+                                env,
+                            })
+                        }
+                    } else {
+                        Err(EvalError::TypeError(
+                            String::from("List"),
+                            String::from("@, 2nd operand"),
+                            snd_pos,
+                            RichTerm {
+                                term: t2,
+                                pos: pos2,
+                            },
+                        ))
+
+                    }
+                },
+            } else {
                 Err(EvalError::TypeError(
-                    String::from("Enum <Json, Yaml, Toml>"),
-                    String::from("serialize, 1st argument"),
+                    String::from("List"),
+                    String::from("@, 1st operand"),
                     fst_pos,
                     RichTerm {
                         term: t1,
                         pos: pos1,
                     },
                 ))
-            };
-
-            if let Term::Enum(ref id) = t1.as_ref() {
-                // Serialization needs all variables term to be fully substituted
-                let global_env = Environment::new();
-                let rt2 = subst(
-                    RichTerm {
+            }
+        },
+        BinaryOp::ListFilter() => match_sharedterm! {t1, with {
+                Term::List(ts) if ts.is_empty() => Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::List(ts),
+                    pos_op_inh,
+                ))),
+                Term::List(ts) => {
+                    let mut shared_env = Environment::new();
+                    let pred_as_var = RichTerm {
                         term: t2,
                         pos: pos2,
-                    },
-                    &global_env,
-                    &env2,
-                );
+                    }
+                    .closurize(&mut shared_env, env2);
 
-                let format = match id.to_string().as_str() {
-                    "Json" => ExportFormat::Json,
-                    "Yaml" => ExportFormat::Yaml,
-                    "Toml" => ExportFormat::Toml,
-                    _ => return mk_err_fst(t1),
-                };
+                    // The remaining elements are stored in reverse order, such that the next one
+                    // to test is at the end.
+                    let remaining: Vec<RichTerm> = ts
+                        .into_iter()
+                        .rev()
+                        .map(|t| t.closurize(&mut shared_env, env1.clone()))
+                        .collect();
+                    let pred = Closure {
+                        body: pred_as_var,
+                        env: shared_env,
+                    };
 
-                serialize::validate(format, &rt2)?;
-                Ok(Closure::atomic_closure(RichTerm::new(
-                    Term::Str(serialize::to_string(format, &rt2)?),
-                    pos_op_inh,
-                )))
+                    Ok(filter_step(
+                        Vec::new(),
+                        remaining,
+                        pred,
+                        stack,
+                        call_stack,
+                        pos_op,
+                    ))
+                }
             } else {
-                mk_err_fst(t1)
-            }
-        }
-        BinaryOp::Deserialize() => {
-            let mk_err_fst = |t1| {
                 Err(EvalError::TypeError(
-                    String::from("Enum <Json, Yaml, Toml>"),
-                    String::from("deserialize, 1st argument"),
+                    String::from("List"),
+                    String::from("filter, 1st argument"),
                     fst_pos,
                     RichTerm {
                         term: t1,
                         pos: pos1,
                     },
                 ))
-            };
-
-            if let Term::Enum(id) = &*t1 {
-                if let Term::Str(s) = &*t2 {
-                    let rt: RichTerm = match id.as_ref() {
-                        "Json" => serde_json::from_str(s).map_err(|err| {
-                            EvalError::DeserializationError(
-                                String::from("json"),
-                                format!("{}", err),
-                                pos_op,
-                            )
-                        })?,
-                        "Yaml" => serde_yaml::from_str(s).map_err(|err| {
-                            EvalError::DeserializationError(
-                                String::from("yaml"),
-                                format!("{}", err),
-                                pos_op,
-                            )
-                        })?,
-                        "Toml" => toml::from_str(s).map_err(|err| {
-                            EvalError::DeserializationError(
-                                String::from("toml"),
-                                format!("{}", err),
-                                pos_op,
-                            )
-                        })?,
-                        _ => return mk_err_fst(t1),
-                    };
-
-                    Ok(Closure::atomic_closure(rt.with_pos(pos_op_inh)))
-                } else {
-                    Err(EvalError::TypeError(
-                        String::from("Str"),
-                        String::from("deserialize, 2nd argument"),
-                        snd_pos,
-                        RichTerm {
-                            term: t2,
-                            pos: pos2,
-                        },
+            }
+        },
+        BinaryOp::ListElemAt() => match (&*t1, &*t2) {
+            (Term::List(ts), Term::Num(n)) => {
+                let n_int = *n as usize;
+                if n.fract() != 0.0 {
+                    Err(EvalError::NotAnInteger(
+                        String::from("elemAt, 2nd argument"),
+                        *n,
+                        pos_op,
+                    ))
+                } else if *n < 0.0 || n_int >= ts.len() {
+                    Err(EvalError::IndexOutOfBounds(
+                        String::from("elemAt, 2nd argument"),
+                        *n,
+                        0..ts.len(),
+                        pos_op,
                     ))
+                } else {
+                    Ok(Closure {
+                        body: ts[n_int].clone(),
+                        env: env1,
+                    })
                 }
-            } else {
-                mk_err_fst(t1)
             }
-        }
+            (Term::List(_), _) => Err(EvalError::TypeError(
+                String::from("Num"),
+                String::from("elemAt, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("List"),
+                String::from("elemAt, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::ListAll() | BinaryOp::ListAny() => {
+            let (op_name, stop_on) = if let BinaryOp::ListAll() = b_op {
+                ("all", false)
+            } else {
+                ("any", true)
+            };
+
+            match_sharedterm! {t1, with {
+                    Term::List(ts) if ts.is_empty() => Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Bool(!stop_on),
+                        pos_op_inh,
+                    ))),
+                    Term::List(ts) => {
+                        let mut shared_env = Environment::new();
+                        let pred_as_var = RichTerm {
+                            term: t2,
+                            pos: pos2,
+                        }
+                        .closurize(&mut shared_env, env2);
+
+                        // The remaining elements are stored in reverse order, such that the next
+                        // one to test is at the end.
+                        let remaining: Vec<RichTerm> = ts
+                            .into_iter()
+                            .rev()
+                            .map(|t| t.closurize(&mut shared_env, env1.clone()))
+                            .collect();
+                        let pred = Closure {
+                            body: pred_as_var,
+                            env: shared_env,
+                        };
+
+                        Ok(quantifier_step(
+                            remaining,
+                            pred,
+                            stop_on,
+                            stack,
+                            call_stack,
+                            pos_op,
+                        ))
+                    }
+                } else {
+                    Err(EvalError::TypeError(
+                        String::from("List"),
+                        format!("{}, 1st argument", op_name),
+                        fst_pos,
+                        RichTerm {
+                            term: t1,
+                            pos: pos1,
+                        },
+                    ))
+                }
+            }
+        }
+        BinaryOp::ListContains() => match_sharedterm! {t2, with {
+                Term::List(ts) => {
+                    // `%contains% x [e1, ..., en]` is rewritten to `x == e1 || (... || x == en)`,
+                    // such that elements are compared exactly as by `==`, and only until a match
+                    // is found.
+                    let mut shared_env = Environment::new();
+                    let elt = RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    }
+                    .closurize(&mut shared_env, env1);
+
+                    let body = ts.into_iter().rev().fold(None, |acc, t| {
+                        let t = t.closurize(&mut shared_env, env2.clone());
+                        let eq = mk_term::op2(BinaryOp::Eq(), elt.clone(), t);
+
+                        Some(match acc {
+                            None => eq,
+                            Some(acc) => mk_app!(mk_term::op1(UnaryOp::BoolOr(), eq), acc),
+                        })
+                    });
+
+                    Ok(Closure {
+                        body: body
+                            .unwrap_or_else(|| Term::Bool(false).into())
+                            .with_pos(pos_op_inh),
+                        env: shared_env,
+                    })
+                }
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("List"),
+                    String::from("contains, 2nd argument"),
+                    snd_pos,
+                    RichTerm {
+                        term: t2,
+                        pos: pos2,
+                    },
+                ))
+            }
+        },
+        BinaryOp::ListTake() | BinaryOp::ListDrop() => {
+            let op_name = if let BinaryOp::ListTake() = b_op {
+                "take"
+            } else {
+                "drop"
+            };
+
+            match (&*t1, &*t2) {
+                (Term::List(ts), Term::Num(n)) => {
+                    if *n < 0.0 || n.fract() != 0.0 {
+                        Err(EvalError::Other(
+                            format!(
+                                "{}: expected the 2nd argument to be a non-negative integer, got {}",
+                                op_name, n
+                            ),
+                            pos_op, None,
+                        ))
+                    } else {
+                        let n = (*n as usize).min(ts.len());
+                        let ts = if let BinaryOp::ListTake() = b_op {
+                            ts[..n].to_vec()
+                        } else {
+                            ts[n..].to_vec()
+                        };
+
+                        Ok(Closure {
+                            body: RichTerm::new(Term::List(ts), pos_op_inh),
+                            env: env1,
+                        })
+                    }
+                }
+                (Term::List(_), _) => Err(EvalError::TypeError(
+                    String::from("Num"),
+                    format!("{}, 2nd argument", op_name),
+                    snd_pos,
+                    RichTerm {
+                        term: t2,
+                        pos: pos2,
+                    },
+                )),
+                (_, _) => Err(EvalError::TypeError(
+                    String::from("List"),
+                    format!("{}, 1st argument", op_name),
+                    fst_pos,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                )),
+            }
+        }
+        BinaryOp::ListRange() => match (&*t1, &*t2) {
+            (Term::Num(start), Term::Num(end)) => {
+                if start.fract() != 0.0 {
+                    Err(EvalError::NotAnInteger(
+                        String::from("range, 1st argument (start)"),
+                        *start,
+                        pos_op,
+                    ))
+                } else if end.fract() != 0.0 {
+                    Err(EvalError::NotAnInteger(
+                        String::from("range, 2nd argument (end)"),
+                        *end,
+                        pos_op,
+                    ))
+                } else if let Some(ts) = range(*start, *end, 1.0) {
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::List(ts),
+                        pos_op_inh,
+                    )))
+                } else {
+                    Err(EvalError::Other(
+                        format!(
+                            "range: the range from {} to {} has more than {} elements",
+                            start, end, MAX_RANGE_LEN
+                        ),
+                        pos_op,
+                        None,
+                    ))
+                }
+            }
+            (Term::Num(_), _) => Err(EvalError::TypeError(
+                String::from("Num"),
+                String::from("range, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Num"),
+                String::from("range, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::Merge(path) => merge(
+            RichTerm {
+                term: t1,
+                pos: pos1,
+            },
+            env1,
+            RichTerm {
+                term: t2,
+                pos: pos2,
+            },
+            env2,
+            pos_op,
+            MergeMode::Standard,
+            path,
+        ),
+
+        BinaryOp::Hash() => {
+            let mk_err_fst = |t1| {
+                Err(EvalError::TypeError(
+                    String::from("Enum <Md5, Sha1, Sha224, Sha256, Sha384, Sha512>"),
+                    String::from("hash, 1st argument"),
+                    fst_pos,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                ))
+            };
+
+            if let Term::Enum(id) = &*t1 {
+                if let Term::Str(s) = &*t2 {
+                    let result = match id.as_ref() {
+                        "Md5" => {
+                            let mut hasher = md5::Md5::new();
+                            hasher.update(s);
+                            format!("{:x}", hasher.finalize())
+                        }
+                        "Sha1" => {
+                            let mut hasher = sha1::Sha1::new();
+                            hasher.update(s);
+                            format!("{:x}", hasher.finalize())
+                        }
+                        "Sha224" => {
+                            let mut hasher = sha2::Sha224::new();
+                            hasher.update(s);
+                            format!("{:x}", hasher.finalize())
+                        }
+                        "Sha256" => {
+                            let mut hasher = sha2::Sha256::new();
+                            hasher.update(s);
+                            format!("{:x}", hasher.finalize())
+                        }
+                        "Sha384" => {
+                            let mut hasher = sha2::Sha384::new();
+                            hasher.update(s);
+                            format!("{:x}", hasher.finalize())
+                        }
+                        "Sha512" => {
+                            let mut hasher = sha2::Sha512::new();
+                            hasher.update(s);
+                            format!("{:x}", hasher.finalize())
+                        }
+                        _ => return mk_err_fst(t1),
+                    };
+
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Str(result),
+                        pos_op_inh,
+                    )))
+                } else {
+                    Err(EvalError::TypeError(
+                        String::from("Str"),
+                        String::from("hash, 2nd argument"),
+                        snd_pos,
+                        RichTerm {
+                            term: t2,
+                            pos: pos2,
+                        },
+                    ))
+                }
+            } else {
+                mk_err_fst(t1)
+            }
+        }
+        BinaryOp::Serialize() => {
+            let mk_err_fst = |t1| {
+                Err(EvalError::TypeError(
+                    String::from("Enum <Json, Yaml, Toml, Xml, Ini>"),
+                    String::from("serialize, 1st argument"),
+                    fst_pos,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                ))
+            };
+
+            if let Term::Enum(ref id) = t1.as_ref() {
+                // Serialization needs all variables term to be fully substituted
+                let global_env = Environment::new();
+                let rt2 = subst(
+                    RichTerm {
+                        term: t2,
+                        pos: pos2,
+                    },
+                    &global_env,
+                    &env2,
+                );
+
+                let format = match id.to_string().as_str() {
+                    "Json" => ExportFormat::Json,
+                    "Yaml" => ExportFormat::Yaml,
+                    "Toml" => ExportFormat::Toml,
+                    "Xml" => ExportFormat::Xml,
+                    "Ini" => ExportFormat::Ini,
+                    _ => return mk_err_fst(t1),
+                };
+
+                serialize::validate(format, &rt2)?;
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(serialize::to_string(format, &rt2)?),
+                    pos_op_inh,
+                )))
+            } else {
+                mk_err_fst(t1)
+            }
+        }
+        BinaryOp::Deserialize() => {
+            let mk_err_fst = |t1| {
+                Err(EvalError::TypeError(
+                    String::from("Enum <Json, Yaml, Toml, Xml, Ini>"),
+                    String::from("deserialize, 1st argument"),
+                    fst_pos,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                ))
+            };
+
+            if let Term::Enum(id) = &*t1 {
+                if let Term::Str(s) = &*t2 {
+                    // The locations reported by the parsers are converted to byte offsets in `s`.
+                    // The columns of JSON and TOML are counted in bytes, while the columns of YAML
+                    // are counted in characters.
+                    let rt: RichTerm = match id.as_ref() {
+                        "Json" => serde_json::from_str(s).map_err(|err| {
+                            let offset = if err.line() > 0 {
+                                let line_start = line_offset(s, err.line() - 1);
+                                Some(line_start + err.column().saturating_sub(1))
+                            } else {
+                                None
+                            };
+
+                            EvalError::DeserializationError(
+                                String::from("json"),
+                                format!("{}", err),
+                                deserialization_error_pos(s, offset, pos2, pos_op),
+                            )
+                        })?,
+                        "Yaml" => serde_yaml::from_str(s).map_err(|err| {
+                            let offset = err.location().map(|loc| {
+                                char_column_offset(
+                                    s,
+                                    loc.line().saturating_sub(1),
+                                    loc.column().saturating_sub(1),
+                                )
+                            });
+
+                            EvalError::DeserializationError(
+                                String::from("yaml"),
+                                format!("{}", err),
+                                deserialization_error_pos(s, offset, pos2, pos_op),
+                            )
+                        })?,
+                        "Toml" => toml::from_str(s).map_err(|err| {
+                            let offset =
+                                err.line_col().map(|(line, col)| line_offset(s, line) + col);
+
+                            EvalError::DeserializationError(
+                                String::from("toml"),
+                                format!("{}", err),
+                                deserialization_error_pos(s, offset, pos2, pos_op),
+                            )
+                        })?,
+                        "Xml" => serialize::xml::from_str(s).map_err(|err| {
+                            EvalError::DeserializationError(
+                                String::from("xml"),
+                                format!("{}", err),
+                                deserialization_error_pos(s, Some(err.offset), pos2, pos_op),
+                            )
+                        })?,
+                        "Ini" => serialize::ini::from_str(s).map_err(|err| {
+                            EvalError::DeserializationError(
+                                String::from("ini"),
+                                format!("{}", err),
+                                deserialization_error_pos(
+                                    s,
+                                    Some(line_offset(s, err.line)),
+                                    pos2,
+                                    pos_op,
+                                ),
+                            )
+                        })?,
+                        _ => return mk_err_fst(t1),
+                    };
+
+                    Ok(Closure::atomic_closure(rt.with_pos(pos_op_inh)))
+                } else {
+                    Err(EvalError::TypeError(
+                        String::from("Str"),
+                        String::from("deserialize, 2nd argument"),
+                        snd_pos,
+                        RichTerm {
+                            term: t2,
+                            pos: pos2,
+                        },
+                    ))
+                }
+            } else {
+                mk_err_fst(t1)
+            }
+        }
         BinaryOp::StrSplit() => match (&*t1, &*t2) {
             (Term::Str(s1), Term::Str(s2)) => {
                 let list: Vec<RichTerm> = s1
@@ -1961,37 +3800,541 @@ fn process_binary_operation(
                     .map(|s| Term::Str(String::from(s)).into())
                     .collect();
                 Ok(Closure::atomic_closure(RichTerm::new(
-                    Term::List(list),
+                    Term::List(list),
+                    pos_op_inh,
+                )))
+            }
+            (Term::Str(_), _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strSplit, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strSplit, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::StrSplitOnce() => match (&*t1, &*t2) {
+            (Term::Str(s1), Term::Str(s2)) => {
+                let (tag, before, after) = match s1.split_once(s2.as_str()) {
+                    Some((before, after)) => ("Split", before, after),
+                    None => ("NotFound", s1.as_str(), ""),
+                };
+
+                Ok(Closure::atomic_closure(
+                    mk_record!(
+                        ("tag", Term::Enum(Ident::from(tag))),
+                        ("before", Term::Str(String::from(before))),
+                        ("after", Term::Str(String::from(after)))
+                    )
+                    .with_pos(pos_op_inh),
+                ))
+            }
+            (Term::Str(_), _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strSplitOnce, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strSplitOnce, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::StrUnlines() => match (&*t1, &*t2) {
+            (Term::List(ts), Term::Bool(trailing_newline)) => match str_list(ts, &env1) {
+                Ok(Some(lines)) => {
+                    let mut result = String::new();
+
+                    for line in lines {
+                        result.push_str(&line);
+                        result.push('\n');
+                    }
+
+                    // Each line has been followed by a line feed, including the last one.
+                    if !trailing_newline {
+                        result.pop();
+                    }
+
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Str(result),
+                        pos_op_inh,
+                    )))
+                }
+                Ok(None) => Ok(seq_list_elements(
+                    ts,
+                    &env1,
+                    pos1,
+                    Environment::new(),
+                    |list| {
+                        mk_term::op2(
+                            BinaryOp::StrUnlines(),
+                            list,
+                            RichTerm::new(Term::Bool(*trailing_newline), pos2),
+                        )
+                    },
+                    pos_op_inh,
+                )),
+                Err(t) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    String::from("strUnlines, element of the 1st argument"),
+                    t.pos,
+                    t.clone(),
+                )),
+            },
+            (Term::List(_), _) => Err(EvalError::TypeError(
+                String::from("Bool"),
+                String::from("strUnlines, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("List"),
+                String::from("strUnlines, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::StrJoin() => match (&*t1, &*t2) {
+            (Term::Str(sep), Term::List(ts)) => match str_list(ts, &env2) {
+                Ok(Some(strs)) => Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(strs.join(sep)),
+                    pos_op_inh,
+                ))),
+                Ok(None) => Ok(seq_list_elements(
+                    ts,
+                    &env2,
+                    pos2,
+                    Environment::new(),
+                    |list| {
+                        mk_term::op2(
+                            BinaryOp::StrJoin(),
+                            RichTerm::new(Term::Str(sep.clone()), pos1),
+                            list,
+                        )
+                    },
+                    pos_op_inh,
+                )),
+                Err(t) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    String::from("strJoin, element of the 2nd argument"),
+                    t.pos,
+                    t.clone(),
+                )),
+            },
+            (Term::Str(_), _) => Err(EvalError::TypeError(
+                String::from("List"),
+                String::from("strJoin, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strJoin, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::StrContains() => match (&*t1, &*t2) {
+            (Term::Str(s1), Term::Str(s2)) => Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Bool(s1.contains(s2)),
+                pos_op_inh,
+            ))),
+            (Term::Str(_), _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strContains, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strContains, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::StrToEnumChecked() => match (&*t1, &*t2) {
+            (Term::Str(s), Term::List(ts)) => match str_list(ts, &env2) {
+                Ok(Some(allowed)) => {
+                    if allowed.contains(s) {
+                        Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::Enum(s.into()),
+                            pos_op_inh,
+                        )))
+                    } else {
+                        Err(EvalError::Other(
+                            format!(
+                                "strToEnumChecked: invalid tag `{}`. Expected one of: {}",
+                                s,
+                                allowed.join(", ")
+                            ),
+                            pos_op,
+                            None,
+                        ))
+                    }
+                }
+                Ok(None) => Ok(seq_list_elements(
+                    ts,
+                    &env2,
+                    pos2,
+                    Environment::new(),
+                    |list| {
+                        mk_term::op2(
+                            BinaryOp::StrToEnumChecked(),
+                            RichTerm::new(Term::Str(s.clone()), pos1),
+                            list,
+                        )
+                    },
+                    pos_op_inh,
+                )),
+                Err(t) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    String::from("strToEnumChecked, element of the 2nd argument"),
+                    t.pos,
+                    t.clone(),
+                )),
+            },
+            (Term::Str(_), _) => Err(EvalError::TypeError(
+                String::from("List"),
+                String::from("strToEnumChecked, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strToEnumChecked, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::NumToStrRadix() => match (&*t1, &*t2) {
+            (Term::Num(n), Term::Num(radix)) => {
+                let radix = valid_radix(*radix)
+                    .ok_or_else(|| invalid_radix("numToStrRadix", *radix, pos_op))?;
+
+                if n.fract() != 0.0 {
+                    return Err(EvalError::NotAnInteger(
+                        String::from("numToStrRadix, 1st argument"),
+                        *n,
+                        pos_op,
+                    ));
+                }
+
+                if n.abs() >= u128::MAX as f64 {
+                    return Err(EvalError::Other(
+                        format!("numToStrRadix: the number {} is too large", n),
+                        pos_op,
+                        None,
+                    ));
+                }
+
+                let digits = to_str_radix(n.abs() as u128, radix);
+                let result = if *n < 0.0 {
+                    format!("-{}", digits)
+                } else {
+                    digits
+                };
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(result),
+                    pos_op_inh,
+                )))
+            }
+            (Term::Num(_), _) => Err(EvalError::TypeError(
+                String::from("Num"),
+                String::from("numToStrRadix, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Num"),
+                String::from("numToStrRadix, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::NumToStrFixed() => match (&*t1, &*t2) {
+            (Term::Num(n), Term::Num(decimals)) => {
+                if decimals.fract() != 0.0 || !(0.0..=100.0).contains(decimals) {
+                    return Err(EvalError::Other(
+                        format!(
+                            "numToStrFixed: the number of decimal places must be an integer between 0 and 100, got {}",
+                            decimals
+                        ),
+                        pos_op,
+                        None,
+                    ));
+                }
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Str(format!("{:.*}", *decimals as usize, n)),
                     pos_op_inh,
                 )))
             }
+            (Term::Num(_), _) => Err(EvalError::TypeError(
+                String::from("Num"),
+                String::from("numToStrFixed, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Num"),
+                String::from("numToStrFixed, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::StrToNumRadix() => match (&*t1, &*t2) {
+            (Term::Str(s), Term::Num(radix)) => {
+                let radix = valid_radix(*radix)
+                    .ok_or_else(|| invalid_radix("strToNumRadix", *radix, pos_op))?;
+
+                let (tag, value) = match i128::from_str_radix(s, radix) {
+                    Ok(n) => ("Some", n as f64),
+                    Err(_) => ("None", 0.0),
+                };
+
+                Ok(Closure::atomic_closure(
+                    mk_record!(
+                        ("tag", Term::Enum(Ident::from(tag))),
+                        ("value", Term::Num(value))
+                    )
+                    .with_pos(pos_op_inh),
+                ))
+            }
+            (Term::Str(_), _) => Err(EvalError::TypeError(
+                String::from("Num"),
+                String::from("strToNumRadix, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strToNumRadix, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::StrStartsWith() => match (&*t1, &*t2) {
+            (Term::Str(s1), Term::Str(s2)) => Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Bool(s1.starts_with(s2.as_str())),
+                pos_op_inh,
+            ))),
+            (Term::Str(_), _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strStartsWith, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strStartsWith, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::StrEndsWith() => match (&*t1, &*t2) {
+            (Term::Str(s1), Term::Str(s2)) => Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Bool(s1.ends_with(s2.as_str())),
+                pos_op_inh,
+            ))),
+            (Term::Str(_), _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strEndsWith, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strEndsWith, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::StrTrimChars() => match (&*t1, &*t2) {
+            (Term::Str(s), Term::Str(chars)) => Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Str(String::from(s.trim_matches(|c| chars.contains(c)))),
+                pos_op_inh,
+            ))),
+            (Term::Str(_), _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strTrimChars, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strTrimChars, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::StrContainsCaseInsensitive()
+        | BinaryOp::StrStartsWithCaseInsensitive()
+        | BinaryOp::StrEndsWithCaseInsensitive()
+        | BinaryOp::StrEqCaseInsensitive() => {
+            let op_name = match b_op {
+                BinaryOp::StrContainsCaseInsensitive() => "strContainsCaseInsensitive",
+                BinaryOp::StrStartsWithCaseInsensitive() => "strStartsWithCaseInsensitive",
+                BinaryOp::StrEndsWithCaseInsensitive() => "strEndsWithCaseInsensitive",
+                _ => "strEqCaseInsensitive",
+            };
+
+            match (&*t1, &*t2) {
+                (Term::Str(s1), Term::Str(s2)) => {
+                    let (s1, s2) = (case_fold(s1), case_fold(s2));
+                    let result = match b_op {
+                        BinaryOp::StrContainsCaseInsensitive() => s1.contains(&s2),
+                        BinaryOp::StrStartsWithCaseInsensitive() => s1.starts_with(&s2),
+                        BinaryOp::StrEndsWithCaseInsensitive() => s1.ends_with(&s2),
+                        _ => s1 == s2,
+                    };
+
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Bool(result),
+                        pos_op_inh,
+                    )))
+                }
+                (Term::Str(_), _) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    format!("{}, 2nd argument", op_name),
+                    snd_pos,
+                    RichTerm {
+                        term: t2,
+                        pos: pos2,
+                    },
+                )),
+                (_, _) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    format!("{}, 1st argument", op_name),
+                    fst_pos,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                )),
+            }
+        }
+        BinaryOp::StrRepeat() => match (&*t1, &*t2) {
+            (Term::Str(s), Term::Num(n)) => {
+                if *n < 0.0 || n.fract() != 0.0 {
+                    Err(EvalError::Other(
+                        format!(
+                            "strRepeat: expected the 2nd argument to be a non-negative integer, got {}",
+                            n
+                        ),
+                        snd_pos,
+                        None,
+                    ))
+                } else {
+                    // Converting to `usize` saturates, which is then caught by the length check
+                    let count = *n as usize;
+                    match s.len().checked_mul(count) {
+                        Some(len) if len <= MAX_REPEATED_STR_LEN => {
+                            let result = if len == 0 {
+                                String::new()
+                            } else {
+                                s.repeat(count)
+                            };
+                            Ok(Closure::atomic_closure(RichTerm::new(
+                                Term::Str(result),
+                                pos_op_inh,
+                            )))
+                        }
+                        _ => Err(EvalError::Other(
+                            format!(
+                                "strRepeat: the result would be too long ({} copies of a string of \
+                                length {}, while the maximum length is {})",
+                                n,
+                                s.len(),
+                                MAX_REPEATED_STR_LEN
+                            ),
+                            snd_pos,
+                            None,
+                        )),
+                    }
+                }
+            }
             (Term::Str(_), _) => Err(EvalError::TypeError(
-                String::from("Str"),
-                String::from("strSplit, 2nd argument"),
-                snd_pos,
-                RichTerm {
-                    term: t2,
-                    pos: pos2,
-                },
-            )),
-            (_, _) => Err(EvalError::TypeError(
-                String::from("Str"),
-                String::from("strSplit, 1st argument"),
-                fst_pos,
-                RichTerm {
-                    term: t1,
-                    pos: pos1,
-                },
-            )),
-        },
-        BinaryOp::StrContains() => match (&*t1, &*t2) {
-            (Term::Str(s1), Term::Str(s2)) => Ok(Closure::atomic_closure(RichTerm::new(
-                Term::Bool(s1.contains(s2)),
-                pos_op_inh,
-            ))),
-            (Term::Str(_), _) => Err(EvalError::TypeError(
-                String::from("Str"),
-                String::from("strContains, 2nd argument"),
+                String::from("Num"),
+                String::from("strRepeat, 2nd argument"),
                 snd_pos,
                 RichTerm {
                     term: t2,
@@ -2000,7 +4343,7 @@ fn process_binary_operation(
             )),
             (_, _) => Err(EvalError::TypeError(
                 String::from("Str"),
-                String::from("strContains, 1st argument"),
+                String::from("strRepeat, 1st argument"),
                 fst_pos,
                 RichTerm {
                     term: t1,
@@ -2010,8 +4353,8 @@ fn process_binary_operation(
         },
         BinaryOp::StrIsMatch() => match (&*t1, &*t2) {
             (Term::Str(s1), Term::Str(s2)) => {
-                let re = regex::Regex::new(s2)
-                    .map_err(|err| EvalError::Other(err.to_string(), pos_op))?;
+                let re = compile_regex(s2)
+                    .map_err(|err| EvalError::Other(err.to_string(), pos_op, None))?;
 
                 Ok(Closure::atomic_closure(RichTerm::new(
                     Term::Bool(re.is_match(s1)),
@@ -2040,26 +4383,12 @@ fn process_binary_operation(
         BinaryOp::StrMatch() => {
             match (&*t1, &*t2) {
                 (Term::Str(s1), Term::Str(s2)) => {
-                    let re = regex::Regex::new(s2)
-                        .map_err(|err| EvalError::Other(err.to_string(), pos_op))?;
+                    let re = compile_regex(s2)
+                        .map_err(|err| EvalError::Other(err.to_string(), pos_op, None))?;
                     let capt = re.captures(s1);
 
                     let result = if let Some(capt) = capt {
-                        let first_match = capt.get(0).unwrap();
-                        let groups: Vec<RichTerm> = capt
-                            .iter()
-                            .skip(1)
-                            .map(|s_opt| {
-                                s_opt.map(|s| RichTerm::from(Term::Str(String::from(s.as_str()))))
-                            })
-                            .flatten()
-                            .collect();
-
-                        mk_record!(
-                            ("match", Term::Str(String::from(first_match.as_str()))),
-                            ("index", Term::Num(first_match.start() as f64)),
-                            ("groups", Term::List(groups))
-                        )
+                        captures_to_record(&capt)
                     } else {
                         //FIXME: what should we return when there's no match?
                         mk_record!(
@@ -2091,6 +4420,39 @@ fn process_binary_operation(
                 )),
             }
         }
+        BinaryOp::StrMatchAll() => match (&*t1, &*t2) {
+            (Term::Str(s1), Term::Str(s2)) => {
+                let re = compile_regex(s2)
+                    .map_err(|err| EvalError::Other(err.to_string(), pos_op, None))?;
+                let matches: Vec<RichTerm> = re
+                    .captures_iter(s1)
+                    .map(|capt| captures_to_record(&capt))
+                    .collect();
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::List(matches),
+                    pos_op_inh,
+                )))
+            }
+            (Term::Str(_), _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strMatchAll, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("strMatchAll, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
     }
 }
 
@@ -2101,7 +4463,8 @@ fn process_binary_operation(
 fn process_nary_operation(
     n_op: NAryOp,
     args: Vec<(Closure, TermPos)>,
-    _stack: &mut Stack,
+    stack: &mut Stack,
+    call_stack: &mut CallStack,
     pos_op: TermPos,
 ) -> Result<Closure, EvalError> {
     let pos_op_inh = pos_op.into_inherited();
@@ -2119,22 +4482,326 @@ fn process_nary_operation(
             debug_assert!(args_wo_env.next().is_none());
 
             match (&*fst, &*snd, &*thd) {
-                (Term::Str(s), Term::Str(from), Term::Str(to)) => {
-                    let result = if let NAryOp::StrReplace() = n_op {
-                        str::replace(s, from, to)
-                    } else {
-                        let re = regex::Regex::new(from)
-                            .map_err(|err| EvalError::Other(err.to_string(), pos_op))?;
+                (Term::Str(s), Term::Str(from), Term::Str(to)) => {
+                    let result = if let NAryOp::StrReplace() = n_op {
+                        str::replace(s, from, to)
+                    } else {
+                        let re = compile_regex(from)
+                            .map_err(|err| EvalError::Other(err.to_string(), pos_op, None))?;
+
+                        re.replace_all(s, to.as_str()).into_owned()
+                    };
+
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Str(result),
+                        pos_op_inh,
+                    )))
+                }
+                (Term::Str(_), Term::Str(_), _) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    format!("{}, 3rd argument", n_op),
+                    thd_pos,
+                    RichTerm {
+                        term: thd,
+                        pos: pos3,
+                    },
+                )),
+                (Term::Str(_), _, _) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    format!("{}, 2nd argument", n_op),
+                    snd_pos,
+                    RichTerm {
+                        term: snd,
+                        pos: pos2,
+                    },
+                )),
+                (_, _, _) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    format!("{}, 1st argument", n_op),
+                    fst_pos,
+                    RichTerm {
+                        term: fst,
+                        pos: pos1,
+                    },
+                )),
+            }
+        }
+        NAryOp::StrReplaceN() => {
+            let mut args_wo_env = args
+                .into_iter()
+                .map(|(clos, pos)| (clos.body.term, clos.body.pos, pos));
+            let (fst, pos1, fst_pos) = args_wo_env.next().unwrap();
+            let (snd, pos2, snd_pos) = args_wo_env.next().unwrap();
+            let (thd, pos3, thd_pos) = args_wo_env.next().unwrap();
+            let (fourth, pos4, fourth_pos) = args_wo_env.next().unwrap();
+            debug_assert!(args_wo_env.next().is_none());
+
+            match (&*fst, &*snd, &*thd, &*fourth) {
+                (Term::Str(s), Term::Str(from), Term::Str(to), Term::Num(n)) => {
+                    if n.fract() != 0.0 {
+                        Err(EvalError::NotAnInteger(
+                            format!("{}, 4th argument", n_op),
+                            *n,
+                            pos_op,
+                        ))
+                    } else if *n < 0.0 {
+                        Err(EvalError::Other(
+                            format!(
+                                "{}: expected the 4th argument to be a non-negative integer, got {}",
+                                n_op, n
+                            ),
+                            pos_op, None,
+                        ))
+                    } else {
+                        Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::Str(s.replacen(from.as_str(), to, *n as usize)),
+                            pos_op_inh,
+                        )))
+                    }
+                }
+                (Term::Str(_), Term::Str(_), Term::Str(_), _) => Err(EvalError::TypeError(
+                    String::from("Num"),
+                    format!("{}, 4th argument", n_op),
+                    fourth_pos,
+                    RichTerm {
+                        term: fourth,
+                        pos: pos4,
+                    },
+                )),
+                (Term::Str(_), Term::Str(_), _, _) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    format!("{}, 3rd argument", n_op),
+                    thd_pos,
+                    RichTerm {
+                        term: thd,
+                        pos: pos3,
+                    },
+                )),
+                (Term::Str(_), _, _, _) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    format!("{}, 2nd argument", n_op),
+                    snd_pos,
+                    RichTerm {
+                        term: snd,
+                        pos: pos2,
+                    },
+                )),
+                (_, _, _, _) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    format!("{}, 1st argument", n_op),
+                    fst_pos,
+                    RichTerm {
+                        term: fst,
+                        pos: pos1,
+                    },
+                )),
+            }
+        }
+        NAryOp::StrSplitN() => {
+            let mut args_wo_env = args
+                .into_iter()
+                .map(|(clos, pos)| (clos.body.term, clos.body.pos, pos));
+            let (fst, pos1, fst_pos) = args_wo_env.next().unwrap();
+            let (snd, pos2, snd_pos) = args_wo_env.next().unwrap();
+            let (thd, pos3, thd_pos) = args_wo_env.next().unwrap();
+            debug_assert!(args_wo_env.next().is_none());
+
+            match (&*fst, &*snd, &*thd) {
+                (Term::Str(s), Term::Str(sep), Term::Num(n)) => {
+                    if n.fract() != 0.0 {
+                        Err(EvalError::NotAnInteger(
+                            format!("{}, 3rd argument", n_op),
+                            *n,
+                            pos_op,
+                        ))
+                    } else if *n < 1.0 {
+                        Err(EvalError::Other(
+                            format!(
+                                "{}: expected the 3rd argument to be a positive integer, got {}",
+                                n_op, n
+                            ),
+                            pos_op,
+                            None,
+                        ))
+                    } else {
+                        let list: Vec<RichTerm> = s
+                            .splitn(*n as usize, sep.as_str())
+                            .map(|s| Term::Str(String::from(s)).into())
+                            .collect();
+
+                        Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::List(list),
+                            pos_op_inh,
+                        )))
+                    }
+                }
+                (Term::Str(_), Term::Str(_), _) => Err(EvalError::TypeError(
+                    String::from("Num"),
+                    format!("{}, 3rd argument", n_op),
+                    thd_pos,
+                    RichTerm {
+                        term: thd,
+                        pos: pos3,
+                    },
+                )),
+                (Term::Str(_), _, _) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    format!("{}, 2nd argument", n_op),
+                    snd_pos,
+                    RichTerm {
+                        term: snd,
+                        pos: pos2,
+                    },
+                )),
+                (_, _, _) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    format!("{}, 1st argument", n_op),
+                    fst_pos,
+                    RichTerm {
+                        term: fst,
+                        pos: pos1,
+                    },
+                )),
+            }
+        }
+        NAryOp::StrSubstr() => {
+            let mut args_wo_env = args
+                .into_iter()
+                .map(|(clos, pos)| (clos.body.term, clos.body.pos, pos));
+            let (fst, pos1, fst_pos) = args_wo_env.next().unwrap();
+            let (snd, pos2, snd_pos) = args_wo_env.next().unwrap();
+            let (thd, pos3, thd_pos) = args_wo_env.next().unwrap();
+            debug_assert!(args_wo_env.next().is_none());
+
+            match (&*fst, &*snd, &*thd) {
+                (Term::Str(s), Term::Num(start), Term::Num(end)) => {
+                    let start_int = *start as usize;
+                    let end_int = *end as usize;
+
+                    if start.fract() != 0.0 {
+                        Err(EvalError::NotAnInteger(
+                            String::from("substring, 2nd argument (start)"),
+                            *start,
+                            pos_op,
+                        ))
+                    } else if !s.is_char_boundary(start_int) {
+                        Err(EvalError::IndexOutOfBounds(
+                            String::from("substring, 2nd argument (start)"),
+                            *start,
+                            0..s.len() + 1,
+                            pos_op,
+                        ))
+                    } else if end.fract() != 0.0 {
+                        Err(EvalError::NotAnInteger(
+                            String::from("substring, 3rd argument (end)"),
+                            *end,
+                            pos_op,
+                        ))
+                    } else if end <= start || !s.is_char_boundary(end_int) {
+                        Err(EvalError::IndexOutOfBounds(
+                            String::from("substring, 3rd argument (end)"),
+                            *end,
+                            start_int + 1..s.len() + 1,
+                            pos_op,
+                        ))
+                    } else {
+                        Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::Str(s[start_int..end_int].to_owned()),
+                            pos_op_inh,
+                        )))
+                    }
+                }
+                (Term::Str(_), Term::Num(_), _) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    String::from("strReplace, 3rd argument"),
+                    thd_pos,
+                    RichTerm {
+                        term: thd,
+                        pos: pos3,
+                    },
+                )),
+                (Term::Str(_), _, _) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    String::from("strReplace, 2nd argument"),
+                    snd_pos,
+                    RichTerm {
+                        term: snd,
+                        pos: pos2,
+                    },
+                )),
+                (_, _, _) => Err(EvalError::TypeError(
+                    String::from("Str"),
+                    String::from("strReplace, 1st argument"),
+                    fst_pos,
+                    RichTerm {
+                        term: fst,
+                        pos: pos1,
+                    },
+                )),
+            }
+        }
+        NAryOp::StrPadLeft() | NAryOp::StrPadRight() => {
+            let mut args_wo_env = args
+                .into_iter()
+                .map(|(clos, pos)| (clos.body.term, clos.body.pos, pos));
+            let (fst, pos1, fst_pos) = args_wo_env.next().unwrap();
+            let (snd, pos2, snd_pos) = args_wo_env.next().unwrap();
+            let (thd, pos3, thd_pos) = args_wo_env.next().unwrap();
+            debug_assert!(args_wo_env.next().is_none());
+
+            match (&*fst, &*snd, &*thd) {
+                (Term::Str(s), Term::Num(width), Term::Str(pad)) => {
+                    let len = s.chars().count();
 
-                        re.replace_all(s, to.as_str()).into_owned()
-                    };
+                    if width.fract() != 0.0 {
+                        Err(EvalError::NotAnInteger(
+                            format!("{}, 2nd argument (width)", n_op),
+                            *width,
+                            pos_op,
+                        ))
+                    } else if *width < 0.0 {
+                        Err(EvalError::Other(format!("{}: expected the 2nd argument (width) to be a non-negative integer, got {}", n_op, width), pos_op, None))
+                    } else if len >= *width as usize {
+                        Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::Str(s.clone()),
+                            pos_op_inh,
+                        )))
+                    } else if pad.is_empty() {
+                        Err(EvalError::Other(
+                            format!(
+                                "{}: expected the 3rd argument (pad) to be a non-empty string",
+                                n_op
+                            ),
+                            pos_op,
+                            None,
+                        ))
+                    } else if *width > MAX_PADDED_STR_LEN as f64 {
+                        Err(EvalError::Other(
+                            format!(
+                                "{}: the width {} is too large (the maximum width is {})",
+                                n_op, width, MAX_PADDED_STR_LEN
+                            ),
+                            pos_op,
+                            None,
+                        ))
+                    } else {
+                        // The pad string is repeated as many times as needed, and truncated to
+                        // fill exactly the remaining space.
+                        let padding: String =
+                            pad.chars().cycle().take(*width as usize - len).collect();
+                        let result = if let NAryOp::StrPadLeft() = n_op {
+                            padding + s
+                        } else {
+                            s.clone() + &padding
+                        };
 
-                    Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Str(result),
-                        pos_op_inh,
-                    )))
+                        Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::Str(result),
+                            pos_op_inh,
+                        )))
+                    }
                 }
-                (Term::Str(_), Term::Str(_), _) => Err(EvalError::TypeError(
+                (Term::Str(_), Term::Num(_), _) => Err(EvalError::TypeError(
                     String::from("Str"),
                     format!("{}, 3rd argument", n_op),
                     thd_pos,
@@ -2144,7 +4811,7 @@ fn process_nary_operation(
                     },
                 )),
                 (Term::Str(_), _, _) => Err(EvalError::TypeError(
-                    String::from("Str"),
+                    String::from("Num"),
                     format!("{}, 2nd argument", n_op),
                     snd_pos,
                     RichTerm {
@@ -2163,7 +4830,76 @@ fn process_nary_operation(
                 )),
             }
         }
-        NAryOp::StrSubstr() => {
+        NAryOp::ListFoldl() | NAryOp::ListFoldr() => {
+            let op_name = if let NAryOp::ListFoldl() = n_op {
+                "foldl"
+            } else {
+                "foldr"
+            };
+
+            let mut args = args.into_iter();
+            let (
+                Closure {
+                    body:
+                        RichTerm {
+                            term: t1,
+                            pos: pos1,
+                        },
+                    env: env1,
+                },
+                fst_pos,
+            ) = args.next().unwrap();
+            let (f, _) = args.next().unwrap();
+            let (init, _) = args.next().unwrap();
+            debug_assert!(args.next().is_none());
+
+            match_sharedterm! {t1, with {
+                    Term::List(ts) if ts.is_empty() => Ok(init),
+                    Term::List(ts) => {
+                        let mut shared_env = Environment::new();
+                        let f_as_var = f.body.closurize(&mut shared_env, f.env);
+                        let ts: Vec<RichTerm> = ts
+                            .into_iter()
+                            .map(|t| t.closurize(&mut shared_env, env1.clone()))
+                            .collect();
+
+                        if let NAryOp::ListFoldl() = n_op {
+                            // The remaining elements are stored in reverse order, such that the
+                            // next one to fold is at the end.
+                            let mut remaining = ts;
+                            remaining.reverse();
+                            let f = Closure {
+                                body: f_as_var,
+                                env: shared_env,
+                            };
+
+                            Ok(foldl_step(init, remaining, f, stack, call_stack, pos_op))
+                        } else {
+                            let init_as_var = init.body.closurize(&mut shared_env, init.env);
+                            let folded = ts.into_iter().rev().fold(init_as_var, |acc, t| {
+                                mk_app!(f_as_var.clone(), acc, t).with_pos(pos_op_inh)
+                            });
+
+                            Ok(Closure {
+                                body: folded,
+                                env: shared_env,
+                            })
+                        }
+                    }
+                } else {
+                    Err(EvalError::TypeError(
+                        String::from("List"),
+                        format!("{}, 1st argument", op_name),
+                        fst_pos,
+                        RichTerm {
+                            term: t1,
+                            pos: pos1,
+                        },
+                    ))
+                }
+            }
+        }
+        NAryOp::ListRangeStep() => {
             let mut args_wo_env = args
                 .into_iter()
                 .map(|(clos, pos)| (clos.body.term, clos.body.pos, pos));
@@ -2173,37 +4909,49 @@ fn process_nary_operation(
             debug_assert!(args_wo_env.next().is_none());
 
             match (&*fst, &*snd, &*thd) {
-                (Term::Str(s), Term::Num(start), Term::Num(end)) => {
-                    let start_int = *start as usize;
-                    let end_int = *end as usize;
-
+                (Term::Num(start), Term::Num(end), Term::Num(step)) => {
                     if start.fract() != 0.0 {
-                        Err(EvalError::Other(format!("substring: expected the 2nd agument (start) to be an integer, got the floating-point value {}", start), pos_op))
-                    } else if !s.is_char_boundary(start_int) {
-                        Err(EvalError::Other(format!("substring: index out of bounds. Expected the 2nd argument (start) to be between 0 and {}, got {}", s.len(), start), pos_op))
+                        Err(EvalError::NotAnInteger(
+                            String::from("rangeStep, 1st argument (start)"),
+                            *start,
+                            pos_op,
+                        ))
                     } else if end.fract() != 0.0 {
-                        Err(EvalError::Other(format!("substring: expected the 3nd argument (end) to be an integer, got the floating-point value {}", end), pos_op))
-                    } else if end <= start || !s.is_char_boundary(end_int) {
-                        Err(EvalError::Other(format!("substring: index out of bounds. Expected the 3rd argument (end) to be between {} and {}, got {}", start+1., s.len(), end), pos_op))
-                    } else {
+                        Err(EvalError::NotAnInteger(
+                            String::from("rangeStep, 2nd argument (end)"),
+                            *end,
+                            pos_op,
+                        ))
+                    } else if step.fract() != 0.0 || *step == 0.0 {
+                        Err(EvalError::Other(format!("rangeStep: expected the 3rd argument (step) to be a non-zero integer, got {}", step), pos_op, None))
+                    } else if let Some(ts) = range(*start, *end, *step) {
                         Ok(Closure::atomic_closure(RichTerm::new(
-                            Term::Str(s[start_int..end_int].to_owned()),
+                            Term::List(ts),
                             pos_op_inh,
                         )))
+                    } else {
+                        Err(EvalError::Other(
+                            format!(
+                                "rangeStep: the range from {} to {} by steps of {} has more than {} elements",
+                                start, end, step, MAX_RANGE_LEN
+                            ),
+                            pos_op,
+                            None,
+                        ))
                     }
                 }
-                (Term::Str(_), Term::Num(_), _) => Err(EvalError::TypeError(
-                    String::from("Str"),
-                    String::from("strReplace, 3rd argument"),
+                (Term::Num(_), Term::Num(_), _) => Err(EvalError::TypeError(
+                    String::from("Num"),
+                    String::from("rangeStep, 3rd argument"),
                     thd_pos,
                     RichTerm {
                         term: thd,
                         pos: pos3,
                     },
                 )),
-                (Term::Str(_), _, _) => Err(EvalError::TypeError(
-                    String::from("Str"),
-                    String::from("strReplace, 2nd argument"),
+                (Term::Num(_), _, _) => Err(EvalError::TypeError(
+                    String::from("Num"),
+                    String::from("rangeStep, 2nd argument"),
                     snd_pos,
                     RichTerm {
                         term: snd,
@@ -2211,43 +4959,332 @@ fn process_nary_operation(
                     },
                 )),
                 (_, _, _) => Err(EvalError::TypeError(
-                    String::from("Str"),
-                    String::from("strReplace, 1st argument"),
+                    String::from("Num"),
+                    String::from("rangeStep, 1st argument"),
+                    fst_pos,
+                    RichTerm {
+                        term: fst,
+                        pos: pos1,
+                    },
+                )),
+            }
+        }
+        NAryOp::MergeContract() => {
+            let mut args_iter = args.into_iter();
+            let (
+                Closure {
+                    body: RichTerm { term: t1, pos: _ },
+                    env: _,
+                },
+                _,
+            ) = args_iter.next().unwrap();
+            let (
+                Closure {
+                    body:
+                        RichTerm {
+                            term: t2,
+                            pos: pos2,
+                        },
+                    env: env2,
+                },
+                _,
+            ) = args_iter.next().unwrap();
+            let (
+                Closure {
+                    body:
+                        RichTerm {
+                            term: t3,
+                            pos: pos3,
+                        },
+                    env: env3,
+                },
+                _,
+            ) = args_iter.next().unwrap();
+            debug_assert!(args_iter.next().is_none());
+
+            match_sharedterm! {t1, with {
+                    Term::Lbl(lbl) => {
+                        merge(
+                            RichTerm {
+                                term: t2,
+                                pos: pos2,
+                            },
+                            env2,
+                            RichTerm {
+                                term: t3,
+                                pos: pos3,
+                            },
+                            env3,
+                            pos_op,
+                            MergeMode::Contract(lbl),
+                            MergePath::default(),
+                        )
+                    }
+                } else {
+                    Err(EvalError::InternalError(format!("The MergeContract() operator was expecting a first argument of type Label, got {}", t1.type_of().unwrap_or(String::from("<unevaluated>"))), pos_op))
+                }
+            }
+        }
+        NAryOp::Hmac() => {
+            let mut args_wo_env = args
+                .into_iter()
+                .map(|(clos, pos)| (clos.body.term, clos.body.pos, pos));
+            let (fst, pos1, fst_pos) = args_wo_env.next().unwrap();
+            let (snd, pos2, snd_pos) = args_wo_env.next().unwrap();
+            let (thd, pos3, thd_pos) = args_wo_env.next().unwrap();
+            debug_assert!(args_wo_env.next().is_none());
+
+            match (&*fst, &*snd, &*thd) {
+                (Term::Enum(id), Term::Str(key), Term::Str(msg))
+                    if id.label == "Sha256" || id.label == "Sha512" =>
+                {
+                    let result = if id.label == "Sha256" {
+                        hmac::<sha2::Sha256>(64, key.as_bytes(), msg.as_bytes())
+                    } else {
+                        hmac::<sha2::Sha512>(128, key.as_bytes(), msg.as_bytes())
+                    };
+
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Str(result),
+                        pos_op_inh,
+                    )))
+                }
+                (Term::Enum(id), Term::Str(_), _)
+                    if id.label == "Sha256" || id.label == "Sha512" =>
+                {
+                    Err(EvalError::TypeError(
+                        String::from("Str"),
+                        format!("{}, 3rd argument", n_op),
+                        thd_pos,
+                        RichTerm {
+                            term: thd,
+                            pos: pos3,
+                        },
+                    ))
+                }
+                (Term::Enum(id), _, _) if id.label == "Sha256" || id.label == "Sha512" => {
+                    Err(EvalError::TypeError(
+                        String::from("Str"),
+                        format!("{}, 2nd argument", n_op),
+                        snd_pos,
+                        RichTerm {
+                            term: snd,
+                            pos: pos2,
+                        },
+                    ))
+                }
+                (_, _, _) => Err(EvalError::TypeError(
+                    String::from("Enum <Sha256, Sha512>"),
+                    format!("{}, 1st argument", n_op),
                     fst_pos,
                     RichTerm {
-                        term: fst,
+                        term: fst,
+                        pos: pos1,
+                    },
+                )),
+            }
+        }
+        NAryOp::SerializeWith() => {
+            let mut args_iter = args.into_iter();
+            let (
+                Closure {
+                    body:
+                        RichTerm {
+                            term: t1,
+                            pos: pos1,
+                        },
+                    env: _,
+                },
+                fst_pos,
+            ) = args_iter.next().unwrap();
+            let (
+                Closure {
+                    body:
+                        RichTerm {
+                            term: t2,
+                            pos: pos2,
+                        },
+                    env: _,
+                },
+                snd_pos,
+            ) = args_iter.next().unwrap();
+            let (
+                Closure {
+                    body: t3,
+                    env: env3,
+                },
+                _,
+            ) = args_iter.next().unwrap();
+            debug_assert!(args_iter.next().is_none());
+
+            let format = match t1.as_ref() {
+                Term::Enum(id) if id.label == "Json" => ExportFormat::Json,
+                Term::Enum(id) if id.label == "Yaml" => ExportFormat::Yaml,
+                Term::Enum(id) if id.label == "Toml" => ExportFormat::Toml,
+                Term::Enum(id) if id.label == "Xml" => ExportFormat::Xml,
+                Term::Enum(id) if id.label == "Ini" => ExportFormat::Ini,
+                _ => {
+                    return Err(EvalError::TypeError(
+                        String::from("Enum <Json, Yaml, Toml, Xml, Ini>"),
+                        String::from("serializeWith, 1st argument"),
+                        fst_pos,
+                        RichTerm {
+                            term: t1,
+                            pos: pos1,
+                        },
+                    ))
+                }
+            };
+
+            let style = match t2.as_ref() {
+                Term::Enum(id) if id.label == "Pretty" => serialize::Style::Pretty,
+                Term::Enum(id) if id.label == "Compact" => serialize::Style::Compact,
+                _ => {
+                    return Err(EvalError::TypeError(
+                        String::from("Enum <Pretty, Compact>"),
+                        String::from("serializeWith, 2nd argument"),
+                        snd_pos,
+                        RichTerm {
+                            term: t2,
+                            pos: pos2,
+                        },
+                    ))
+                }
+            };
+
+            // Serialization needs all variables term to be fully substituted
+            let rt3 = subst(t3, &Environment::new(), &env3);
+
+            serialize::validate(format, &rt3)?;
+            Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Str(serialize::to_string_with_style(format, style, &rt3)?),
+                pos_op_inh,
+            )))
+        }
+        NAryOp::RecordSetPath() => {
+            let (record, fst_pos, path, value) =
+                match path_op_args(NAryOp::RecordSetPath(), args, pos_op_inh)? {
+                    PathOpArgs::Evaluated(record, fst_pos, path, value) => {
+                        (record, fst_pos, path, value)
+                    }
+                    PathOpArgs::Unevaluated(closure) => return Ok(closure),
+                };
+            let Closure {
+                body:
+                    RichTerm {
+                        term: t1,
                         pos: pos1,
                     },
-                )),
+                env: env1,
+            } = record;
+
+            let (id, rest) = match path.split_first() {
+                Some((id, rest)) => (Ident::from(id), rest),
+                None => return Ok(value),
+            };
+            let rest_path = Term::List(rest.iter().map(mk_term::string).collect());
+
+            match_sharedterm! {t1, with {
+                    Term::Record(fields, attrs) => {
+                        // The existing fields refer to `env1`, which is extended with the new value
+                        // and shared by the result.
+                        let mut fields = fields;
+                        let mut env = env1;
+                        let value = value.body.closurize(&mut env, value.env);
+                        let inner = fields
+                            .get(&id)
+                            .cloned()
+                            .unwrap_or_else(|| Term::Record(IndexMap::new(), Default::default()).into());
+
+                        // The new field is closurized as the other ones, such that it is updated
+                        // once evaluated.
+                        let field_env = env.clone();
+                        let field = mk_opn!(NAryOp::RecordSetPath(), inner, rest_path, value)
+                            .closurize(&mut env, field_env);
+                        fields.insert(id, field);
+
+                        Ok(Closure {
+                            body: RichTerm::new(Term::Record(fields, attrs), pos_op_inh),
+                            env,
+                        })
+                    }
+                } else {
+                    Err(EvalError::TypeError(
+                        String::from("Record"),
+                        String::from("recordSetPath, 1st argument"),
+                        fst_pos,
+                        RichTerm {
+                            term: t1,
+                            pos: pos1,
+                        },
+                    ))
+                }
             }
         }
-        NAryOp::MergeContract() => {
+        NAryOp::RecordGetPathOr() => {
+            let (record, path, default) =
+                match path_op_args(NAryOp::RecordGetPathOr(), args, pos_op_inh)? {
+                    PathOpArgs::Evaluated(record, _, path, default) => (record, path, default),
+                    PathOpArgs::Unevaluated(closure) => return Ok(closure),
+                };
+
+            let (id, rest) = match path.split_first() {
+                Some((id, rest)) => (Ident::from(id), rest),
+                None => return Ok(record),
+            };
+
+            let field = match record.body.as_ref() {
+                Term::Record(fields, _) => fields.get(&id).cloned(),
+                _ => None,
+            };
+
+            // The remaining path is accessed lazily, falling back to the default as soon as a field
+            // is missing or a value is not a record.
+            Ok(match field {
+                Some(field) if rest.is_empty() => {
+                    call_stack.enter_field(id, record.body.pos, field.pos, pos_op);
+                    Closure {
+                        body: field,
+                        env: record.env,
+                    }
+                }
+                Some(field) => {
+                    let rest_path = Term::List(rest.iter().map(mk_term::string).collect());
+                    let mut env = record.env;
+                    let default = default.body.closurize(&mut env, default.env);
+
+                    Closure {
+                        body: mk_opn!(NAryOp::RecordGetPathOr(), field, rest_path, default)
+                            .with_pos(pos_op_inh),
+                        env,
+                    }
+                }
+                None => default,
+            })
+        }
+        NAryOp::MergeByKey() => {
             let mut args_iter = args.into_iter();
             let (
                 Closure {
-                    body: RichTerm { term: t1, pos: _ },
+                    body:
+                        RichTerm {
+                            term: t1,
+                            pos: pos1,
+                        },
                     env: _,
                 },
-                _,
+                fst_pos,
             ) = args_iter.next().unwrap();
             let (
                 Closure {
-                    body:
-                        RichTerm {
-                            term: t2,
-                            pos: pos2,
-                        },
+                    body: t2,
                     env: env2,
                 },
                 _,
             ) = args_iter.next().unwrap();
             let (
                 Closure {
-                    body:
-                        RichTerm {
-                            term: t3,
-                            pos: pos3,
-                        },
+                    body: t3,
                     env: env3,
                 },
                 _,
@@ -2255,27 +5292,538 @@ fn process_nary_operation(
             debug_assert!(args_iter.next().is_none());
 
             match_sharedterm! {t1, with {
-                    Term::Lbl(lbl) => {
-                        merge(
-                            RichTerm {
-                                term: t2,
-                                pos: pos2,
-                            },
-                            env2,
-                            RichTerm {
-                                term: t3,
-                                pos: pos3,
-                            },
-                            env3,
-                            pos_op,
-                            MergeMode::Contract(lbl),
-                        )
-                    }
+                    Term::Str(key) => merge(
+                        t2,
+                        env2,
+                        t3,
+                        env3,
+                        pos_op,
+                        MergeMode::ListsByKey(Ident::from(key)),
+                        MergePath::default(),
+                    )
                 } else {
-                    Err(EvalError::InternalError(format!("The MergeContract() operator was expecting a first argument of type Label, got {}", t1.type_of().unwrap_or(String::from("<unevaluated>"))), pos_op))
+                    Err(EvalError::TypeError(
+                        String::from("Str"),
+                        String::from("mergeByKey, 1st argument"),
+                        fst_pos,
+                        RichTerm {
+                            term: t1,
+                            pos: pos1,
+                        },
+                    ))
+                }
+            }
+        }
+        NAryOp::MergeListsByKey() => {
+            let mut args_wo_pos = args.into_iter().map(|(clos, _)| clos);
+            let key = args_wo_pos.next().unwrap();
+            let l1 = args_wo_pos.next().unwrap();
+            let l2 = args_wo_pos.next().unwrap();
+            let keys = args_wo_pos.next().unwrap();
+            debug_assert!(args_wo_pos.next().is_none());
+
+            match (
+                key.body.term.into_owned(),
+                l1.body.term.into_owned(),
+                l2.body.term.into_owned(),
+                keys.body.term.into_owned(),
+            ) {
+                (Term::Str(key), Term::List(ts1), Term::List(ts2), Term::List(keys_ts)) => {
+                    merge::merge_lists_by_key(
+                        Ident::from(key),
+                        ts1,
+                        l1.env,
+                        ts2,
+                        l2.env,
+                        keys_ts,
+                        keys.env,
+                        pos_op,
+                    )
                 }
+                _ => Err(EvalError::InternalError(
+                    String::from(
+                        "The MergeListsByKey() operator was expecting a string and three lists",
+                    ),
+                    pos_op,
+                )),
+            }
+        }
+    }
+}
+
+/// Push a [`UnaryOp::BoolGuard`] continuation checking the second operand of the lazy boolean
+/// operator `op_name`.
+///
+/// If the top of the stack is already such a guard, the value of the second operand is also the
+/// value checked by the existing guard. The existing guard is then replaced instead of stacking
+/// a new one, which keeps the second operand in tail position: recursive boolean predicates such
+/// as `f n = n == 0 || f (n - 1)` run in constant stack space. The replacement guard reports the
+/// innermost operator and operand, but keeps the call stack size of the existing one.
+fn push_bool_guard(
+    stack: &mut Stack,
+    op_name: &'static str,
+    arg_pos: TermPos,
+    call_stack_len: usize,
+    pos_op: TermPos,
+) {
+    let call_stack_len = if stack.is_top_bool_guard() {
+        let (_, len, _) = stack.pop_op_cont().unwrap();
+        len
+    } else {
+        call_stack_len
+    };
+
+    stack.push_op_cont(
+        OperationCont::Op1(UnaryOp::BoolGuard(op_name), arg_pos),
+        call_stack_len,
+        pos_op,
+    );
+}
+
+/// Compute the HMAC of a message as defined by RFC 2104, using the hash function `D` whose block
+/// size is `block_size` bytes. Return the result as a lowercase hexadecimal string.
+fn hmac<D: Digest>(block_size: usize, key: &[u8], msg: &[u8]) -> String {
+    // Keys longer than a block are hashed first, and keys are then padded with zeros.
+    let mut key = if key.len() > block_size {
+        D::digest(key).to_vec()
+    } else {
+        key.to_vec()
+    };
+    key.resize(block_size, 0);
+
+    let mut inner = D::new();
+    inner.update(key.iter().map(|b| b ^ 0x36).collect::<Vec<u8>>());
+    inner.update(msg);
+
+    let mut outer = D::new();
+    outer.update(key.iter().map(|b| b ^ 0x5c).collect::<Vec<u8>>());
+    outer.update(inner.finalize());
+
+    outer
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode bytes in base64 as defined by RFC 4648, with padding.
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity((bytes.len() + 2) / 3 * 4);
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, b)| acc | (*b as u32) << (16 - 8 * i));
+
+        // A chunk of n bytes is encoded by n + 1 characters, and padded to 4 characters.
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                result.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+
+    result
+}
+
+/// Decode a base64 string as defined by RFC 4648, with padding. ASCII whitespaces are ignored.
+fn base64_decode(s: &str) -> Result<Vec<u8>, String> {
+    let chars: Vec<u8> = s.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+
+    if chars.len() % 4 != 0 {
+        return Err(String::from(
+            "invalid base64 input: the number of characters, ignoring whitespaces, must be a multiple of 4",
+        ));
+    }
+
+    let mut result = Vec::with_capacity(chars.len() / 4 * 3);
+    let nb_groups = chars.len() / 4;
+
+    for (group_index, group) in chars.chunks(4).enumerate() {
+        // Padding is only allowed at the end of the last group.
+        let padding = group.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || (padding > 0 && group_index + 1 != nb_groups) {
+            return Err(String::from("invalid base64 input: unexpected padding"));
+        }
+
+        let mut value = 0u32;
+        for (i, c) in group[..4 - padding].iter().enumerate() {
+            let index = BASE64_ALPHABET.iter().position(|a| a == c).ok_or_else(|| {
+                format!(
+                    "invalid base64 input: unexpected character `{}`",
+                    *c as char
+                )
+            })?;
+            value |= (index as u32) << (18 - 6 * i);
+        }
+
+        result.extend(value.to_be_bytes()[1..4 - padding].iter());
+    }
+
+    Ok(result)
+}
+
+/// Extract the values of the elements of a list using `extract`. Return `Ok(None)` if some
+/// elements are not evaluated yet, or the first evaluated element rejected by `extract`.
+fn list_values<'a, T>(
+    ts: &'a [RichTerm],
+    env: &Environment,
+    extract: impl Fn(&Term) -> Option<T>,
+) -> Result<Option<Vec<T>>, &'a RichTerm> {
+    let mut values = Vec::with_capacity(ts.len());
+
+    for t in ts {
+        let value = subst(t.clone(), &Environment::new(), env);
+
+        match extract(value.as_ref()) {
+            Some(v) => values.push(v),
+            None if value.as_ref().is_whnf() => return Err(t),
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(values))
+}
+
+/// Extract the strings of a list. Return `Ok(None)` if some elements are not evaluated yet, or
+/// the first evaluated element which is not a string.
+fn str_list<'a>(
+    ts: &'a [RichTerm],
+    env: &Environment,
+) -> Result<Option<Vec<String>>, &'a RichTerm> {
+    list_values(ts, env, |t| match t {
+        Term::Str(s) => Some(s.clone()),
+        _ => None,
+    })
+}
+
+/// Build a closure which forces each element of the list `ts` with `%seq%`, and then evaluates
+/// the operation built by `mk_op` from the list of the closurized elements, which are evaluated
+/// by then. `env` holds the other arguments of the operation, which the caller has already
+/// closurized.
+fn seq_list_elements(
+    ts: &[RichTerm],
+    list_env: &Environment,
+    list_pos: TermPos,
+    mut env: Environment,
+    mk_op: impl FnOnce(RichTerm) -> RichTerm,
+    pos_op_inh: TermPos,
+) -> Closure {
+    let ts: Vec<RichTerm> = ts
+        .iter()
+        .map(|t| t.clone().closurize(&mut env, list_env.clone()))
+        .collect();
+    let op = mk_op(RichTerm::new(Term::List(ts.clone()), list_pos)).with_pos(pos_op_inh);
+    let body = ts.into_iter().rev().fold(op, |acc, t| {
+        mk_app!(mk_term::op1(UnaryOp::Seq(), t), acc).with_pos(pos_op_inh)
+    });
+
+    Closure { body, env }
+}
+
+/// The arguments of a record path operator.
+enum PathOpArgs {
+    /// The record together with the position of the first argument, the path, and the last
+    /// argument.
+    Evaluated(Closure, TermPos, Vec<String>, Closure),
+    /// Some elements of the path are not evaluated yet: the closure forces them, and then applies
+    /// the operator again.
+    Unevaluated(Closure),
+}
+
+/// Extract the arguments of the record path operator `n_op` ([`NAryOp::RecordSetPath`] or
+/// [`NAryOp::RecordGetPathOr`]), once the elements of the path are evaluated.
+fn path_op_args(
+    n_op: NAryOp,
+    args: Vec<(Closure, TermPos)>,
+    pos_op_inh: TermPos,
+) -> Result<PathOpArgs, EvalError> {
+    let mut args_iter = args.into_iter();
+    let (record, fst_pos) = args_iter.next().unwrap();
+    let (
+        Closure {
+            body: RichTerm {
+                term: t2,
+                pos: pos2,
+            },
+            env: env2,
+        },
+        snd_pos,
+    ) = args_iter.next().unwrap();
+    let (last, _) = args_iter.next().unwrap();
+    debug_assert!(args_iter.next().is_none());
+
+    let ts = match &*t2 {
+        Term::List(ts) => ts,
+        _ => {
+            return Err(EvalError::TypeError(
+                String::from("List"),
+                format!("{}, 2nd argument", n_op),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            ))
+        }
+    };
+
+    match str_list(ts, &env2) {
+        Ok(Some(path)) => Ok(PathOpArgs::Evaluated(record, fst_pos, path, last)),
+        Ok(None) => {
+            let mut env = Environment::new();
+            let record = record.body.closurize(&mut env, record.env);
+            let last = last.body.closurize(&mut env, last.env);
+
+            Ok(PathOpArgs::Unevaluated(seq_list_elements(
+                ts,
+                &env2,
+                pos2,
+                env,
+                |list| RichTerm::from(Term::OpN(n_op, vec![record, list, last])),
+                pos_op_inh,
+            )))
+        }
+        Err(t) => Err(EvalError::TypeError(
+            String::from("Str"),
+            format!("{}, element of the 2nd argument", n_op),
+            t.pos,
+            t.clone(),
+        )),
+    }
+}
+
+/// Generate the integers from `start` (included) to `end` (excluded) by increments of `step`. The
+/// arguments must be integers, and `step` must be non-zero. Return `None` if the result would have
+/// more than [`MAX_RANGE_LEN`] elements.
+fn range(start: f64, end: f64, step: f64) -> Option<Vec<RichTerm>> {
+    // Converting to `i128` saturates, which is then caught by the length check.
+    let (start, end, step) = (start as i128, end as i128, step as i128);
+    let span = if step > 0 {
+        end.checked_sub(start)?
+    } else {
+        start.checked_sub(end)?
+    };
+    let len = if span > 0 {
+        (span as u128 - 1) / step.unsigned_abs() + 1
+    } else {
+        0
+    };
+
+    if len > MAX_RANGE_LEN as u128 {
+        return None;
+    }
+
+    Some(
+        (0..len as i128)
+            .map(|i| RichTerm::from(Term::Num((start + i * step) as f64)))
+            .collect(),
+    )
+}
+
+/// Floor division of `n1` by `n2`: the quotient is rounded towards negative infinity, and the
+/// remainder has the same sign as the divisor, such that `n1 == quot * n2 + rem`.
+fn floor_div_mod(n1: f64, n2: f64) -> (f64, f64) {
+    let mut rem = n1 % n2;
+
+    if rem != 0.0 && (rem < 0.0) != (n2 < 0.0) {
+        rem += n2;
+    }
+
+    ((n1 - rem) / n2, rem)
+}
+
+/// Check that a number is a valid radix for the conversions between numbers and strings, that is
+/// an integer between 2 and 36.
+fn valid_radix(radix: f64) -> Option<u32> {
+    if radix.fract() == 0.0 && (2.0..=36.0).contains(&radix) {
+        Some(radix as u32)
+    } else {
+        None
+    }
+}
+
+/// The error raised by the conversions between numbers and strings on an invalid radix.
+fn invalid_radix(op_name: &str, radix: f64, pos_op: TermPos) -> EvalError {
+    EvalError::Other(
+        format!(
+            "{}: the radix must be an integer between 2 and 36, got {}",
+            op_name, radix
+        ),
+        pos_op,
+        None,
+    )
+}
+
+/// Write a natural number in the given radix, using lowercase letters for the digits above 9.
+fn to_str_radix(mut n: u128, radix: u32) -> String {
+    let mut digits = Vec::new();
+
+    loop {
+        digits.push(std::char::from_digit((n % radix as u128) as u32, radix).unwrap());
+        n /= radix as u128;
+
+        if n == 0 {
+            break;
+        }
+    }
+
+    digits.into_iter().rev().collect()
+}
+
+/// Encode bytes in lowercase hexadecimal.
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decode a hexadecimal string, either in lowercase or uppercase.
+fn hex_decode(s: &str) -> Result<Vec<u8>, String> {
+    if s.len() % 2 != 0 {
+        return Err(String::from(
+            "invalid hexadecimal input: the number of digits must be even",
+        ));
+    }
+
+    s.as_bytes()
+        .chunks(2)
+        .map(|digits| {
+            match (
+                (digits[0] as char).to_digit(16),
+                (digits[1] as char).to_digit(16),
+            ) {
+                (Some(high), Some(low)) => Ok((high * 16 + low) as u8),
+                _ => Err(format!(
+                    "invalid hexadecimal input: unexpected characters `{}`",
+                    String::from_utf8_lossy(digits)
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Convert decoded bytes to a string, failing if they are not valid UTF-8.
+fn decoded_to_string(bytes: Vec<u8>) -> Result<String, String> {
+    String::from_utf8(bytes)
+        .map_err(|_| String::from("the decoded bytes are not a valid UTF-8 string"))
+}
+
+/// Compile a regex, or retrieve it from [`REGEX_CACHE`] if the same pattern has already been
+/// compiled. Invalid patterns are not cached, and fail each time they are compiled.
+fn compile_regex(pattern: &str) -> Result<regex::Regex, regex::Error> {
+    if let Some(re) = REGEX_CACHE.with(|cache| cache.borrow().get(pattern).cloned()) {
+        return Ok(re);
+    }
+
+    let re = regex::Regex::new(pattern)?;
+
+    REGEX_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        // We don't bother implementing a smarter eviction policy: programs using that many
+        // different patterns are rare.
+        if cache.len() >= REGEX_CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(String::from(pattern), re.clone());
+    });
+
+    Ok(re)
+}
+
+/// Build the result of `StrMatch` from the captures of a regex match: a record with the matched
+/// string, the index of the match and the list of the captured groups.
+fn captures_to_record(capt: &regex::Captures) -> RichTerm {
+    let first_match = capt.get(0).unwrap();
+    let groups: Vec<RichTerm> = capt
+        .iter()
+        .skip(1)
+        .map(|s_opt| s_opt.map(|s| RichTerm::from(Term::Str(String::from(s.as_str())))))
+        .flatten()
+        .collect();
+
+    mk_record!(
+        ("match", Term::Str(String::from(first_match.as_str()))),
+        ("index", Term::Num(first_match.start() as f64)),
+        ("groups", Term::List(groups))
+    )
+}
+
+/// Case fold a string, for case-insensitive comparisons. See
+/// [`BinaryOp::StrEqCaseInsensitive`](../../term/enum.BinaryOp.html#variant.StrEqCaseInsensitive).
+///
+/// The mapping is done character by character, and not using `str::to_lowercase`, which maps a
+/// final `Σ` to `ς`: the folding of a character must not depend on its context.
+fn case_fold(s: &str) -> String {
+    s.chars()
+        .flat_map(char::to_lowercase)
+        .flat_map(char::to_uppercase)
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Remove the common indentation of the lines of a string. See
+/// [`UnaryOp::StrDedent`](../../term/enum.UnaryOp.html#variant.StrDedent).
+fn dedent(s: &str) -> String {
+    fn is_blank(line: &str) -> bool {
+        line.trim_start().is_empty()
+    }
+
+    let indent = s
+        .split('\n')
+        .filter(|line| !is_blank(line))
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .reduce(|common, indent| {
+            let len = common
+                .chars()
+                .zip(indent.chars())
+                .take_while(|(c1, c2)| c1 == c2)
+                .map(|(c, _)| c.len_utf8())
+                .sum();
+            &common[..len]
+        })
+        .unwrap_or("");
+
+    s.split('\n')
+        .map(|line| {
+            if is_blank(line) {
+                ""
+            } else {
+                &line[indent.len()..]
             }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Recursively remove the `null` fields of the records of a fully substituted term. If `in_lists`
+/// is `true`, `null` elements of lists are also removed.
+fn elide_nulls(rt: RichTerm, in_lists: bool) -> RichTerm {
+    let pos = rt.pos;
+
+    match rt.term.into_owned() {
+        Term::Record(map, attrs) => {
+            let map = map
+                .into_iter()
+                .filter(|(_, t)| !matches!(t.as_ref(), Term::Null))
+                .map(|(id, t)| (id, elide_nulls(t, in_lists)))
+                .collect();
+            RichTerm::new(Term::Record(map, attrs), pos)
+        }
+        Term::List(ts) => {
+            let ts = ts
+                .into_iter()
+                .filter(|t| !(in_lists && matches!(t.as_ref(), Term::Null)))
+                .map(|t| elide_nulls(t, in_lists))
+                .collect();
+            RichTerm::new(Term::List(ts), pos)
         }
+        t => RichTerm::new(t, pos),
     }
 }
 
@@ -2368,6 +5916,7 @@ fn eq(env: &mut Environment, c1: Closure, c2: Closure) -> EqResult {
 mod tests {
     use super::*;
     use crate::eval::{CallStack, Environment};
+    use assert_matches::assert_matches;
 
     #[test]
     fn ite_operation() {
@@ -2390,7 +5939,7 @@ mod tests {
         stack.push_op_cont(cont, 0, TermPos::None);
         let mut call_stack = CallStack::new();
 
-        clos = continuate_operation(clos, &mut stack, &mut call_stack).unwrap();
+        clos = continuate_operation(clos, &mut stack, &mut call_stack, None).unwrap();
 
         assert_eq!(
             clos,
@@ -2402,6 +5951,42 @@ mod tests {
         assert_eq!(0, stack.count_args());
     }
 
+    #[test]
+    fn nested_bool_guards() {
+        let mut stack = Stack::new();
+        stack.push_op_cont(
+            OperationCont::Op1(UnaryOp::BoolGuard("||"), TermPos::None),
+            0,
+            TermPos::None,
+        );
+        stack.push_arg(
+            Closure::atomic_closure(Term::Bool(false).into()),
+            TermPos::None,
+        );
+        stack.push_op_cont(
+            OperationCont::Op1(UnaryOp::BoolAnd(), TermPos::None),
+            0,
+            TermPos::None,
+        );
+        let mut call_stack = CallStack::new();
+
+        let clos = continuate_operation(
+            Closure::atomic_closure(Term::Bool(true).into()),
+            &mut stack,
+            &mut call_stack,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(clos, Closure::atomic_closure(Term::Bool(false).into()));
+        // The existing guard is replaced by a guard for the innermost operator
+        assert_eq!(1, stack.count_conts());
+        assert_matches!(
+            stack.pop_op_cont(),
+            Some((OperationCont::Op1(UnaryOp::BoolGuard("&&"), _), 0, _))
+        );
+    }
+
     #[test]
     fn plus_first_term_operation() {
         let cont = OperationCont::Op2First(
@@ -2421,7 +6006,7 @@ mod tests {
         stack.push_op_cont(cont, 0, TermPos::None);
         let mut call_stack = CallStack::new();
 
-        clos = continuate_operation(clos, &mut stack, &mut call_stack).unwrap();
+        clos = continuate_operation(clos, &mut stack, &mut call_stack, None).unwrap();
 
         assert_eq!(
             clos,
@@ -2469,7 +6054,7 @@ mod tests {
         stack.push_op_cont(cont, 0, TermPos::None);
         let mut call_stack = CallStack::new();
 
-        clos = continuate_operation(clos, &mut stack, &mut call_stack).unwrap();
+        clos = continuate_operation(clos, &mut stack, &mut call_stack, None).unwrap();
 
         assert_eq!(
             clos,
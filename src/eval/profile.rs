@@ -0,0 +1,122 @@
+//! Profiling of the evaluation of primitive operators.
+//!
+//! When given a [`Profile`](struct.Profile.html), the abstract machine records, for each kind of
+//! primitive operator, the number of times it has been applied and the accumulated wall time spent
+//! computing its result. Operators are identified regardless of their parameters: all the
+//! `Switch` operators, for example, share the same entry.
+//!
+//! The time of an operator only covers the final step performed in
+//! [`continuate_operation`](../operation/fn.continuate_operation.html), once its arguments have
+//! been evaluated: the evaluation of the arguments themselves, or of the term an operator
+//! reduces to, is accounted to the operators involved there, if any.
+use crate::term::{BinaryOp, NAryOp, UnaryOp};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::mem::{discriminant, Discriminant};
+use std::time::{Duration, Instant};
+
+/// A kind of primitive operator, that is an operator regardless of its parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OpKind {
+    Unary(Discriminant<UnaryOp>),
+    Binary(Discriminant<BinaryOp>),
+    NAry(Discriminant<NAryOp>),
+}
+
+impl From<&UnaryOp> for OpKind {
+    fn from(op: &UnaryOp) -> Self {
+        OpKind::Unary(discriminant(op))
+    }
+}
+
+impl From<&BinaryOp> for OpKind {
+    fn from(op: &BinaryOp) -> Self {
+        OpKind::Binary(discriminant(op))
+    }
+}
+
+impl From<&NAryOp> for OpKind {
+    fn from(op: &NAryOp) -> Self {
+        OpKind::NAry(discriminant(op))
+    }
+}
+
+/// The statistics of a kind of operator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpStats {
+    /// The name of the operator, as the name of its variant, e.g. `ListMap`.
+    pub name: String,
+    /// The number of times the operator has been applied.
+    pub count: usize,
+    /// The accumulated wall time spent computing the results of the operator.
+    pub time: Duration,
+}
+
+/// The statistics of the primitive operators applied during an evaluation.
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    ops: HashMap<OpKind, OpStats>,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The statistics of an operator, or `None` if it hasn't been applied.
+    pub fn get<'a, O>(&self, op: &'a O) -> Option<&OpStats>
+    where
+        &'a O: Into<OpKind>,
+    {
+        self.ops.get(&op.into())
+    }
+
+    /// The statistics of all the operators which have been applied, sorted by decreasing time.
+    pub fn stats(&self) -> Vec<&OpStats> {
+        let mut stats: Vec<_> = self.ops.values().collect();
+        stats.sort_by_key(|s| std::cmp::Reverse(s.time));
+        stats
+    }
+
+    /// Start measuring the application of an operator. The measure stops when the returned timer
+    /// is dropped.
+    pub(crate) fn start<'a, O>(&mut self, op: &'a O) -> OpTimer<'_>
+    where
+        &'a O: Into<OpKind>,
+        O: Debug,
+    {
+        let stats = self.ops.entry(op.into()).or_insert_with(|| OpStats {
+            name: variant_name(op),
+            count: 0,
+            time: Duration::ZERO,
+        });
+
+        OpTimer {
+            stats,
+            start: Instant::now(),
+        }
+    }
+}
+
+/// A measure of the application of an operator, which is recorded once dropped.
+pub(crate) struct OpTimer<'a> {
+    stats: &'a mut OpStats,
+    start: Instant,
+}
+
+impl Drop for OpTimer<'_> {
+    fn drop(&mut self) {
+        self.stats.count += 1;
+        self.stats.time += self.start.elapsed();
+    }
+}
+
+/// The name of the variant of an operator, obtained from its debug representation without the
+/// parameters.
+fn variant_name(op: &impl Debug) -> String {
+    let repr = format!("{:?}", op);
+    match repr.find('(') {
+        Some(idx) => repr[..idx].to_owned(),
+        None => repr,
+    }
+}
@@ -4,7 +4,7 @@
 use super::operation::OperationCont;
 use crate::eval::{Closure, Environment, IdentKind, Thunk, ThunkUpdateFrame};
 use crate::position::TermPos;
-use crate::term::{RichTerm, StrChunk};
+use crate::term::{RichTerm, StrChunk, UnaryOp};
 
 /// An element of the stack.
 pub enum Marker {
@@ -39,11 +39,13 @@ pub enum Marker {
     /// the top element of the stack, which must be `StrAcc`.
     StrChunk(StrChunk<RichTerm>),
     /// A string accumulator. Used by `ChunksConcat` to store additional state, that is the string being
-    /// constructed, the indentation of the chunk being evaluated, and the common initial environment of chunks.
+    /// constructed, the indentation of the chunk being evaluated, the common initial environment of
+    /// chunks, and the position of the whole string.
     StrAcc(
         String,      /* the accumulator */
         usize,       /* the indentation level of the chunk currently evaluated */
         Environment, /* the common environment of chunks */
+        TermPos,     /* the position of the whole string */
     ),
     Strictness(bool),
 }
@@ -57,7 +59,7 @@ impl std::fmt::Debug for Marker {
             Marker::Thunk(_) => write!(f, "Thunk"),
             Marker::Cont(op, sz, _) => write!(f, "Cont {:?} (callstack size {})", op, sz),
             Marker::StrChunk(_) => write!(f, "StrChunk"),
-            Marker::StrAcc(_, _, _) => write!(f, "StrAcc"),
+            Marker::StrAcc(_, _, _, _) => write!(f, "StrAcc"),
             Marker::Strictness(s) => write!(f, "Strictness = {}", s),
         }
     }
@@ -164,8 +166,8 @@ impl Stack {
     }
 
     /// Push a string accumulator on the stack.
-    pub fn push_str_acc(&mut self, acc: String, indent: usize, env: Environment) {
-        self.0.push(Marker::StrAcc(acc, indent, env));
+    pub fn push_str_acc(&mut self, acc: String, indent: usize, env: Environment, pos: TermPos) {
+        self.0.push(Marker::StrAcc(acc, indent, env, pos));
     }
 
     pub fn push_strictness(&mut self, strict: bool) {
@@ -245,10 +247,10 @@ impl Stack {
 
     /// Try to pop the a string accumulator from the stack. If `None` is returned, the top element
     /// was not a string accumulator and the stack is left unchanged.
-    pub fn pop_str_acc(&mut self) -> Option<(String, usize, Environment)> {
+    pub fn pop_str_acc(&mut self) -> Option<(String, usize, Environment, TermPos)> {
         if self.0.last().map(Marker::is_str_acc).unwrap_or(false) {
             match self.0.pop() {
-                Some(Marker::StrAcc(acc, indent, env)) => Some((acc, indent, env)),
+                Some(Marker::StrAcc(acc, indent, env, pos)) => Some((acc, indent, env, pos)),
                 _ => panic!(),
             }
         } else {
@@ -290,6 +292,17 @@ impl Stack {
         self.0.last().map(Marker::is_cont).unwrap_or(false)
     }
 
+    /// Check if the top element is the continuation of a [`UnaryOp::BoolGuard`].
+    pub fn is_top_bool_guard(&self) -> bool {
+        matches!(
+            self.0.last(),
+            Some(Marker::Cont(
+                OperationCont::Op1(UnaryOp::BoolGuard(_), _),
+                ..
+            ))
+        )
+    }
+
     /// Discard all the consecutive equality from the top of the stack. This drops the continuation
     /// of the equality being currently evaluated.
     pub fn clear_eqs(&mut self) {
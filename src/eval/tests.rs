@@ -1,12 +1,14 @@
 use super::*;
-use crate::cache::resolvers::{DummyResolver, SimpleResolver};
+use crate::cache::resolvers::{DummyResolver, SimpleResolver, VirtualFsResolver};
 use crate::error::ImportError;
 use crate::label::Label;
 use crate::parser::{grammar, lexer};
+use crate::position::TermPos;
 use crate::term::make as mk_term;
-use crate::term::{BinaryOp, StrChunk, UnaryOp};
+use crate::term::{BinaryOp, MergePath, StrChunk, UnaryOp};
 use crate::transform::import_resolution::resolve_imports;
 use crate::{mk_app, mk_fun};
+use assert_matches::assert_matches;
 use codespan::Files;
 
 /// Evaluate a term without import support.
@@ -14,6 +16,16 @@ fn eval_no_import(t: RichTerm) -> Result<Term, EvalError> {
     eval(t, &Environment::new(), &mut DummyResolver {}).map(Term::from)
 }
 
+/// Parse and evaluate a string without import support.
+fn eval_str(s: &str) -> Result<Term, EvalError> {
+    eval_no_import(parse(s).unwrap())
+}
+
+/// Same as [`eval_str`], but apply the program transformations before evaluating.
+fn eval_transformed(s: &str) -> Result<Term, EvalError> {
+    eval_no_import(crate::transform::transform(parse(s).unwrap()))
+}
+
 fn parse(s: &str) -> Option<RichTerm> {
     let id = Files::new().add("<test>", String::from(s));
 
@@ -54,11 +66,74 @@ fn lone_var_panics() {
     eval_no_import(mk_term::var("unbound")).unwrap();
 }
 
+#[test]
+fn unbound_identifier_suggestions() {
+    let t = parse("let value = 1 in let other = 2 in valeu + other").unwrap();
+    match eval(t, &Environment::new(), &mut DummyResolver {}) {
+        Err(EvalError::UnboundIdentifier(id, suggestions, ..)) => {
+            assert_eq!(id, Ident::from("valeu"));
+            assert_eq!(suggestions, vec![Ident::from("value")]);
+        }
+        res => panic!("expected an unbound identifier error, got {:?}", res),
+    }
+}
+
+#[test]
+fn unbound_identifier_call_stack() {
+    use crate::eval::callstack::StackElem;
+
+    let t = crate::transform::transform(
+        parse("let f = fun x => let g = fun y => y + unbound in g x in f 1").unwrap(),
+    );
+    match eval(t, &Environment::new(), &mut DummyResolver {}) {
+        Err(EvalError::UnboundIdentifier(_, _, _, Some(call_stack))) => {
+            let entered: Vec<_> = call_stack
+                .as_ref()
+                .iter()
+                .filter_map(|elem| match elem {
+                    StackElem::Var { id, .. } => Some(id.label.as_str()),
+                    _ => None,
+                })
+                .collect();
+            assert!(entered.contains(&"f"));
+            assert!(entered.contains(&"g"));
+        }
+        res => panic!(
+            "expected an unbound identifier error with a call stack, got {:?}",
+            res
+        ),
+    }
+}
+
 #[test]
 fn only_fun_are_applicable() {
     eval_no_import(mk_app!(Term::Bool(true), Term::Num(45.))).unwrap_err();
 }
 
+#[test]
+fn not_a_func_reports_all_args() {
+    let source = "{a = 1} 2 3";
+    let id = Files::new().add("<test>", String::from(source));
+    let t = grammar::TermParser::new()
+        .parse_term(id, lexer::Lexer::new(source))
+        .unwrap();
+
+    match eval(t, &Environment::new(), &mut DummyResolver {}) {
+        Err(EvalError::NotAFunc(t, arg, arg_count, pos_app, _)) => {
+            assert_matches!(t.as_ref(), Term::Record(..));
+            assert_eq!(arg.as_ref(), &Term::Num(2.));
+            assert_eq!(arg_count, 2);
+            // The position spans the whole application.
+            let span = pos_app.unwrap();
+            assert_eq!(
+                (span.start.to_usize(), span.end.to_usize()),
+                (0, source.len())
+            );
+        }
+        res => panic!("expected a NotAFunc error, got {:?}", res),
+    }
+}
+
 #[test]
 fn simple_app() {
     let t = mk_app!(mk_term::id(), Term::Num(5.0));
@@ -91,6 +166,12 @@ fn asking_for_various_types() {
     let boolean = mk_term::op1(UnaryOp::IsBool(), Term::Bool(true));
     assert_eq!(Ok(Term::Bool(true)), eval_no_import(boolean));
 
+    let null = mk_term::op1(UnaryOp::IsNull(), Term::Null);
+    assert_eq!(Ok(Term::Bool(true)), eval_no_import(null));
+
+    let not_null = mk_term::op1(UnaryOp::IsNull(), Term::Num(0.0));
+    assert_eq!(Ok(Term::Bool(false)), eval_no_import(not_null));
+
     let lambda = mk_term::op1(
         UnaryOp::IsFun(),
         mk_fun!("x", mk_app!(mk_term::var("x"), mk_term::var("x"))),
@@ -98,6 +179,648 @@ fn asking_for_various_types() {
     assert_eq!(Ok(Term::Bool(true)), eval_no_import(lambda));
 }
 
+#[test]
+fn type_of() {
+    for (value, type_name) in [
+        ("1 + 1", "Num"),
+        ("true", "Bool"),
+        ("\"a\" ++ \"b\"", "Str"),
+        ("{a = 1}", "Record"),
+        ("[1, 2]", "List"),
+        ("fun x => x", "Fun"),
+        ("`foo", "Enum"),
+        ("null", "Null"),
+    ] {
+        assert_eq!(
+            eval_transformed(&format!("%type_of% ({})", value)),
+            Ok(Term::Str(String::from(type_name)))
+        );
+    }
+}
+
+#[test]
+fn str_repeat() {
+    let t = mk_term::op2(
+        BinaryOp::StrRepeat(),
+        Term::Str(String::from("ab")),
+        Term::Num(3.0),
+    );
+    assert_eq!(Ok(Term::Str(String::from("ababab"))), eval_no_import(t));
+
+    let t = mk_term::op2(
+        BinaryOp::StrRepeat(),
+        Term::Str(String::from("ab")),
+        Term::Num(-1.0),
+    );
+    assert_matches!(eval_no_import(t), Err(EvalError::Other(..)));
+
+    let t = mk_term::op2(
+        BinaryOp::StrRepeat(),
+        Term::Str(String::from("ab")),
+        Term::Num(1.5),
+    );
+    assert_matches!(eval_no_import(t), Err(EvalError::Other(..)));
+
+    // Too long results are errors instead of exhausting the memory
+    for n in [1e20, 1e12, f64::INFINITY] {
+        let t = mk_term::op2(
+            BinaryOp::StrRepeat(),
+            Term::Str(String::from("ab")),
+            Term::Num(n),
+        );
+        assert_matches!(eval_no_import(t), Err(EvalError::Other(..)));
+    }
+
+    let t = mk_term::op2(
+        BinaryOp::StrRepeat(),
+        Term::Str(String::new()),
+        Term::Num(1e20),
+    );
+    assert_eq!(Ok(Term::Str(String::new())), eval_no_import(t));
+}
+
+#[test]
+fn str_dedent() {
+    let dedent = |s: &str| eval_no_import(mk_term::op1(UnaryOp::StrDedent(), Term::Str(s.into())));
+    let ok = |s: &str| Ok(Term::Str(String::from(s)));
+
+    assert_eq!(dedent("    foo\n      bar\n    baz"), ok("foo\n  bar\nbaz"));
+    // Blank lines are emptied, and don't count for the common indentation
+    assert_eq!(dedent("  foo\n\t \n\n    bar\n"), ok("foo\n\n\n  bar\n"));
+    // The indentation is compared character by character: a tab is not a sequence of spaces
+    assert_eq!(dedent("\t\tfoo\n\tbar"), ok("\tfoo\nbar"));
+    assert_eq!(dedent("\tfoo\n    bar"), ok("\tfoo\n    bar"));
+    assert_eq!(
+        dedent(" \tfoo\n \t  bar\n  baz"),
+        ok("\tfoo\n\t  bar\n baz")
+    );
+    // Unicode whitespace is handled as well
+    assert_eq!(dedent("\u{3000}foo\n\u{3000}bar"), ok("foo\nbar"));
+    assert_eq!(dedent("foo\n  bar"), ok("foo\n  bar"));
+    assert_eq!(dedent("   "), ok(""));
+
+    assert_matches!(
+        eval_no_import(mk_term::op1(UnaryOp::StrDedent(), Term::Num(1.0))),
+        Err(EvalError::TypeError(..))
+    );
+}
+
+#[test]
+fn enum_tag() {
+    assert_eq!(
+        eval_str("%enum_tag% `foo"),
+        Ok(Term::Str(String::from("foo")))
+    );
+    assert_eq!(
+        eval_str("%enum_tag% (%enum_from_str% \"a:b\")"),
+        Ok(Term::Str(String::from("a:b")))
+    );
+    assert_eq!(
+        eval_str("%enum_tag% `low == %enum_tag% `low"),
+        Ok(Term::Bool(true))
+    );
+    assert_matches!(
+        eval_str("%enum_tag% \"foo\""),
+        Err(EvalError::TypeError(..))
+    );
+}
+
+#[test]
+fn match_field() {
+    let handlers = "{ok = fun x => x + 1, err = fun e => e ++ \"!\"}";
+
+    assert_eq!(
+        eval_transformed(&format!("%match_field% {{ok = 1}} {}", handlers)),
+        Ok(Term::Num(2.0))
+    );
+    assert_eq!(
+        eval_transformed(&format!("%match_field% {{err = \"fail\"}} {}", handlers)),
+        Ok(Term::Str(String::from("fail!")))
+    );
+    assert_matches!(
+        eval_transformed(&format!("%match_field% {{other = 1}} {}", handlers)),
+        Err(EvalError::Other(..))
+    );
+    assert_matches!(
+        eval_transformed(&format!("%match_field% {{}} {}", handlers)),
+        Err(EvalError::Other(..))
+    );
+}
+
+#[test]
+fn record_fields_diff() {
+    for (r1, r2, diff) in [
+        (
+            "{b = 1, a = 2}",
+            "{d = 3, c = 4}",
+            r#"{added = ["c", "d"], removed = ["a", "b"], common = []}"#,
+        ),
+        (
+            "{c = 1, a = 2, b = 3}",
+            "{b = 4, d = 5, c = 6}",
+            r#"{added = ["d"], removed = ["a"], common = ["b", "c"]}"#,
+        ),
+        (
+            "{b = 1, a = 2}",
+            "{a = 3, b = 4}",
+            r#"{added = [], removed = [], common = ["a", "b"]}"#,
+        ),
+        ("{}", "{}", "{added = [], removed = [], common = []}"),
+    ] {
+        assert_eq!(
+            eval_str(&format!("%record_fields_diff% {} {} == {}", r1, r2, diff)),
+            Ok(Term::Bool(true))
+        );
+    }
+
+    assert_matches!(
+        eval_str("%record_fields_diff% {a = 1} [1]"),
+        Err(EvalError::TypeError(..))
+    );
+}
+
+#[test]
+fn division_by_zero() {
+    assert_matches!(eval_str("1 / 0"), Err(EvalError::DivisionByZero(..)));
+    assert_matches!(eval_str("1 % 0"), Err(EvalError::DivisionByZero(..)));
+    assert_matches!(eval_str("0 % (1 - 1)"), Err(EvalError::DivisionByZero(..)));
+    assert_eq!(eval_str("1 % 0.5"), Ok(Term::Num(0.0)));
+}
+
+#[test]
+fn int_div_and_div_mod() {
+    for (n1, n2, quot, rem) in [
+        (7, 2, 3, 1),
+        (-7, 2, -4, 1),
+        (7, -2, -4, -1),
+        (-7, -2, 3, -1),
+        (6, 3, 2, 0),
+        (-6, 3, -2, 0),
+        (0, -5, 0, 0),
+    ] {
+        assert_eq!(
+            eval_str(&format!("%int_div% ({}) ({})", n1, n2)),
+            Ok(Term::Num(quot as f64))
+        );
+        assert_eq!(
+            eval_str(&format!("(%div_mod% ({}) ({})).quot", n1, n2)),
+            Ok(Term::Num(quot as f64))
+        );
+        assert_eq!(
+            eval_str(&format!("(%div_mod% ({}) ({})).rem", n1, n2)),
+            Ok(Term::Num(rem as f64))
+        );
+    }
+
+    assert_matches!(
+        eval_str("%int_div% 1 0"),
+        Err(EvalError::DivisionByZero(..))
+    );
+    assert_matches!(
+        eval_str("%div_mod% 1 0"),
+        Err(EvalError::DivisionByZero(..))
+    );
+}
+
+#[test]
+fn num_to_str() {
+    let assert_str = |s: &str, expected: &str| {
+        assert_eq!(eval_str(s), Ok(Term::Str(String::from(expected))));
+    };
+
+    assert_str("%to_str% 5", "5");
+    assert_str("%to_str% (10 / 2)", "5");
+    assert_str("%to_str% (-42)", "-42");
+    assert_str("%to_str% 0.25", "0.25");
+    assert_str("%to_str% (%pow% 10 (-7))", "0.0000001");
+    assert_str("%to_str% (%pow% 10 21)", "1000000000000000000000");
+    assert_str("%to_str% (%pow% 2 64)", "18446744073709552000");
+
+    assert_str("%num_to_str_fixed% 3.14159 2", "3.14");
+    assert_str("%num_to_str_fixed% 5 3", "5.000");
+    assert_str("%num_to_str_fixed% (-0.5) 0", "-0");
+    assert_str("%num_to_str_fixed% 2.5 0", "2");
+    assert_str(
+        "%num_to_str_fixed% (%pow% 10 21) 1",
+        "1000000000000000000000.0",
+    );
+
+    for decimals in ["(-1)", "1.5", "101"] {
+        assert_matches!(
+            eval_str(&format!("%num_to_str_fixed% 1 {}", decimals)),
+            Err(EvalError::Other(..))
+        );
+    }
+}
+
+#[test]
+fn radix_conversions() {
+    assert_eq!(
+        eval_str("%num_to_str_radix% 255 16"),
+        Ok(Term::Str(String::from("ff")))
+    );
+    assert_eq!(
+        eval_str("%num_to_str_radix% (-5) 2"),
+        Ok(Term::Str(String::from("-101")))
+    );
+    assert_eq!(
+        eval_str("(%str_to_num_radix% (%num_to_str_radix% 48879 16) 16).value"),
+        Ok(Term::Num(48879.0))
+    );
+    assert_eq!(
+        eval_str("(%str_to_num_radix% (%num_to_str_radix% 42 2) 2).value"),
+        Ok(Term::Num(42.0))
+    );
+    assert_eq!(
+        eval_str("(%str_to_num_radix% \"12\" 2).tag"),
+        Ok(Term::Enum(Ident::from("None")))
+    );
+
+    for radix in ["1", "37", "2.5"] {
+        assert_matches!(
+            eval_str(&format!("%num_to_str_radix% 10 {}", radix)),
+            Err(EvalError::Other(..))
+        );
+        assert_matches!(
+            eval_str(&format!("%str_to_num_radix% \"10\" {}", radix)),
+            Err(EvalError::Other(..))
+        );
+    }
+
+    assert_matches!(
+        eval_str("%num_to_str_radix% 1.5 16"),
+        Err(EvalError::NotAnInteger(..))
+    );
+}
+
+#[test]
+fn str_pad() {
+    assert_eq!(
+        eval_str("%str_pad_left% \"42\" 5 \"0\""),
+        Ok(Term::Str(String::from("00042")))
+    );
+
+    assert_matches!(
+        eval_str("%str_pad_left% \"a\" 2.5 \"x\""),
+        Err(EvalError::NotAnInteger(..))
+    );
+    assert_matches!(
+        eval_str("%str_pad_right% \"a\" (-1) \"x\""),
+        Err(EvalError::Other(..))
+    );
+
+    // Too large widths are errors instead of exhausting the memory
+    for op in ["str_pad_left", "str_pad_right"] {
+        assert_matches!(
+            eval_str(&format!("%{}% \"a\" 1000000000000000 \"x\"", op)),
+            Err(EvalError::Other(..))
+        );
+    }
+}
+
+#[test]
+fn signum_of_negative_zero() {
+    let t = mk_term::op1(UnaryOp::Signum(), Term::Num(-0.0));
+    assert_matches!(eval_no_import(t), Ok(Term::Num(n)) if n == 0.0 && n.is_sign_positive());
+}
+
+#[test]
+fn list_sort() {
+    let t = parse(
+        "%sort% [3, 1, 2] (fun x y => if x < y then `Less else if x == y then `Equal else `Greater) \
+            == [1, 2, 3]",
+    )
+    .unwrap();
+    assert_eq!(
+        eval_no_import(crate::transform::transform(t)),
+        Ok(Term::Bool(true))
+    );
+}
+
+#[test]
+fn interpolation_error_position() {
+    let src = r##"let x = 1 in "#{"a"}#{x}""##;
+    let id = Files::new().add("<test>", String::from(src));
+    let t = grammar::TermParser::new()
+        .parse_term(id, lexer::Lexer::new(src))
+        .unwrap();
+
+    // The error should point to the second interpolated expression `x`, and not to the whole
+    // string nor to the definition of `x`.
+    match eval_no_import(t) {
+        Err(EvalError::TypeError(_, _, TermPos::Original(span), _)) => {
+            assert_eq!(
+                (span.start.to_usize(), span.end.to_usize()),
+                (src.len() - 3, src.len() - 2)
+            )
+        }
+        res => panic!("expected a type error, got {:?}", res),
+    }
+}
+
+/// Deserialize `input` in the given format, and return the part of the source pointed at by the
+/// resulting error.
+fn deserialization_error_text(format: &str, input: &str) -> String {
+    let src = format!("%deserialize% `{} \"{}\"", format, input);
+    let id = Files::new().add("<test>", src.clone());
+    let t = grammar::TermParser::new()
+        .parse_term(id, lexer::Lexer::new(&src))
+        .unwrap();
+
+    match eval_no_import(t) {
+        Err(EvalError::DeserializationError(_, _, TermPos::Original(span))) => {
+            String::from(&src[span.start.to_usize()..span.end.to_usize()])
+        }
+        res => panic!("expected a deserialization error, got {:?}", res),
+    }
+}
+
+#[test]
+fn deserialization_error_position() {
+    // The errors should point to the unexpected `x` after the first line of the string
+    assert_eq!(deserialization_error_text("Json", "[1,\n 2, x]"), "x");
+    assert_eq!(deserialization_error_text("Json", "[\n\n  1, x]"), "x");
+    // The columns of JSON and TOML are counted in bytes, and those of YAML in characters
+    assert_eq!(
+        deserialization_error_text("Json", "[1,\n \u{e9}, x]"),
+        "\u{e9}"
+    );
+    assert_eq!(deserialization_error_text("Toml", "a = 1\nb = x"), "x");
+    assert_eq!(
+        deserialization_error_text("Toml", "a = 1\nb = '\u{e9}\u{e9}' x"),
+        "x"
+    );
+    assert_eq!(
+        deserialization_error_text("Yaml", "a: 1\n\u{e9}\u{e9}: b: c"),
+        ":"
+    );
+    assert_eq!(deserialization_error_text("Xml", "<a>\n<b></c></a>"), "c");
+    assert_eq!(deserialization_error_text("Ini", "[s]\na = 1\nb"), "b");
+}
+
+#[test]
+fn list_flatten() {
+    let t = parse("%flatten% [[1, 2], [3], [4, 5]] == [1, 2, 3, 4, 5]").unwrap();
+    assert_eq!(eval_no_import(t), Ok(Term::Bool(true)));
+}
+
+#[test]
+fn list_all_any() {
+    assert_eq!(
+        eval_transformed("%all% [] (fun x => false)"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval_transformed("%any% [] (fun x => true)"),
+        Ok(Term::Bool(false))
+    );
+    assert_eq!(
+        eval_transformed("%all% [1, 2, 3] (fun x => x > 0)"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval_transformed("%any% [1, 2, 3] (fun x => x > 2)"),
+        Ok(Term::Bool(true))
+    );
+    // The predicate is not applied past the first decisive element
+    assert_eq!(
+        eval_transformed("%all% [1, 2] (fun x => if x == 1 then false else 1 + \"a\")"),
+        Ok(Term::Bool(false))
+    );
+    assert_eq!(
+        eval_transformed("%any% [1, 2] (fun x => if x == 1 then true else 1 + \"a\")"),
+        Ok(Term::Bool(true))
+    );
+}
+
+#[test]
+fn list_contains() {
+    assert_eq!(eval_str("%contains% 2 [1, 2, 3]"), Ok(Term::Bool(true)));
+    assert_eq!(eval_str("%contains% 4 [1, 2, 3]"), Ok(Term::Bool(false)));
+    assert_eq!(eval_str("%contains% 1 []"), Ok(Term::Bool(false)));
+    assert_eq!(
+        eval_str("%contains% \"a\" [\"b\", \"a\"]"),
+        Ok(Term::Bool(true))
+    );
+    // Records are compared structurally
+    assert_eq!(
+        eval_str("%contains% {a = 1, b = {c = `C}} [{a = 1}, {b = {c = `C}, a = 1}]"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval_str("%contains% {a = 1, b = 2} [{a = 1}, {a = 1, b = 3}]"),
+        Ok(Term::Bool(false))
+    );
+}
+
+#[test]
+fn list_filter_fold() {
+    assert_eq!(
+        eval_transformed("%filter% [1, 2, 3, 4] (fun x => x > 2) == [3, 4]"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval_transformed("%foldl% [1, 2, 3, 4] (fun acc x => acc + x) 0"),
+        Ok(Term::Num(10.))
+    );
+    assert_eq!(
+        eval_transformed("%foldr% [1, 2, 3, 4] (fun acc x => acc + x) 0"),
+        Ok(Term::Num(10.))
+    );
+    // The accumulator comes first, and the list is folded from the right
+    assert_eq!(
+        eval_transformed("%foldr% [1, 2, 3] (fun acc x => acc @ [x]) [] == [3, 2, 1]"),
+        Ok(Term::Bool(true))
+    );
+}
+
+#[test]
+fn record_filter() {
+    assert_eq!(
+        eval_transformed(
+            "%record_filter% {a = 1, b = 2, c = 3} (fun s x => x != 2) == {a = 1, c = 3}"
+        ),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval_transformed("%record_filter% {a = 1, b = 2} (fun s x => s == \"c\") == {}"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval_transformed("%record_filter% {} (fun s x => 1) == {}"),
+        Ok(Term::Bool(true))
+    );
+}
+
+#[test]
+fn record_ordering_follows_definition() {
+    let records = [
+        (
+            "{c = 3, a = 1, d = 4, b = 2}",
+            ["c", "a", "d", "b"],
+            [3, 1, 4, 2],
+        ),
+        (
+            "{a = 1, b = 2, c = 3, d = 4}",
+            ["a", "b", "c", "d"],
+            [1, 2, 3, 4],
+        ),
+        (
+            "{d = 4, c = 3, b = 2, a = 1}",
+            ["d", "c", "b", "a"],
+            [4, 3, 2, 1],
+        ),
+    ];
+
+    for (r, fields, values) in records {
+        assert_eq!(
+            eval_str(&format!("%fields% {} == {:?}", r, fields)),
+            Ok(Term::Bool(true))
+        );
+        assert_eq!(
+            eval_str(&format!("%values% {} == {:?}", r, values)),
+            Ok(Term::Bool(true))
+        );
+        assert_eq!(
+            eval_str(&format!(
+                "%record_to_list% {} == [{}]",
+                r,
+                fields
+                    .iter()
+                    .zip(values)
+                    .map(|(f, v)| format!("{{field = \"{}\", value = {}}}", f, v))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            Ok(Term::Bool(true))
+        );
+    }
+
+    assert_eq!(
+        eval_str("%fields% ({b = 1, a = 2} & {c = 3, a = 2}) == [\"b\", \"a\", \"c\"]"),
+        Ok(Term::Bool(true))
+    );
+}
+
+#[test]
+fn record_merge_deep() {
+    assert_eq!(
+        eval_str(
+            "%record_merge_deep% {a = {b = {c = 1, d = 2}, e = 3}, f = 4} \
+            {a = {b = {c = 5}, e = {g = 6}}, h = 7} \
+            == {a = {b = {c = 5, d = 2}, e = {g = 6}}, f = 4, h = 7}"
+        ),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval_str("%record_merge_deep% {a = {b = 1}} {a = [1]} == {a = [1]}"),
+        Ok(Term::Bool(true))
+    );
+    // Unlike merge, conflicting base values are not an error
+    assert_eq!(
+        eval_str("(%record_merge_deep% {a = {b = 1}} {a = {b = 2}}).a.b"),
+        Ok(Term::Num(2.))
+    );
+}
+
+#[test]
+fn merge_reverts_recursive_fields() {
+    // The fields depending on an overridden field are evaluated again in the merged record
+    assert_eq!(
+        eval_transformed("({a = b + 1, b | default = 1} & {b = 10}).a"),
+        Ok(Term::Num(11.))
+    );
+    // Including when the original record has already been evaluated
+    assert_eq!(
+        eval_transformed(
+            "let r = {a = b + 1, b | default = 1} in \
+            %seq% r.a (r & {b = 10}).a + r.a"
+        ),
+        Ok(Term::Num(13.))
+    );
+    // Overriding a field propagates transitively
+    assert_eq!(
+        eval_transformed("({a = b + 1, b = c * 2, c | default = 1} & {c = 5}).a"),
+        Ok(Term::Num(11.))
+    );
+}
+
+#[test]
+fn profile_counts_operators() {
+    let t = crate::transform::transform(
+        parse(
+            "let l = %map% [1, 2, 3] (fun x => x + 1) in \
+             %length% (%map% l (fun x => x * 2))",
+        )
+        .unwrap(),
+    );
+    let (result, profile) = eval_profiled(
+        t,
+        &Environment::new(),
+        &mut DummyResolver {},
+        &EvalConfig::default(),
+    )
+    .unwrap();
+
+    assert_eq!(Term::from(result), Term::Num(3.0));
+    assert_eq!(profile.get(&UnaryOp::ListMap()).unwrap().count, 2);
+    assert_eq!(profile.get(&UnaryOp::ListMap()).unwrap().name, "ListMap");
+    assert_eq!(profile.get(&UnaryOp::ListLength()).unwrap().count, 1);
+    // The mapped functions are not applied, as the elements are never forced
+    assert!(profile.get(&BinaryOp::Plus()).is_none());
+    assert!(profile.get(&BinaryOp::Mult()).is_none());
+}
+
+#[test]
+fn memoization() {
+    let eval_memo = |s: &str| {
+        let mut memo = MemoTable::new();
+        let result = eval_closure_with_memo(
+            Closure::atomic_closure(crate::transform::transform(parse(s).unwrap())),
+            &Environment::new(),
+            &mut DummyResolver {},
+            true,
+            &EvalConfig::default(),
+            Some(&mut memo),
+        )
+        .map(|(t, _)| Term::from(t));
+        (result, memo.hits())
+    };
+    let expensive = "%foldl% (%generate% 100 (fun i => i)) (fun acc x => acc + x) 0";
+
+    // The second occurrence reuses the result of the first one
+    assert_eq!(
+        eval_memo(&format!(
+            "let a = {e} in let b = {e} in a + b",
+            e = expensive
+        )),
+        (Ok(Term::Num(9900.)), 1)
+    );
+    // Records with the same fields in a different order are distinct
+    assert_eq!(
+        eval_memo("let a = {x = 1, y = 2} in let b = {y = 2, x = 1} in %seq% a (%fields% b)"),
+        (
+            Ok(Term::List(vec![mk_term::string("y"), mk_term::string("x")])),
+            0
+        )
+    );
+    // Terms depending on the local environment are not memoized
+    assert_eq!(
+        eval_memo("let f = fun x => let a = x + 1 in a in f 1 + f 2"),
+        (Ok(Term::Num(5.)), 0)
+    );
+}
+
+#[test]
+fn min_max_nan() {
+    for op in [BinaryOp::Min(), BinaryOp::Max()] {
+        let t = mk_term::op2(op.clone(), Term::Num(f64::NAN), Term::Num(1.));
+        assert_matches!(eval_no_import(t), Ok(Term::Num(n)) if n.is_nan());
+        let t = mk_term::op2(op, Term::Num(1.), Term::Num(f64::NAN));
+        assert_matches!(eval_no_import(t), Ok(Term::Num(n)) if n.is_nan());
+    }
+}
+
 fn mk_default(t: RichTerm) -> Term {
     use crate::term::MergePriority;
 
@@ -106,6 +829,14 @@ fn mk_default(t: RichTerm) -> Term {
     Term::MetaValue(meta)
 }
 
+fn mk_force(t: RichTerm) -> Term {
+    use crate::term::MergePriority;
+
+    let mut meta = MetaValue::from(t);
+    meta.priority = MergePriority::Force;
+    Term::MetaValue(meta)
+}
+
 fn mk_docstring<S>(t: RichTerm, s: S) -> Term
 where
     S: Into<String>,
@@ -125,17 +856,55 @@ fn enriched_terms_unwrapping() {
 #[test]
 fn merge_enriched_default() {
     let t = mk_term::op2(
-        BinaryOp::Merge(),
+        BinaryOp::Merge(MergePath::default()),
         Term::Num(1.0),
         mk_default(Term::Num(2.0).into()),
     );
     assert_eq!(Ok(Term::Num(1.0)), eval_no_import(t));
 }
 
+#[test]
+fn merge_enriched_force() {
+    let t = mk_term::op2(
+        BinaryOp::Merge(MergePath::default()),
+        mk_force(Term::Num(1.0).into()),
+        Term::Num(2.0),
+    );
+    assert_eq!(Ok(Term::Num(1.0)), eval_no_import(t));
+
+    let t = mk_term::op2(
+        BinaryOp::Merge(MergePath::default()),
+        mk_default(Term::Num(1.0).into()),
+        mk_force(Term::Num(2.0).into()),
+    );
+    assert_eq!(Ok(Term::Num(2.0)), eval_no_import(t));
+}
+
+#[test]
+fn merge_default_loses_to_normal() {
+    let t = mk_term::op2(
+        BinaryOp::Merge(MergePath::default()),
+        mk_default(Term::Num(1.0).into()),
+        Term::Num(2.0),
+    );
+    assert_eq!(Ok(Term::Num(2.0)), eval_no_import(t));
+}
+
+#[test]
+fn merge_incompatible_forces() {
+    let t = mk_term::op2(
+        BinaryOp::Merge(MergePath::default()),
+        mk_force(Term::Num(1.0).into()),
+        mk_force(Term::Num(2.0).into()),
+    );
+
+    eval_no_import(t).unwrap_err();
+}
+
 #[test]
 fn merge_incompatible_defaults() {
     let t = mk_term::op2(
-        BinaryOp::Merge(),
+        BinaryOp::Merge(MergePath::default()),
         mk_default(Term::Num(1.0).into()),
         mk_default(Term::Num(2.0).into()),
     );
@@ -148,6 +917,11 @@ fn imports() {
     let mut resolver = SimpleResolver::new();
     resolver.add_source(String::from("two"), String::from("1 + 1"));
     resolver.add_source(String::from("lib"), String::from("{f = true}"));
+    resolver.add_source(
+        String::from("data.json"),
+        String::from(r#"{"f": {"g": [1, 2]}}"#),
+    );
+    resolver.add_source(String::from("bad.json"), String::from(r#"{"f": }"#));
     resolver.add_source(String::from("bad"), String::from("^$*/.23ab 0°@"));
     resolver.add_source(
         String::from("nested"),
@@ -190,6 +964,29 @@ fn imports() {
         _ => assert!(false),
     };
 
+    // let x = import "bad.json" in x
+    match mk_import("x", "bad.json", mk_term::var("x"), &mut resolver).unwrap_err() {
+        ImportError::ParseErrors(_, _) => (),
+        _ => assert!(false),
+    };
+
+    // let x = import "data.json" in x.f
+    assert_matches!(
+        eval(
+            mk_import(
+                "x",
+                "data.json",
+                mk_term::op1(UnaryOp::StaticAccess(Ident::from("f")), mk_term::var("x")),
+                &mut resolver,
+            )
+            .unwrap(),
+            &Environment::new(),
+            &mut resolver
+        )
+        .map(Term::from),
+        Ok(Term::Record(..))
+    );
+
     // let x = import "two" in x
     assert_eq!(
         eval(
@@ -221,6 +1018,190 @@ fn imports() {
     );
 }
 
+#[test]
+fn virtual_fs_imports() {
+    let mut resolver = VirtualFsResolver::new();
+    resolver
+        .add_file("main.ncl", String::from("import \"lib/a.ncl\""))
+        .unwrap();
+    resolver
+        .add_file(
+            "lib/a.ncl",
+            String::from("let b = import \"sub/b.ncl\" in {value = b.value + 1}"),
+        )
+        .unwrap();
+    resolver
+        .add_file(
+            "lib/sub/b.ncl",
+            String::from("{value = (import \"../../two.ncl\") + (import \"./../c.ncl\")}"),
+        )
+        .unwrap();
+    resolver.add_file("two.ncl", String::from("1 + 1")).unwrap();
+    resolver
+        .add_file("lib/data.toml", String::from("[section]\nvalue = 2"))
+        .unwrap();
+    resolver
+        .add_file(
+            "lib/data_user.ncl",
+            String::from("(import \"data.toml\").section.value"),
+        )
+        .unwrap();
+    resolver
+        .add_file(
+            "lib/raw_user.ncl",
+            String::from("importStr \"../two.ncl\" ++ importStr \"data.toml\""),
+        )
+        .unwrap();
+    resolver
+        .add_file(
+            "./lib/c.ncl",
+            String::from("let f = fun {x} => x in f {x = 3}"),
+        )
+        .unwrap();
+    resolver
+        .add_file(
+            "cycle/a.ncl",
+            String::from("let other = import \"b.ncl\" in {a = 1, b = other.a}"),
+        )
+        .unwrap();
+    resolver
+        .add_file(
+            "cycle/b.ncl",
+            String::from("let other = import \"a.ncl\" in {a = other.a}"),
+        )
+        .unwrap();
+    resolver
+        .add_file("top_level/a.ncl", String::from("(import \"b.ncl\") + 1"))
+        .unwrap();
+    resolver
+        .add_file("top_level/b.ncl", String::from("(import \"c.ncl\") + 1"))
+        .unwrap();
+    resolver
+        .add_file("top_level/c.ncl", String::from("(import \"a.ncl\") + 1"))
+        .unwrap();
+    resolver
+        .add_file("missing.ncl", String::from("import \"does_not_exist.ncl\""))
+        .unwrap();
+    resolver
+        .add_file("bad.ncl", String::from("^$*/.23ab 0°@"))
+        .unwrap();
+    resolver.add_file("x.ncl", String::from("1")).unwrap();
+    resolver
+        .add_file("escape.ncl", String::from("import \"../x.ncl\""))
+        .unwrap();
+    resolver
+        .add_file("lib/escape.ncl", String::from("import \"../../x.ncl\""))
+        .unwrap();
+
+    // (import "main.ncl").value, where main.ncl -> lib/a.ncl -> lib/sub/b.ncl -> {two.ncl,
+    // lib/c.ncl}
+    let t = mk_term::op1(
+        UnaryOp::StaticAccess(Ident::from("value")),
+        mk_term::import("main.ncl"),
+    );
+    let (t, _) = resolve_imports(t, &mut resolver).unwrap();
+    assert_eq!(
+        eval(t, &Environment::new(), &mut resolver).map(Term::from),
+        Ok(Term::Num(6.0))
+    );
+
+    // import "lib/data_user.ncl", which imports lib/data.toml
+    let (t, _) = resolve_imports(mk_term::import("lib/data_user.ncl"), &mut resolver).unwrap();
+    assert_eq!(
+        eval(t, &Environment::new(), &mut resolver).map(Term::from),
+        Ok(Term::Num(2.0))
+    );
+
+    // import "lib/raw_user.ncl", which imports two.ncl and lib/data.toml as strings
+    let (t, _) = resolve_imports(mk_term::import("lib/raw_user.ncl"), &mut resolver).unwrap();
+    assert_eq!(
+        eval(t, &Environment::new(), &mut resolver).map(Term::from),
+        Ok(Term::Str(String::from("1 + 1[section]\nvalue = 2")))
+    );
+
+    // (import "cycle/a.ncl").b
+    let t = mk_term::op1(
+        UnaryOp::StaticAccess(Ident::from("b")),
+        mk_term::import("cycle/a.ncl"),
+    );
+    let (t, _) = resolve_imports(t, &mut resolver).unwrap();
+    assert_eq!(
+        eval(t, &Environment::new(), &mut resolver).map(Term::from),
+        Ok(Term::Num(1.0))
+    );
+
+    // import "top_level/a.ncl", where a.ncl -> b.ncl -> c.ncl -> a.ncl
+    let (t, _) = resolve_imports(mk_term::import("top_level/a.ncl"), &mut resolver).unwrap();
+    match eval(t, &Environment::new(), &mut resolver) {
+        Err(EvalError::CyclicImport(paths, _)) => assert_eq!(
+            paths,
+            vec![
+                "top_level/a.ncl",
+                "top_level/b.ncl",
+                "top_level/c.ncl",
+                "top_level/a.ncl",
+            ]
+        ),
+        res => panic!("expected a cyclic import error, got {:?}", res),
+    }
+
+    assert_matches!(
+        resolve_imports(mk_term::import("does_not_exist.ncl"), &mut resolver),
+        Err(ImportError::IOError(..))
+    );
+    assert_matches!(
+        resolve_imports(mk_term::import("missing.ncl"), &mut resolver),
+        Err(ImportError::IOError(..))
+    );
+    assert_matches!(
+        resolve_imports(mk_term::import("bad.ncl"), &mut resolver),
+        Err(ImportError::ParseErrors(..))
+    );
+
+    // `..` components going above the root are errors, instead of being ignored
+    for path in ["../x.ncl", "escape.ncl", "lib/escape.ncl"] {
+        assert_matches!(
+            resolve_imports(mk_term::import(path), &mut resolver),
+            Err(ImportError::IOError(_, msg, _)) if msg.contains("above the root")
+        );
+    }
+    assert_matches!(
+        resolver.add_file("../y.ncl", String::from("1")),
+        Err(ImportError::IOError(..))
+    );
+}
+
+#[test]
+fn virtual_fs_failed_imports() {
+    let mut resolver = VirtualFsResolver::new();
+    resolver
+        .add_file("bad.ncl", String::from("^$*/.23ab 0°@"))
+        .unwrap();
+    resolver
+        .add_file("uses_bad.ncl", String::from("(import \"bad.ncl\") + 1"))
+        .unwrap();
+
+    // A failed import is not cached, and fails again
+    for _ in 0..2 {
+        assert_matches!(
+            resolve_imports(mk_term::import("bad.ncl"), &mut resolver),
+            Err(ImportError::ParseErrors(..))
+        );
+        assert_matches!(
+            resolve_imports(mk_term::import("uses_bad.ncl"), &mut resolver),
+            Err(ImportError::ParseErrors(..))
+        );
+    }
+
+    // Once fixed, the file is loaded again from scratch
+    resolver.add_file("bad.ncl", String::from("1")).unwrap();
+    let (t, _) = resolve_imports(mk_term::import("uses_bad.ncl"), &mut resolver).unwrap();
+    assert_eq!(
+        eval(t, &Environment::new(), &mut resolver).map(Term::from),
+        Ok(Term::Num(2.0))
+    );
+}
+
 #[test]
 fn interpolation_simple() {
     let mut chunks = vec![
@@ -316,6 +1297,43 @@ fn global_env() {
     );
 }
 
+#[test]
+fn evaluated_global_env() {
+    let mut global_env = Environment::new();
+    let mut resolver = DummyResolver {};
+    global_env.insert(
+        Ident::from("g"),
+        Thunk::new(
+            Closure::atomic_closure(mk_term::op2(
+                BinaryOp::Plus(),
+                Term::Num(1.0),
+                Term::Num(1.0),
+            )),
+            IdentKind::Record,
+        ),
+    );
+    global_env.insert(
+        Ident::from("h"),
+        Thunk::new(
+            Closure::atomic_closure(mk_term::var("g")),
+            IdentKind::Record,
+        ),
+    );
+
+    let evaluated = eval_global_env(&global_env, &mut resolver).unwrap();
+    for id in ["g", "h"] {
+        let thunk = evaluated.get(&Ident::from(id)).unwrap();
+        assert_eq!(thunk.state(), ThunkState::Evaluated);
+        assert_eq!(thunk.borrow().body.as_ref(), &Term::Num(2.0));
+    }
+
+    let t = mk_term::op2(BinaryOp::Plus(), mk_term::var("g"), mk_term::var("h"));
+    assert_eq!(
+        eval(t, &evaluated, &mut resolver).map(Term::from),
+        Ok(Term::Num(4.0))
+    );
+}
+
 fn mk_env(bindings: Vec<(&str, RichTerm)>) -> Environment {
     bindings
         .into_iter()
@@ -353,3 +1371,88 @@ fn substitution() {
         parse("switch {`x => [1, 1], `y => (if false then 1 else \"Glob2\"), `z => {id = true, other = false}} true").unwrap()
     );
 }
+
+#[test]
+fn missing_field_def_modes() {
+    let t = parse("{a | Num, b = 1}.a").unwrap();
+    assert_matches!(
+        eval_no_import(t.clone()),
+        Err(EvalError::MissingFieldDef(..))
+    );
+
+    let config = EvalConfig {
+        missing_field_def: MissingFieldDefMode::Null,
+        ..EvalConfig::default()
+    };
+    assert_eq!(
+        eval_with_config(t, &Environment::new(), &mut DummyResolver {}, &config).map(Term::from),
+        Ok(Term::Null)
+    );
+}
+
+#[test]
+fn record_merge_prefer() {
+    // Contrary to merge, a conflicting field is not an error
+    assert_matches!(
+        eval_str("({a = 1, b = 2} & {a = 3}).a"),
+        Err(EvalError::MergeIncompatibleArgs(..))
+    );
+    assert_eq!(
+        eval_str("(%record_merge_prefer_left% {a = 1, b = 2} {a = 3}).a"),
+        Ok(Term::Num(1.))
+    );
+    assert_eq!(
+        eval_str("(%record_merge_prefer_right% {a = 1, b = 2} {a = 3}).a"),
+        Ok(Term::Num(3.))
+    );
+    assert_eq!(
+        eval_str(
+            "%record_merge_prefer_right% {a = 1, b = 2} {a = 3, c = 4} == {a = 3, b = 2, c = 4}"
+        ),
+        Ok(Term::Bool(true))
+    );
+    // The combination is shallow, while merge is recursive
+    assert_eq!(
+        eval_str("({a = {b = 1}} & {a = {c = 2}}) == {a = {b = 1, c = 2}}"),
+        Ok(Term::Bool(true))
+    );
+    assert_eq!(
+        eval_str("%record_merge_prefer_left% {a = {b = 1}} {a = {c = 2}} == {a = {b = 1}}"),
+        Ok(Term::Bool(true))
+    );
+}
+
+#[test]
+fn step_limit() {
+    let config = EvalConfig {
+        max_steps: Some(10_000),
+        ..EvalConfig::default()
+    };
+
+    let t = crate::transform::transform(parse("let f = {g = fun x => g x}.g in f 1").unwrap());
+    assert_matches!(
+        eval_with_config(t, &Environment::new(), &mut DummyResolver {}, &config),
+        Err(EvalError::StepLimitExceeded(10_000, _))
+    );
+
+    let t = crate::transform::transform(
+        parse("let f = {g = fun n => if n == 0 then 0 else g (n - 1)}.g in f 10").unwrap(),
+    );
+    assert_eq!(
+        eval_with_config(t, &Environment::new(), &mut DummyResolver {}, &config).map(Term::from),
+        Ok(Term::Num(0.0))
+    );
+}
+
+#[test]
+fn drop_long_thunk_chain() {
+    // Each call creates a thunk for `acc + 1` referring to the previous one. Dropping this chain
+    // of unevaluated thunks must not overflow the stack.
+    let t = crate::transform::transform(
+        parse(
+            "let f = {g = fun n acc => if n == 0 then 0 else g (n - 1) (acc + 1)}.g in f 100000 0",
+        )
+        .unwrap(),
+    );
+    assert_eq!(eval_no_import(t), Ok(Term::Num(0.0)));
+}
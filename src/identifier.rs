@@ -76,3 +76,108 @@ impl AsRef<str> for Ident {
         &self.label
     }
 }
+
+/// The maximum number of suggestions returned by [`closest_idents`].
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// Compute the edit distance between two strings, counted in characters. The edit operations
+/// are the insertion, the deletion and the substitution of a character, as well as the
+/// transposition of two adjacent characters, which is a common typo.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // `dist[i][j]` is the distance between the prefixes of length `i` of `a` and `j` of `b`.
+    let mut dist = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in dist.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in dist[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut d = (dist[i - 1][j] + 1)
+                .min(dist[i][j - 1] + 1)
+                .min(dist[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d = d.min(dist[i - 2][j - 2] + 1);
+            }
+
+            dist[i][j] = d;
+        }
+    }
+
+    dist[a.len()][b.len()]
+}
+
+/// Return the identifiers among `candidates` which are the closest to `id` by edit distance, to
+/// be suggested when `id` is unbound.
+///
+/// Only candidates whose distance is small relative to the length of `id` are considered, such
+/// that unrelated names are not suggested. At most [`MAX_SUGGESTIONS`] identifiers are returned,
+/// closest first. Generated identifiers are ignored.
+pub fn closest_idents<'a, I>(id: &Ident, candidates: I) -> Vec<Ident>
+where
+    I: IntoIterator<Item = &'a Ident>,
+{
+    let max_distance = std::cmp::max(1, id.label.chars().count() / 3);
+
+    let mut closest: Vec<(usize, &Ident)> = candidates
+        .into_iter()
+        .filter(|candidate| !candidate.is_generated() && *candidate != id)
+        .map(|candidate| (edit_distance(&id.label, &candidate.label), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+
+    closest.sort();
+    closest.dedup_by(|(_, id1), (_, id2)| id1 == id2);
+
+    closest
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| Ident::from(candidate.label.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("flaw", "lawn"), 2);
+        assert_eq!(edit_distance("héllo", "hello"), 1);
+        assert_eq!(edit_distance("valeu", "value"), 1);
+    }
+
+    #[test]
+    fn suggestions() {
+        let candidates: Vec<Ident> = ["value", "values", "other", "val", "%valu", "separator"]
+            .iter()
+            .map(|s| Ident::from(*s))
+            .collect();
+
+        assert_eq!(
+            closest_idents(&Ident::from("valu"), &candidates),
+            vec![Ident::from("val"), Ident::from("value")]
+        );
+        assert_eq!(
+            closest_idents(&Ident::from("seperater"), &candidates),
+            vec![Ident::from("separator")]
+        );
+        assert_eq!(
+            closest_idents(&Ident::from("x"), &candidates),
+            Vec::<Ident>::new()
+        );
+        // The identifier itself, if bound, is not a suggestion.
+        assert!(!closest_idents(&Ident::from("other"), &candidates).contains(&Ident::from("other")));
+    }
+}
@@ -3,6 +3,7 @@
 //! A label is a value holding metadata relative to contract checking. It gives the user useful
 //! information about the context of a contract failure.
 use crate::eval::lazy::Thunk;
+use crate::identifier::Ident;
 use crate::position::{RawSpan, TermPos};
 use crate::types::{AbsType, Types};
 use codespan::Files;
@@ -246,6 +247,8 @@ pub struct Label {
     pub polarity: bool,
     /// The path of the type being currently checked in the original type.
     pub path: ty_path::Path,
+    /// The name of the record field whose definition the contract is attached to, if any.
+    pub field_name: Option<Ident>,
 }
 
 impl Label {
@@ -263,6 +266,7 @@ impl Label {
             arg_pos: TermPos::None,
             polarity: true,
             path: Vec::new(),
+            field_name: None,
         }
     }
 }
@@ -7,6 +7,7 @@ pub mod identifier;
 pub mod label;
 pub mod parser;
 pub mod position;
+pub mod pretty;
 pub mod program;
 pub mod repl;
 pub mod serialize;
@@ -15,3 +16,5 @@ pub mod term;
 pub mod transform;
 pub mod typecheck;
 pub mod types;
+#[cfg(feature = "wasm-eval")]
+pub mod wasm_eval;
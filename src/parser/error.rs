@@ -9,6 +9,8 @@ pub enum LexicalError {
     InvalidEscapeSequence(usize),
     /// Invalid escape ASCII code in a string literal.
     InvalidAsciiEscapeCode(usize),
+    /// An integer literal which can't be represented exactly as a number.
+    InexactIntegerLiteral(usize, usize),
     /// Generic lexer error
     Generic(usize, usize),
 }
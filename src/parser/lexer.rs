@@ -130,6 +130,8 @@ pub enum NormalToken<'input> {
     Fun,
     #[token("import")]
     Import,
+    #[token("importStr")]
+    ImportStr,
     #[token("|")]
     Pipe,
     #[token("|>")]
@@ -161,6 +163,10 @@ pub enum NormalToken<'input> {
     IsList,
     #[token("%is_record%")]
     IsRecord,
+    #[token("%is_null%")]
+    IsNull,
+    #[token("%type_of%")]
+    TypeOf,
 
     #[token("%assume%")]
     Assume,
@@ -185,6 +191,8 @@ pub enum NormalToken<'input> {
     Unwrap,
     #[token("%embed%")]
     Embed,
+    #[token("%record_insert%")]
+    RecordInsert,
     #[token("%record_map%")]
     RecordMap,
     #[token("%seq%")]
@@ -195,27 +203,113 @@ pub enum NormalToken<'input> {
     Head,
     #[token("%tail%")]
     Tail,
+    #[token("%last%")]
+    Last,
+    #[token("%init%")]
+    Init,
+    #[token("%reverse%")]
+    Reverse,
+    #[token("%flatten%")]
+    Flatten,
+    #[token("%take%")]
+    Take,
+    #[token("%drop%")]
+    Drop,
+    #[token("%all%")]
+    All,
+    #[token("%any%")]
+    Any,
+    #[token("%contains%")]
+    Contains,
     #[token("%length%")]
     Length,
+    #[token("%sum%")]
+    Sum,
     #[token("%fields%")]
     FieldsOf,
     #[token("%values%")]
     ValuesOf,
+    #[token("%record_to_list%")]
+    RecordToList,
+    #[token("%elide_nulls%")]
+    ElideNulls,
+    #[token("%elide_all_nulls%")]
+    ElideAllNulls,
     #[token("%pow%")]
     Pow,
+    #[token("%int_div%")]
+    IntDiv,
+    #[token("%div_mod%")]
+    DivMod,
+    #[token("%min%")]
+    Min,
+    #[token("%max%")]
+    Max,
+    #[token("%floor%")]
+    Floor,
+    #[token("%ceil%")]
+    Ceil,
+    #[token("%round%")]
+    Round,
+    #[token("%abs%")]
+    Abs,
+    #[token("%signum%")]
+    Signum,
+    #[token("%sqrt%")]
+    Sqrt,
+    #[token("%exp%")]
+    Exp,
+    #[token("%ln%")]
+    Ln,
+    #[token("%log10%")]
+    Log10,
 
     #[token("%has_field%")]
     HasField,
+    #[token("%match_field%")]
+    MatchField,
+    #[token("%record_fields_diff%")]
+    RecordFieldsDiff,
+    #[token("%record_filter%")]
+    RecordFilter,
+    #[token("%record_project%")]
+    RecordProject,
+    #[token("%record_merge_deep%")]
+    RecordMergeDeep,
+    #[token("%record_merge_prefer_left%")]
+    RecordMergePreferLeft,
+    #[token("%record_merge_prefer_right%")]
+    RecordMergePreferRight,
     #[token("%map%")]
     Map,
+    #[token("%sort%")]
+    Sort,
     #[token("%elem_at%")]
     ElemAt,
     #[token("%generate%")]
     ListGen,
+    #[token("%range%")]
+    ListRange,
+    #[token("%range_step%")]
+    ListRangeStep,
+    #[token("%filter%")]
+    ListFilter,
+    #[token("%foldl%")]
+    ListFoldl,
+    #[token("%foldr%")]
+    ListFoldr,
+    #[token("%merge_by_key%")]
+    MergeByKey,
+    #[token("%record_set_path%")]
+    RecordSetPath,
+    #[token("%record_get_path_or%")]
+    RecordGetPathOr,
     #[token("merge")]
     Merge,
     #[token("default")]
     Default,
+    #[token("force")]
+    Force,
     #[token("doc")]
     Doc,
 
@@ -227,10 +321,28 @@ pub enum NormalToken<'input> {
     Deserialize,
     #[token("%str_split%")]
     StrSplit,
+    #[token("%str_split_once%")]
+    StrSplitOnce,
+    #[token("%str_split_n%")]
+    StrSplitN,
+    #[token("%str_unlines%")]
+    StrUnlines,
+    #[token("%str_join%")]
+    StrJoin,
     #[token("%str_trim%")]
     StrTrim,
+    #[token("%str_trim_start%")]
+    StrTrimStart,
+    #[token("%str_trim_end%")]
+    StrTrimEnd,
+    #[token("%str_trim_chars%")]
+    StrTrimChars,
     #[token("%str_chars%")]
     StrChars,
+    #[token("%str_lines%")]
+    StrLines,
+    #[token("%str_dedent%")]
+    StrDedent,
     #[token("%char_code%")]
     CharCode,
     #[token("%char_from_code%")]
@@ -239,26 +351,70 @@ pub enum NormalToken<'input> {
     StrUppercase,
     #[token("%str_lowercase%")]
     StrLowercase,
+    #[token("%base64_encode%")]
+    Base64Encode,
+    #[token("%base64_decode%")]
+    Base64Decode,
+    #[token("%hex_encode%")]
+    HexEncode,
+    #[token("%hex_decode%")]
+    HexDecode,
     #[token("%str_contains%")]
     StrContains,
+    #[token("%str_repeat%")]
+    StrRepeat,
+    #[token("%str_starts_with%")]
+    StrStartsWith,
+    #[token("%str_ends_with%")]
+    StrEndsWith,
+    #[token("%str_contains_ci%")]
+    StrContainsCaseInsensitive,
+    #[token("%str_starts_with_ci%")]
+    StrStartsWithCaseInsensitive,
+    #[token("%str_ends_with_ci%")]
+    StrEndsWithCaseInsensitive,
+    #[token("%str_eq_ci%")]
+    StrEqCaseInsensitive,
     #[token("%str_replace%")]
     StrReplace,
     #[token("%str_replace_regex%")]
     StrReplaceRegex,
+    #[token("%str_replace_n%")]
+    StrReplaceN,
     #[token("%str_is_match%")]
     StrIsMatch,
     #[token("%str_match%")]
     StrMatch,
+    #[token("%str_match_all%")]
+    StrMatchAll,
     #[token("%str_length%")]
     StrLength,
     #[token("%str_substr%")]
     StrSubstr,
+    #[token("%str_pad_left%")]
+    StrPadLeft,
+    #[token("%str_pad_right%")]
+    StrPadRight,
+    #[token("%serialize_with%")]
+    SerializeWith,
+    #[token("%hmac%")]
+    Hmac,
     #[token("%to_str%")]
     ToStr,
     #[token("%num_from_str%")]
     NumFromStr,
     #[token("%enum_from_str%")]
     EnumFromStr,
+    #[token("%enum_tag%")]
+    EnumTag,
+    #[token("%enum_from_str_checked%")]
+    EnumFromStrChecked,
+    #[token("%num_to_str_radix%")]
+    NumToStrRadix,
+    #[token("%num_to_str_fixed%")]
+    NumToStrFixed,
+    #[token("%str_to_num_radix%")]
+    StrToNumRadix,
 
     #[token("{")]
     LBrace,
@@ -372,7 +528,7 @@ impl<'input> Iterator for ModalLexer<'input> {
     }
 }
 
-// Wrap the `span()` function of the underlying lexer.
+// Wrap the `span()` and `source()` functions of the underlying lexer.
 impl<'input> ModalLexer<'input> {
     pub fn span(&self) -> std::ops::Range<usize> {
         match self {
@@ -381,6 +537,14 @@ impl<'input> ModalLexer<'input> {
             ModalLexer::MultiStr(lexer) => lexer.span(),
         }
     }
+
+    pub fn source(&self) -> &'input str {
+        match self {
+            ModalLexer::Normal(lexer) => lexer.source(),
+            ModalLexer::Str(lexer) => lexer.source(),
+            ModalLexer::MultiStr(lexer) => lexer.source(),
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Copy)]
@@ -539,6 +703,16 @@ impl<'input> Iterator for Lexer<'input> {
                 self.enter_indstr(*hash_count)
             }
             Some(Normal(NormalToken::LBrace)) => self.count += 1,
+            // Numbers are floating point numbers: reject the integer literals which would be
+            // silently rounded
+            Some(Normal(NormalToken::NumLiteral(n))) => {
+                let literal = &self.lexer.as_ref().unwrap().source()[span.clone()];
+                if !is_exact_literal(literal, *n) {
+                    return Some(Err(ParseError::Lexical(
+                        LexicalError::InexactIntegerLiteral(span.start, span.end),
+                    )));
+                }
+            }
             Some(Normal(NormalToken::RBrace)) => {
                 if self.count == 0 {
                     if self.stack.is_empty() {
@@ -643,6 +817,19 @@ impl<'input> Iterator for Lexer<'input> {
     }
 }
 
+/// Determine if the value of a number literal is exactly the number it denotes. Literals with a
+/// fractional part are always considered exact, as most decimal fractions can't be represented as
+/// floating point numbers anyway: only integer literals are checked.
+fn is_exact_literal(literal: &str, value: f64) -> bool {
+    if literal.contains('.') {
+        return true;
+    }
+
+    let digits = literal.trim_start_matches('0');
+    // Floats are formatted with all their digits, without rounding
+    format!("{:.0}", value) == if digits.is_empty() { "0" } else { digits }
+}
+
 /// Generate the character corresponding to an escape char.
 fn escape_char(chr: char) -> Option<char> {
     match chr {
@@ -346,6 +346,31 @@ fn ascii_escape() {
     assert_eq!(parse_without_pos("m#\"\\x7F\"#m"), mk_single_chunk("\\x7F"));
 }
 
+#[test]
+fn inexact_integer_literals() {
+    assert_matches!(
+        parse("9007199254740993"),
+        Err(ParseError::InexactIntegerLiteral(..))
+    );
+    assert_matches!(
+        parse("[1, 100000000000000000000000000000]"),
+        Err(ParseError::InexactIntegerLiteral(..))
+    );
+
+    assert_eq!(
+        parse_without_pos("9007199254740992"),
+        Num(9007199254740992.0).into()
+    );
+    assert_eq!(
+        parse_without_pos("4611686018427387904"),
+        Num(4611686018427387904.0).into()
+    );
+    assert_eq!(parse_without_pos("007"), Num(7.0).into());
+    assert_eq!(parse_without_pos("0"), Num(0.0).into());
+    // Fractional literals are rounded as usual
+    assert_eq!(parse_without_pos("0.1"), Num(0.1).into());
+}
+
 /// Regression test for [#230](https://github.com/tweag/nickel/issues/230).
 #[test]
 fn multiline_str_escape() {
@@ -1,7 +1,7 @@
 //! Various helpers and companion code for the parser are put here to keep the grammar definition
 //! uncluttered.
-use std::collections::hash_map::Entry;
-use std::collections::{HashMap, HashSet};
+use indexmap::map::Entry;
+use std::collections::HashSet;
 use std::fmt::Debug;
 
 use codespan::FileId;
@@ -12,7 +12,10 @@ use crate::{
     mk_app, mk_fun,
     parser::error::ParseError,
     position::{RawSpan, TermPos},
-    term::{make as mk_term, BinaryOp, MetaValue, RecordAttrs, RichTerm, StrChunk, Term, UnaryOp},
+    term::{
+        make as mk_term, BinaryOp, IndexMap, MergePath, MetaValue, RecordAttrs, RichTerm,
+        SharedTerm, StrChunk, Term, UnaryOp,
+    },
     types::{AbsType, Types},
 };
 
@@ -139,7 +142,8 @@ pub fn elaborate_field_path(
 
     let content = it.rev().fold(content, |acc, path_elem| match path_elem {
         FieldPathElem::Ident(id) => {
-            let mut map = HashMap::new();
+            let mut map = IndexMap::new();
+            let acc = attach_field_name(acc, &id);
             map.insert(id, acc);
             Term::Record(map, Default::default()).into()
         }
@@ -165,11 +169,12 @@ pub fn elaborate_field_path(
                     pos: exp.pos,
                 };
 
-                let mut map = HashMap::new();
+                let mut map = IndexMap::new();
+                let acc = attach_field_name(acc, &id);
                 map.insert(id, acc);
                 Term::Record(map, Default::default()).into()
             } else {
-                let empty = Term::Record(HashMap::new(), Default::default());
+                let empty = Term::Record(IndexMap::new(), Default::default());
                 mk_app!(mk_term::op2(BinaryOp::DynExtend(), exp, empty), acc)
             }
         }
@@ -184,10 +189,12 @@ pub fn build_record<I>(fields: I, attrs: RecordAttrs) -> Term
 where
     I: IntoIterator<Item = (FieldPathElem, RichTerm)> + Debug,
 {
-    let mut static_map = HashMap::new();
+    let mut static_map = IndexMap::new();
     let mut dynamic_fields = Vec::new();
 
-    fn insert_static_field(static_map: &mut HashMap<Ident, RichTerm>, id: Ident, t: RichTerm) {
+    fn insert_static_field(static_map: &mut IndexMap<Ident, RichTerm>, id: Ident, t: RichTerm) {
+        let t = attach_field_name(t, &id);
+
         match static_map.entry(id) {
             Entry::Occupied(mut occpd) => {
                 // temporary putting null in the entry to take the previous value.
@@ -251,6 +258,18 @@ where
     Term::RecRecord(static_map, dynamic_fields, attrs)
 }
 
+/// Record the name of a field in the labels of the contracts attached to its definition, so that
+/// blame errors can tell which field failed.
+fn attach_field_name(mut rt: RichTerm, id: &Ident) -> RichTerm {
+    if let Term::MetaValue(meta) = SharedTerm::make_mut(&mut rt.term) {
+        for ctr in meta.types.iter_mut().chain(meta.contracts.iter_mut()) {
+            ctr.label.field_name = Some(id.clone());
+        }
+    }
+
+    rt
+}
+
 /// Merge two fields by performing the merge of both their value and MetaValue if any.
 fn merge_field(rterm1: RichTerm, rterm2: RichTerm) -> Option<RichTerm> {
     let term1 = if let Term::MetaValue(meta) = &*rterm1.term {
@@ -266,7 +285,7 @@ fn merge_field(rterm1: RichTerm, rterm2: RichTerm) -> Option<RichTerm> {
     };
 
     let new_value = match (term1.1, term2.1) {
-        (Some(t1), Some(t2)) => Some(mk_term::op2(BinaryOp::Merge(), t1, t2)),
+        (Some(t1), Some(t2)) => Some(mk_term::op2(BinaryOp::Merge(MergePath::default()), t1, t2)),
         (Some(t), None) | (None, Some(t)) => Some(t),
         (None, None) => None,
     };
@@ -309,6 +328,7 @@ pub fn mk_label(types: Types, src_id: FileId, l: usize, r: usize) -> Label {
         arg_pos: TermPos::None,
         polarity: true,
         path: Vec::new(),
+        field_name: None,
     }
 }
 
@@ -0,0 +1,948 @@
+//! Pretty-printing of Nickel source code.
+//!
+//! [`format`] renders a term back to Nickel source with a canonical layout:
+//!
+//! - records and `switch` cases have one entry per line,
+//! - lists stay on a single line, unless they don't fit in [`MAX_WIDTH`] columns, in which case
+//!   they have one element per line,
+//! - each `let ... in` binding starts a new line, and its body is aligned with it.
+//!
+//! Parentheses are inserted only where precedence requires them, so that parsing the output gives
+//! back the original term, up to positions. Comments and the original layout are not part of the
+//! AST, and are thus not preserved.
+//!
+//! Some terms can't be written in the source syntax, as they are only introduced during program
+//! transformations or evaluation (symbols, labels, resolved imports, and so on). They are rendered
+//! using [`Term::shallow_repr`], and the output doesn't parse back to them.
+use crate::destruct::{Destruct, Match};
+use crate::identifier::Ident;
+use crate::parser::lexer::{Lexer, NormalToken, Token};
+use crate::term::{
+    BinaryOp, IndexMap, MergePriority, MetaValue, NAryOp, RichTerm, StrChunk, Term, UnaryOp,
+};
+use crate::types::{AbsType, Types};
+use codespan::ByteIndex;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// The maximum width of a list rendered on a single line, including indentation.
+pub const MAX_WIDTH: usize = 80;
+
+/// The number of spaces of one level of indentation.
+const INDENT: usize = 2;
+
+/// The precedence level of a term, from the tightest to the loosest. A term can be used as is
+/// where a term of the same or of a looser level is expected, and must be parenthesized otherwise.
+type Prec = u8;
+
+/// Literals, variables, records, lists and parenthesized terms.
+const ATOM: Prec = 0;
+/// Record operations (accesses, field removals and extensions) and atoms.
+const OPERAND: Prec = 1;
+/// Applications of functions and of prefix operators.
+const APP: Prec = 2;
+/// Annotated terms, such as `value : Num | doc "..."`.
+const ANNOTATED: Prec = infix(10) + 1;
+/// Terms extending as far as possible to the right: `let`, `fun`, `if` and `switch`.
+const ROOT: Prec = ANNOTATED + 1;
+
+/// The precedence of infix operators, from `1` for the unary minus to `10` for `||`.
+const fn infix(level: u8) -> Prec {
+    APP + level
+}
+
+/// Render a term as Nickel source code.
+pub fn format(term: &RichTerm) -> String {
+    fmt_term(term, ROOT, 0)
+}
+
+/// Render `rt` where a term of precedence `max` is expected. `indent` is the indentation of the
+/// line the term starts on, which is used for the following lines.
+fn fmt_term(rt: &RichTerm, max: Prec, indent: usize) -> String {
+    let (prec, s) = fmt_prec(rt, indent);
+
+    if prec > max {
+        format!("({})", s)
+    } else {
+        s
+    }
+}
+
+/// Render `rt` and return its precedence.
+fn fmt_prec(rt: &RichTerm, indent: usize) -> (Prec, String) {
+    let term = rt.term.as_ref();
+
+    let rendered = match term {
+        Term::Null => Some((ATOM, String::from("null"))),
+        Term::Bool(b) => Some((ATOM, b.to_string())),
+        // A negative literal is parsed back as a subtraction, which binds like the unary minus
+        Term::Num(n) if n.is_sign_negative() => Some((infix(1), n.to_string())),
+        Term::Num(n) => Some((ATOM, n.to_string())),
+        Term::Str(s) => Some((ATOM, fmt_static_str(s))),
+        Term::StrChunks(chunks) => Some((ATOM, fmt_str(chunks.iter().rev(), indent))),
+        Term::Fun(..) | Term::FunPattern(..) => Some((ROOT, fmt_fun(rt, indent))),
+        Term::Let(..) | Term::LetPattern(..) => fmt_let(term, indent).map(|s| (ROOT, s)),
+        Term::App(t1, t2) => Some(fmt_app(t1, t2, indent)),
+        Term::Var(id) => Some((ATOM, id.to_string())),
+        Term::Enum(id) => Some((ATOM, format!("`{}", fmt_ident(id)))),
+        Term::Record(fields, attrs) => Some((ATOM, fmt_record(fields, &[], attrs.open, indent))),
+        Term::RecRecord(fields, dyn_fields, attrs) => {
+            Some((ATOM, fmt_record(fields, dyn_fields, attrs.open, indent)))
+        }
+        Term::Switch(exp, cases, default) => {
+            Some((ROOT, fmt_switch(exp, cases, default.as_ref(), indent)))
+        }
+        Term::List(elts) => Some((ATOM, fmt_list(elts, indent))),
+        Term::Op1(op, t) => fmt_op1(op, t, indent),
+        Term::Op2(op, t1, t2) => fmt_op2(op, t1, t2, indent),
+        Term::OpN(op, args) => fmt_opn(op, args, indent),
+        Term::MetaValue(meta) => match (&meta.value, fmt_annots(meta, indent)) {
+            (Some(value), annots) if !annots.is_empty() => Some((
+                ANNOTATED,
+                format!("{}{}", fmt_term(value, infix(10), indent), annots),
+            )),
+            (Some(value), _) => Some(fmt_prec(value, indent)),
+            (None, _) => None,
+        },
+        Term::Import(path) => Some((
+            APP,
+            format!("import {}", fmt_static_str(&path.to_string_lossy())),
+        )),
+        Term::ImportStr(path) => Some((
+            APP,
+            format!("importStr {}", fmt_static_str(&path.to_string_lossy())),
+        )),
+        Term::Lbl(_)
+        | Term::Sym(_)
+        | Term::Wrapped(..)
+        | Term::ResolvedImport(_)
+        | Term::ParseError => None,
+    };
+
+    rendered.unwrap_or_else(|| (ATOM, term.shallow_repr()))
+}
+
+/// Render a string literal without interpolated expressions.
+fn fmt_static_str(s: &str) -> String {
+    fmt_str(std::iter::once(&StrChunk::Literal(String::from(s))), 0)
+}
+
+/// Render the chunks of a string, given in their order of appearance, as a string literal.
+fn fmt_str<'a, I>(chunks: I, indent: usize) -> String
+where
+    I: Iterator<Item = &'a StrChunk<RichTerm>>,
+{
+    let mut out = String::from("\"");
+
+    for chunk in chunks {
+        match chunk {
+            StrChunk::Literal(s) => {
+                let mut chars = s.chars().peekable();
+
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => out.push_str("\\\""),
+                        '\\' => out.push_str("\\\\"),
+                        '#' if chars.peek() == Some(&'{') => out.push_str("\\#"),
+                        '\n' => out.push_str("\\n"),
+                        '\r' => out.push_str("\\r"),
+                        '\t' => out.push_str("\\t"),
+                        c if c.is_ascii_control() => write!(out, "\\x{:02x}", c as u32).unwrap(),
+                        c => out.push(c),
+                    }
+                }
+            }
+            StrChunk::Expr(t, _) => write!(out, "#{{{}}}", fmt_term(t, ROOT, indent)).unwrap(),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+/// Render an identifier used as a record field, an enum tag or a switch case, which is quoted if
+/// it isn't a valid identifier.
+fn fmt_ident(id: &Ident) -> String {
+    let is_identifier = matches!(
+        Lexer::new(&id.label).collect::<Vec<_>>().as_slice(),
+        [Ok((0, Token::Normal(NormalToken::Identifier(_)), end))] if *end == id.label.len()
+    );
+
+    if is_identifier {
+        id.label.clone()
+    } else {
+        fmt_static_str(&id.label)
+    }
+}
+
+/// The key to sort record fields and switch cases, which puts them in their order of appearance in
+/// the source, if known.
+fn source_order(id: &Ident) -> (Option<ByteIndex>, &str) {
+    (id.pos.into_opt().map(|span| span.start), &id.label)
+}
+
+fn indentation(indent: usize) -> String {
+    " ".repeat(indent)
+}
+
+/// Lay out the comma-separated `items` between `open` and `close`. They are put on a single line
+/// if `inline` is true and if they fit, and one per line otherwise.
+fn fmt_delimited(
+    open: &str,
+    close: &str,
+    items: Vec<String>,
+    indent: usize,
+    inline: bool,
+) -> String {
+    if items.is_empty() {
+        return format!("{}{}", open, close);
+    }
+
+    let single_line = format!("{}{}{}", open, items.join(", "), close);
+    if inline && !single_line.contains('\n') && indent + single_line.chars().count() <= MAX_WIDTH {
+        return single_line;
+    }
+
+    let item_indent = indentation(indent + INDENT);
+    let items: Vec<String> = items
+        .into_iter()
+        .map(|item| format!("{}{}", item_indent, item))
+        .collect();
+
+    format!(
+        "{}\n{}\n{}{}",
+        open,
+        items.join(",\n"),
+        indentation(indent),
+        close
+    )
+}
+
+fn fmt_list(elts: &[RichTerm], indent: usize) -> String {
+    let items = elts
+        .iter()
+        .map(|t| fmt_term(t, ROOT, indent + INDENT))
+        .collect();
+
+    fmt_delimited("[", "]", items, indent, true)
+}
+
+fn fmt_record(
+    fields: &IndexMap<Ident, RichTerm>,
+    dyn_fields: &[(RichTerm, RichTerm)],
+    open: bool,
+    indent: usize,
+) -> String {
+    let field_indent = indent + INDENT;
+
+    let mut entries: Vec<(Option<ByteIndex>, String)> = fields
+        .iter()
+        .map(|(id, t)| {
+            (
+                id.pos.into_opt().map(|span| span.start),
+                fmt_field(fmt_ident(id), t, field_indent),
+            )
+        })
+        .collect();
+
+    // Dynamic fields are sorted with the static ones by their position, and are put last
+    // otherwise
+    let dyn_entries = dyn_fields.iter().map(|(name, t)| {
+        let name_str = match name.term.as_ref() {
+            Term::StrChunks(_) => fmt_term(name, ATOM, field_indent),
+            _ => format!("\"#{{{}}}\"", fmt_term(name, ROOT, field_indent)),
+        };
+
+        (
+            name.pos.into_opt().map(|span| span.start),
+            fmt_field(name_str, t, field_indent),
+        )
+    });
+
+    for (pos, entry) in dyn_entries {
+        let index = match pos {
+            Some(pos) => entries
+                .iter()
+                .position(|(other, _)| matches!(other, Some(other) if *other > pos))
+                .unwrap_or(entries.len()),
+            None => entries.len(),
+        };
+        entries.insert(index, (pos, entry));
+    }
+
+    let mut items: Vec<String> = entries.into_iter().map(|(_, entry)| entry).collect();
+    if open {
+        items.push(String::from(".."));
+    }
+
+    fmt_delimited("{", "}", items, indent, false)
+}
+
+/// Render a record field. Fields defined by a path, as in `{a.b.c = 1}`, are parsed as nested
+/// records with a single field and are rendered back as a path.
+fn fmt_field(name: String, value: &RichTerm, indent: usize) -> String {
+    match value.term.as_ref() {
+        Term::Record(fields, attrs) if fields.len() == 1 && !attrs.open => {
+            let (id, t) = fields.iter().next().unwrap();
+            fmt_field(format!("{}.{}", name, fmt_ident(id)), t, indent)
+        }
+        Term::MetaValue(meta) => {
+            let annots = fmt_annots(meta, indent);
+
+            match &meta.value {
+                Some(t) => format!("{}{} = {}", name, annots, fmt_term(t, ROOT, indent)),
+                None => format!("{}{}", name, annots),
+            }
+        }
+        _ => format!("{} = {}", name, fmt_term(value, ROOT, indent)),
+    }
+}
+
+/// Render the annotations of a metavalue, each one preceded by a space.
+fn fmt_annots(meta: &MetaValue, indent: usize) -> String {
+    let mut out = String::new();
+
+    if let Some(ctr) = &meta.types {
+        write!(out, " : {}", fmt_types(&ctr.types, indent)).unwrap();
+    }
+
+    for ctr in meta.contracts.iter() {
+        write!(out, " | {}", fmt_types(&ctr.types, indent)).unwrap();
+    }
+
+    match meta.priority {
+        MergePriority::Default => out.push_str(" | default"),
+        MergePriority::Force => out.push_str(" | force"),
+        MergePriority::Normal => (),
+    }
+
+    if let Some(doc) = &meta.doc {
+        write!(out, " | doc {}", fmt_static_str(doc)).unwrap();
+    }
+
+    out
+}
+
+fn fmt_types(ty: &Types, indent: usize) -> String {
+    match &ty.0 {
+        AbsType::Dyn() => String::from("Dyn"),
+        AbsType::Num() => String::from("Num"),
+        AbsType::Bool() => String::from("Bool"),
+        AbsType::Str() => String::from("Str"),
+        AbsType::Sym() => String::from("Sym"),
+        AbsType::List(elt) if elt.0 == AbsType::Dyn() => String::from("List"),
+        AbsType::List(elt) => format!("List {}", fmt_subtype(elt, indent)),
+        AbsType::Flat(t) => format!("#{}", fmt_term(t, OPERAND, indent)),
+        AbsType::Var(id) => id.to_string(),
+        AbsType::Forall(..) => {
+            let mut vars = Vec::new();
+            let mut body = ty;
+
+            while let Types(AbsType::Forall(var, ty)) = body {
+                vars.push(var.to_string());
+                body = ty;
+            }
+
+            format!("forall {}. {}", vars.join(" "), fmt_types(body, indent))
+        }
+        AbsType::Enum(row) => format!("<{}>", fmt_row(row, indent)),
+        AbsType::StaticRecord(row) => format!("{{{}}}", fmt_row(row, indent)),
+        AbsType::DynRecord(ty) => format!("{{_: {}}}", fmt_types(ty, indent)),
+        AbsType::RowEmpty() | AbsType::RowExtend(..) => fmt_row(ty, indent),
+        AbsType::Arrow(dom, codom) => {
+            let codom = match codom.0 {
+                AbsType::Forall(..) => format!("({})", fmt_types(codom, indent)),
+                _ => fmt_types(codom, indent),
+            };
+
+            format!("{} -> {}", fmt_subtype(dom, indent), codom)
+        }
+    }
+}
+
+/// Render a type which is not allowed to be an arrow or a polymorphic type without parentheses.
+fn fmt_subtype(ty: &Types, indent: usize) -> String {
+    match ty.0 {
+        AbsType::Arrow(..) | AbsType::Forall(..) => format!("({})", fmt_types(ty, indent)),
+        _ => fmt_types(ty, indent),
+    }
+}
+
+/// Render the rows of an enum or of a record type, without the delimiters.
+fn fmt_row(row: &Types, indent: usize) -> String {
+    let mut rows = Vec::new();
+    let mut tail = None;
+    let mut current = row;
+
+    loop {
+        match &current.0 {
+            AbsType::RowExtend(id, Some(ty), rest) => {
+                rows.push(format!("{}: {}", fmt_ident(id), fmt_types(ty, indent)));
+                current = rest;
+            }
+            AbsType::RowExtend(id, None, rest) => {
+                rows.push(fmt_ident(id));
+                current = rest;
+            }
+            AbsType::RowEmpty() => break,
+            _ => {
+                tail = Some(fmt_types(current, indent));
+                break;
+            }
+        }
+    }
+
+    match tail {
+        Some(tail) if rows.is_empty() => format!("| {}", tail),
+        Some(tail) => format!("{} | {}", rows.join(", "), tail),
+        None => rows.join(", "),
+    }
+}
+
+/// Render the pattern of a `let` or of a function argument.
+fn fmt_pattern(id: Option<&Ident>, pat: &Destruct, indent: usize) -> String {
+    let (matches, open, rest) = match pat {
+        Destruct::Empty => return id.map(Ident::to_string).unwrap_or_default(),
+        Destruct::Record(matches, open, rest) => (matches, *open, rest.as_ref()),
+        Destruct::List(matches) => (matches, false, None),
+    };
+
+    let mut items: Vec<String> = matches.iter().map(|m| fmt_match(m, indent)).collect();
+    if open {
+        items.push(format!(
+            "..{}",
+            rest.map(Ident::to_string).unwrap_or_default()
+        ));
+    }
+
+    match id {
+        Some(id) => format!("{} @ {{{}}}", id, items.join(", ")),
+        None => format!("{{{}}}", items.join(", ")),
+    }
+}
+
+fn fmt_match(m: &Match, indent: usize) -> String {
+    match m {
+        Match::Simple(id, meta) => format!("{}{}", id, fmt_match_annots(meta, indent)),
+        Match::Assign(id, meta, (bound, pat)) => format!(
+            "{}{} = {}",
+            id,
+            fmt_match_annots(meta, indent),
+            fmt_pattern(bound.as_ref(), pat, indent)
+        ),
+    }
+}
+
+/// Render the annotations of a field of a pattern, where a default value is given by `? value`.
+fn fmt_match_annots(meta: &MetaValue, indent: usize) -> String {
+    match &meta.value {
+        // `? value` sets the priority to default by itself
+        Some(value) => {
+            let annots = fmt_annots(
+                &MetaValue {
+                    priority: MergePriority::Normal,
+                    value: None,
+                    ..meta.clone()
+                },
+                indent,
+            );
+
+            format!("{} ? {}", annots, fmt_term(value, infix(10), indent))
+        }
+        None => fmt_annots(meta, indent),
+    }
+}
+
+/// Render the body of a function or the bound value of a `let`, starting a new indented line if it
+/// is itself a `let`.
+fn fmt_body(body: &RichTerm, indent: usize) -> String {
+    match body.term.as_ref() {
+        Term::Let(..) | Term::LetPattern(..) => format!(
+            "\n{}{}",
+            indentation(indent + INDENT),
+            fmt_term(body, ROOT, indent + INDENT)
+        ),
+        _ => format!(" {}", fmt_term(body, ROOT, indent)),
+    }
+}
+
+/// Render a function, merging nested functions into one with several arguments.
+fn fmt_fun(rt: &RichTerm, indent: usize) -> String {
+    let mut args = Vec::new();
+    let mut body = rt;
+
+    loop {
+        match body.term.as_ref() {
+            Term::Fun(id, t) => {
+                args.push(id.to_string());
+                body = t;
+            }
+            Term::FunPattern(id, pat, t) => {
+                args.push(fmt_pattern(id.as_ref(), pat, indent));
+                body = t;
+            }
+            _ => break,
+        }
+    }
+
+    format!("fun {} =>{}", args.join(" "), fmt_body(body, indent))
+}
+
+fn fmt_let(term: &Term, indent: usize) -> Option<String> {
+    let (binding, bound, body) = match term {
+        Term::Let(id, t1, t2, _) => (id.to_string(), t1, t2),
+        Term::LetPattern(id, pat, t1, t2) => (fmt_pattern(id.as_ref(), pat, indent), t1, t2),
+        _ => return None,
+    };
+
+    // Annotations of the bound value are put on the left-hand side
+    let (annots, bound) = match bound.term.as_ref() {
+        Term::MetaValue(meta) => match (&meta.value, fmt_annots(meta, indent)) {
+            (Some(value), annots) if !annots.is_empty() => (annots, value),
+            _ => (String::new(), bound),
+        },
+        _ => (String::new(), bound),
+    };
+
+    let bound = match fmt_body(bound, indent) {
+        bound if bound.starts_with('\n') => format!("{}\n{}in", bound, indentation(indent)),
+        bound => format!("{} in", bound),
+    };
+
+    Some(format!(
+        "let {}{} ={}\n{}{}",
+        binding,
+        annots,
+        bound,
+        indentation(indent),
+        fmt_term(body, ROOT, indent)
+    ))
+}
+
+fn fmt_app(t1: &RichTerm, t2: &RichTerm, indent: usize) -> (Prec, String) {
+    match t1.term.as_ref() {
+        Term::App(ite, t) => {
+            if let Term::Op1(UnaryOp::Ite(), cond) = ite.term.as_ref() {
+                return (
+                    ROOT,
+                    format!(
+                        "if {} then {} else {}",
+                        fmt_term(cond, ROOT, indent),
+                        fmt_term(t, ROOT, indent),
+                        fmt_term(t2, ROOT, indent)
+                    ),
+                );
+            }
+        }
+        Term::Op1(UnaryOp::BoolAnd(), t) => return fmt_infix("&&", 9, t, t2, indent),
+        Term::Op1(UnaryOp::BoolOr(), t) => return fmt_infix("||", 10, t, t2, indent),
+        Term::Op2(BinaryOp::DynExtend(), name, record) => {
+            return (
+                OPERAND,
+                format!(
+                    "{}$[{} = {}]",
+                    fmt_term(record, OPERAND, indent),
+                    fmt_term(name, ROOT, indent),
+                    fmt_term(t2, ROOT, indent)
+                ),
+            )
+        }
+        _ => (),
+    };
+
+    (
+        APP,
+        format!(
+            "{} {}",
+            fmt_term(t1, APP, indent),
+            fmt_term(t2, OPERAND, indent)
+        ),
+    )
+}
+
+/// Render a left-associative infix operator of the given level.
+fn fmt_infix(op: &str, level: u8, t1: &RichTerm, t2: &RichTerm, indent: usize) -> (Prec, String) {
+    (
+        infix(level),
+        format!(
+            "{} {} {}",
+            fmt_term(t1, infix(level), indent),
+            op,
+            fmt_term(t2, infix(level - 1), indent)
+        ),
+    )
+}
+
+fn fmt_op1(op: &UnaryOp, t: &RichTerm, indent: usize) -> Option<(Prec, String)> {
+    let keyword = match op {
+        UnaryOp::StaticAccess(id) => {
+            return Some((
+                OPERAND,
+                format!("{}.{}", fmt_term(t, OPERAND, indent), fmt_ident(id)),
+            ))
+        }
+        UnaryOp::BoolNot() => {
+            return Some(match t.term.as_ref() {
+                Term::Op2(BinaryOp::Eq(), t1, t2) => fmt_infix("!=", 8, t1, t2, indent),
+                _ => (infix(5), format!("!{}", fmt_term(t, infix(5), indent))),
+            })
+        }
+        UnaryOp::Embed(id) => {
+            return Some((
+                APP,
+                format!("%embed% {} {}", id, fmt_term(t, OPERAND, indent)),
+            ))
+        }
+        UnaryOp::RecordInsert(id) => {
+            return Some((
+                APP,
+                format!("%record_insert% {} {}", id, fmt_term(t, OPERAND, indent)),
+            ))
+        }
+        UnaryOp::RecordElideNulls(false) => "%elide_nulls%",
+        UnaryOp::RecordElideNulls(true) => "%elide_all_nulls%",
+        op => unary_keyword(op)?,
+    };
+
+    Some((APP, format!("{} {}", keyword, fmt_term(t, OPERAND, indent))))
+}
+
+fn fmt_op2(op: &BinaryOp, t1: &RichTerm, t2: &RichTerm, indent: usize) -> Option<(Prec, String)> {
+    let infix_op = match op {
+        BinaryOp::StrConcat() => Some(("++", 2)),
+        BinaryOp::ListConcat() => Some(("@", 2)),
+        BinaryOp::Mult() => Some(("*", 3)),
+        BinaryOp::Div() => Some(("/", 3)),
+        BinaryOp::Modulo() => Some(("%", 3)),
+        BinaryOp::Plus() => Some(("+", 4)),
+        BinaryOp::Sub() => Some(("-", 4)),
+        BinaryOp::Merge(_) => Some(("&", 6)),
+        BinaryOp::LessThan() => Some(("<", 7)),
+        BinaryOp::LessOrEq() => Some(("<=", 7)),
+        BinaryOp::GreaterThan() => Some((">", 7)),
+        BinaryOp::GreaterOrEq() => Some((">=", 7)),
+        BinaryOp::Eq() => Some(("==", 8)),
+        _ => None,
+    };
+
+    if let Some((symbol, level)) = infix_op {
+        return Some(fmt_infix(symbol, level, t1, t2, indent));
+    }
+
+    match op {
+        BinaryOp::DynAccess() => {
+            let name = match t1.term.as_ref() {
+                Term::StrChunks(_) => fmt_term(t1, ATOM, indent),
+                _ => format!("\"#{{{}}}\"", fmt_term(t1, ROOT, indent)),
+            };
+
+            Some((
+                OPERAND,
+                format!("{}.{}", fmt_term(t2, OPERAND, indent), name),
+            ))
+        }
+        BinaryOp::DynRemove() => Some((
+            OPERAND,
+            format!(
+                "{} -$ {}",
+                fmt_term(t2, OPERAND, indent),
+                fmt_term(t1, ATOM, indent)
+            ),
+        )),
+        op => Some((
+            APP,
+            format!(
+                "{} {} {}",
+                binary_keyword(op)?,
+                fmt_term(t1, OPERAND, indent),
+                fmt_term(t2, ATOM, indent)
+            ),
+        )),
+    }
+}
+
+fn fmt_opn(op: &NAryOp, args: &[RichTerm], indent: usize) -> Option<(Prec, String)> {
+    let args: Vec<String> = args
+        .iter()
+        .map(|arg| fmt_term(arg, OPERAND, indent))
+        .collect();
+
+    Some((APP, format!("{} {}", nary_keyword(op)?, args.join(" "))))
+}
+
+fn fmt_switch(
+    exp: &RichTerm,
+    cases: &HashMap<Ident, RichTerm>,
+    default: Option<&RichTerm>,
+    indent: usize,
+) -> String {
+    let case_indent = indent + INDENT;
+
+    let mut cases: Vec<_> = cases.iter().collect();
+    cases.sort_by(|(id1, _), (id2, _)| source_order(id1).cmp(&source_order(id2)));
+
+    let mut items: Vec<String> = cases
+        .into_iter()
+        .map(|(id, t)| format!("`{} => {}", fmt_ident(id), fmt_term(t, ROOT, case_indent)))
+        .collect();
+
+    if let Some(t) = default {
+        items.push(format!("_ => {}", fmt_term(t, ROOT, case_indent)));
+    }
+
+    format!(
+        "switch {} {}",
+        fmt_delimited("{", "}", items, indent, false),
+        fmt_term(exp, ROOT, indent)
+    )
+}
+
+/// The keyword of a prefix unary operator, if it has a syntax.
+fn unary_keyword(op: &UnaryOp) -> Option<&'static str> {
+    let keyword = match op {
+        UnaryOp::IsNum() => "%is_num%",
+        UnaryOp::IsBool() => "%is_bool%",
+        UnaryOp::IsStr() => "%is_str%",
+        UnaryOp::IsFun() => "%is_fun%",
+        UnaryOp::IsList() => "%is_list%",
+        UnaryOp::IsRecord() => "%is_record%",
+        UnaryOp::IsNull() => "%is_null%",
+        UnaryOp::TypeOf() => "%type_of%",
+        UnaryOp::Blame() => "%blame%",
+        UnaryOp::ChangePolarity() => "%chng_pol%",
+        UnaryOp::Pol() => "%polarity%",
+        UnaryOp::GoDom() => "%go_dom%",
+        UnaryOp::GoCodom() => "%go_codom%",
+        UnaryOp::GoList() => "%go_list%",
+        UnaryOp::Wrap() => "%wrap%",
+        UnaryOp::ListMap() => "%map%",
+        UnaryOp::ListSort() => "%sort%",
+        UnaryOp::ListGen() => "%generate%",
+        UnaryOp::RecordMap() => "%record_map%",
+        UnaryOp::Seq() => "%seq%",
+        UnaryOp::DeepSeq() => "%deep_seq%",
+        UnaryOp::ListHead() => "%head%",
+        UnaryOp::ListTail() => "%tail%",
+        UnaryOp::ListLast() => "%last%",
+        UnaryOp::ListInit() => "%init%",
+        UnaryOp::ListReverse() => "%reverse%",
+        UnaryOp::ListFlatten() => "%flatten%",
+        UnaryOp::ListLength() => "%length%",
+        UnaryOp::ListSum() => "%sum%",
+        UnaryOp::FieldsOf() => "%fields%",
+        UnaryOp::ValuesOf() => "%values%",
+        UnaryOp::RecordToList() => "%record_to_list%",
+        UnaryOp::StrTrim() => "%str_trim%",
+        UnaryOp::StrTrimStart() => "%str_trim_start%",
+        UnaryOp::StrTrimEnd() => "%str_trim_end%",
+        UnaryOp::StrChars() => "%str_chars%",
+        UnaryOp::StrLines() => "%str_lines%",
+        UnaryOp::StrDedent() => "%str_dedent%",
+        UnaryOp::CharCode() => "%char_code%",
+        UnaryOp::CharFromCode() => "%char_from_code%",
+        UnaryOp::StrUppercase() => "%str_uppercase%",
+        UnaryOp::Base64Encode() => "%base64_encode%",
+        UnaryOp::Base64Decode() => "%base64_decode%",
+        UnaryOp::HexEncode() => "%hex_encode%",
+        UnaryOp::HexDecode() => "%hex_decode%",
+        UnaryOp::StrLowercase() => "%str_lowercase%",
+        UnaryOp::StrLength() => "%str_length%",
+        UnaryOp::ToStr() => "%to_str%",
+        UnaryOp::NumFromStr() => "%num_from_str%",
+        UnaryOp::EnumFromStr() => "%enum_from_str%",
+        UnaryOp::EnumTag() => "%enum_tag%",
+        UnaryOp::Floor() => "%floor%",
+        UnaryOp::Ceil() => "%ceil%",
+        UnaryOp::Round() => "%round%",
+        UnaryOp::Abs() => "%abs%",
+        UnaryOp::Signum() => "%signum%",
+        UnaryOp::Sqrt() => "%sqrt%",
+        UnaryOp::Exp() => "%exp%",
+        UnaryOp::Ln() => "%ln%",
+        UnaryOp::Log10() => "%log10%",
+        _ => return None,
+    };
+
+    Some(keyword)
+}
+
+/// The keyword of a prefix binary operator, if it has a syntax.
+fn binary_keyword(op: &BinaryOp) -> Option<&'static str> {
+    let keyword = match op {
+        BinaryOp::Assume() => "%assume%",
+        BinaryOp::Unwrap() => "%unwrap%",
+        BinaryOp::GoField() => "%go_field%",
+        BinaryOp::HasField() => "%has_field%",
+        BinaryOp::MatchField() => "%match_field%",
+        BinaryOp::RecordFieldsDiff() => "%record_fields_diff%",
+        BinaryOp::RecordFilter() => "%record_filter%",
+        BinaryOp::RecordProject() => "%record_project%",
+        BinaryOp::RecordMergeDeep() => "%record_merge_deep%",
+        BinaryOp::RecordMergePreferLeft() => "%record_merge_prefer_left%",
+        BinaryOp::RecordMergePreferRight() => "%record_merge_prefer_right%",
+        BinaryOp::ListElemAt() => "%elem_at%",
+        BinaryOp::ListRange() => "%range%",
+        BinaryOp::ListFilter() => "%filter%",
+        BinaryOp::ListTake() => "%take%",
+        BinaryOp::ListDrop() => "%drop%",
+        BinaryOp::ListAll() => "%all%",
+        BinaryOp::ListAny() => "%any%",
+        BinaryOp::ListContains() => "%contains%",
+        BinaryOp::Tag() => "%tag%",
+        BinaryOp::Hash() => "%hash%",
+        BinaryOp::Serialize() => "%serialize%",
+        BinaryOp::Deserialize() => "%deserialize%",
+        BinaryOp::Pow() => "%pow%",
+        BinaryOp::IntDiv() => "%int_div%",
+        BinaryOp::DivMod() => "%div_mod%",
+        BinaryOp::Min() => "%min%",
+        BinaryOp::Max() => "%max%",
+        BinaryOp::StrSplit() => "%str_split%",
+        BinaryOp::StrSplitOnce() => "%str_split_once%",
+        BinaryOp::StrUnlines() => "%str_unlines%",
+        BinaryOp::StrJoin() => "%str_join%",
+        BinaryOp::StrContains() => "%str_contains%",
+        BinaryOp::StrRepeat() => "%str_repeat%",
+        BinaryOp::StrToEnumChecked() => "%enum_from_str_checked%",
+        BinaryOp::NumToStrRadix() => "%num_to_str_radix%",
+        BinaryOp::NumToStrFixed() => "%num_to_str_fixed%",
+        BinaryOp::StrToNumRadix() => "%str_to_num_radix%",
+        BinaryOp::StrStartsWith() => "%str_starts_with%",
+        BinaryOp::StrEndsWith() => "%str_ends_with%",
+        BinaryOp::StrTrimChars() => "%str_trim_chars%",
+        BinaryOp::StrContainsCaseInsensitive() => "%str_contains_ci%",
+        BinaryOp::StrStartsWithCaseInsensitive() => "%str_starts_with_ci%",
+        BinaryOp::StrEndsWithCaseInsensitive() => "%str_ends_with_ci%",
+        BinaryOp::StrEqCaseInsensitive() => "%str_eq_ci%",
+        BinaryOp::StrMatch() => "%str_match%",
+        BinaryOp::StrMatchAll() => "%str_match_all%",
+        BinaryOp::StrIsMatch() => "%str_is_match%",
+        _ => return None,
+    };
+
+    Some(keyword)
+}
+
+/// The keyword of an n-ary operator, if it has a syntax.
+fn nary_keyword(op: &NAryOp) -> Option<&'static str> {
+    let keyword = match op {
+        NAryOp::StrReplace() => "%str_replace%",
+        NAryOp::StrReplaceRegex() => "%str_replace_regex%",
+        NAryOp::StrReplaceN() => "%str_replace_n%",
+        NAryOp::StrSubstr() => "%str_substr%",
+        NAryOp::StrSplitN() => "%str_split_n%",
+        NAryOp::StrPadLeft() => "%str_pad_left%",
+        NAryOp::StrPadRight() => "%str_pad_right%",
+        NAryOp::SerializeWith() => "%serialize_with%",
+        NAryOp::Hmac() => "%hmac%",
+        NAryOp::ListRangeStep() => "%range_step%",
+        NAryOp::ListFoldl() => "%foldl%",
+        NAryOp::ListFoldr() => "%foldr%",
+        NAryOp::MergeByKey() => "%merge_by_key%",
+        NAryOp::RecordSetPath() => "%record_set_path%",
+        NAryOp::RecordGetPathOr() => "%record_get_path_or%",
+        _ => return None,
+    };
+
+    Some(keyword)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::grammar::TermParser;
+    use codespan::Files;
+
+    fn parse(s: &str) -> RichTerm {
+        let id = Files::new().add("<test>", String::from(s));
+
+        TermParser::new()
+            .parse_term(id, Lexer::new(s))
+            .unwrap_or_else(|err| panic!("failed to parse `{}`: {:?}", s, err))
+    }
+
+    /// Check that formatting the parsed `source` is idempotent, and return the formatted term.
+    fn assert_idempotent(source: &str) -> String {
+        let formatted = format(&parse(source));
+        assert_eq!(format(&parse(&formatted)), formatted, "from `{}`", source);
+        formatted
+    }
+
+    /// Check that parsing the formatted `source` gives back the same term.
+    fn assert_round_trip(source: &str) {
+        let term = parse(source);
+        let formatted = format(&term);
+
+        assert_eq!(
+            parse(&formatted).without_pos(),
+            term.without_pos(),
+            "`{}` formatted as `{}`",
+            source,
+            formatted
+        );
+        assert_idempotent(source);
+    }
+
+    #[test]
+    fn round_trip() {
+        let sources = [
+            "null",
+            "[true, false, 1, 2.5, \"str\", `tag, `\"quoted tag\"]",
+            "{foo = 1, \"bar baz\" = [], nested = {a = {}, b.c.d = null}}",
+            "{a = 1, \"#{x}\" = 2, b = 3, ..}",
+            "let x = 1 in let y = x + 1 in {x = x, y = y}",
+            "let {a, b = {c, ..rest}, ..} = r in a",
+            "fun x y => fun {z, ..} => x y z",
+            "let f = fun x => let y = x in y in let z = let w = 1 in w in f z",
+            "\"line\\n#{x}\\\"quoted\\\" \\#{not interpolated} #{\"inner #{y}\"}\"",
+            "(1 + 2) * 3 - 4 / (5 - 6) - -7",
+            "a ++ b @ c == d && !(e || f) && g != h",
+            "(fun x => x) (if a then b else c) (f x).foo",
+            "r.foo.\"bar baz\".\"#{x}\" -$ \"foo\"",
+            "r$[\"foo\" = 1] & {bar = 2}",
+            "switch { `foo => 1, `bar => fun x => x, _ => 3 } x",
+            "%head% (%map% f l) (%elem_at% l 0)",
+            "%str_replace% s \"a\" \"b\"",
+            "import \"lib.ncl\"",
+        ];
+
+        for source in sources.iter() {
+            assert_round_trip(source);
+        }
+    }
+
+    #[test]
+    fn idempotent() {
+        let sources = [
+            "let x : Num = 1 in x",
+            "let f | doc \"a function\" : forall a. a -> (Num -> a) -> List a = g in f",
+            "{foo | Num | default = 1, bar | #Contract, baz : {a: Num, b: List Str | Dyn} = null}",
+            "{foo | force = 1, bar | Num | force = 2}",
+            "let {a | Num ? 1, b : <foo, bar>, ..} = r in a : Num",
+            "fun x => (x | #(contracts.Positive x)) + 1",
+        ];
+
+        for source in sources.iter() {
+            assert_idempotent(source);
+        }
+    }
+
+    #[test]
+    fn layout() {
+        assert_eq!(
+            assert_idempotent("let r = {a = 1, b = {c = [1, 2]}} in r.b"),
+            "let r = {\n  a = 1,\n  b = {\n    c = [1, 2]\n  }\n} in\nr.b"
+        );
+
+        assert_eq!(
+            assert_idempotent("let f = fun x => let y = x in y in f"),
+            "let f = fun x =>\n  let y = x in\n  y in\nf"
+        );
+
+        let long_list = format!("[{}]", vec!["\"a long string\""; 6].join(", "));
+        assert_eq!(
+            assert_idempotent(&long_list),
+            format!("[\n{}\n]", vec!["  \"a long string\""; 6].join(",\n"))
+        );
+    }
+}
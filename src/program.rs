@@ -31,6 +31,7 @@ use codespan::FileId;
 use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
 use std::ffi::OsString;
 use std::io::{self, Read};
+use std::path::PathBuf;
 use std::result::Result;
 
 /// A Nickel program.
@@ -69,29 +70,52 @@ impl Program {
         Ok(Program { main_id, cache })
     }
 
-    /// Retrieve the parsed term and typecheck it, and generate a fresh global environment. Return
-    /// both.
-    fn prepare_eval(&mut self) -> Result<(RichTerm, eval::Environment), Error> {
-        let GlobalEnv { eval_env, type_env } = self.cache.prepare_stdlib()?;
+    /// Add directories to the import search path. See
+    /// [`Cache::add_import_paths`](../cache/struct.Cache.html#method.add_import_paths).
+    pub fn add_import_paths<P>(&mut self, paths: impl IntoIterator<Item = P>)
+    where
+        P: Into<PathBuf>,
+    {
+        self.cache.add_import_paths(paths);
+    }
+
+    /// Retrieve the parsed term and typecheck it, and generate a fresh global environment, which
+    /// is passed through `extend`. Return both.
+    fn prepare_eval<F>(&mut self, extend: F) -> Result<(RichTerm, eval::Environment), Error>
+    where
+        F: FnOnce(GlobalEnv) -> GlobalEnv,
+    {
+        let GlobalEnv { eval_env, type_env } = extend(self.cache.prepare_stdlib()?);
         self.cache.prepare(self.main_id, &type_env)?;
         Ok((self.cache.get(self.main_id).unwrap(), eval_env))
     }
 
     /// Parse if necessary, typecheck and then evaluate the program.
     pub fn eval(&mut self) -> Result<RichTerm, Error> {
-        let (t, global_env) = self.prepare_eval()?;
+        let (t, global_env) = self.prepare_eval(|global_env| global_env)?;
+        eval::eval(t, &global_env, &mut self.cache).map_err(|e| e.into())
+    }
+
+    /// Same as `eval`, but in a custom global environment, built by `extend` from the one of the
+    /// standard library. This is typically used to register builtins implemented in Rust with
+    /// [`GlobalEnv::with_builtin`](../cache/struct.GlobalEnv.html#method.with_builtin).
+    pub fn eval_with_env<F>(&mut self, extend: F) -> Result<RichTerm, Error>
+    where
+        F: FnOnce(GlobalEnv) -> GlobalEnv,
+    {
+        let (t, global_env) = self.prepare_eval(extend)?;
         eval::eval(t, &global_env, &mut self.cache).map_err(|e| e.into())
     }
 
     /// Same as `eval`, but proceeds to a full evaluation.
     pub fn eval_full(&mut self) -> Result<RichTerm, Error> {
-        let (t, global_env) = self.prepare_eval()?;
+        let (t, global_env) = self.prepare_eval(|global_env| global_env)?;
         eval::eval_full(t, &global_env, &mut self.cache).map_err(|e| e.into())
     }
 
     /// Same as `eval_full`, but does not substitute all variables.
     pub fn eval_deep(&mut self) -> Result<RichTerm, Error> {
-        let (t, global_env) = self.prepare_eval()?;
+        let (t, global_env) = self.prepare_eval(|global_env| global_env)?;
         eval::eval_deep(t, &global_env, &mut self.cache).map_err(|e| e.into())
     }
 
@@ -194,7 +218,17 @@ where
     let writer = StandardStream::stderr(ColorChoice::Always);
     let config = codespan_reporting::term::Config::default();
     let contracts_id = cache.id_of("<stdlib/contracts.ncl>");
-    let diagnostics = error.to_diagnostic(cache.files_mut(), contracts_id);
+    let mut diagnostics = error.to_diagnostic(cache.files_mut(), contracts_id);
+
+    if let Some(diagnostic) = diagnostics.first_mut() {
+        if let Some(code) = &diagnostic.code {
+            let note = format!(
+                "for more information about this error, run `nickel --explain {}`",
+                code
+            );
+            diagnostic.notes.push(note);
+        }
+    }
 
     let result = diagnostics.iter().try_for_each(|d| {
         codespan_reporting::term::emit(&mut writer.lock(), &config, cache.files_mut(), d)
@@ -211,10 +245,11 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::error::EvalError;
+    use crate::error::{EvalError, TypecheckError};
     use crate::parser::{grammar, lexer};
     use crate::position::TermPos;
     use crate::term::SharedTerm;
+    use assert_matches::assert_matches;
     use codespan::Files;
     use std::io::Cursor;
 
@@ -235,6 +270,7 @@ mod tests {
             Error::EvalError(EvalError::Other(
                 format!("IO error: {}", io_err),
                 TermPos::None,
+                None,
             ))
         })?;
         p.eval_full()
@@ -271,4 +307,109 @@ mod tests {
         // that this test fails.
         eval_full("{y = fun x => x, x = fun y => y}").unwrap();
     }
+
+    #[test]
+    fn render_error() {
+        let src = Cursor::new("let x = 1 in\nlet y = x + z in\ny");
+        let mut p = Program::new_from_source(src, "<test>").unwrap();
+        let err = p.eval().unwrap_err();
+        assert_matches!(
+            err,
+            Error::TypecheckError(TypecheckError::UnboundIdentifier(..))
+        );
+
+        let rendered = p.cache.render_error(err);
+        assert!(rendered.contains("Unbound identifier"));
+        assert!(rendered.contains("let y = x + z in"));
+        assert!(rendered.contains("<test>:2:13"));
+    }
+
+    #[test]
+    fn builtins() {
+        use crate::term::NativeFn;
+
+        let eval_with_builtins = |s: &str| {
+            let mut p = Program::new_from_source(Cursor::new(s), "<test>").unwrap();
+            p.eval_with_env(|env| {
+                env.with_builtin(
+                    "hostHostname",
+                    NativeFn::new(0, |_| Ok(Term::Str(String::from("localhost")))),
+                )
+                .with_builtin(
+                    "hostAdd",
+                    NativeFn::new(2, |args| match args {
+                        [Term::Num(n1), Term::Num(n2)] => Ok(Term::Num(n1 + n2)),
+                        _ => Err(EvalError::Other(
+                            String::from("hostAdd: expected numbers"),
+                            TermPos::None,
+                            None,
+                        )),
+                    }),
+                )
+            })
+            .map(RichTerm::without_pos)
+        };
+
+        assert_eq!(
+            eval_with_builtins("hostHostname ++ \".local\""),
+            Ok(Term::Str(String::from("localhost.local")).into())
+        );
+        assert_eq!(
+            eval_with_builtins("let add = hostAdd 1 in add (1 + 1) + lists.length [hostHostname]"),
+            Ok(Term::Num(4.0).into())
+        );
+        assert_matches!(
+            eval_with_builtins("hostAdd 1 \"a\""),
+            Err(Error::EvalError(EvalError::Other(..)))
+        );
+        // The arguments are forced entirely before the call
+        assert_matches!(
+            eval_with_builtins("hostAdd 1 {a = 1 + \"a\"}"),
+            Err(Error::EvalError(EvalError::TypeError(..)))
+        );
+    }
+
+    #[test]
+    fn fresh_variables_are_unique_across_files() {
+        use crate::term::{make as mk_term, BinaryOp, MergePath, TraverseMethod};
+
+        let mut cache = Cache::new();
+        let terms: Vec<RichTerm> = ["{a = 1 + 1, b = [2 + 2, 3]}", "{c = [4 + 4], d = 5 + 5}"]
+            .iter()
+            .enumerate()
+            .map(|(i, src)| {
+                let file_id = cache.add_string(format!("<test{}>", i), String::from(*src));
+                cache.parse(file_id).unwrap();
+                cache.transform(file_id).unwrap();
+                cache.get_owned(file_id).unwrap()
+            })
+            .collect();
+
+        let merged = mk_term::op2(
+            BinaryOp::Merge(MergePath::default()),
+            terms[0].clone(),
+            terms[1].clone(),
+        );
+        let mut generated = Vec::new();
+        merged
+            .traverse(
+                &mut |rt: RichTerm, generated: &mut Vec<Ident>| -> Result<RichTerm, ()> {
+                    if let Term::Let(id, ..) = rt.as_ref() {
+                        if id.is_generated() {
+                            generated.push(id.clone());
+                        }
+                    }
+                    Ok(rt)
+                },
+                &mut generated,
+                TraverseMethod::TopDown,
+            )
+            .unwrap();
+
+        let total = generated.len();
+        generated.sort_by(|id1, id2| id1.label.cmp(&id2.label));
+        generated.dedup();
+        assert!(total > 0);
+        assert_eq!(generated.len(), total);
+    }
 }
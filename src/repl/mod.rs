@@ -200,6 +200,7 @@ impl Repl for ReplImpl {
                 return Err(Error::EvalError(EvalError::Other(
                     String::from("load: expected a record"),
                     *pos,
+                    None,
                 )))
             }
         };
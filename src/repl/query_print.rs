@@ -238,7 +238,7 @@ fn write_query_result_<R: QueryPrinter>(
                     found = true;
                 }
                 MetaValue {
-                    priority: MergePriority::Normal,
+                    priority: MergePriority::Normal | MergePriority::Force,
                     value: Some(t),
                     ..
                 } if selected_attrs.value => {
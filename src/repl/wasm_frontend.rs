@@ -252,6 +252,8 @@ pub enum WasmExportFormat {
     Json = "json",
     Yaml = "yaml",
     Toml = "toml",
+    Xml = "xml",
+    Ini = "ini",
 }
 
 pub type ExportFormaParseError = ();
@@ -265,6 +267,8 @@ impl TryInto<ExportFormat> for WasmExportFormat {
             WasmExportFormat::Json => Ok(ExportFormat::Json),
             WasmExportFormat::Yaml => Ok(ExportFormat::Yaml),
             WasmExportFormat::Toml => Ok(ExportFormat::Toml),
+            WasmExportFormat::Xml => Ok(ExportFormat::Xml),
+            WasmExportFormat::Ini => Ok(ExportFormat::Ini),
             _ => Err(()),
         }
     }
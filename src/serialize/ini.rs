@@ -0,0 +1,218 @@
+//! Serialization to and deserialization from INI.
+//!
+//! INI files are made of sections of key-value pairs, and only support two levels of nesting.
+//! Nickel values are mapped to INI files using the following conventions:
+//!
+//! - The value must be a record.
+//! - A top-level field whose value is a record is a section `[name]`, and the fields of this
+//!   record are the key-value pairs of the section. Sections are written in definition order.
+//! - Other top-level fields are key-value pairs of the default section, which comes first and has
+//!   no header.
+//! - Values must be strings, numbers, booleans or enum tags. In particular, a record inside a
+//!   section is nested too deeply, and lists and `null` are not supported.
+//! - Keys, section names and values can't contain line breaks, nor start or end with whitespaces.
+//!   Keys can't contain `=`, and section names can't contain `]`.
+//!
+//! Deserialization is the converse: sections are deserialized as records, and values are always
+//! deserialized as strings. Lines starting with `;` or `#` are comments. Duplicated keys or
+//! sections, as well as a key of the default section with the same name as a section, are errors.
+use super::{scalar_text, value, ExportFormat};
+use crate::error::SerializationError;
+use crate::identifier::Ident;
+use crate::term::{IndexMap, RichTerm, Term};
+use indexmap::map::Entry;
+use std::fmt;
+
+/// An error occurring during the deserialization of an INI file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IniError {
+    pub msg: String,
+    /// The 0-based line of the error in the deserialized string.
+    pub line: usize,
+}
+
+impl fmt::Display for IniError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {}", self.msg, self.line + 1)
+    }
+}
+
+/// Check that a string can be written as is in an INI file, without being altered by the parser.
+fn check_text(text: &str, what: &str, forbidden: &[char]) -> Result<(), SerializationError> {
+    if text.contains(|c| c == '\n' || c == '\r' || forbidden.contains(&c)) {
+        Err(SerializationError::Other(format!(
+            "INI: the {} `{}` contains a line break or a forbidden character",
+            what, text
+        )))
+    } else if text.trim() != text {
+        Err(SerializationError::Other(format!(
+            "INI: the {} `{}` starts or ends with whitespaces",
+            what, text
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Write the key-value pairs of a section, in definition order. `path` is the list of the
+/// enclosing fields, used in error messages.
+fn write_entries(
+    out: &mut String,
+    path: &[&str],
+    entries: Vec<(&Ident, &RichTerm)>,
+) -> Result<(), SerializationError> {
+    for (id, t) in entries {
+        let field = path
+            .iter()
+            .chain(std::iter::once(&id.label.as_str()))
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(".");
+
+        let text = match value(t) {
+            Term::Record(..) => {
+                return Err(SerializationError::Other(format!(
+                    "INI: the field `{}` is nested too deeply. Only top-level records, which are sections, can contain other fields",
+                    field
+                )))
+            }
+            Term::Null => {
+                return Err(SerializationError::UnsupportedNull(
+                    ExportFormat::Ini,
+                    t.clone(),
+                ))
+            }
+            t_value => scalar_text(t_value).ok_or_else(|| {
+                SerializationError::Other(format!(
+                    "INI: the field `{}` must be a string, a number, a boolean or an enum tag",
+                    field
+                ))
+            })?,
+        };
+
+        check_text(&id.label, "key", &['='])?;
+        if id.label.starts_with(['[', ';', '#']) {
+            return Err(SerializationError::Other(format!(
+                "INI: the key `{}` can't start with `[`, `;` or `#`",
+                id.label
+            )));
+        }
+        check_text(&text, "value", &[])?;
+
+        out.push_str(&format!("{} = {}\n", id.label, text));
+    }
+
+    Ok(())
+}
+
+/// Serialize a term to an INI file. The term is expected to be fully evaluated.
+pub fn to_string(rt: &RichTerm) -> Result<String, SerializationError> {
+    let map = match value(rt) {
+        Term::Record(map, _) => map,
+        _ => {
+            return Err(SerializationError::Other(String::from(
+                "INI: expected a record at the top-level",
+            )))
+        }
+    };
+
+    let (sections, globals): (Vec<_>, Vec<_>) = map
+        .iter()
+        .partition(|(_, t)| matches!(value(t), Term::Record(..)));
+
+    let mut out = String::new();
+    write_entries(&mut out, &[], globals)?;
+
+    for (id, t) in sections {
+        check_text(&id.label, "section name", &[']'])?;
+
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("[{}]\n", id.label));
+
+        if let Term::Record(section, _) = value(t) {
+            write_entries(&mut out, &[&id.label], section.iter().collect())?;
+        }
+    }
+
+    Ok(out)
+}
+
+/// Insert a key-value pair in a section, failing if the key is already defined.
+fn insert(
+    fields: &mut IndexMap<Ident, RichTerm>,
+    key: &str,
+    value: RichTerm,
+    line: usize,
+) -> Result<(), IniError> {
+    match fields.entry(Ident::from(key)) {
+        Entry::Occupied(_) => Err(IniError {
+            msg: format!("duplicated key or section `{}`", key),
+            line,
+        }),
+        Entry::Vacant(entry) => {
+            entry.insert(value);
+            Ok(())
+        }
+    }
+}
+
+/// Deserialize an INI file.
+pub fn from_str(s: &str) -> Result<RichTerm, IniError> {
+    let mut globals = IndexMap::new();
+    // The sections, with the line of their header.
+    let mut sections: Vec<(String, usize, IndexMap<Ident, RichTerm>)> = Vec::new();
+
+    for (line, content) in s.lines().enumerate() {
+        let content = content.trim();
+
+        if content.is_empty() || content.starts_with(';') || content.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = content.strip_prefix('[') {
+            let name = header.strip_suffix(']').ok_or_else(|| IniError {
+                msg: String::from("unterminated section header"),
+                line,
+            })?;
+            let name = name.trim();
+
+            if sections.iter().any(|(other, _, _)| other == name) {
+                return Err(IniError {
+                    msg: format!("duplicated section `{}`", name),
+                    line,
+                });
+            }
+
+            sections.push((String::from(name), line, IndexMap::new()));
+        } else {
+            let (key, value) = content.split_once('=').ok_or_else(|| IniError {
+                msg: String::from("expected a section header or a key-value pair"),
+                line,
+            })?;
+            let fields = match sections.last_mut() {
+                Some((_, _, fields)) => fields,
+                None => &mut globals,
+            };
+
+            insert(
+                fields,
+                key.trim(),
+                RichTerm::from(Term::Str(String::from(value.trim()))),
+                line,
+            )?;
+        }
+    }
+
+    for (name, line, fields) in sections {
+        insert(
+            &mut globals,
+            &name,
+            RichTerm::from(Term::Record(fields, Default::default())),
+            line,
+        )?;
+    }
+
+    Ok(RichTerm::from(Term::Record(globals, Default::default())))
+}
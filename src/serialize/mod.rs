@@ -1,14 +1,16 @@
 //! Serialization of an evaluated program to various data format.
 use crate::error::SerializationError;
 use crate::identifier::Ident;
-use crate::term::{MetaValue, RecordAttrs, RichTerm, Term};
-use serde::de::{Deserialize, Deserializer};
+use crate::term::{IndexMap, MetaValue, RecordAttrs, RichTerm, Term};
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
 use serde::ser::{Error, Serialize, SerializeMap, Serializer};
-use std::collections::HashMap;
 use std::fmt;
 use std::io;
 use std::str::FromStr;
 
+pub mod ini;
+pub mod xml;
+
 /// Available export formats.
 // If you add or remove variants, remember to update the CLI docs in `src/bin/nickel.rs'
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -17,6 +19,8 @@ pub enum ExportFormat {
     Json,
     Yaml,
     Toml,
+    Xml,
+    Ini,
 }
 
 impl std::default::Default for ExportFormat {
@@ -32,6 +36,8 @@ impl fmt::Display for ExportFormat {
             Self::Json => write!(f, "json"),
             Self::Yaml => write!(f, "yaml"),
             Self::Toml => write!(f, "toml"),
+            Self::Xml => write!(f, "xml"),
+            Self::Ini => write!(f, "ini"),
         }
     }
 }
@@ -54,6 +60,8 @@ impl FromStr for ExportFormat {
             "json" => Ok(ExportFormat::Json),
             "yaml" => Ok(ExportFormat::Yaml),
             "toml" => Ok(ExportFormat::Toml),
+            "xml" => Ok(ExportFormat::Xml),
+            "ini" => Ok(ExportFormat::Ini),
             _ => Err(ParseFormatError(String::from(s))),
         }
     }
@@ -78,6 +86,32 @@ where
     n.serialize(serializer)
 }
 
+/// Return the value of a term, skipping metavalues. Used by the formats which are not serialized
+/// through serde.
+fn value(rt: &RichTerm) -> &Term {
+    match rt.as_ref() {
+        Term::MetaValue(MetaValue { value: Some(t), .. }) => value(t),
+        t => t,
+    }
+}
+
+/// Return the text representation of a scalar, or `None` if the term is not a scalar. Used by the
+/// formats which only have strings.
+fn scalar_text(t: &Term) -> Option<String> {
+    match t {
+        Term::Bool(b) => Some(b.to_string()),
+        // Numbers are formatted as in JSON
+        Term::Num(n) => Some(
+            serialize_num(n, serde_json::value::Serializer)
+                .ok()?
+                .to_string(),
+        ),
+        Term::Str(s) => Some(s.clone()),
+        Term::Enum(id) => Some(id.label.clone()),
+        _ => None,
+    }
+}
+
 /// Serializer for metavalues.
 pub fn serialize_meta_value<S>(meta: &MetaValue, serializer: S) -> Result<S::Ok, S::Error>
 where
@@ -91,38 +125,23 @@ where
     }
 }
 
-/// Serializer for a record. Serialize fields in alphabetical order to get a deterministic output
-/// (by default, `HashMap`'s randomness implies a randomized order of fields in the output).
+/// Serializer for a record. Fields are serialized in their definition order.
 pub fn serialize_record<S>(
-    map: &HashMap<Ident, RichTerm>,
+    map: &IndexMap<Ident, RichTerm>,
     _attrs: &RecordAttrs,
     serializer: S,
 ) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    let mut entries: Vec<(_, _)> = map.iter().collect();
-    entries.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
-
-    let mut map_ser = serializer.serialize_map(Some(entries.len()))?;
-    for (id, t) in entries.iter() {
+    let mut map_ser = serializer.serialize_map(Some(map.len()))?;
+    for (id, t) in map.iter() {
         map_ser.serialize_entry(&id.to_string(), &t)?
     }
 
     map_ser.end()
 }
 
-/// Deserialize for a record. Required to set the record attributes to default.
-pub fn deserialize_record<'de, D>(
-    deserializer: D,
-) -> Result<(HashMap<Ident, RichTerm>, RecordAttrs), D::Error>
-where
-    D: Deserializer<'de>,
-{
-    let map: HashMap<Ident, RichTerm> = HashMap::deserialize(deserializer)?;
-    Ok((map, Default::default()))
-}
-
 impl Serialize for RichTerm {
     /// Serialize the underlying term.
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -133,6 +152,109 @@ impl Serialize for RichTerm {
     }
 }
 
+/// Convert an integer of a data format to a number. Integers which can't be represented exactly
+/// would be silently rounded, and are rejected instead, as integer literals are by the Nickel
+/// parser.
+///
+/// Integers beyond the 64-bit range are already parsed as floating point numbers by the data
+/// format libraries, and can't be checked.
+fn exact_num<E: de::Error>(n: i128) -> Result<Term, E> {
+    let value = n as f64;
+
+    if value as i128 == n {
+        Ok(Term::Num(value))
+    } else {
+        Err(E::custom(format!(
+            "integer {} can't be represented exactly as a number",
+            n
+        )))
+    }
+}
+
+/// Visitor building a term from the data model of serde. Only produce data: null, booleans,
+/// numbers, strings, lists and records.
+struct TermVisitor;
+
+impl<'de> Visitor<'de> for TermVisitor {
+    type Value = Term;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a null, boolean, number, string, list or record value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Term, E> {
+        Ok(Term::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Term, E> {
+        Ok(Term::Null)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Term, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Term::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<Term, E> {
+        Ok(Term::Bool(b))
+    }
+
+    fn visit_i64<E: de::Error>(self, n: i64) -> Result<Term, E> {
+        exact_num(n as i128)
+    }
+
+    fn visit_u64<E: de::Error>(self, n: u64) -> Result<Term, E> {
+        exact_num(n as i128)
+    }
+
+    fn visit_f64<E: de::Error>(self, n: f64) -> Result<Term, E> {
+        Ok(Term::Num(n))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Term, E> {
+        Ok(Term::Str(String::from(s)))
+    }
+
+    fn visit_string<E: de::Error>(self, s: String) -> Result<Term, E> {
+        Ok(Term::Str(s))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Term, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut ts = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(t) = seq.next_element()? {
+            ts.push(t);
+        }
+
+        Ok(Term::List(ts))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Term, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut fields = IndexMap::with_capacity(map.size_hint().unwrap_or(0));
+        while let Some((id, t)) = map.next_entry::<Ident, RichTerm>()? {
+            fields.insert(id, t);
+        }
+
+        Ok(Term::Record(fields, Default::default()))
+    }
+}
+
+impl<'de> Deserialize<'de> for Term {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(TermVisitor)
+    }
+}
+
 impl<'de> Deserialize<'de> for RichTerm {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -157,8 +279,8 @@ pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), SerializationE
         }
     } else {
         match t.term.as_ref() {
-            // TOML doesn't support null values
-            Null if format == ExportFormat::Json || format == ExportFormat::Yaml => Ok(()),
+            // TOML and INI don't support null values
+            Null if !matches!(format, ExportFormat::Toml | ExportFormat::Ini) => Ok(()),
             Null => Err(SerializationError::UnsupportedNull(format, t.clone())),
             Bool(_) | Num(_) | Str(_) | Enum(_) => Ok(()),
             Record(map, _) => {
@@ -196,6 +318,16 @@ where
             .and_then(|v| {
                 write!(writer, "{}", v).map_err(|err| SerializationError::Other(err.to_string()))
             }),
+        ExportFormat::Xml => xml::to_string(rt).and_then(|s| {
+            writer
+                .write_all(s.as_bytes())
+                .map_err(|err| SerializationError::Other(err.to_string()))
+        }),
+        ExportFormat::Ini => ini::to_string(rt).and_then(|s| {
+            writer
+                .write_all(s.as_bytes())
+                .map_err(|err| SerializationError::Other(err.to_string()))
+        }),
         ExportFormat::Raw => match rt.as_ref() {
             Term::Str(s) => writer
                 .write_all(s.as_bytes())
@@ -208,6 +340,30 @@ where
     }
 }
 
+/// The layout of a serialized value.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Style {
+    /// Spread over several lines and indented.
+    Pretty,
+    /// On as few lines as possible, without superfluous whitespaces.
+    Compact,
+}
+
+/// Serialize a term to a string with the given layout. Only JSON has a compact layout: the other
+/// formats are serialized as by [`to_string`], whatever the style.
+pub fn to_string_with_style(
+    format: ExportFormat,
+    style: Style,
+    rt: &RichTerm,
+) -> Result<String, SerializationError> {
+    match (format, style) {
+        (ExportFormat::Json, Style::Compact) => {
+            serde_json::to_string(&rt).map_err(|err| SerializationError::Other(err.to_string()))
+        }
+        _ => to_string(format, rt),
+    }
+}
+
 pub fn to_string(format: ExportFormat, rt: &RichTerm) -> Result<String, SerializationError> {
     match format {
         ExportFormat::Json => serde_json::to_string_pretty(&rt)
@@ -218,6 +374,8 @@ pub fn to_string(format: ExportFormat, rt: &RichTerm) -> Result<String, Serializ
         ExportFormat::Toml => toml::Value::try_from(&rt)
             .map(|v| format!("{}", v))
             .map_err(|err| SerializationError::Other(err.to_string())),
+        ExportFormat::Xml => xml::to_string(rt),
+        ExportFormat::Ini => ini::to_string(rt),
         ExportFormat::Raw => match rt.as_ref() {
             Term::Str(s) => Ok(s.clone()),
             t => Err(SerializationError::Other(format!(
@@ -246,6 +404,7 @@ mod tests {
             Error::EvalError(EvalError::Other(
                 format!("IO error: {}", io_err),
                 TermPos::None,
+                None,
             ))
         })
     }
@@ -260,6 +419,17 @@ mod tests {
         };
     }
 
+    /// Same as `assert_json_eq`, but ignore the order of the fields of records.
+    macro_rules! assert_json_eq_unordered {
+        ( $term:expr, $result:expr ) => {
+            assert_eq!(
+                serde_json::to_value(&mk_program($term).and_then(|mut p| p.eval_full()).unwrap())
+                    .unwrap(),
+                serde_json::to_value(&$result).unwrap()
+            )
+        };
+    }
+
     macro_rules! assert_pass_validation {
         ( $format:expr, $term:expr, true) => {
             validate(
@@ -363,12 +533,24 @@ mod tests {
             json!({"a": {"b": {"c": "richtig"}}})
         );
 
-        assert_json_eq!(
+        assert_json_eq_unordered!(
             "{foo = let z = 0.5 + 0.5 in z, bar = [\"str\", true || false], baz = {subfoo = !false} & {subbar = 1 - 1}}",
             json!({"foo": 1, "bar": ["str", true], "baz": {"subfoo": true, "subbar": 0}})
         );
     }
 
+    #[test]
+    fn field_order() {
+        let evaluated = mk_program("{c = 1, a = {z = 2, y = 3}, b = 4}")
+            .and_then(|mut p| p.eval_full())
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&evaluated).unwrap(),
+            r#"{"c":1,"a":{"z":2,"y":3},"b":4}"#
+        );
+    }
+
     #[test]
     fn meta_values() {
         assert_json_eq!(
@@ -381,7 +563,7 @@ mod tests {
             json!({"a": {"b": {"c": "faux"}}})
         );
 
-        assert_json_eq!(
+        assert_json_eq_unordered!(
             "{baz | default = {subfoo | default = !false} & {subbar | default = 1 - 1}}",
             json!({"baz": {"subfoo": true, "subbar": 0}})
         );
@@ -406,4 +588,165 @@ mod tests {
         assert_involutory!("{val = [\"a\", 3, []]}");
         assert_involutory!("{a.foo.bar = \"2\", b = false, c = [{d = \"e\"}, {d = \"f\"}]}");
     }
+
+    #[test]
+    fn large_integers() {
+        let evaluated = mk_program("{id = 4611686018427387904, neg = -(2 * 4611686018427387904)}")
+            .and_then(|mut p| p.eval_full())
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&evaluated).unwrap(),
+            r#"{"id":4611686018427387904,"neg":-9223372036854775808}"#
+        );
+        assert_involutory!("{id = 4611686018427387904}");
+    }
+
+    #[test]
+    fn inexact_integers() {
+        assert_eq!(
+            serde_json::from_str::<Term>("[9007199254740992, -9223372036854775808, 0.1]").unwrap(),
+            Term::List(vec![
+                Term::Num(9007199254740992.0).into(),
+                Term::Num(-9223372036854775808.0).into(),
+                Term::Num(0.1).into(),
+            ])
+        );
+
+        for n in [
+            "9007199254740993",
+            "-9007199254740993",
+            "18446744073709551615",
+        ] {
+            assert!(serde_json::from_str::<Term>(n).is_err());
+            assert!(serde_yaml::from_str::<Term>(n).is_err());
+        }
+        assert!(toml::from_str::<Term>("id = 9007199254740993").is_err());
+
+        // Out of the 64-bit range, integers are already parsed as floats by serde_json
+        assert_eq!(
+            serde_json::from_str::<Term>("100000000000000000000000000001").unwrap(),
+            Term::Num(1e29)
+        );
+    }
+
+    #[test]
+    fn xml() {
+        let evaluated: RichTerm = mk_program(
+            r#"{
+                config = {
+                    "@version" = "1",
+                    name = "test",
+                    servers.server = [
+                        {"@id" = "a", "$text" = "alpha"},
+                        {"@id" = "b", "$text" = "beta"},
+                    ],
+                    empty = "",
+                    missing = null,
+                }
+            }"#,
+        )
+        .and_then(|mut p| p.eval_full())
+        .unwrap()
+        .into();
+
+        let serialized = to_string(ExportFormat::Xml, &evaluated).unwrap();
+        assert_eq!(
+            serialized,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<config version=\"1\">
+  <name>test</name>
+  <servers>
+    <server id=\"a\">alpha</server>
+    <server id=\"b\">beta</server>
+  </servers>
+  <empty></empty>
+  <missing/>
+</config>"
+        );
+
+        let deserialized = xml::from_str(&serialized).unwrap();
+        assert_eq!(
+            crate::eval::eval(
+                mk_term::op2(BinaryOp::Eq(), deserialized, evaluated),
+                &Environment::new(),
+                &mut crate::cache::resolvers::DummyResolver {}
+            )
+            .map(Term::from),
+            Ok(Term::Bool(true))
+        );
+
+        assert!(xml::from_str("<a>text<b/></a>").is_err());
+        let two_fields: RichTerm = mk_program("{a = \"1\", b = \"2\"}")
+            .and_then(|mut p| p.eval_full())
+            .unwrap()
+            .into();
+        assert!(to_string(ExportFormat::Xml, &two_fields).is_err());
+    }
+
+    #[test]
+    fn ini() {
+        let evaluated: RichTerm = mk_program(
+            r#"{
+                name = "app",
+                debug = true,
+                server = {host = "localhost", port = 8080},
+                database.user = "admin",
+                empty = {},
+            }"#,
+        )
+        .and_then(|mut p| p.eval_full())
+        .unwrap()
+        .into();
+
+        let serialized = to_string(ExportFormat::Ini, &evaluated).unwrap();
+        assert_eq!(
+            serialized,
+            "name = app
+debug = true
+
+[server]
+host = localhost
+port = 8080
+
+[database]
+user = admin
+
+[empty]
+"
+        );
+
+        let deserialized = ini::from_str(&serialized).unwrap();
+        let expected = mk_program(
+            r#"{
+                name = "app",
+                debug = "true",
+                server = {host = "localhost", port = "8080"},
+                database.user = "admin",
+                empty = {},
+            }"#,
+        )
+        .and_then(|mut p| p.eval_full())
+        .unwrap();
+        assert_eq!(
+            crate::eval::eval(
+                mk_term::op2(BinaryOp::Eq(), deserialized, expected),
+                &Environment::new(),
+                &mut crate::cache::resolvers::DummyResolver {}
+            )
+            .map(Term::from),
+            Ok(Term::Bool(true))
+        );
+
+        assert!(ini::from_str("a = 1\n[a]\nb = 2").is_err());
+        assert!(ini::from_str("[a]\nb = 1\nb = 2").is_err());
+        assert!(ini::from_str("[a\nb = 1").is_err());
+
+        let too_deep: RichTerm = mk_program("{a.b.c = 1}")
+            .and_then(|mut p| p.eval_full())
+            .unwrap()
+            .into();
+        assert!(to_string(ExportFormat::Ini, &too_deep).is_err());
+        assert_pass_validation!(ExportFormat::Ini, "{foo = null}", false);
+    }
 }
@@ -0,0 +1,365 @@
+//! Serialization to and deserialization from XML.
+//!
+//! XML has no native notion of records, lists or numbers, and doesn't fit the serde data model
+//! used for the other formats. Nickel values are mapped to XML documents using the following
+//! conventions:
+//!
+//! - The value must be a record with exactly one field, whose name is the name of the root
+//!   element.
+//! - A record is an element with one child element per field, in definition order. Fields whose
+//!   name starts with `@` are attributes of the element instead of children: `{"@id" = "a"}`
+//!   corresponds to the attribute `id="a"`. The content of the special field `$text` is the text
+//!   content of the element, which then can't have children. Attributes and `$text` must be
+//!   scalars. A record without children nor text content is an empty element.
+//! - A list is the repetition of the element of the enclosing field for each one of its items:
+//!   `{a = [1, 2]}` gives `<a>1</a><a>2</a>`. Lists of lists are not supported.
+//! - A string, a number, a boolean or an enum tag is the text content of the element. `null` is
+//!   an empty element `<a/>`, while the empty string is an element without content `<a></a>`.
+//!
+//! Deserialization is the converse, with the following limitations:
+//!
+//! - Text content and attribute values are always deserialized as strings: `<a>1</a>` gives
+//!   `{a = "1"}`. Leading and trailing whitespaces of text content are trimmed.
+//! - A child element is deserialized as a list if and only if it is repeated. A list with only
+//!   one element is thus deserialized as this element, and an empty list as a missing field.
+//! - Elements with both text content and child elements (mixed content) are not supported.
+//!   Comments, processing instructions and the XML declaration are ignored.
+//! - Elements can't be nested more than 128 levels deep.
+use super::{scalar_text, value};
+use crate::error::SerializationError;
+use crate::identifier::Ident;
+use crate::term::{IndexMap, RichTerm, Term};
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+use std::fmt;
+
+/// Prefix of the fields corresponding to attributes.
+const ATTRIBUTE_PREFIX: char = '@';
+/// Name of the field corresponding to the text content of an element with attributes.
+const TEXT_FIELD: &str = "$text";
+/// Maximum nesting depth of the elements of a deserialized document. Elements are read
+/// recursively, and deeper documents would overflow the stack.
+const MAX_DEPTH: usize = 128;
+
+/// An error occurring during the deserialization of an XML document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct XmlError {
+    pub msg: String,
+    /// Offset in bytes of the error in the deserialized string.
+    pub offset: usize,
+}
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.msg, self.offset)
+    }
+}
+
+/// Check that a field name is a valid XML name. Only the ASCII subset of XML names is accepted.
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn check_name(name: &str) -> Result<(), SerializationError> {
+    if is_valid_name(name) {
+        Ok(())
+    } else {
+        Err(SerializationError::Other(format!(
+            "XML: `{}` is not a valid element or attribute name",
+            name
+        )))
+    }
+}
+
+fn write_event(writer: &mut Writer<Vec<u8>>, event: Event) -> Result<(), SerializationError> {
+    writer
+        .write_event(event)
+        .map_err(|err| SerializationError::Other(err.to_string()))
+}
+
+/// Write the element `name` corresponding to the field of a record with content `rt`, or the
+/// sequence of such elements if `rt` is a list.
+fn write_field(
+    writer: &mut Writer<Vec<u8>>,
+    name: &str,
+    rt: &RichTerm,
+) -> Result<(), SerializationError> {
+    match value(rt) {
+        Term::List(ts) => ts.iter().try_for_each(|t| {
+            if let Term::List(_) = value(t) {
+                Err(SerializationError::Other(format!(
+                    "XML: the field `{}` is a list of lists, which is not supported",
+                    name
+                )))
+            } else {
+                write_field(writer, name, t)
+            }
+        }),
+        Term::Record(map, _) => {
+            let mut start = BytesStart::owned_name(name);
+            let mut text = None;
+            let mut children = Vec::new();
+
+            for (id, t) in map {
+                if let Some(attr) = id.label.strip_prefix(ATTRIBUTE_PREFIX) {
+                    check_name(attr)?;
+                    let content = scalar_text(value(t)).ok_or_else(|| {
+                        SerializationError::Other(format!(
+                            "XML: the attribute `{}` of `{}` must be a string, a number, a boolean or an enum tag",
+                            attr, name
+                        ))
+                    })?;
+                    start.push_attribute((attr, content.as_str()));
+                } else if id.label == TEXT_FIELD {
+                    text = Some(scalar_text(value(t)).ok_or_else(|| {
+                        SerializationError::Other(format!(
+                            "XML: the text content of `{}` must be a string, a number, a boolean or an enum tag",
+                            name
+                        ))
+                    })?);
+                } else {
+                    check_name(&id.label)?;
+                    children.push((id, t));
+                }
+            }
+
+            match text {
+                Some(_) if !children.is_empty() => Err(SerializationError::Other(format!(
+                    "XML: the element `{}` can't have both text content and children",
+                    name
+                ))),
+                Some(text) => {
+                    write_event(writer, Event::Start(start))?;
+                    write_event(writer, Event::Text(BytesText::from_plain_str(&text)))?;
+                    write_event(writer, Event::End(BytesEnd::borrowed(name.as_bytes())))
+                }
+                None if children.is_empty() => write_event(writer, Event::Empty(start)),
+                None => {
+                    write_event(writer, Event::Start(start))?;
+                    for (id, t) in children {
+                        write_field(writer, &id.label, t)?;
+                    }
+                    write_event(writer, Event::End(BytesEnd::borrowed(name.as_bytes())))
+                }
+            }
+        }
+        Term::Null => write_event(
+            writer,
+            Event::Empty(BytesStart::borrowed_name(name.as_bytes())),
+        ),
+        t => {
+            let text = scalar_text(t).ok_or_else(|| {
+                SerializationError::NonSerializable(RichTerm::new(t.clone(), rt.pos))
+            })?;
+            write_event(
+                writer,
+                Event::Start(BytesStart::borrowed_name(name.as_bytes())),
+            )?;
+            write_event(writer, Event::Text(BytesText::from_plain_str(&text)))?;
+            write_event(writer, Event::End(BytesEnd::borrowed(name.as_bytes())))
+        }
+    }
+}
+
+/// Serialize a term to an XML document. The term is expected to be fully evaluated.
+pub fn to_string(rt: &RichTerm) -> Result<String, SerializationError> {
+    let (root, content) = match value(rt) {
+        Term::Record(map, _) if map.len() == 1 => map.iter().next().unwrap(),
+        _ => {
+            return Err(SerializationError::Other(String::from(
+                "XML: expected a record with exactly one field, the root element",
+            )))
+        }
+    };
+    check_name(&root.label)?;
+
+    if let Term::List(_) = value(content) {
+        return Err(SerializationError::Other(String::from(
+            "XML: the root element can't be a list",
+        )));
+    }
+
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    write_event(
+        &mut writer,
+        Event::Decl(BytesDecl::new(b"1.0", Some(b"UTF-8"), None)),
+    )?;
+    write_field(&mut writer, &root.label, content)?;
+
+    String::from_utf8(writer.into_inner()).map_err(|err| SerializationError::Other(err.to_string()))
+}
+
+/// Build an error at the current position of the reader.
+fn mk_error(reader: &Reader<&[u8]>, msg: impl Into<String>) -> XmlError {
+    XmlError {
+        msg: msg.into(),
+        offset: reader.buffer_position(),
+    }
+}
+
+fn decode_name(reader: &Reader<&[u8]>, name: &[u8]) -> Result<String, XmlError> {
+    reader
+        .decode(name)
+        .map(String::from)
+        .map_err(|err| mk_error(reader, err.to_string()))
+}
+
+/// Read the content of an element, whose start tag has already been consumed. `depth` is the
+/// number of enclosing elements.
+fn read_element(
+    reader: &mut Reader<&[u8]>,
+    start: &BytesStart,
+    empty: bool,
+    depth: usize,
+) -> Result<RichTerm, XmlError> {
+    if depth >= MAX_DEPTH {
+        return Err(mk_error(
+            reader,
+            format!("elements are nested more than {} levels deep", MAX_DEPTH),
+        ));
+    }
+
+    let mut fields = IndexMap::new();
+
+    for attr in start.attributes() {
+        let attr = attr.map_err(|err| mk_error(reader, err.to_string()))?;
+        let key = decode_name(reader, attr.key)?;
+        let value = attr
+            .unescape_and_decode_value(reader)
+            .map_err(|err| mk_error(reader, err.to_string()))?;
+        fields.insert(
+            Ident::from(format!("{}{}", ATTRIBUTE_PREFIX, key)),
+            RichTerm::from(Term::Str(value)),
+        );
+    }
+
+    if empty {
+        return Ok(if fields.is_empty() {
+            RichTerm::from(Term::Null)
+        } else {
+            RichTerm::from(Term::Record(fields, Default::default()))
+        });
+    }
+
+    let mut text = String::new();
+    // Children are grouped by name, such that repeated elements form a list.
+    let mut children: Vec<(String, Vec<RichTerm>)> = Vec::new();
+    let mut buf = Vec::new();
+
+    loop {
+        let (name, child) = match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                let e = e.to_owned();
+                (
+                    decode_name(reader, e.name())?,
+                    read_element(reader, &e, false, depth + 1)?,
+                )
+            }
+            Ok(Event::Empty(ref e)) => {
+                let e = e.to_owned();
+                (
+                    decode_name(reader, e.name())?,
+                    read_element(reader, &e, true, depth + 1)?,
+                )
+            }
+            Ok(Event::Text(ref e)) => {
+                let s = e
+                    .unescape_and_decode(reader)
+                    .map_err(|err| mk_error(reader, err.to_string()))?;
+                text.push_str(&s);
+                continue;
+            }
+            Ok(Event::CData(ref e)) => {
+                text.push_str(&decode_name(reader, e)?);
+                continue;
+            }
+            Ok(Event::End(_)) => break,
+            Ok(Event::Eof) => return Err(mk_error(reader, "unexpected end of document")),
+            Ok(Event::DocType(_)) | Ok(Event::Decl(_)) => {
+                return Err(mk_error(reader, "unexpected declaration inside an element"))
+            }
+            Ok(Event::Comment(_)) | Ok(Event::PI(_)) => continue,
+            Err(err) => return Err(mk_error(reader, err.to_string())),
+        };
+
+        match children.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, elts)) => elts.push(child),
+            None => children.push((name, vec![child])),
+        }
+        buf.clear();
+    }
+
+    if !children.is_empty() && !text.is_empty() {
+        return Err(mk_error(
+            reader,
+            "elements with both text content and child elements are not supported",
+        ));
+    }
+
+    if children.is_empty() && fields.is_empty() {
+        return Ok(RichTerm::from(Term::Str(text)));
+    }
+
+    if !text.is_empty() {
+        fields.insert(Ident::from(TEXT_FIELD), RichTerm::from(Term::Str(text)));
+    }
+
+    for (name, mut elts) in children {
+        let value = if elts.len() == 1 {
+            elts.pop().unwrap()
+        } else {
+            RichTerm::from(Term::List(elts))
+        };
+        fields.insert(Ident::from(name), value);
+    }
+
+    Ok(RichTerm::from(Term::Record(fields, Default::default())))
+}
+
+/// Deserialize an XML document.
+pub fn from_str(s: &str) -> Result<RichTerm, XmlError> {
+    let mut reader = Reader::from_str(s);
+    reader.trim_text(true).trim_text_end(true);
+
+    let mut buf = Vec::new();
+    let mut root = None;
+
+    loop {
+        let (start, empty) = match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => (e.to_owned(), false),
+            Ok(Event::Empty(ref e)) => (e.to_owned(), true),
+            Ok(Event::Eof) => break,
+            Ok(Event::Decl(_))
+            | Ok(Event::DocType(_))
+            | Ok(Event::Comment(_))
+            | Ok(Event::PI(_)) => continue,
+            Ok(_) => {
+                return Err(mk_error(
+                    &reader,
+                    "unexpected content outside of the root element",
+                ))
+            }
+            Err(err) => return Err(mk_error(&reader, err.to_string())),
+        };
+
+        if root.is_some() {
+            return Err(mk_error(&reader, "multiple root elements"));
+        }
+
+        let name = decode_name(&reader, start.name())?;
+        let content = read_element(&mut reader, &start, empty, 0)?;
+        root = Some((name, content));
+        buf.clear();
+    }
+
+    match root {
+        Some((name, content)) => {
+            let mut fields = IndexMap::new();
+            fields.insert(Ident::from(name), content);
+            Ok(RichTerm::from(Term::Record(fields, Default::default())))
+        }
+        None => Err(mk_error(&reader, "missing root element")),
+    }
+}
@@ -19,11 +19,12 @@ pub const FUNCTIONS: (&str, &str) = (
     "<stdlib/functions>",
     include_str!("../stdlib/functions.ncl"),
 );
+pub const PRELUDE: (&str, &str) = ("<stdlib/prelude>", include_str!("../stdlib/prelude.ncl"));
 
 /// Return the list `(name, source_code)` of all the stdlib modules.
 pub fn modules() -> Vec<(&'static str, &'static str)> {
     vec![
-        BUILTINS, CONTRACTS, LISTS, RECORDS, STRINGS, NUMS, FUNCTIONS,
+        BUILTINS, CONTRACTS, LISTS, RECORDS, STRINGS, NUMS, FUNCTIONS, PRELUDE,
     ]
 }
 
@@ -16,17 +16,22 @@
 //! contracts, default values, documentation, etc. They bring such usually external object down to
 //! the term level, and together with [merge](../merge/index.html), they allow for flexible and
 //! modular definitions of contracts, record and metadata all together.
-use crate::destruct::Destruct;
+use crate::destruct::{Destruct, Match};
+use crate::error::EvalError;
 use crate::identifier::Ident;
 use crate::label::Label;
 use crate::match_sharedterm;
 use crate::position::TermPos;
 use crate::types::{AbsType, Types};
 use codespan::FileId;
-use serde::{Deserialize, Serialize};
+pub use indexmap::IndexMap;
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::ffi::OsString;
 use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem::ManuallyDrop;
 use std::ops::Deref;
 use std::rc::Rc;
 
@@ -35,7 +40,7 @@ use std::rc::Rc;
 /// Parsed terms also need to store their position in the source for error reporting.  This is why
 /// this type is nested with [`RichTerm`](type.RichTerm.html).
 ///
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
 #[serde(untagged)]
 pub enum Term {
     /// The null value.
@@ -85,12 +90,11 @@ pub enum Term {
 
     /// A record, mapping identifiers to terms.
     #[serde(serialize_with = "crate::serialize::serialize_record")]
-    #[serde(deserialize_with = "crate::serialize::deserialize_record")]
-    Record(HashMap<Ident, RichTerm>, RecordAttrs),
+    Record(IndexMap<Ident, RichTerm>, RecordAttrs),
     /// A recursive record, where the fields can reference each others.
     #[serde(skip)]
     RecRecord(
-        HashMap<Ident, RichTerm>,
+        IndexMap<Ident, RichTerm>,
         Vec<(RichTerm, RichTerm)>, /* field whose name is defined by interpolation */
         RecordAttrs,
     ),
@@ -143,12 +147,15 @@ pub enum Term {
     Wrapped(i32, RichTerm),
 
     #[serde(serialize_with = "crate::serialize::serialize_meta_value")]
-    #[serde(skip_deserializing)]
     MetaValue(MetaValue),
 
     /// An unresolved import.
     #[serde(skip)]
     Import(OsString),
+    /// An unresolved import of the raw content of a file as a string. It is replaced by the
+    /// corresponding string during import resolution.
+    #[serde(skip)]
+    ImportStr(OsString),
     /// A resolved import (which has already been loaded and parsed).
     #[serde(skip)]
     ResolvedImport(FileId),
@@ -165,7 +172,7 @@ impl From<MetaValue> for Term {
 /// Type of let-binding. This only affects run-time behavior. Revertible bindings introduce
 /// revertible thunks at evaluation, which are devices used for the implementation of recursive
 /// records merging. See the [`merge`] and [`eval`] modules for more details.
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
 pub enum BindingType {
     Normal,
     Revertible,
@@ -190,10 +197,15 @@ impl RecordAttrs {
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+/// The priority of a value, which decides which one is kept when merging two values. A value wins
+/// over the ones of lower priority, while values of the same priority are merged together.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone)]
 pub enum MergePriority {
+    /// Set by the `default` annotation.
     Default,
     Normal,
+    /// Set by the `force` annotation.
+    Force,
 }
 
 impl Default for MergePriority {
@@ -287,7 +299,12 @@ impl MetaValue {
             doc: doc.or(inner.doc),
             types: types.or(inner.types),
             contracts,
-            priority: std::cmp::min(priority, inner.priority),
+            // An explicit priority annotation takes precedence over the normal one, which is
+            // implicitly set by all the other annotations.
+            priority: match (priority, inner.priority) {
+                (MergePriority::Normal, p) | (p, MergePriority::Normal) => p,
+                (p1, p2) => std::cmp::min(p1, p2),
+            },
             value: inner.value,
         }
     }
@@ -344,7 +361,7 @@ impl Term {
                 });
             }
             Bool(_) | Num(_) | Str(_) | Lbl(_) | Var(_) | Sym(_) | Enum(_) | Import(_)
-            | ResolvedImport(_) => {}
+            | ImportStr(_) | ResolvedImport(_) => {}
             Fun(_, ref mut t)
             | FunPattern(_, _, ref mut t)
             | Op1(_, ref mut t)
@@ -406,6 +423,7 @@ impl Term {
             | Term::Op2(_, _, _)
             | Term::OpN(..)
             | Term::Import(_)
+            | Term::ImportStr(_)
             | Term::ResolvedImport(_)
             | Term::StrChunks(_)
             | Term::ParseError => None,
@@ -458,10 +476,10 @@ impl Term {
                     content.push_str("contract,");
                 }
 
-                let value_label = if meta.priority == MergePriority::Default {
-                    "default"
-                } else {
-                    "value"
+                let value_label = match meta.priority {
+                    MergePriority::Default => "default",
+                    MergePriority::Normal => "value",
+                    MergePriority::Force => "force",
                 };
                 let value = if let Some(t) = &meta.value {
                     t.as_ref().shallow_repr()
@@ -481,6 +499,7 @@ impl Term {
             | Term::Op2(_, _, _)
             | Term::OpN(..)
             | Term::Import(_)
+            | Term::ImportStr(_)
             | Term::ResolvedImport(_) => String::from("<unevaluated>"),
         }
     }
@@ -537,6 +556,7 @@ impl Term {
             | Term::Wrapped(_, _)
             | Term::MetaValue(_)
             | Term::Import(_)
+            | Term::ImportStr(_)
             | Term::ResolvedImport(_)
             | Term::StrChunks(_)
             | Term::RecRecord(..)
@@ -577,6 +597,7 @@ impl Term {
             | Term::Wrapped(_, _)
             | Term::MetaValue(_)
             | Term::Import(_)
+            | Term::ImportStr(_)
             | Term::ResolvedImport(_)
             | Term::StrChunks(_)
             | Term::RecRecord(..)
@@ -598,7 +619,13 @@ impl SharedTerm {
     }
 
     pub fn into_owned(self) -> Term {
-        Rc::try_unwrap(self.shared).unwrap_or_else(|rc| Term::clone(&rc))
+        // Move the pointer out without running the `Drop` implementation, which would free the
+        // subterms of the term.
+        let this = ManuallyDrop::new(self);
+        // Safety: `this` is neither used nor dropped afterwards, so the pointer is moved out
+        // exactly once.
+        let shared = unsafe { std::ptr::read(&this.shared) };
+        Rc::try_unwrap(shared).unwrap_or_else(|rc| Term::clone(&rc))
     }
 
     pub fn make_mut(this: &mut Self) -> &mut Term {
@@ -626,6 +653,83 @@ impl Deref for SharedTerm {
     }
 }
 
+/// Free a term iteratively. The default drop glue would recurse into the subterms, such that
+/// freeing a deeply nested term, as a long list built by evaluation, could overflow the native
+/// stack. Instead, the subterms of a term which is not shared anymore are moved to an explicit
+/// stack and freed one by one, each one being left without subterms when actually dropped.
+impl Drop for SharedTerm {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        take_subterms(&mut self.shared, &mut pending);
+
+        while let Some(mut shared) = pending.pop() {
+            take_subterms(&mut shared.shared, &mut pending);
+        }
+    }
+}
+
+/// If `shared` is the last reference to its term, move the direct subterms of the term to
+/// `pending`, leaving `Null` in place. The remaining content of the term, such as the types of
+/// metavalues, is dropped as usual.
+fn take_subterms(shared: &mut Rc<Term>, pending: &mut Vec<SharedTerm>) {
+    let term = match Rc::get_mut(shared) {
+        Some(
+            Term::Null
+            | Term::Bool(_)
+            | Term::Num(_)
+            | Term::Str(_)
+            | Term::Lbl(_)
+            | Term::Var(_)
+            | Term::Enum(_)
+            | Term::Sym(_)
+            | Term::Import(_)
+            | Term::ImportStr(_)
+            | Term::ResolvedImport(_)
+            | Term::ParseError,
+        )
+        | None => return,
+        Some(term) => std::mem::replace(term, Term::Null),
+    };
+
+    let mut push = |rt: RichTerm| pending.push(rt.term);
+
+    match term {
+        Term::Fun(_, t) | Term::FunPattern(_, _, t) | Term::Op1(_, t) | Term::Wrapped(_, t) => {
+            push(t)
+        }
+        Term::Let(_, t1, t2, _)
+        | Term::LetPattern(_, _, t1, t2)
+        | Term::App(t1, t2)
+        | Term::Op2(_, t1, t2) => {
+            push(t1);
+            push(t2);
+        }
+        Term::List(ts) | Term::OpN(_, ts) => ts.into_iter().for_each(push),
+        Term::Record(fields, _) => fields.into_iter().for_each(|(_, t)| push(t)),
+        Term::RecRecord(fields, dyn_fields, _) => {
+            fields.into_iter().for_each(|(_, t)| push(t));
+            for (id_t, t) in dyn_fields {
+                push(id_t);
+                push(t);
+            }
+        }
+        Term::Switch(t, cases, default) => {
+            push(t);
+            cases.into_iter().for_each(|(_, t)| push(t));
+            default.into_iter().for_each(push);
+        }
+        Term::StrChunks(chunks) => {
+            for chunk in chunks {
+                if let StrChunk::Expr(t, _) = chunk {
+                    push(t);
+                }
+            }
+        }
+        Term::MetaValue(meta) => meta.value.into_iter().for_each(push),
+        _ => (),
+    }
+}
+
 /// Primitive unary operators.
 ///
 /// Some operators, such as if-then-else or `seq`, actually take several arguments but are only
@@ -634,7 +738,7 @@ impl Deref for SharedTerm {
 /// elseBlock`, `if-then-else` can be seen as a unary operator taking a `Bool` argument and
 /// evaluating to either the first projection `fun x y => x` or the second projection `fun x y =>
 /// y`.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum UnaryOp {
     /// If-then-else.
     Ite(),
@@ -651,6 +755,12 @@ pub enum UnaryOp {
     IsList(),
     /// Test if a term is a record.
     IsRecord(),
+    /// Test if a term is `null`.
+    IsNull(),
+    /// Return the runtime type of a term as a string, which is one of `"Num"`, `"Bool"`, `"Str"`,
+    /// `"Record"`, `"List"`, `"Fun"`, `"Enum"` and `"Null"`, or `"Other"` for the remaining values,
+    /// such as labels.
+    TypeOf(),
 
     // Boolean AND and OR operator are encoded as unary operators so that they can be lazy in their
     // second argument.
@@ -660,6 +770,12 @@ pub enum UnaryOp {
     BoolOr(),
     /// Boolean NOT operator.
     BoolNot(),
+    /// Check that the second argument of a lazy boolean operator is a boolean, and return it
+    /// unchanged. The parameter is the name of the original operator, used for error reporting.
+    ///
+    /// Only produced during the evaluation of `&&` and `||`, once their first argument has been
+    /// evaluated and the result depends on the second one.
+    BoolGuard(&'static str),
 
     /// Raise a blame, which stops the execution and prints an error according to the label argument.
     Blame(),
@@ -677,9 +793,51 @@ pub enum UnaryOp {
     ///
     /// Static means that the field identifier is a statically known string inside the source.
     StaticAccess(Ident),
+    /// Static insertion of a field in a record. The value of the new field is the next argument on
+    /// the stack.
+    ///
+    /// As for `StaticAccess`, the field identifier is known statically, such that the typechecker
+    /// can track the new field.
+    RecordInsert(Ident),
 
     /// Map a function on each element of a list.
     ListMap(),
+    /// Sort a list according to a comparison function returning `Less`, `Equal` or `Greater`. The
+    /// sort is stable.
+    ListSort(),
+    /// Insert the next element in the list being sorted by `ListSort`, given the result of its
+    /// comparison with the middle of the range `[lo, hi)` of already sorted elements where it may
+    /// be inserted.
+    ///
+    /// Only produced during the evaluation of `ListSort`, which keeps the sorted elements, the
+    /// elements remaining to insert and the comparison function on the stack.
+    ListSortStep(usize /* lo */, usize /* hi */),
+    /// Keep or drop the next element of the list being filtered by `ListFilter`, given the result
+    /// of the predicate on this element.
+    ///
+    /// Only produced during the evaluation of `ListFilter`, which keeps the elements retained so
+    /// far, the elements remaining to test and the predicate on the stack.
+    ListFilterStep(),
+    /// Apply the function folded by `ListFoldl` to the next element of the list, given the
+    /// evaluated accumulator.
+    ///
+    /// Only produced during the evaluation of `ListFoldl`, which keeps the elements remaining to
+    /// fold and the function on the stack.
+    ListFoldlStep(),
+    /// Stop or continue the evaluation of `ListAll` or `ListAny`, given the result of the
+    /// predicate on the next element of the list. The evaluation stops as soon as the predicate
+    /// returns the given boolean, which is then the result of the whole operation.
+    ///
+    /// Only produced during the evaluation of `ListAll` and `ListAny`, which keep the elements
+    /// remaining to test and the predicate on the stack.
+    ListQuantifierStep(bool /* stop_on */),
+    /// Keep or drop the next field of the record being filtered by `RecordFilter`, given the
+    /// result of the predicate on this field.
+    ///
+    /// Only produced during the evaluation of `RecordFilter`, which keeps the fields retained so
+    /// far, the names and the contents of the fields remaining to test and the predicate on the
+    /// stack.
+    RecordFilterStep(),
     /// Map a function on a record.
     ///
     /// The mapped function must take two arguments, the name of the field as a string, and the
@@ -735,8 +893,22 @@ pub enum UnaryOp {
     ListHead(),
     /// Return the tail of a list.
     ListTail(),
+    /// Return the last element of a list.
+    ListLast(),
+    /// Return all the elements of a list but the last one.
+    ListInit(),
+    /// Reverse the order of the elements of a list.
+    ListReverse(),
+    /// Concatenate the elements of a list of lists.
+    ListFlatten(),
     /// Return the length of a list.
     ListLength(),
+    /// Sum the elements of a list of numbers, which are expected to be already evaluated.
+    ///
+    /// If all the elements are integers, the sum is exact, and it is an error if the result, or
+    /// one of the elements, is too large to be represented exactly (that is, if its absolute value
+    /// is above 2^53). Otherwise, the elements are summed as floating-point numbers.
+    ListSum(),
     /// Generate a list of a given length by mapping a `Num -> Num` function onto `[1,..,n]`.
     ListGen(),
 
@@ -746,35 +918,195 @@ pub enum UnaryOp {
     /// evaluated, all stored on the stack.
     ChunksConcat(),
 
-    /// Return the names of the fields of a record as a string list.
+    /// Return the names of the fields of a record as a string list, in definition order.
     FieldsOf(),
-    /// Return the values of the fields of a record as a list.
+    /// Return the values of the fields of a record as a list, in definition order.
     ValuesOf(),
+    /// Return the fields of a record as a list of records `{field, value}`, where `field` is the
+    /// name of the field as a string and `value` its content, in definition order.
+    RecordToList(),
 
     /// Remove heading and trailing spaces from a string.
     StrTrim(),
+    /// Remove heading spaces from a string.
+    StrTrimStart(),
+    /// Remove trailing spaces from a string.
+    StrTrimEnd(),
     /// Return the list of characters of a string.
     StrChars(),
+    /// Split a string into lines. Return a record with the list of `lines`, without their line
+    /// feed, and a boolean `trailing_newline` indicating if the string ended with a line feed, such
+    /// that [`BinaryOp::StrUnlines`] can rebuild the original string exactly.
+    StrLines(),
+    /// Remove the longest common prefix of leading whitespace from the non-blank lines of a
+    /// string, and make the blank lines, which contain only whitespace, empty. Whitespace is
+    /// compared character by character: a tab and a space are different, such that a line
+    /// indented with tabs and a line indented with spaces have no common prefix.
+    StrDedent(),
     /// Return the code of a character (givne as a string of length 1).
     CharCode(),
     /// Return the character corresponding to a code.
     CharFromCode(),
+    /// Recursively remove the fields of a record whose value is `null`. The argument must have
+    /// been fully evaluated beforehand (see [`UnaryOp::DeepSeq`]).
+    ///
+    /// Records inside lists are handled as well. If the boolean parameter is `true`, `null`
+    /// elements of lists are also removed.
+    RecordElideNulls(bool),
     /// Transform a string to uppercase.
     StrUppercase(),
     /// Transform a string to lowercase.
     StrLowercase(),
     /// Return the length of a string.
     StrLength(),
-    /// Transform a data to a string.
+    /// Encode the UTF-8 bytes of a string in base64, as defined by RFC 4648, with padding.
+    Base64Encode(),
+    /// Decode a base64 string, as defined by RFC 4648, with padding. ASCII whitespaces are
+    /// ignored. The decoded bytes must be valid UTF-8.
+    Base64Decode(),
+    /// Encode the UTF-8 bytes of a string in lowercase hexadecimal.
+    HexEncode(),
+    /// Decode a hexadecimal string, either in lowercase or uppercase. The decoded bytes must be
+    /// valid UTF-8.
+    HexDecode(),
+    /// Transform a data to a string. Numbers are written in plain decimal notation, never in
+    /// scientific notation, and whole numbers are written without a fractional part.
     ToStr(),
     /// Transform a string to a number.
     NumFromStr(),
     /// Transform a string to an enum.
     EnumFromStr(),
+    /// Transform an enum to the string of its tag. Inverse of `EnumFromStr`.
+    EnumTag(),
+
+    /// Round a number down to the nearest integer.
+    Floor(),
+    /// Round a number up to the nearest integer.
+    Ceil(),
+    /// Round a number to the nearest integer. Half-way cases are rounded away from zero.
+    Round(),
+    /// Absolute value of a number.
+    Abs(),
+    /// Sign of a number, as either `-1`, `0` or `1`.
+    Signum(),
+    /// Square root of a number.
+    Sqrt(),
+    /// Exponential of a number.
+    Exp(),
+    /// Natural logarithm of a number.
+    Ln(),
+    /// Base 10 logarithm of a number.
+    Log10(),
+
+    /// Call a native function on a list of arguments, which must have been fully evaluated
+    /// beforehand. Has no syntax: it is only introduced by the builtins registered with
+    /// [`GlobalEnv::with_builtin`](../cache/struct.GlobalEnv.html#method.with_builtin).
+    Native(NativeFn),
+}
+
+/// A function implemented in Rust, callable from Nickel code.
+///
+/// A native function receives its arguments as fully evaluated terms, in the order of
+/// application. It must return a closed term: the result doesn't have access to any environment.
+#[derive(Clone)]
+pub struct NativeFn {
+    arity: usize,
+    fun: Rc<NativeFnBody>,
+}
+
+type NativeFnBody = dyn Fn(&[Term]) -> Result<Term, EvalError>;
+
+impl NativeFn {
+    /// Create a native function taking `arity` arguments. A native function of arity zero is
+    /// called, once, when the builtin is accessed for the first time.
+    pub fn new<F>(arity: usize, fun: F) -> Self
+    where
+        F: Fn(&[Term]) -> Result<Term, EvalError> + 'static,
+    {
+        NativeFn {
+            arity,
+            fun: Rc::new(fun),
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    pub(crate) fn body(&self) -> &NativeFnBody {
+        self.fun.as_ref()
+    }
+}
+
+impl fmt::Debug for NativeFn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NativeFn")
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Native functions are compared by identity.
+impl PartialEq for NativeFn {
+    fn eq(&self, other: &Self) -> bool {
+        self.arity == other.arity && Rc::ptr_eq(&self.fun, &other.fun)
+    }
+}
+
+impl Hash for NativeFn {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.arity.hash(state);
+        Rc::as_ptr(&self.fun).cast::<()>().hash(state);
+    }
+}
+
+/// The path of the fields leading to a merge when it comes from the merge of two records, or the
+/// empty path otherwise (see [`BinaryOp::Merge`]).
+///
+/// The path is stored as a persistent list from the last field to the first one, such that the
+/// paths of the subfields of a record all share the path of the record. Two paths are equal if
+/// they have the same fields.
+#[derive(Clone, Default, PartialEq, Hash)]
+pub struct MergePath(Option<Rc<MergePathNode>>);
+
+#[derive(PartialEq, Hash)]
+struct MergePathNode {
+    field: Ident,
+    parent: MergePath,
+}
+
+impl MergePath {
+    /// Return the path extended with a field.
+    pub fn with_field(&self, field: Ident) -> Self {
+        MergePath(Some(Rc::new(MergePathNode {
+            field,
+            parent: self.clone(),
+        })))
+    }
+
+    /// Return the fields of the path, from the first one to the last one.
+    pub fn to_vec(&self) -> Vec<Ident> {
+        let mut path = Vec::new();
+        let mut current = self;
+
+        while let Some(node) = &current.0 {
+            path.push(node.field.clone());
+            current = &node.parent;
+        }
+
+        path.reverse();
+        path
+    }
+}
+
+impl fmt::Debug for MergePath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("MergePath").field(&self.to_vec()).finish()
+    }
 }
 
 /// Primitive binary operators
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum BinaryOp {
     /// Addition of numerals.
     Plus(),
@@ -786,8 +1118,17 @@ pub enum BinaryOp {
     Div(),
     /// Modulo of numerals.
     Modulo(),
+    /// Floor division of numerals, rounding the quotient towards negative infinity.
+    IntDiv(),
+    /// Floor division of numerals, returning both the quotient and the remainder as a record
+    /// `{quot, rem}`. The remainder has the same sign as the divisor.
+    DivMod(),
     /// Raise a number to a power.
     Pow(),
+    /// Minimum of two numerals. If one of them is `NaN`, the result is `NaN`.
+    Min(),
+    /// Maximum of two numerals. If one of them is `NaN`, the result is `NaN`.
+    Max(),
     /// Concatenation of strings.
     StrConcat(),
     /// Polymorphic equality.
@@ -831,12 +1172,64 @@ pub enum BinaryOp {
     DynAccess(),
     /// Test if a record has a specific field.
     HasField(),
-    /// Concatenate two lists.
+    /// Dispatch on the first field of a record, in definition order: apply the handler with the
+    /// same name in the second record to the content of this field. A missing handler is an
+    /// error.
+    MatchField(),
+    /// Compare the field names of two records. Return a record `{added, removed, common}` of
+    /// lists of field names, sorted alphabetically: `added` are the fields only in the second
+    /// record, `removed` the fields only in the first one, and `common` the fields in both.
+    RecordFieldsDiff(),
+    /// Keep the fields of a record which satisfy a predicate. The predicate takes the name of the
+    /// field as a string and its content as arguments.
+    RecordFilter(),
+    /// Keep only the fields of a record whose names are given in a list of strings. Projecting on
+    /// a field which is not in the record is an error. The elements of the list are expected to be
+    /// already evaluated.
+    RecordProject(),
+    /// Recursively merge two records, the fields of the second one taking precedence over the ones
+    /// of the first one.
+    ///
+    /// Contrary to `Merge`, this operation ignores metadata: there is no notion of default values
+    /// or of merge conflicts, and contracts are not combined. When a field is defined in both
+    /// records, the two values are merged recursively if both are records, and the value of the
+    /// second record replaces the one of the first record otherwise. The first value is only
+    /// evaluated if the second one is a record.
+    RecordMergeDeep(),
+    /// Shallowly combine two records. When a field is defined in both records, the value of the
+    /// first record is kept and the other one is discarded without being evaluated.
+    ///
+    /// Contrary to `Merge`, conflicting fields are never an error, and metadata is ignored.
+    RecordMergePreferLeft(),
+    /// Shallowly combine two records. When a field is defined in both records, the value of the
+    /// second record is kept and the other one is discarded without being evaluated.
+    ///
+    /// See [`BinaryOp::RecordMergePreferLeft`].
+    RecordMergePreferRight(),
+    /// Concatenate two lists. The elements are not evaluated.
     ListConcat(),
     /// Access the n-th element of a list.
     ListElemAt(),
-    /// The merge operator (see the [merge module](../merge/index.html)).
-    Merge(),
+    /// Generate the list of integers in the half-open interval `[start, end)`.
+    ListRange(),
+    /// Keep the elements of a list which satisfy a predicate.
+    ListFilter(),
+    /// Keep the first n elements of a list, or the whole list if it has less than n elements.
+    ListTake(),
+    /// Drop the first n elements of a list, or all of them if it has less than n elements.
+    ListDrop(),
+    /// Test if all the elements of a list satisfy a predicate. Stop at the first element which
+    /// doesn't.
+    ListAll(),
+    /// Test if at least one element of a list satisfies a predicate. Stop at the first element
+    /// which does.
+    ListAny(),
+    /// Test if a list contains an element, using the same equality as [`BinaryOp::Eq`]. The
+    /// element is the first argument, and the list the second one.
+    ListContains(),
+    /// The merge operator (see the [merge module](../merge/index.html)), together with the path of
+    /// the fields leading to this merge, which is only used to report errors.
+    Merge(MergePath),
 
     /// Hash a string.
     Hash(),
@@ -847,19 +1240,72 @@ pub enum BinaryOp {
 
     /// Split a string into a list.
     StrSplit(),
+    /// Concatenate a list of strings, inserting a separator between consecutive elements. The
+    /// arguments are in order the separator and the list. Inverse of [`BinaryOp::StrSplit`].
+    StrJoin(),
+    /// Split a string on the first occurrence of a separator. Return a record with the parts
+    /// `before` and `after` the separator, and a `tag` which is either `Split`, or `NotFound` if
+    /// the separator doesn't occur in the string.
+    StrSplitOnce(),
+    /// Join a list of lines with line feeds. The second argument is a boolean indicating if a
+    /// final line feed must be appended, which is only done if the list is not empty. Inverse of
+    /// [`UnaryOp::StrLines`].
+    StrUnlines(),
     /// Determine if a string is a substring of another one.
     StrContains(),
+    /// Repeat a string a given number of times.
+    StrRepeat(),
+    /// Transform a string to an enum, checking that the result belongs to a given list of
+    /// allowed tags.
+    StrToEnumChecked(),
+    /// Write an integer in a given radix, between 2 and 36.
+    NumToStrRadix(),
+    /// Write a number with a fixed number of decimal places, between 0 and 100, rounding half-way
+    /// cases to the nearest even digit.
+    NumToStrFixed(),
+    /// Parse an integer written in a given radix, between 2 and 36. Return a record `{tag, value}`
+    /// where `tag` is `Some` if the string is a valid integer, and `None` otherwise, in which case
+    /// `value` is `0`.
+    StrToNumRadix(),
+    /// Determine if a string starts with a given prefix.
+    StrStartsWith(),
+    /// Determine if a string ends with a given suffix.
+    StrEndsWith(),
+    /// Remove from a string the heading and trailing characters which belong to the set of
+    /// characters of the second argument.
+    StrTrimChars(),
+    /// Same as [`BinaryOp::StrContains`], but ignoring case. See
+    /// [`BinaryOp::StrEqCaseInsensitive`] for the details of case folding.
+    StrContainsCaseInsensitive(),
+    /// Same as [`BinaryOp::StrStartsWith`], but ignoring case. See
+    /// [`BinaryOp::StrEqCaseInsensitive`] for the details of case folding.
+    StrStartsWithCaseInsensitive(),
+    /// Same as [`BinaryOp::StrEndsWith`], but ignoring case. See
+    /// [`BinaryOp::StrEqCaseInsensitive`] for the details of case folding.
+    StrEndsWithCaseInsensitive(),
+    /// Determine if two strings are equal, ignoring case.
+    ///
+    /// Both strings are case folded before being compared. Case folding maps each character to
+    /// its lowercase, then uppercase, then lowercase again, using the full (possibly
+    /// multi-character) Unicode mappings. This coincides with the Unicode full case folding in
+    /// practice: for example `ß` and `ẞ` fold to `ss`, `ς` to `σ`, and `İ` to `i` followed by a
+    /// combining dot above, which is thus different from `i`. Locale-specific rules, such as the
+    /// Turkish dotless `ı`, are not taken into account.
+    StrEqCaseInsensitive(),
     /// Test if a regex matches a string.
     StrIsMatch(),
     /// Match a regex on a string, and returns the captured groups together, the index of the
     /// match, etc.
     StrMatch(),
+    /// Same as [`BinaryOp::StrMatch`], but return the list of all the successive non-overlapping
+    /// matches of the regex in the string.
+    StrMatchAll(),
 }
 
 impl BinaryOp {
     pub fn is_strict(&self) -> bool {
         match self {
-            BinaryOp::Merge() => false,
+            BinaryOp::Merge(_) => false,
             _ => true,
         }
     }
@@ -867,7 +1313,7 @@ impl BinaryOp {
 
 /// Primitive n-ary operators. Unary and binary operator make up for most of operators and are
 /// hence special cased. `NAryOp` handles strict operations of arity greater than 2.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Hash)]
 pub enum NAryOp {
     /// Replace a substring by another one in a string.
     StrReplace(),
@@ -875,12 +1321,61 @@ pub enum NAryOp {
     ///
     /// [`StrReplace()`]: NAryOp::StrReplace
     StrReplaceRegex(),
+    /// Same as [`StrReplace()`], but only replace at most a given number of non-overlapping
+    /// occurrences, from the left. The count is the 4th argument.
+    ///
+    /// [`StrReplace()`]: NAryOp::StrReplace
+    StrReplaceN(),
     /// Return a substring of an original string.
     StrSubstr(),
+    /// Split a string into at most n parts, on the first n - 1 occurrences of a separator. The
+    /// arguments are in order the string, the separator and the maximum number of parts.
+    StrSplitN(),
+    /// Same as [`ListRange()`], but with an explicit step between consecutive elements.
+    ///
+    /// [`ListRange()`]: BinaryOp::ListRange
+    ListRangeStep(),
+    /// Fold a function over a list from the left. The accumulator is evaluated at each step.
+    ListFoldl(),
+    /// Fold a function over a list from the right. As for [`ListFoldl()`], the function takes the
+    /// accumulator first and the element second.
+    ///
+    /// [`ListFoldl()`]: NAryOp::ListFoldl
+    ListFoldr(),
+    /// Pad a string on the left with a pad string, up to a given width.
+    StrPadLeft(),
+    /// Pad a string on the right with a pad string, up to a given width.
+    StrPadRight(),
+    /// Serialize a value to a string, as [`BinaryOp::Serialize`], with an explicit layout. The
+    /// arguments are in order the format, the layout as an enum tag (`Pretty` or `Compact`), and
+    /// the value to serialize.
+    SerializeWith(),
+    /// Compute the HMAC of a message. The arguments are in order the hash function (`Sha256` or
+    /// `Sha512`), the key and the message.
+    Hmac(),
+    /// Set the value at a field path in a record, creating the intermediate records as needed.
+    /// The arguments are in order the record, the path as a list of strings, and the value. An
+    /// empty path replaces the whole record. The nested fields are updated lazily, such that
+    /// traversing a non-record is only an error once the corresponding field is accessed.
+    RecordSetPath(),
+    /// Get the value at a field path in a record, or a default value if a field of the path is
+    /// missing or a value along the path is not a record. The arguments are in order the record,
+    /// the path as a list of strings, and the default value. An empty path returns the record
+    /// itself. The default value is only evaluated if it is returned.
+    RecordGetPathOr(),
     /// The merge operator in contract mode (see the [merge module](../merge/index.html)). The
     /// arguments are in order the contract's label, the value to check, and the contract as a
     /// record.
     MergeContract(),
+    /// The merge operator in lists by key mode (see the [merge module](../merge/index.html)). The
+    /// arguments are in order the name of the key field and the two values to merge.
+    MergeByKey(),
+    /// Merge two lists by key, once the value of the key field of their elements have been
+    /// evaluated. The arguments are in order the name of the key field, the two lists, and the
+    /// list of the keys of their elements.
+    ///
+    /// Only produced during the evaluation of `MergeByKey`.
+    MergeListsByKey(),
 }
 
 impl NAryOp {
@@ -889,12 +1384,33 @@ impl NAryOp {
             NAryOp::StrReplace()
             | NAryOp::StrReplaceRegex()
             | NAryOp::StrSubstr()
-            | NAryOp::MergeContract() => 3,
+            | NAryOp::StrSplitN()
+            | NAryOp::ListRangeStep()
+            | NAryOp::ListFoldl()
+            | NAryOp::ListFoldr()
+            | NAryOp::StrPadLeft()
+            | NAryOp::StrPadRight()
+            | NAryOp::SerializeWith()
+            | NAryOp::Hmac()
+            | NAryOp::RecordSetPath()
+            | NAryOp::RecordGetPathOr()
+            | NAryOp::MergeContract()
+            | NAryOp::MergeByKey() => 3,
+            NAryOp::StrReplaceN() | NAryOp::MergeListsByKey() => 4,
+        }
+    }
+
+    /// The number of leading arguments which are evaluated before applying the operator. The
+    /// remaining arguments are passed unevaluated.
+    pub fn strict_arity(&self) -> usize {
+        match self {
+            NAryOp::RecordGetPathOr() => 2,
+            _ => self.arity(),
         }
     }
 
     pub fn is_strict(&self) -> bool {
-        true
+        !matches!(self, NAryOp::MergeByKey())
     }
 }
 
@@ -903,8 +1419,21 @@ impl fmt::Display for NAryOp {
         match self {
             NAryOp::StrReplace() => write!(f, "strReplace"),
             NAryOp::StrReplaceRegex() => write!(f, "strReplaceRegex"),
+            NAryOp::StrReplaceN() => write!(f, "strReplaceN"),
             NAryOp::StrSubstr() => write!(f, "substring"),
+            NAryOp::StrSplitN() => write!(f, "strSplitN"),
+            NAryOp::ListRangeStep() => write!(f, "rangeStep"),
+            NAryOp::ListFoldl() => write!(f, "foldl"),
+            NAryOp::ListFoldr() => write!(f, "foldr"),
+            NAryOp::StrPadLeft() => write!(f, "strPadLeft"),
+            NAryOp::StrPadRight() => write!(f, "strPadRight"),
+            NAryOp::SerializeWith() => write!(f, "serializeWith"),
+            NAryOp::Hmac() => write!(f, "hmac"),
+            NAryOp::RecordSetPath() => write!(f, "recordSetPath"),
+            NAryOp::RecordGetPathOr() => write!(f, "recordGetPathOr"),
             NAryOp::MergeContract() => write!(f, "mergeContract"),
+            NAryOp::MergeByKey() => write!(f, "mergeByKey"),
+            NAryOp::MergeListsByKey() => write!(f, "mergeListsByKey"),
         }
     }
 }
@@ -952,6 +1481,32 @@ impl RichTerm {
         self
     }
 
+    /// Compute a hash of the content of a term, which can be used for content-addressed caching.
+    ///
+    /// The hash only depends on the structure and on the literals of the term: positions are
+    /// ignored, and so are the names of generated variables, as long as they are used consistently.
+    /// Numbers are hashed by their bit pattern. The hash is stable across runs, but not across
+    /// different builds of Nickel.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = ContentHasher::new(DefaultHasher::new());
+        hasher.term(self);
+        hasher.state.finish()
+    }
+
+    /// Determine if two terms have the same content, in the sense of
+    /// [`content_hash`](#method.content_hash): they are structurally equal up to positions and to a
+    /// consistent renaming of generated variables. Contrary to the comparison of content hashes,
+    /// this comparison is exact.
+    pub fn content_eq(&self, other: &RichTerm) -> bool {
+        let encode = |rt: &RichTerm| {
+            let mut hasher = ContentHasher::new(ContentEncoder::default());
+            hasher.term(rt);
+            hasher.state.0
+        };
+
+        encode(self) == encode(other)
+    }
+
     /// Apply a transformation on a whole term by mapping a function `f` on each node in
     /// manner defined by the method.
     /// `f` may return a generic error `E` and use the state `S` which is passed around.
@@ -1061,7 +1616,7 @@ impl RichTerm {
             Term::Record(map, attrs) => {
                 // The annotation on `map_res` uses Result's corresponding trait to convert from
                 // Iterator<Result> to a Result<Iterator>
-                let map_res: Result<HashMap<Ident, RichTerm>, E> = map
+                let map_res: Result<IndexMap<Ident, RichTerm>, E> = map
                     .into_iter()
                     // For the conversion to work, note that we need a Result<(Ident,RichTerm), E>
                     .map(|(id, t)| t.traverse(f, state, method).map(|t_ok| (id.clone(), t_ok)))
@@ -1074,7 +1629,7 @@ impl RichTerm {
             Term::RecRecord(map, dyn_fields, attrs) => {
                 // The annotation on `map_res` uses Result's corresponding trait to convert from
                 // Iterator<Result> to a Result<Iterator>
-                let map_res: Result<HashMap<Ident, RichTerm>, E> = map
+                let map_res: Result<IndexMap<Ident, RichTerm>, E> = map
                     .into_iter()
                     // For the conversion to work, note that we need a Result<(Ident,RichTerm), E>
                     .map(|(id, t)| Ok((id, t.traverse(f, state, method)?)))
@@ -1195,6 +1750,287 @@ impl From<Term> for RichTerm {
     }
 }
 
+/// A hasher which records all the data fed to it instead of digesting it. Since the content hasher
+/// writes the discriminants, the lengths and the delimiters of the data it hashes, this record is
+/// an unambiguous encoding of the content of a term.
+#[derive(Default)]
+struct ContentEncoder(Vec<u8>);
+
+impl Hasher for ContentEncoder {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        unreachable!("the content encoder is only used to compare encodings")
+    }
+}
+
+/// State of the computation of the content hash of a term (see
+/// [`RichTerm::content_hash`](struct.RichTerm.html#method.content_hash)).
+///
+/// Generated identifiers (see [`Ident::is_generated`]) are hashed as the order of their first
+/// occurrence in the term, such that terms which only differ by the fresh variables introduced by
+/// program transformations have the same hash.
+struct ContentHasher<H: Hasher> {
+    state: H,
+    generated: HashMap<Ident, usize>,
+}
+
+impl<H: Hasher> ContentHasher<H> {
+    fn new(state: H) -> Self {
+        ContentHasher {
+            state,
+            generated: HashMap::new(),
+        }
+    }
+
+    fn ident(&mut self, id: &Ident) {
+        if id.is_generated() {
+            let next = self.generated.len();
+            let index = *self.generated.entry(id.clone()).or_insert(next);
+            true.hash(&mut self.state);
+            index.hash(&mut self.state);
+        } else {
+            false.hash(&mut self.state);
+            id.label.hash(&mut self.state);
+        }
+    }
+
+    fn opt_ident(&mut self, id: &Option<Ident>) {
+        id.is_some().hash(&mut self.state);
+        if let Some(id) = id {
+            self.ident(id);
+        }
+    }
+
+    /// Hash the fields of a record in their definition order, which is observable.
+    fn fields(&mut self, fields: &IndexMap<Ident, RichTerm>) {
+        fields.len().hash(&mut self.state);
+        for (id, t) in fields {
+            self.ident(id);
+            self.term(t);
+        }
+    }
+
+    /// Hash the cases of a switch, in the order of the keys, to be independent of the iteration
+    /// order of the map.
+    fn cases(&mut self, cases: &HashMap<Ident, RichTerm>) {
+        let mut sorted: Vec<_> = cases.iter().collect();
+        sorted.sort_by_key(|&(id, _)| id);
+
+        sorted.len().hash(&mut self.state);
+        for (id, t) in sorted {
+            self.ident(id);
+            self.term(t);
+        }
+    }
+
+    fn terms<'a, I>(&mut self, ts: I)
+    where
+        I: ExactSizeIterator<Item = &'a RichTerm>,
+    {
+        ts.len().hash(&mut self.state);
+        for t in ts {
+            self.term(t);
+        }
+    }
+
+    fn types(&mut self, ty: &Types) {
+        std::mem::discriminant(&ty.0).hash(&mut self.state);
+        match &ty.0 {
+            AbsType::Dyn()
+            | AbsType::Num()
+            | AbsType::Bool()
+            | AbsType::Str()
+            | AbsType::Sym()
+            | AbsType::RowEmpty() => (),
+            AbsType::Flat(t) => self.term(t),
+            AbsType::Arrow(s, t) => {
+                self.types(s);
+                self.types(t);
+            }
+            AbsType::Var(id) => self.ident(id),
+            AbsType::Forall(id, t) => {
+                self.ident(id);
+                self.types(t);
+            }
+            AbsType::RowExtend(id, ty_opt, tail) => {
+                self.ident(id);
+                ty_opt.is_some().hash(&mut self.state);
+                if let Some(ty) = ty_opt {
+                    self.types(ty);
+                }
+                self.types(tail);
+            }
+            AbsType::Enum(t)
+            | AbsType::StaticRecord(t)
+            | AbsType::DynRecord(t)
+            | AbsType::List(t) => self.types(t),
+        }
+    }
+
+    /// Hash a contract. Only the static information of the label is taken into account, and not
+    /// its positions nor the data set at run-time.
+    fn contract(&mut self, ctr: &Contract) {
+        self.types(&ctr.types);
+        self.types(&ctr.label.types);
+        ctr.label.tag.hash(&mut self.state);
+        ctr.label.polarity.hash(&mut self.state);
+    }
+
+    fn meta_value(&mut self, meta: &MetaValue) {
+        meta.doc.hash(&mut self.state);
+        meta.types.is_some().hash(&mut self.state);
+        if let Some(ctr) = &meta.types {
+            self.contract(ctr);
+        }
+        meta.contracts.len().hash(&mut self.state);
+        for ctr in meta.contracts.iter() {
+            self.contract(ctr);
+        }
+        meta.priority.hash(&mut self.state);
+        meta.value.is_some().hash(&mut self.state);
+        if let Some(t) = &meta.value {
+            self.term(t);
+        }
+    }
+
+    fn matches(&mut self, matches: &[Match]) {
+        matches.len().hash(&mut self.state);
+        for m in matches {
+            std::mem::discriminant(m).hash(&mut self.state);
+            match m {
+                Match::Assign(id, meta, (alias, d)) => {
+                    self.ident(id);
+                    self.meta_value(meta);
+                    self.opt_ident(alias);
+                    self.destruct(d);
+                }
+                Match::Simple(id, meta) => {
+                    self.ident(id);
+                    self.meta_value(meta);
+                }
+            }
+        }
+    }
+
+    fn destruct(&mut self, d: &Destruct) {
+        std::mem::discriminant(d).hash(&mut self.state);
+        match d {
+            Destruct::Record(matches, open, rest) => {
+                self.matches(matches);
+                open.hash(&mut self.state);
+                self.opt_ident(rest);
+            }
+            Destruct::List(matches) => self.matches(matches),
+            Destruct::Empty => (),
+        }
+    }
+
+    fn term(&mut self, rt: &RichTerm) {
+        let term = rt.as_ref();
+        std::mem::discriminant(term).hash(&mut self.state);
+
+        match term {
+            Term::Null | Term::ParseError => (),
+            Term::Bool(b) => b.hash(&mut self.state),
+            Term::Num(n) => n.to_bits().hash(&mut self.state),
+            Term::Str(s) => s.hash(&mut self.state),
+            Term::StrChunks(chunks) => {
+                chunks.len().hash(&mut self.state);
+                for chunk in chunks {
+                    match chunk {
+                        StrChunk::Literal(s) => {
+                            false.hash(&mut self.state);
+                            s.hash(&mut self.state);
+                        }
+                        StrChunk::Expr(t, indent) => {
+                            true.hash(&mut self.state);
+                            indent.hash(&mut self.state);
+                            self.term(t);
+                        }
+                    }
+                }
+            }
+            Term::Fun(id, t) => {
+                self.ident(id);
+                self.term(t);
+            }
+            Term::FunPattern(id, d, t) => {
+                self.opt_ident(id);
+                self.destruct(d);
+                self.term(t);
+            }
+            Term::Lbl(l) => {
+                self.types(&l.types);
+                l.tag.hash(&mut self.state);
+                l.polarity.hash(&mut self.state);
+            }
+            Term::Let(id, t1, t2, btype) => {
+                self.ident(id);
+                self.term(t1);
+                self.term(t2);
+                btype.hash(&mut self.state);
+            }
+            Term::LetPattern(id, d, t1, t2) => {
+                self.opt_ident(id);
+                self.destruct(d);
+                self.term(t1);
+                self.term(t2);
+            }
+            Term::App(t1, t2) => {
+                self.term(t1);
+                self.term(t2);
+            }
+            Term::Var(id) | Term::Enum(id) => self.ident(id),
+            Term::Record(fields, attrs) => {
+                self.fields(fields);
+                attrs.open.hash(&mut self.state);
+            }
+            Term::RecRecord(fields, dyn_fields, attrs) => {
+                self.fields(fields);
+                dyn_fields.len().hash(&mut self.state);
+                for (id_t, t) in dyn_fields {
+                    self.term(id_t);
+                    self.term(t);
+                }
+                attrs.open.hash(&mut self.state);
+            }
+            Term::Switch(t, cases, default) => {
+                self.term(t);
+                self.cases(cases);
+                default.is_some().hash(&mut self.state);
+                if let Some(default) = default {
+                    self.term(default);
+                }
+            }
+            Term::List(ts) => self.terms(ts.iter()),
+            Term::Op1(op, t) => {
+                op.hash(&mut self.state);
+                self.term(t);
+            }
+            Term::Op2(op, t1, t2) => {
+                op.hash(&mut self.state);
+                self.term(t1);
+                self.term(t2);
+            }
+            Term::OpN(op, ts) => {
+                op.hash(&mut self.state);
+                self.terms(ts.iter());
+            }
+            Term::Sym(i) => i.hash(&mut self.state),
+            Term::Wrapped(i, t) => {
+                i.hash(&mut self.state);
+                self.term(t);
+            }
+            Term::MetaValue(meta) => self.meta_value(meta),
+            Term::Import(path) | Term::ImportStr(path) => path.hash(&mut self.state),
+            Term::ResolvedImport(file_id) => file_id.hash(&mut self.state),
+        }
+    }
+}
+
 /// Allows to match on SharedTerm without taking ownership of the matched part until the match.
 /// In the `else` clause, we haven't taken ownership yet, so we can still use the richterm at that point.
 ///
@@ -1291,7 +2127,7 @@ pub mod make {
     macro_rules! mk_record {
         ( $( ($id:expr, $body:expr) ),* ) => {
             {
-                let mut map = std::collections::HashMap::new();
+                let mut map = $crate::term::IndexMap::new();
                 $(
                     map.insert($id.into(), $body.into());
                 )*
@@ -1362,7 +2198,6 @@ pub mod make {
         }
     }
 
-    #[cfg(test)]
     pub fn if_then_else<T1, T2, T3>(cond: T1, t1: T2, t2: T3) -> RichTerm
     where
         T1: Into<RichTerm>,
@@ -1439,4 +2274,154 @@ mod tests {
         let res = MetaValue::flatten(outer, inner);
         assert_ne!(res.types, None);
     }
+
+    fn parse(s: &str) -> RichTerm {
+        use crate::parser::{grammar, lexer};
+        use codespan::Files;
+
+        let id = Files::new().add("<test>", String::from(s));
+        grammar::TermParser::new()
+            .parse_term(id, lexer::Lexer::new(s))
+            .unwrap()
+    }
+
+    #[test]
+    fn content_hash_ignores_positions() {
+        let t1 = parse("let x = {a = 1, b = \"s\"} in x.a + 2");
+        let t2 = parse("let x   =   { a = 1,b = \"s\" }\nin x.a+2");
+        assert_ne!(t1.pos, t2.pos);
+        assert_eq!(t1.content_hash(), t2.content_hash());
+        assert_eq!(t1.content_hash(), t1.clone().without_pos().content_hash());
+    }
+
+    #[test]
+    fn content_hash_ignores_generated_names() {
+        let t1 = mk_fun!("%1", mk_app!(make::var("%1"), make::var("%2")));
+        let t2 = mk_fun!("%7", mk_app!(make::var("%7"), make::var("%3")));
+        assert_eq!(t1.content_hash(), t2.content_hash());
+
+        // Generated variables must still be used consistently
+        let t3 = mk_fun!("%7", mk_app!(make::var("%3"), make::var("%7")));
+        assert_ne!(t1.content_hash(), t3.content_hash());
+        // User-written names are not ignored
+        let t4 = mk_fun!("x", mk_app!(make::var("x"), make::var("%2")));
+        assert_ne!(t1.content_hash(), t4.content_hash());
+    }
+
+    #[test]
+    fn content_hash_distinguishes_structures() {
+        let hashes: Vec<u64> = [
+            "1",
+            "2",
+            "0.1 + 0.2",
+            "0.3",
+            "\"1\"",
+            "[1, 2]",
+            "[2, 1]",
+            "[[1], 2]",
+            "{a = 1}",
+            "{a = 1, b = 1}",
+            "{b = 1, a = 1}",
+            "{b = 1}",
+            "fun x => x",
+            "fun x y => x",
+            "let x = 1 in x",
+            "`a",
+            "x 1",
+            "1 + 2",
+            "1 - 2",
+            "%head% [1]",
+            "%tail% [1]",
+        ]
+        .iter()
+        .map(|s| parse(s).content_hash())
+        .collect();
+
+        for (i, h1) in hashes.iter().enumerate() {
+            for h2 in hashes[i + 1..].iter() {
+                assert_ne!(h1, h2);
+            }
+        }
+    }
+
+    #[test]
+    fn content_hash_floats() {
+        assert_eq!(
+            RichTerm::from(Term::Num(f64::NAN)).content_hash(),
+            RichTerm::from(Term::Num(f64::NAN)).content_hash()
+        );
+        assert_ne!(
+            RichTerm::from(Term::Num(0.1 + 0.2)).content_hash(),
+            RichTerm::from(Term::Num(0.3)).content_hash()
+        );
+    }
+
+    #[test]
+    fn merge_path() {
+        let path = MergePath::default()
+            .with_field(Ident::from("a"))
+            .with_field(Ident::from("b"));
+        assert_eq!(path.to_vec(), vec![Ident::from("a"), Ident::from("b")]);
+
+        let merge = |path| make::op2(BinaryOp::Merge(path), Term::Null, Term::Null);
+        let t1 = merge(path.clone());
+        let t2 = merge(
+            MergePath::default()
+                .with_field(Ident::from("a"))
+                .with_field(Ident::from("b")),
+        );
+        assert_eq!(t1, t2);
+        assert_eq!(t1.content_hash(), t2.content_hash());
+
+        let t3 = merge(path.with_field(Ident::from("c")));
+        assert_ne!(t1, t3);
+        assert_ne!(t1, merge(MergePath::default()));
+        assert_ne!(t1.content_hash(), t3.content_hash());
+    }
+
+    #[test]
+    fn content_eq() {
+        let t1 = parse("let x = {a = 1, b = \"s\"} in x.a + 2");
+        let t2 = parse("let x   =   { a = 1,b = \"s\" }\nin x.a+2");
+        assert!(t1.content_eq(&t2));
+        assert!(!t1.content_eq(&parse("let x = {a = 1, b = \"s\"} in x.a + 3")));
+        // The order of fields is observable
+        assert!(!t1.content_eq(&parse("let x = {b = \"s\", a = 1} in x.a + 2")));
+
+        let t1 = mk_fun!("%1", mk_app!(make::var("%1"), make::var("%2")));
+        let t2 = mk_fun!("%7", mk_app!(make::var("%7"), make::var("%3")));
+        let t3 = mk_fun!("%7", mk_app!(make::var("%3"), make::var("%7")));
+        assert!(t1.content_eq(&t2));
+        assert!(!t1.content_eq(&t3));
+
+        // Strings are delimited, such that their contents can't spill over the next literal
+        assert!(!parse("[\"ab\", \"c\"]").content_eq(&parse("[\"a\", \"bc\"]")));
+    }
+
+    /// Dropping a deeply nested term must not overflow the stack.
+    #[test]
+    fn drop_deep_terms() {
+        let mut list = Term::List(Vec::new());
+        let mut record = Term::Record(IndexMap::new(), Default::default());
+        let mut app = Term::Null;
+
+        for _ in 0..100_000 {
+            list = Term::List(vec![RichTerm::from(list), Term::Num(1.0).into()]);
+            record = Term::Record(
+                std::iter::once((Ident::from("a"), RichTerm::from(record))).collect(),
+                Default::default(),
+            );
+            app = Term::App(Term::Var(Ident::from("f")).into(), app.into());
+        }
+
+        // Shared subterms are only freed with their last reference
+        let shared = RichTerm::from(list);
+        let copy = shared.clone();
+        drop(shared);
+        assert!(matches!(copy.as_ref(), Term::List(ts) if ts.len() == 2));
+
+        drop(copy);
+        drop(record);
+        drop(app);
+    }
 }
@@ -1,6 +1,7 @@
 //! Import resolution. Search for imports in the AST, load the corresponding file in the cache, and
 //! replace the original import node by a resolved import one, which stores the corresponding file
-//! identifier directly.
+//! identifier directly. Imports of raw files via `importStr` are directly replaced by the content
+//! of the file.
 use super::ImportResolver;
 use crate::error::ImportError;
 use crate::term::{RichTerm, Term, TraverseMethod};
@@ -78,6 +79,10 @@ where
             let (_, file_id) = resolver.resolve(path, parent.clone(), &rt.pos)?;
             Ok(RichTerm::new(Term::ResolvedImport(file_id), rt.pos))
         }
+        Term::ImportStr(path) => {
+            let content = resolver.resolve_str(path, parent.clone(), &rt.pos)?;
+            Ok(RichTerm::new(Term::Str(content), rt.pos))
+        }
         _ => Ok(rt),
     }
 }
@@ -46,6 +46,11 @@ pub fn transform(rt: RichTerm) -> RichTerm {
 }
 
 /// Generate a new fresh variable which do not clash with user-defined variables.
+///
+/// All the fresh variables are drawn from the same counter, which is never reset. Generated
+/// variables are thus unique across all the terms transformed by a thread, and in particular
+/// across the different files of a program, which can then be merged or imported into one another
+/// without risking a capture.
 pub fn fresh_var() -> Ident {
     use crate::identifier::GEN_PREFIX;
 
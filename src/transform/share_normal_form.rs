@@ -79,6 +79,14 @@ pub fn transform_one(rt: RichTerm) -> RichTerm {
                 // fields of recursive records contain either a constant or a *generated* variable,
                 // but never a user-supplied variable directly (the former starts with a special
                 // marker). See comments inside [`RichTerm::closurize`] for more details.
+                //
+                // Fields which already are generated variables satisfy this post-condition, and
+                // are left untouched. This happens when a term which is already in share normal
+                // form is transformed again: without this exception, each transformation would
+                // add a new chain of `let`s, one per field. Generated variables are unique (see
+                // [`super::fresh_var`]), so such a variable has been introduced for this very
+                // field, and it is safe for the evaluation to patch its thunk with the recursive
+                // environment.
                 let mut bindings = Vec::with_capacity(map.len());
 
                 let map = map
@@ -86,7 +94,7 @@ pub fn transform_one(rt: RichTerm) -> RichTerm {
                     .map(|(id, t)| {
                         // CHANGE THIS CONDITION CAREFULLY. Doing so can break the post-condition
                         // explained above.
-                        if !t.as_ref().is_constant() {
+                        if should_share_rec(&t.term) {
                             let fresh_var = fresh_var();
                             let pos_t = t.pos;
                             bindings.push((fresh_var.clone(), t));
@@ -100,7 +108,7 @@ pub fn transform_one(rt: RichTerm) -> RichTerm {
                 let dyn_fields = dyn_fields
                     .into_iter()
                     .map(|(id_t, t)| {
-                        if !t.as_ref().is_constant() {
+                        if should_share_rec(&t.term) {
                             let fresh_var = fresh_var();
                             let pos_t = t.pos;
                             bindings.push((fresh_var.clone(), t));
@@ -165,6 +173,15 @@ fn should_share(t: &Term) -> bool {
     }
 }
 
+/// Determine if the field of a recursive record should be wrapped in a thunk. All the fields must
+/// be, except constants and generated variables, which are already bound to a thunk.
+fn should_share_rec(t: &Term) -> bool {
+    match t {
+        Term::Var(id) => !id.is_generated(),
+        t => !t.is_constant(),
+    }
+}
+
 /// Bind a list of pairs `(identifier, term)` in a term.
 ///
 /// Given the term `body` and bindings of identifiers to terms represented as a list of pairs
@@ -182,3 +199,52 @@ fn with_bindings(
             RichTerm::new(Term::Let(id, t, acc, btype), pos)
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::term::make as mk_term;
+    use crate::term::{BinaryOp, RecordAttrs};
+
+    fn rec_record(fields: Vec<(Ident, RichTerm)>) -> RichTerm {
+        Term::RecRecord(
+            fields.into_iter().collect(),
+            Vec::new(),
+            RecordAttrs::default(),
+        )
+        .into()
+    }
+
+    #[test]
+    fn generated_fields_are_not_shared_again() {
+        let fields: Vec<_> = (0..100)
+            .map(|i| (Ident::from(format!("f{}", i)), mk_term::var(fresh_var())))
+            .collect();
+
+        let rt = transform_one(rec_record(fields.clone()));
+        assert_eq!(rt.without_pos(), rec_record(fields).without_pos());
+    }
+
+    #[test]
+    fn user_fields_are_shared() {
+        let fields = vec![
+            (Ident::from("gen"), mk_term::var(fresh_var())),
+            (Ident::from("user"), mk_term::var("x")),
+            (
+                Ident::from("op"),
+                mk_term::op2(BinaryOp::Plus(), Term::Num(1.0), Term::Num(1.0)),
+            ),
+            (Ident::from("const"), Term::Num(1.0).into()),
+        ];
+
+        let mut rt = transform_one(rec_record(fields));
+        let mut bindings = 0;
+        while let Term::Let(_, _, body, BindingType::Revertible) = rt.as_ref() {
+            bindings += 1;
+            rt = body.clone();
+        }
+
+        assert_eq!(bindings, 2);
+        assert!(matches!(rt.as_ref(), Term::RecRecord(..)));
+    }
+}
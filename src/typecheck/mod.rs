@@ -43,7 +43,7 @@
 use crate::cache::ImportResolver;
 use crate::environment::Environment as GenericEnvironment;
 use crate::error::TypecheckError;
-use crate::identifier::Ident;
+use crate::identifier::{closest_idents, Ident};
 use crate::term::{Contract, MetaValue, RichTerm, StrChunk, Term};
 use crate::types::{AbsType, Types};
 use crate::{mk_tyw_arrow, mk_tyw_enum, mk_tyw_enum_row, mk_tyw_record, mk_tyw_row};
@@ -157,6 +157,14 @@ impl<'a> Envs<'a> {
         self.local.get(ident).or_else(|| self.global.get(ident))
     }
 
+    /// Iterate over the identifiers bound in the local and the global environments.
+    pub fn idents(&self) -> impl Iterator<Item = &Ident> {
+        self.local
+            .iter_elems()
+            .chain(self.global.iter_elems())
+            .map(|(id, _)| id)
+    }
+
     /// Wrapper to insert a new binding in the local environment.
     pub fn insert(&mut self, ident: Ident, tyw: TypeWrapper) {
         self.local.insert(ident, tyw);
@@ -486,9 +494,9 @@ fn type_check_<L: Linearizer>(
             type_check_(state, envs, lin, linearizer, strict, exp, mk_tyw_enum!(row))
         }
         Term::Var(x) => {
-            let x_ty = envs
-                .get(x)
-                .ok_or_else(|| TypecheckError::UnboundIdentifier(x.clone(), *pos))?;
+            let x_ty = envs.get(x).ok_or_else(|| {
+                TypecheckError::UnboundIdentifier(x.clone(), closest_idents(x, envs.idents()), *pos)
+            })?;
 
             let instantiated = instantiate_foralls(state, x_ty, ForallInst::Ptr);
             unify(state, strict, ty, instantiated)
@@ -701,6 +709,8 @@ fn type_check_<L: Linearizer>(
             .map_err(|err| err.into_typecheck_err(state, rt.pos)),
         Term::Import(_) => unify(state, strict, ty, mk_typewrapper::dynamic())
             .map_err(|err| err.into_typecheck_err(state, rt.pos)),
+        Term::ImportStr(_) => unify(state, strict, ty, mk_typewrapper::str())
+            .map_err(|err| err.into_typecheck_err(state, rt.pos)),
         // We use the apparent type of the import for checking. This function doesn't recursively
         // typecheck imports: this is the responsibility of the caller.
         Term::ResolvedImport(file_id) => {
@@ -823,7 +833,9 @@ pub fn apparent_type(
         Term::Num(_) => ApparentType::Inferred(Types(AbsType::Num())),
         Term::Bool(_) => ApparentType::Inferred(Types(AbsType::Bool())),
         Term::Sym(_) => ApparentType::Inferred(Types(AbsType::Sym())),
-        Term::Str(_) | Term::StrChunks(_) => ApparentType::Inferred(Types(AbsType::Str())),
+        Term::Str(_) | Term::StrChunks(_) | Term::ImportStr(_) => {
+            ApparentType::Inferred(Types(AbsType::Str()))
+        }
         Term::List(_) => {
             ApparentType::Approximated(Types(AbsType::List(Box::new(Types(AbsType::Dyn())))))
         }
@@ -1134,6 +1146,13 @@ pub fn unify_(
             )),
         },
         (TypeWrapper::Ptr(p1), TypeWrapper::Ptr(p2)) if p1 == p2 => Ok(()),
+        // Assigning to a variable a type which contains it would create an infinite type
+        (TypeWrapper::Ptr(p), tyw) if occurs(state.table, p, &tyw) => {
+            Err(UnifError::TypeMismatch(TypeWrapper::Ptr(p), tyw))
+        }
+        (tyw, TypeWrapper::Ptr(p)) if occurs(state.table, p, &tyw) => {
+            Err(UnifError::TypeMismatch(tyw, TypeWrapper::Ptr(p)))
+        }
         // The two following cases are not merged just to correctly distinguish between the
         // expected type (first component of the tuple) and the inferred type when reporting a row
         // unification error.
@@ -1159,6 +1178,25 @@ pub fn unify_(
     }
 }
 
+/// Check if the unification variable `p` occurs in the type `tyw`.
+fn occurs(table: &UnifTable, p: usize, tyw: &TypeWrapper) -> bool {
+    match tyw {
+        TypeWrapper::Ptr(q) => match table.root(*q) {
+            TypeWrapper::Ptr(root) => root == p,
+            tyw => occurs(table, p, &tyw),
+        },
+        TypeWrapper::Constant(_) => false,
+        TypeWrapper::Concrete(t) => {
+            let mut found = false;
+            t.clone().map(|ty| {
+                found = found || occurs(table, p, &ty);
+                ty
+            });
+            found
+        }
+    }
+}
+
 /// Try to unify two row types. Return an [`IllformedRow`](./enum.RowUnifError.html#variant.IllformedRow) error if one of the given type
 /// is not a row type.
 pub fn unify_rows(
@@ -28,10 +28,16 @@ pub fn get_uop_type(
         | UnaryOp::IsStr()
         | UnaryOp::IsFun()
         | UnaryOp::IsList()
-        | UnaryOp::IsRecord() => {
+        | UnaryOp::IsRecord()
+        | UnaryOp::IsNull() => {
             let inp = TypeWrapper::Ptr(state.table.fresh_var());
             (inp, mk_typewrapper::bool())
         }
+        // forall a. a -> Str
+        UnaryOp::TypeOf() => {
+            let inp = TypeWrapper::Ptr(state.table.fresh_var());
+            (inp, mk_typewrapper::str())
+        }
         // Bool -> Bool -> Bool
         UnaryOp::BoolAnd() | UnaryOp::BoolOr() => (
             mk_typewrapper::bool(),
@@ -72,6 +78,18 @@ pub fn get_uop_type(
 
             (mk_tyw_record!((id.clone(), res.clone()); row), res)
         }
+        // forall rows a. { rows } -> a -> { id: a | rows }
+        UnaryOp::RecordInsert(id) => {
+            let row = TypeWrapper::Ptr(state.table.fresh_var());
+            let res = TypeWrapper::Ptr(state.table.fresh_var());
+            // Constraining a freshly created variable should never fail.
+            constraint(state, row.clone(), id.clone()).unwrap();
+
+            (
+                mk_tyw_record!(; row.clone()),
+                mk_tyw_arrow!(res.clone(), mk_tyw_record!((id.clone(), res); row)),
+            )
+        }
         // forall a b. List a -> (a -> b) -> List b
         UnaryOp::ListMap() => {
             let a = TypeWrapper::Ptr(state.table.fresh_var());
@@ -83,6 +101,30 @@ pub fn get_uop_type(
                 mk_tyw_arrow!(f_type, mk_typewrapper::list(b)),
             )
         }
+        // forall a. List a -> (a -> a -> <Less, Equal, Greater>) -> List a
+        UnaryOp::ListSort() => {
+            let a = TypeWrapper::Ptr(state.table.fresh_var());
+
+            let f_type = mk_tyw_arrow!(
+                a.clone(),
+                a.clone(),
+                mk_tyw_enum!("Less", "Equal", "Greater", mk_typewrapper::row_empty())
+            );
+            (
+                mk_typewrapper::list(a.clone()),
+                mk_tyw_arrow!(f_type, mk_typewrapper::list(a)),
+            )
+        }
+        // This should not happen, as ListSortStep() is only produced during evaluation.
+        UnaryOp::ListSortStep(..) => panic!("cannot type ListSortStep()"),
+        // This should not happen, as ListFilterStep() is only produced during evaluation.
+        UnaryOp::ListFilterStep() => panic!("cannot type ListFilterStep()"),
+        // This should not happen, as RecordFilterStep() is only produced during evaluation.
+        UnaryOp::RecordFilterStep() => panic!("cannot type RecordFilterStep()"),
+        // This should not happen, as ListFoldlStep() is only produced during evaluation.
+        UnaryOp::ListFoldlStep() => panic!("cannot type ListFoldlStep()"),
+        // This should not happen, as ListQuantifierStep() is only produced during evaluation.
+        UnaryOp::ListQuantifierStep(_) => panic!("cannot type ListQuantifierStep()"),
         // forall a. Num -> (Num -> a) -> List a
         UnaryOp::ListGen() => {
             let a = TypeWrapper::Ptr(state.table.fresh_var());
@@ -115,23 +157,35 @@ pub fn get_uop_type(
             (fst, mk_tyw_arrow!(snd.clone(), snd))
         }
         // forall a. List a -> a
-        UnaryOp::ListHead() => {
+        UnaryOp::ListHead() | UnaryOp::ListLast() => {
             let ty_elt = TypeWrapper::Ptr(state.table.fresh_var());
             (mk_typewrapper::list(ty_elt.clone()), ty_elt)
         }
         // forall a. List a -> List a
-        UnaryOp::ListTail() => {
+        UnaryOp::ListTail() | UnaryOp::ListInit() | UnaryOp::ListReverse() => {
             let ty_elt = TypeWrapper::Ptr(state.table.fresh_var());
             (
                 mk_typewrapper::list(ty_elt.clone()),
                 mk_typewrapper::list(ty_elt),
             )
         }
+        // forall a. List (List a) -> List a
+        UnaryOp::ListFlatten() => {
+            let ty_elt = TypeWrapper::Ptr(state.table.fresh_var());
+            (
+                mk_typewrapper::list(mk_typewrapper::list(ty_elt.clone())),
+                mk_typewrapper::list(ty_elt),
+            )
+        }
         // forall a. List a -> Num
         UnaryOp::ListLength() => {
             let ty_elt = TypeWrapper::Ptr(state.table.fresh_var());
             (mk_typewrapper::list(ty_elt), mk_typewrapper::num())
         }
+        // List Num -> Num
+        UnaryOp::ListSum() => (mk_typewrapper::list(AbsType::Num()), mk_typewrapper::num()),
+        // This should not happen, as BoolGuard() is only produced during evaluation.
+        UnaryOp::BoolGuard(_) => panic!("cannot type BoolGuard()"),
         // This should not happen, as ChunksConcat() is only produced during evaluation.
         UnaryOp::ChunksConcat() => panic!("cannot type ChunksConcat()"),
         // BEFORE: forall rows. { rows } -> List
@@ -141,18 +195,43 @@ pub fn get_uop_type(
             //mk_tyw_record!(; TypeWrapper::Ptr(state.table.fresh_var())),
             mk_typewrapper::list(AbsType::Str()),
         ),
+        // {_: Dyn} -> {_: Dyn}
+        UnaryOp::RecordElideNulls(_) => (
+            mk_typewrapper::dyn_record(mk_typewrapper::dynamic()),
+            mk_typewrapper::dyn_record(mk_typewrapper::dynamic()),
+        ),
         // Dyn -> List
         UnaryOp::ValuesOf() => (
             mk_typewrapper::dynamic(),
             mk_typewrapper::list(AbsType::Dyn()),
         ),
+        // forall a. { _ : a } -> List { field : Str, value : a }
+        UnaryOp::RecordToList() => {
+            let a = TypeWrapper::Ptr(state.table.fresh_var());
+            (
+                mk_typewrapper::dyn_record(a.clone()),
+                mk_typewrapper::list(mk_tyw_record!(("field", AbsType::Str()), ("value", a))),
+            )
+        }
         // Str -> Str
-        UnaryOp::StrTrim() => (mk_typewrapper::str(), mk_typewrapper::str()),
+        UnaryOp::StrTrim() | UnaryOp::StrTrimStart() | UnaryOp::StrTrimEnd() => {
+            (mk_typewrapper::str(), mk_typewrapper::str())
+        }
         // Str -> List Str
         UnaryOp::StrChars() => (
             mk_typewrapper::str(),
             mk_typewrapper::list(mk_typewrapper::str()),
         ),
+        // Str -> { lines: List Str, trailing_newline: Bool }
+        UnaryOp::StrLines() => (
+            mk_typewrapper::str(),
+            mk_tyw_record!(
+                ("lines", mk_typewrapper::list(AbsType::Str())),
+                ("trailing_newline", AbsType::Bool())
+            ),
+        ),
+        // Str -> Str
+        UnaryOp::StrDedent() => (mk_typewrapper::str(), mk_typewrapper::str()),
         // Str -> Num
         UnaryOp::CharCode() => (mk_typewrapper::str(), mk_typewrapper::num()),
         // Num -> Str
@@ -163,8 +242,13 @@ pub fn get_uop_type(
         UnaryOp::StrLowercase() => (mk_typewrapper::str(), mk_typewrapper::str()),
         // Str -> Num
         UnaryOp::StrLength() => (mk_typewrapper::str(), mk_typewrapper::num()),
+        // Str -> Str
+        UnaryOp::Base64Encode()
+        | UnaryOp::Base64Decode()
+        | UnaryOp::HexEncode()
+        | UnaryOp::HexDecode() => (mk_typewrapper::str(), mk_typewrapper::str()),
         // Dyn -> Str
-        UnaryOp::ToStr() => (mk_typewrapper::dynamic(), mk_typewrapper::num()),
+        UnaryOp::ToStr() => (mk_typewrapper::dynamic(), mk_typewrapper::str()),
         // Str -> Num
         UnaryOp::NumFromStr() => (mk_typewrapper::str(), mk_typewrapper::num()),
         // Str -> < | Dyn>
@@ -172,6 +256,26 @@ pub fn get_uop_type(
             mk_typewrapper::str(),
             mk_tyw_enum!(mk_typewrapper::dynamic()),
         ),
+        // forall rows. < | rows> -> Str
+        UnaryOp::EnumTag() => {
+            let row = TypeWrapper::Ptr(state.table.fresh_var());
+            (mk_tyw_enum!(row), mk_typewrapper::str())
+        }
+        // Num -> Num
+        UnaryOp::Floor() | UnaryOp::Ceil() | UnaryOp::Round() => {
+            (mk_typewrapper::num(), mk_typewrapper::num())
+        }
+        // Num -> Num
+        UnaryOp::Abs() | UnaryOp::Signum() => (mk_typewrapper::num(), mk_typewrapper::num()),
+        // Num -> Num
+        UnaryOp::Sqrt() | UnaryOp::Exp() | UnaryOp::Ln() | UnaryOp::Log10() => {
+            (mk_typewrapper::num(), mk_typewrapper::num())
+        }
+        // List Dyn -> Dyn
+        UnaryOp::Native(_) => (
+            mk_typewrapper::list(AbsType::Dyn()),
+            mk_typewrapper::dynamic(),
+        ),
     })
 }
 
@@ -272,6 +376,48 @@ pub fn get_bop_type(
             mk_typewrapper::dynamic(),
             mk_typewrapper::bool(),
         ),
+        // Dyn -> { _ : Dyn -> Dyn } -> Dyn
+        BinaryOp::MatchField() => (
+            mk_typewrapper::dynamic(),
+            mk_typewrapper::dyn_record(mk_tyw_arrow!(
+                mk_typewrapper::dynamic(),
+                mk_typewrapper::dynamic()
+            )),
+            mk_typewrapper::dynamic(),
+        ),
+        // Dyn -> Dyn -> {added: List Str, removed: List Str, common: List Str}
+        BinaryOp::RecordFieldsDiff() => (
+            mk_typewrapper::dynamic(),
+            mk_typewrapper::dynamic(),
+            mk_tyw_record!(
+                ("added", mk_typewrapper::list(AbsType::Str())),
+                ("removed", mk_typewrapper::list(AbsType::Str())),
+                ("common", mk_typewrapper::list(AbsType::Str()))
+            ),
+        ),
+        // forall a. { _ : a } -> (Str -> a -> Bool) -> { _ : a }
+        BinaryOp::RecordFilter() => {
+            let a = TypeWrapper::Ptr(state.table.fresh_var());
+            (
+                mk_typewrapper::dyn_record(a.clone()),
+                mk_tyw_arrow!(AbsType::Str(), a.clone(), AbsType::Bool()),
+                mk_typewrapper::dyn_record(a),
+            )
+        }
+        // Dyn -> Dyn -> Dyn
+        BinaryOp::RecordMergeDeep()
+        | BinaryOp::RecordMergePreferLeft()
+        | BinaryOp::RecordMergePreferRight() => (
+            mk_typewrapper::dynamic(),
+            mk_typewrapper::dynamic(),
+            mk_typewrapper::dynamic(),
+        ),
+        // Dyn -> List Str -> Dyn
+        BinaryOp::RecordProject() => (
+            mk_typewrapper::dynamic(),
+            mk_typewrapper::list(mk_typewrapper::str()),
+            mk_typewrapper::dynamic(),
+        ),
         // forall a. List a -> List a -> List a
         BinaryOp::ListConcat() => {
             let ty_elt = TypeWrapper::Ptr(state.table.fresh_var());
@@ -287,44 +433,104 @@ pub fn get_bop_type(
                 ty_elt,
             )
         }
+        // forall a. List a -> (a -> Bool) -> List a
+        BinaryOp::ListFilter() => {
+            let a = TypeWrapper::Ptr(state.table.fresh_var());
+            (
+                mk_typewrapper::list(a.clone()),
+                mk_tyw_arrow!(a.clone(), AbsType::Bool()),
+                mk_typewrapper::list(a),
+            )
+        }
+        // forall a. List a -> (a -> Bool) -> Bool
+        BinaryOp::ListAll() | BinaryOp::ListAny() => {
+            let a = TypeWrapper::Ptr(state.table.fresh_var());
+            (
+                mk_typewrapper::list(a.clone()),
+                mk_tyw_arrow!(a, AbsType::Bool()),
+                mk_typewrapper::bool(),
+            )
+        }
+        // forall a. a -> List a -> Bool
+        BinaryOp::ListContains() => {
+            let a = TypeWrapper::Ptr(state.table.fresh_var());
+            (a.clone(), mk_typewrapper::list(a), mk_typewrapper::bool())
+        }
+        // forall a. List a -> Num -> List a
+        BinaryOp::ListTake() | BinaryOp::ListDrop() => {
+            let ty_elt = TypeWrapper::Ptr(state.table.fresh_var());
+            (
+                mk_typewrapper::list(ty_elt.clone()),
+                mk_typewrapper::num(),
+                mk_typewrapper::list(ty_elt),
+            )
+        }
+        // Num -> Num -> List Num
+        BinaryOp::ListRange() => (
+            mk_typewrapper::num(),
+            mk_typewrapper::num(),
+            mk_typewrapper::list(AbsType::Num()),
+        ),
         // Dyn -> Dyn -> Dyn
-        BinaryOp::Merge() => (
+        BinaryOp::Merge(_) => (
             mk_typewrapper::dynamic(),
             mk_typewrapper::dynamic(),
             mk_typewrapper::dynamic(),
         ),
-        // <Md5, Sha1, Sha256, Sha512> -> Str -> Str
+        // <Md5, Sha1, Sha224, Sha256, Sha384, Sha512> -> Str -> Str
         BinaryOp::Hash() => (
             mk_tyw_enum!(
                 "Md5",
                 "Sha1",
+                "Sha224",
                 "Sha256",
+                "Sha384",
                 "Sha512",
                 mk_typewrapper::row_empty()
             ),
             mk_typewrapper::str(),
             mk_typewrapper::str(),
         ),
-        // forall a. <Json, Yaml, Toml> -> a -> Str
+        // forall a. <Json, Yaml, Toml, Xml, Ini> -> a -> Str
         BinaryOp::Serialize() => {
             let ty_input = TypeWrapper::Ptr(state.table.fresh_var());
             (
-                mk_tyw_enum!("Json", "Yaml", "Toml", mk_typewrapper::row_empty()),
+                mk_tyw_enum!(
+                    "Json",
+                    "Yaml",
+                    "Toml",
+                    "Xml",
+                    "Ini",
+                    mk_typewrapper::row_empty()
+                ),
                 ty_input,
                 mk_typewrapper::str(),
             )
         }
-        // <Json, Yaml, Toml> -> Str -> Dyn
+        // <Json, Yaml, Toml, Xml, Ini> -> Str -> Dyn
         BinaryOp::Deserialize() => (
-            mk_tyw_enum!("Json", "Yaml", "Toml", mk_typewrapper::row_empty()),
+            mk_tyw_enum!(
+                "Json",
+                "Yaml",
+                "Toml",
+                "Xml",
+                "Ini",
+                mk_typewrapper::row_empty()
+            ),
             mk_typewrapper::str(),
             mk_typewrapper::dynamic(),
         ),
         // Num -> Num -> Num
-        BinaryOp::Pow() => (
+        BinaryOp::Pow() | BinaryOp::Min() | BinaryOp::Max() | BinaryOp::IntDiv() => (
+            mk_typewrapper::num(),
+            mk_typewrapper::num(),
             mk_typewrapper::num(),
+        ),
+        // Num -> Num -> {quot: Num, rem: Num}
+        BinaryOp::DivMod() => (
             mk_typewrapper::num(),
             mk_typewrapper::num(),
+            mk_tyw_record!(("quot", AbsType::Num()), ("rem", AbsType::Num())),
         ),
         // Str -> Str -> Bool
         BinaryOp::StrContains() => (
@@ -332,6 +538,53 @@ pub fn get_bop_type(
             mk_typewrapper::str(),
             mk_typewrapper::bool(),
         ),
+        // Str -> List Str -> < | Dyn>
+        BinaryOp::StrToEnumChecked() => (
+            mk_typewrapper::str(),
+            mk_typewrapper::list(AbsType::Str()),
+            mk_tyw_enum!(mk_typewrapper::dynamic()),
+        ),
+        // Num -> Num -> Str
+        BinaryOp::NumToStrRadix() | BinaryOp::NumToStrFixed() => (
+            mk_typewrapper::num(),
+            mk_typewrapper::num(),
+            mk_typewrapper::str(),
+        ),
+        // Str -> Num -> {tag: <Some, None>, value: Num}
+        BinaryOp::StrToNumRadix() => (
+            mk_typewrapper::str(),
+            mk_typewrapper::num(),
+            mk_tyw_record!(
+                (
+                    "tag",
+                    mk_tyw_enum!("Some", "None", mk_typewrapper::row_empty())
+                ),
+                ("value", AbsType::Num())
+            ),
+        ),
+        // Str -> Str -> Bool
+        BinaryOp::StrStartsWith()
+        | BinaryOp::StrEndsWith()
+        | BinaryOp::StrContainsCaseInsensitive()
+        | BinaryOp::StrStartsWithCaseInsensitive()
+        | BinaryOp::StrEndsWithCaseInsensitive()
+        | BinaryOp::StrEqCaseInsensitive() => (
+            mk_typewrapper::str(),
+            mk_typewrapper::str(),
+            mk_typewrapper::bool(),
+        ),
+        // Str -> Str -> Str
+        BinaryOp::StrTrimChars() => (
+            mk_typewrapper::str(),
+            mk_typewrapper::str(),
+            mk_typewrapper::str(),
+        ),
+        // Str -> Num -> Str
+        BinaryOp::StrRepeat() => (
+            mk_typewrapper::str(),
+            mk_typewrapper::num(),
+            mk_typewrapper::str(),
+        ),
         // Str -> Str -> Bool
         BinaryOp::StrIsMatch() => (
             mk_typewrapper::str(),
@@ -348,17 +601,52 @@ pub fn get_bop_type(
                 ("groups", mk_typewrapper::list(AbsType::Str()))
             ),
         ),
+        // Str -> Str -> List {match: Str, index: Num, groups: List Str}
+        BinaryOp::StrMatchAll() => (
+            mk_typewrapper::str(),
+            mk_typewrapper::str(),
+            mk_typewrapper::list(mk_tyw_record!(
+                ("match", AbsType::Str()),
+                ("index", AbsType::Num()),
+                ("groups", mk_typewrapper::list(AbsType::Str()))
+            )),
+        ),
         // Str -> Str -> List Str
         BinaryOp::StrSplit() => (
             mk_typewrapper::str(),
             mk_typewrapper::str(),
             mk_typewrapper::list(AbsType::Str()),
         ),
+        // Str -> Str -> {tag: <Split, NotFound>, before: Str, after: Str}
+        BinaryOp::StrSplitOnce() => (
+            mk_typewrapper::str(),
+            mk_typewrapper::str(),
+            mk_tyw_record!(
+                (
+                    "tag",
+                    mk_tyw_enum!("Split", "NotFound", mk_typewrapper::row_empty())
+                ),
+                ("before", AbsType::Str()),
+                ("after", AbsType::Str())
+            ),
+        ),
+        // List Str -> Bool -> Str
+        BinaryOp::StrUnlines() => (
+            mk_typewrapper::list(AbsType::Str()),
+            mk_typewrapper::bool(),
+            mk_typewrapper::str(),
+        ),
+        // Str -> List Str -> Str
+        BinaryOp::StrJoin() => (
+            mk_typewrapper::str(),
+            mk_typewrapper::list(AbsType::Str()),
+            mk_typewrapper::str(),
+        ),
     })
 }
 
 pub fn get_nop_type(
-    _state: &mut State,
+    state: &mut State,
     op: &NAryOp,
 ) -> Result<(Vec<TypeWrapper>, TypeWrapper), TypecheckError> {
     Ok(match op {
@@ -371,6 +659,25 @@ pub fn get_nop_type(
             ],
             mk_typewrapper::str(),
         ),
+        // Str -> Str -> Str -> Num -> Str
+        NAryOp::StrReplaceN() => (
+            vec![
+                mk_typewrapper::str(),
+                mk_typewrapper::str(),
+                mk_typewrapper::str(),
+                mk_typewrapper::num(),
+            ],
+            mk_typewrapper::str(),
+        ),
+        // Str -> Num -> Str -> Str
+        NAryOp::StrPadLeft() | NAryOp::StrPadRight() => (
+            vec![
+                mk_typewrapper::str(),
+                mk_typewrapper::num(),
+                mk_typewrapper::str(),
+            ],
+            mk_typewrapper::str(),
+        ),
         // Str -> Num -> Num -> Str
         NAryOp::StrSubstr() => (
             vec![
@@ -380,7 +687,99 @@ pub fn get_nop_type(
             ],
             mk_typewrapper::str(),
         ),
+        // Str -> Str -> Num -> List Str
+        NAryOp::StrSplitN() => (
+            vec![
+                mk_typewrapper::str(),
+                mk_typewrapper::str(),
+                mk_typewrapper::num(),
+            ],
+            mk_typewrapper::list(AbsType::Str()),
+        ),
+        // Num -> Num -> Num -> List Num
+        NAryOp::ListRangeStep() => (
+            vec![
+                mk_typewrapper::num(),
+                mk_typewrapper::num(),
+                mk_typewrapper::num(),
+            ],
+            mk_typewrapper::list(AbsType::Num()),
+        ),
+        // forall a b. List a -> (b -> a -> b) -> b -> b
+        NAryOp::ListFoldl() => {
+            let a = TypeWrapper::Ptr(state.table.fresh_var());
+            let b = TypeWrapper::Ptr(state.table.fresh_var());
+            (
+                vec![
+                    mk_typewrapper::list(a.clone()),
+                    mk_tyw_arrow!(b.clone(), a, b.clone()),
+                    b.clone(),
+                ],
+                b,
+            )
+        }
+        // forall a b. List a -> (b -> a -> b) -> b -> b
+        NAryOp::ListFoldr() => {
+            let a = TypeWrapper::Ptr(state.table.fresh_var());
+            let b = TypeWrapper::Ptr(state.table.fresh_var());
+            (
+                vec![
+                    mk_typewrapper::list(a.clone()),
+                    mk_tyw_arrow!(b.clone(), a, b.clone()),
+                    b.clone(),
+                ],
+                b,
+            )
+        }
+        // <Sha256, Sha512> -> Str -> Str -> Str
+        NAryOp::Hmac() => (
+            vec![
+                mk_tyw_enum!("Sha256", "Sha512", mk_typewrapper::row_empty()),
+                mk_typewrapper::str(),
+                mk_typewrapper::str(),
+            ],
+            mk_typewrapper::str(),
+        ),
+        // forall a. <Json, Yaml, Toml, Xml, Ini> -> <Pretty, Compact> -> a -> Str
+        NAryOp::SerializeWith() => {
+            let ty_input = TypeWrapper::Ptr(state.table.fresh_var());
+            (
+                vec![
+                    mk_tyw_enum!(
+                        "Json",
+                        "Yaml",
+                        "Toml",
+                        "Xml",
+                        "Ini",
+                        mk_typewrapper::row_empty()
+                    ),
+                    mk_tyw_enum!("Pretty", "Compact", mk_typewrapper::row_empty()),
+                    ty_input,
+                ],
+                mk_typewrapper::str(),
+            )
+        }
+        // Str -> Dyn -> Dyn -> Dyn
+        NAryOp::MergeByKey() => (
+            vec![
+                mk_typewrapper::str(),
+                mk_typewrapper::dynamic(),
+                mk_typewrapper::dynamic(),
+            ],
+            mk_typewrapper::dynamic(),
+        ),
+        // Dyn -> List Str -> Dyn -> Dyn
+        NAryOp::RecordSetPath() | NAryOp::RecordGetPathOr() => (
+            vec![
+                mk_typewrapper::dynamic(),
+                mk_typewrapper::list(AbsType::Str()),
+                mk_typewrapper::dynamic(),
+            ],
+            mk_typewrapper::dynamic(),
+        ),
         // This should not happen, as Switch() is only produced during evaluation.
         NAryOp::MergeContract() => panic!("cannot typecheck MergeContract()"),
+        // This should not happen, as MergeListsByKey() is only produced during evaluation.
+        NAryOp::MergeListsByKey() => panic!("cannot typecheck MergeListsByKey()"),
     })
 }
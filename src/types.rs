@@ -53,7 +53,7 @@
 //! untyped parts.
 use crate::identifier::Ident;
 use crate::term::make as mk_term;
-use crate::term::{RichTerm, Term, UnaryOp};
+use crate::term::{IndexMap, RichTerm, Term, UnaryOp};
 use crate::{mk_app, mk_fun};
 use std::collections::HashMap;
 use std::fmt;
@@ -219,7 +219,7 @@ impl Types {
                         }
                         AbsType::RowExtend(id, None, rest) => {
                             let rest_contract = form(*rest, h);
-                            let mut map = HashMap::new();
+                            let mut map = IndexMap::new();
                             map.insert(id, Term::Bool(true).into());
 
                             mk_app!(
@@ -0,0 +1,82 @@
+//! Evaluation entry point for environments without a file system, such as WebAssembly in the
+//! browser.
+//!
+//! The program is given as a string, and the files it may import are provided by the caller as a
+//! map from paths to contents, which is resolved by a
+//! [`VirtualFsResolver`](../cache/resolvers/struct.VirtualFsResolver.html). Nothing is ever read
+//! from the file system or the network. The result is returned as a string, either the serialized
+//! value or the rendered error, which is simple to pass through a WASM boundary.
+use crate::cache::resolvers::VirtualFsResolver;
+use crate::cache::{render_error, Cache, GlobalEnv};
+use crate::error::Error;
+use crate::eval;
+use crate::parser::{grammar::TermParser, lexer::Lexer};
+use crate::serialize::{self, ExportFormat};
+use crate::transform::{self, import_resolution};
+use crate::typecheck::{linearization::StubHost, type_check};
+use std::collections::HashMap;
+
+/// The name of the main program in the file database. Relative imports of the main program are
+/// resolved from the root of the import map.
+const MAIN_NAME: &str = "<main>";
+
+/// Parse, typecheck, evaluate and serialize a program to the given format.
+///
+/// `imports` maps paths to the content of the corresponding files. Paths are relative to the root
+/// of the import map: the main program can import `lib.ncl` or `dir/lib.ncl` for example, and the
+/// latter can import `other.ncl` to refer to `dir/other.ncl`.
+///
+/// Imported files are parsed, have their own imports resolved and are transformed, but contrary to
+/// the main program, they are not statically typechecked.
+///
+/// On failure, the error is rendered as an uncolored diagnostic, including the snippets of the
+/// source code it refers to.
+pub fn eval_with_imports(
+    source: &str,
+    imports: HashMap<String, String>,
+    format: ExportFormat,
+) -> Result<String, String> {
+    let mut cache = Cache::new();
+    let global_env = cache
+        .prepare_stdlib()
+        .map_err(|err| cache.render_error(err))?;
+
+    // The resolver is initialized with the files of the cache, such that errors occurring in the
+    // standard library can still be reported.
+    let mut resolver = VirtualFsResolver::with_files(cache.files().clone());
+    for (path, content) in imports {
+        resolver.add_file(path, content).map_err(|err| {
+            render_error(
+                resolver.files(),
+                cache.id_of("<stdlib/contracts.ncl>"),
+                Error::from(err),
+            )
+        })?;
+    }
+    eval_main(&mut resolver, source, &global_env, format)
+        .map_err(|err| render_error(resolver.files(), cache.id_of("<stdlib/contracts.ncl>"), err))
+}
+
+/// Add the main program to the file database of the resolver, and run the whole pipeline on it.
+fn eval_main(
+    resolver: &mut VirtualFsResolver,
+    source: &str,
+    global_env: &GlobalEnv,
+    format: ExportFormat,
+) -> Result<String, Error> {
+    let main_id = resolver.files_mut().add(MAIN_NAME, String::from(source));
+
+    let term = TermParser::new().parse_term(main_id, Lexer::new(source))?;
+    let (term, _) = import_resolution::resolve_imports(term, resolver)?;
+    type_check(
+        &term,
+        &global_env.type_env,
+        resolver,
+        StubHost::<(), (), _>::new(),
+    )?;
+    let term = transform::transform(term);
+
+    let rt = eval::eval_full(term, &global_env.eval_env, resolver)?;
+    serialize::validate(format, &rt)?;
+    Ok(serialize::to_string(format, &rt)?)
+}
@@ -1,5 +1,5 @@
 use assert_matches::assert_matches;
-use nickel::error::{Error, EvalError};
+use nickel::error::{Error, EvalError, SerializationError};
 
 use utilities::eval;
 
@@ -7,8 +7,50 @@ use utilities::eval;
 fn div_by_zero() {
     assert_matches!(
         eval("1 + 1 / (1 - 1)"),
+        Err(Error::EvalError(EvalError::DivisionByZero(..)))
+    );
+    assert_matches!(
+        eval("1 + 1 % (1 - 1)"),
+        Err(Error::EvalError(EvalError::DivisionByZero(..)))
+    );
+}
+
+#[test]
+fn int_div_by_zero() {
+    assert_matches!(
+        eval("nums.int_div 1 (1 - 1)"),
+        Err(Error::EvalError(EvalError::DivisionByZero(..)))
+    );
+    assert_matches!(
+        eval("(nums.div_mod 1 0).quot"),
+        Err(Error::EvalError(EvalError::DivisionByZero(..)))
+    );
+
+    // The error points to the divisor
+    match eval("%int_div% 1 (1 - 1)") {
+        Err(Error::EvalError(EvalError::DivisionByZero(pos))) => {
+            let span = pos.unwrap();
+            assert_eq!((span.start.0, span.end.0), (12, 19));
+        }
+        result => panic!("expected a division by zero, got {:?}", result),
+    }
+}
+
+#[test]
+fn math_domain() {
+    assert_matches!(
+        eval("%sqrt% (-1)"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(eval("%ln% 0"), Err(Error::EvalError(EvalError::Other(..))));
+    assert_matches!(
+        eval("%log10% (-10)"),
         Err(Error::EvalError(EvalError::Other(..)))
     );
+    assert_matches!(
+        eval("%exp% \"a\""),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
 }
 
 #[test]
@@ -53,6 +95,26 @@ fn boolean_ops() {
         eval("\"a\" || false"),
         Err(Error::EvalError(EvalError::TypeError(..)))
     );
+    assert_matches!(
+        eval("!5"),
+        Err(Error::EvalError(EvalError::TypeError(ty, op, ..))) if ty == "Bool" && op == "!"
+    );
+    assert_matches!(
+        eval("true && 2"),
+        Err(Error::EvalError(EvalError::TypeError(ty, op, ..))) if ty == "Bool" && op == "&&"
+    );
+    assert_matches!(
+        eval("false || \"a\""),
+        Err(Error::EvalError(EvalError::TypeError(ty, op, ..))) if ty == "Bool" && op == "||"
+    );
+    assert_matches!(
+        eval("true && (false || 2)"),
+        Err(Error::EvalError(EvalError::TypeError(ty, op, ..))) if ty == "Bool" && op == "||"
+    );
+    assert_matches!(
+        eval("false || (true && 2)"),
+        Err(Error::EvalError(EvalError::TypeError(ty, op, ..))) if ty == "Bool" && op == "&&"
+    );
 }
 
 #[test]
@@ -62,3 +124,72 @@ fn string_chunks() {
         Err(Error::EvalError(EvalError::TypeError(..)))
     );
 }
+
+#[test]
+fn serialize_ini() {
+    assert_matches!(
+        eval("builtins.serialize `Ini {section.nested.key = \"a\"}"),
+        Err(Error::EvalError(EvalError::SerializationError(SerializationError::Other(msg))))
+            if msg.contains("section.nested") && msg.contains("nested too deeply")
+    );
+    assert_matches!(
+        eval("builtins.serialize `Ini {key = null}"),
+        Err(Error::EvalError(EvalError::SerializationError(
+            SerializationError::UnsupportedNull(..)
+        )))
+    );
+    assert_matches!(
+        eval("%deserialize% `Ini \"[section]\\nkey\""),
+        Err(Error::EvalError(EvalError::DeserializationError(..)))
+    );
+    // Deeply nested documents are rejected instead of overflowing the stack
+    let deep = format!("{}{}", "<a>".repeat(100_000), "</a>".repeat(100_000));
+    assert_matches!(
+        eval(format!("%deserialize% `Xml \"{}\"", deep)),
+        Err(Error::EvalError(EvalError::DeserializationError(_, msg, _)))
+            if msg.contains("nested")
+    );
+    let shallow = format!("{}{}", "<a>".repeat(100), "</a>".repeat(100));
+    assert!(eval(format!("%deserialize% `Xml \"{}\"", shallow)).is_ok());
+}
+
+#[test]
+fn deserialize_as() {
+    assert_matches!(
+        eval("(builtins.deserialize_as `Json {a | Num} \"{\\\"a\\\": \\\"1\\\"}\").a"),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+    // extra or missing fields
+    assert_matches!(
+        eval("builtins.deserialize_as `Json {a | Num} \"{\\\"a\\\": 1, \\\"b\\\": 2}\""),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+    assert_matches!(
+        eval("(builtins.deserialize_as `Json {a | Num, b | Str} \"{\\\"a\\\": 1}\").b"),
+        Err(Error::EvalError(EvalError::MissingFieldDef(..)))
+    );
+}
+
+#[test]
+fn hmac() {
+    assert_matches!(
+        eval("%hmac% `Md5 \"key\" \"message\""),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%hmac% `Sha256 \"key\" 1"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn serialize_with() {
+    assert_matches!(
+        eval("%serialize_with% `Json `Tight {a = 1}"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%serialize_with% `Csv `Compact {a = 1}"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
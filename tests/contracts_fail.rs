@@ -170,6 +170,37 @@ fn lists_contracts() {
     res.unwrap_err().to_diagnostic(&mut files, None);
 }
 
+#[test]
+fn blame_field_name() {
+    let mut files = Files::new();
+
+    for (term, field) in [
+        ("{port | Num = \"8080\", host = \"a\"}.port", "port"),
+        ("({port = \"8080\"} & {port | Num}).port", "port"),
+        ("{server.port | Num = \"8080\"}.server.port", "port"),
+    ] {
+        let err = eval(term).unwrap_err();
+        match &err {
+            Error::EvalError(EvalError::BlameError(l, _)) => {
+                assert_matches!(&l.field_name, Some(id) if id.to_string() == field)
+            }
+            err => panic!("expected blame error, got {:?}", err),
+        }
+
+        let diagnostics = err.to_diagnostic(&mut files, None);
+        assert!(diagnostics[0]
+            .message
+            .contains(&format!("at field `{}`", field)));
+    }
+}
+
+#[test]
+fn force_contracts() {
+    let record = "{foo | Num | default = \"a\", bar = 1}";
+    assert_matches!(eval(&format!("({}).bar", record)), Ok(_));
+    assert_raise_blame!(&format!("(builtins.force_contracts {}).bar", record));
+}
+
 #[test]
 fn records_contracts_closed() {
     assert_raise_blame!("{a=1} | #{}");
@@ -1,5 +1,6 @@
 use assert_matches::assert_matches;
-use nickel::error::{Error, EvalError, TypecheckError};
+use nickel::cache::{normalize_path, Cache};
+use nickel::error::{Error, EvalError, ImportError, TypecheckError};
 use nickel::program::Program;
 use nickel::term::Term;
 use std::io::BufReader;
@@ -112,3 +113,170 @@ fn circular_imports_fail() {
         Ok(Term::RecRecord(..)) | Ok(Term::Record(..))
     );
 }
+
+#[test]
+fn data_formats() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(mk_import("data_formats.ncl").as_bytes()),
+        "should_be = true",
+    )
+    .unwrap();
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Bool(true)));
+}
+
+#[test]
+fn json_field_access() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(format!("({}).nested.enabled", mk_import("data.json")).as_bytes()),
+        "should_be = true",
+    )
+    .unwrap();
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Bool(true)));
+}
+
+#[test]
+fn json_parse_fail() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(mk_import("bad.json").as_bytes()),
+        "should_fail",
+    )
+    .unwrap();
+    assert_matches!(
+        prog.eval(),
+        Err(Error::ImportError(ImportError::ParseErrors(..)))
+    );
+}
+
+/// Integers of data formats which can't be represented exactly are rejected, as integer literals
+/// of Nickel sources are. Integers beyond the 64-bit range are parsed as floating point numbers
+/// by the data format libraries, and are rounded silently.
+#[test]
+fn inexact_integer_fail() {
+    for ext in ["json", "yaml", "toml"] {
+        let mut prog = Program::new_from_source(
+            BufReader::new(mk_import(&format!("inexact_integer.{}", ext)).as_bytes()),
+            "should_fail",
+        )
+        .unwrap();
+        assert_matches!(
+            prog.eval(),
+            Err(Error::ImportError(ImportError::ParseErrors(..)))
+        );
+    }
+}
+
+#[test]
+fn circular_imports_top_level_fail() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(mk_import("cycle_top_level.ncl").as_bytes()),
+        "should_fail",
+    )
+    .unwrap();
+
+    match prog.eval() {
+        Err(Error::EvalError(EvalError::CyclicImport(paths, _))) => {
+            assert_eq!(paths.len(), 3);
+            assert!(paths[0].ends_with("cycle_top_level.ncl"));
+            assert!(paths[1].ends_with("cycle_top_level_b.ncl"));
+            assert!(paths[2].ends_with("cycle_top_level.ncl"));
+        }
+        res => panic!("expected a cyclic import error, got {:?}", res),
+    }
+}
+
+fn search_path_roots() -> Vec<PathBuf> {
+    ["first", "second"]
+        .iter()
+        .map(|dir| {
+            let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+            path.push(format!("tests/imports/search_path/{}", dir));
+            path
+        })
+        .collect()
+}
+
+#[test]
+fn search_path() {
+    let mut prog = Program::new_from_source(
+        BufReader::new("import \"lib.ncl\"".as_bytes()),
+        "should_be = 42",
+    )
+    .unwrap();
+    prog.add_import_paths(search_path_roots());
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Num(42.)));
+}
+
+#[test]
+fn search_path_not_found() {
+    let mut prog = Program::new_from_source(
+        BufReader::new("import \"not_there.ncl\"".as_bytes()),
+        "should_fail",
+    )
+    .unwrap();
+    prog.add_import_paths(search_path_roots());
+
+    match prog.eval() {
+        Err(Error::ImportError(ImportError::IOError(path, msg, _))) => {
+            assert_eq!(path, "not_there.ncl");
+            for root in search_path_roots() {
+                assert!(msg.contains(root.join("not_there.ncl").to_str().unwrap()));
+            }
+        }
+        res => panic!("expected an import error, got {:?}", res),
+    }
+}
+
+#[test]
+fn import_str() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(
+            mk_import("header.txt")
+                .replacen("import", "importStr", 1)
+                .as_bytes(),
+        ),
+        "should_be_header",
+    )
+    .unwrap();
+    assert_eq!(
+        prog.eval().map(Term::from),
+        Ok(Term::Str(String::from(
+            "Copyright \"Nickel\" authors.\nLicensed under the MIT license.\n"
+        )))
+    );
+}
+
+#[test]
+fn import_str_then_import() {
+    // The same file is first imported as a string, and then as Nickel source code.
+    let source = format!(
+        "let s = {} in let n = {} in if s == \"1 + 1 : Num\\n\" then n else 0",
+        mk_import("two.ncl").replacen("import", "importStr", 1),
+        mk_import("two.ncl")
+    );
+    let mut prog =
+        Program::new_from_source(BufReader::new(source.as_bytes()), "should_be = 2").unwrap();
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Num(2.)));
+}
+
+#[test]
+fn import_str_not_found() {
+    let mut prog = Program::new_from_source(
+        BufReader::new("importStr \"does_not_exist.txt\"".as_bytes()),
+        "should_fail",
+    )
+    .unwrap();
+    assert_matches!(
+        prog.eval(),
+        Err(Error::ImportError(ImportError::IOError(..)))
+    );
+}
+
+#[test]
+fn in_memory_source_shadows_file() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/imports/two.ncl");
+    let mut cache = Cache::new();
+    let id = cache.add_string(normalize_path(&path).unwrap(), String::from("40 + 2"));
+
+    assert_eq!(cache.get_or_add_file(&path).unwrap().inner(), id);
+}
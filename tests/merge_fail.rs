@@ -1,5 +1,6 @@
 use assert_matches::assert_matches;
-use nickel::error::{Error, EvalError};
+use codespan::Files;
+use nickel::error::{Error, EvalError, ToDiagnostic};
 use nickel::position::TermPos;
 use nickel::program::Program;
 use nickel::term::RichTerm;
@@ -12,6 +13,7 @@ fn eval_full(s: &str) -> Result<RichTerm, Error> {
         Error::EvalError(EvalError::Other(
             format!("IO error: {}", io_err),
             TermPos::None,
+            None,
         ))
     })?;
     p.eval_full()
@@ -30,3 +32,33 @@ macro_rules! assert_merge_fails {
 fn merge_conflict_inside_metavalue() {
     assert_merge_fails!("{ foo = (fun x => x) (1 | default), foo = (fun x => x) (1 | default) } & {foo | default = 2 }");
 }
+
+#[test]
+fn merge_conflict_path() {
+    let err = eval_full("{a = {b = 1}} & {a = {b = 2}}").unwrap_err();
+
+    match &err {
+        Error::EvalError(EvalError::MergeIncompatibleArgs(_, _, _, path)) => {
+            let path: Vec<String> = path.iter().map(ToString::to_string).collect();
+            assert_eq!(path, ["a", "b"]);
+        }
+        err => panic!("expected a merge error, got {:?}", err),
+    }
+
+    let diagnostics = err.to_diagnostic(&mut Files::new(), None);
+    assert!(diagnostics[0].message.contains("`a.b`"));
+}
+
+#[test]
+fn merge_by_key() {
+    // Standard merge doesn't merge lists
+    assert_merge_fails!("{l = [{name = \"a\"}]} & {l = [{name = \"a\", v = 1}]}");
+    // Elements with the same key must be mergeable
+    assert_merge_fails!(
+        "records.merge_by_key \"name\" {l = [{name = \"a\", v = 1}]} {l = [{name = \"a\", v = 2}]}"
+    );
+    assert_matches!(
+        eval_full("records.merge_by_key \"name\" [{name = 1}] [{name = 1}]"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+}
@@ -0,0 +1,159 @@
+//! Property-based tests of the algebraic properties of merge on records.
+//!
+//! Failing cases found by proptest are persisted in `tests/merge_properties.proptest-regressions`,
+//! which should be committed together with the fix: they are replayed first on each subsequent
+//! run. Counterexamples which have been fixed are additionally kept as plain regression tests at
+//! the end of this file.
+use assert_matches::assert_matches;
+use nickel::term::Term;
+use proptest::prelude::*;
+use proptest::test_runner::FileFailurePersistence;
+use std::collections::BTreeMap;
+
+use utilities::eval;
+
+/// A simple Nickel value, made of records and of base values that can be merged.
+#[derive(Clone, Debug)]
+enum Value {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Enum(String),
+    Record(BTreeMap<String, Value>),
+}
+
+impl Value {
+    fn to_nickel(&self) -> String {
+        match self {
+            Value::Null => String::from("null"),
+            Value::Bool(b) => b.to_string(),
+            Value::Num(n) => format!("({})", n),
+            Value::Str(s) => format!("\"{}\"", s),
+            Value::Enum(tag) => format!("`{}", tag),
+            Value::Record(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(id, v)| format!("{} = {}", id, v.to_nickel()))
+                    .collect();
+                format!("{{{}}}", fields.join(", "))
+            }
+        }
+    }
+
+    /// Distribute the leaves of a record over three records, according to the given masks. The
+    /// i-th record receives a leaf if the i-th bit of the corresponding mask is set. Masks are
+    /// consumed in order, and cycled through if there are more leaves than masks.
+    fn split(&self, masks: &mut impl Iterator<Item = u8>) -> [Option<Value>; 3] {
+        match self {
+            Value::Record(fields) if !fields.is_empty() => {
+                let mut parts: [BTreeMap<String, Value>; 3] = Default::default();
+
+                for (id, v) in fields {
+                    for (part, sub) in parts.iter_mut().zip(v.split(masks)) {
+                        if let Some(sub) = sub {
+                            part.insert(id.clone(), sub);
+                        }
+                    }
+                }
+
+                let [p0, p1, p2] = parts;
+                [p0, p1, p2].map(|part| {
+                    if part.is_empty() {
+                        None
+                    } else {
+                        Some(Value::Record(part))
+                    }
+                })
+            }
+            leaf => {
+                let mask = masks.next().unwrap();
+                [0, 1, 2].map(|i| {
+                    if mask & (1 << i) != 0 {
+                        Some(leaf.clone())
+                    } else {
+                        None
+                    }
+                })
+            }
+        }
+    }
+}
+
+fn arb_leaf() -> impl Strategy<Value = Value> {
+    prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        (-100i32..100).prop_map(|n| Value::Num(f64::from(n) / 4.0)),
+        "[a-z]{0,3}".prop_map(Value::Str),
+        "[A-Z][a-z]{0,2}".prop_map(Value::Enum),
+    ]
+}
+
+fn arb_record() -> impl Strategy<Value = BTreeMap<String, Value>> {
+    let value = arb_leaf().prop_recursive(3, 24, 4, |inner| {
+        prop::collection::btree_map("[a-d]", inner, 0..4).prop_map(Value::Record)
+    });
+    prop::collection::btree_map("[a-d]", value, 0..4)
+}
+
+fn config() -> ProptestConfig {
+    ProptestConfig {
+        cases: 64,
+        failure_persistence: Some(Box::new(FileFailurePersistence::WithSource(
+            "proptest-regressions",
+        ))),
+        ..ProptestConfig::default()
+    }
+}
+
+fn assert_true(src: String) {
+    assert_eq!(eval(&src), Ok(Term::Bool(true)), "evaluating `{}`", src);
+}
+
+proptest! {
+    #![proptest_config(config())]
+
+    #[test]
+    fn merge_idempotent(fields in arb_record()) {
+        let r = Value::Record(fields).to_nickel();
+        assert_true(format!("({r} & {r}) == {r}", r = r));
+    }
+
+    #[test]
+    fn merge_associative(
+        fields in arb_record(),
+        masks in prop::collection::vec(1u8..8, 1..16),
+    ) {
+        let r = Value::Record(fields);
+        let mut masks = masks.into_iter().cycle();
+        let [a, b, c] = r
+            .split(&mut masks)
+            .map(|part| part.unwrap_or_else(|| Value::Record(BTreeMap::new())).to_nickel());
+        let r = r.to_nickel();
+
+        assert_true(format!(
+            "(({a} & {b}) & {c}) == ({a} & ({b} & {c}))",
+            a = a,
+            b = b,
+            c = c
+        ));
+        assert_true(format!("(({a} & {b}) & {c}) == {r}", a = a, b = b, c = c, r = r));
+    }
+}
+
+#[test]
+fn merge_base_values() {
+    assert_true(String::from("({a = null} & {a = null}) == {a = null}"));
+    assert_true(String::from("({a = `Foo} & {a = `Foo}) == {a = `Foo}"));
+    assert_matches!(eval("({a = `Foo} & {a = `Bar}).a"), Err(..));
+}
+
+#[test]
+fn merge_num_exact() {
+    // Numbers used to be compared up to `f64::EPSILON`, which broke associativity.
+    assert_matches!(eval("0 & 0.00000000000000015"), Err(..));
+    assert_true(String::from(
+        "(({a = 0} & {a = 0}) & {a = 0}) == ({a = 0} & ({a = 0} & {a = 0}))",
+    ));
+}
@@ -72,6 +72,16 @@ fn metavalues() {
     check_file("metavalues.ncl");
 }
 
+#[test]
+fn nums() {
+    check_file("nums.ncl");
+}
+
+#[test]
+fn prelude() {
+    check_file("prelude.ncl");
+}
+
 #[test]
 fn records() {
     check_file("records.ncl");
@@ -11,7 +11,79 @@ fn records_access() {
     );
     assert_matches!(
         eval("({ \"#{(if false then \"foo\" else \"bar\")}\" = false, bar = true, }).foo"),
-        Err(Error::EvalError(EvalError::Other(msg, ..))) if msg.starts_with("$[ .. ]"));
+        Err(Error::EvalError(EvalError::FieldAlreadyDefined(field, op, ..))) if field == "bar" && op == "$[ .. ]");
+    assert_matches!(
+        eval("%record_insert% foo {foo = 1} 2"),
+        Err(Error::EvalError(EvalError::FieldAlreadyDefined(field, op, ..))) if field == "foo" && op == "recordInsert");
+}
+
+#[test]
+fn record_filter() {
+    assert_matches!(
+        eval("%record_filter% {foo = 1} (fun s x => 1)"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%record_filter% [] (fun s x => true)"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("records.filter (fun s x => x) {foo = true, bar = 1 + \"a\"}"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn record_project() {
+    assert_matches!(
+        eval("records.project [\"foo\", \"baz\"] {foo = 1, bar = 2}"),
+        Err(Error::EvalError(EvalError::FieldMissing(field, ..))) if field == "baz"
+    );
+    assert_matches!(
+        eval("%record_project% {foo = 1} [1]"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%record_project% {foo = 1} [\"fo\" ++ \"o\", 1 + 1]"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%record_project% {foo = 1} [\"b\" ++ \"ar\"]"),
+        Err(Error::EvalError(EvalError::FieldMissing(field, ..))) if field == "bar"
+    );
+    assert_matches!(
+        eval("%record_project% [] []"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn record_merge_deep() {
+    assert_matches!(
+        eval("%record_merge_deep% {a = 1} 1"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%record_merge_deep% [] {}"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    // Values are overridden before being evaluated, so only the remaining ones can fail.
+    assert_matches!(
+        eval("(%record_merge_deep% {a = {b = 1 + \"a\"}} {a = {c = 1}}).a.b"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn record_merge_prefer() {
+    assert_matches!(
+        eval("%record_merge_prefer_left% {a = 1} 1"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%record_merge_prefer_right% [] {}"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
 }
 
 #[test]
@@ -42,6 +114,38 @@ fn non_mergeable_piecewise() {
     );
 }
 
+#[test]
+fn record_set_path() {
+    assert_matches!(
+        eval("(records.set_path [\"a\", \"b\", \"c\"] 1 {a = {b = 2}}).a.b.c"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("records.set_path [\"a\"] 1 [1]"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%record_set_path% {a = 1} [1] 2"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%record_set_path% {a = 1} [\"a\", 1 + 1] 2"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn record_get_path_or() {
+    assert_matches!(
+        eval("%record_get_path_or% {a = 1} {a = 1} 2"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("records.get_path_or [\"a\"] 0 {a = 1 + \"a\"}"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
 #[test]
 fn dynamic_not_recursive() {
     assert_matches!(
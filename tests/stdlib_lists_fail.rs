@@ -7,15 +7,15 @@ use utilities::eval;
 fn elem_at() {
     assert_matches!(
         eval("%elem_at% [] 0"),
-        Err(Error::EvalError(EvalError::Other(..)))
+        Err(Error::EvalError(EvalError::IndexOutOfBounds(_, n, range, _))) if n == 0.0 && range.is_empty()
     );
     assert_matches!(
         eval("%elem_at% [1,2,3] (-1)"),
-        Err(Error::EvalError(EvalError::Other(..)))
+        Err(Error::EvalError(EvalError::IndexOutOfBounds(..)))
     );
     assert_matches!(
         eval("%elem_at% [true, false, true] 3"),
-        Err(Error::EvalError(EvalError::Other(..)))
+        Err(Error::EvalError(EvalError::IndexOutOfBounds(_, n, range, _))) if n == 3.0 && range == (0..3)
     );
     assert_matches!(
         eval("%elem_at% {} 0"),
@@ -23,7 +23,7 @@ fn elem_at() {
     );
     assert_matches!(
         eval("%elem_at% [1, 2, 3] 0.5"),
-        Err(Error::EvalError(EvalError::Other(..)))
+        Err(Error::EvalError(EvalError::NotAnInteger(_, n, _))) if n == 0.5
     );
 
     assert_matches!(
@@ -40,11 +40,11 @@ fn elem_at() {
 fn head_tail() {
     assert_matches!(
         eval("%head% []"),
-        Err(Error::EvalError(EvalError::Other(..)))
+        Err(Error::EvalError(EvalError::EmptyList(op, _))) if op == "head"
     );
     assert_matches!(
         eval("%tail% []"),
-        Err(Error::EvalError(EvalError::Other(..)))
+        Err(Error::EvalError(EvalError::EmptyList(op, _))) if op == "tail"
     );
     assert_matches!(
         eval("%head% {}"),
@@ -54,6 +54,22 @@ fn head_tail() {
         eval("%tail% {}"),
         Err(Error::EvalError(EvalError::TypeError(..)))
     );
+    assert_matches!(
+        eval("%last% []"),
+        Err(Error::EvalError(EvalError::EmptyList(op, _))) if op == "last"
+    );
+    assert_matches!(
+        eval("%init% []"),
+        Err(Error::EvalError(EvalError::EmptyList(op, _))) if op == "init"
+    );
+    assert_matches!(
+        eval("%last% {}"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%init% 1"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
 
     // TODO: add non-empty contract to the input of lists.head and lists.tail
     // assert_matches!(
@@ -73,3 +89,192 @@ fn head_tail() {
         Err(Error::EvalError(EvalError::BlameError(..)))
     );
 }
+
+#[test]
+fn range() {
+    assert_matches!(
+        eval("%range% 0.5 3"),
+        Err(Error::EvalError(EvalError::NotAnInteger(..)))
+    );
+    assert_matches!(
+        eval("%range% 0 2.5"),
+        Err(Error::EvalError(EvalError::NotAnInteger(..)))
+    );
+    assert_matches!(
+        eval("%range% \"a\" 2"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%range_step% 0 10 0"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%range_step% 0 10 1.5"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("lists.range 0 true"),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+    // Too long ranges are errors instead of exhausting the memory
+    assert_matches!(
+        eval("%range% 0 1000000000000000000"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("lists.range (-9223372036854775808) 9223372036854775808"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%range_step% 1 9223372036854775808 2"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+}
+
+#[test]
+fn sort() {
+    assert_matches!(
+        eval("%sort% [2, 1] (fun x y => `Lower)"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%sort% {} (fun x y => `Less)"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("lists.sort (fun x y => `Less) 1"),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+}
+
+#[test]
+fn filter_fold() {
+    assert_matches!(
+        eval("%filter% [1, 2] (fun x => 1)"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%filter% {} (fun x => true)"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%foldl% 1 (fun acc x => acc) 0"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%foldr% \"a\" (fun acc x => acc) 0"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    // foldl is strict in the accumulator
+    assert_matches!(
+        eval("%foldl% [1, 2] (fun acc x => %blame% 0) 0"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn take_drop() {
+    assert_matches!(
+        eval("%take% [1, 2] (-1)"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%drop% [1, 2] (-1)"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%take% [1, 2] 0.5"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%drop% {} 1"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%reverse% 1"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn flatten() {
+    assert_matches!(
+        eval("%flatten% [[1], 2]"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%flatten% {}"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn all_any() {
+    assert_matches!(
+        eval("%all% [1, 2] (fun x => 1)"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%any% {} (fun x => true)"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%all% [1, 2] (fun x => if x == 2 then false else %blame% 0)"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn contains() {
+    assert_matches!(
+        eval("%contains% 1 {a = 1}"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    // elements are forced until a match is found
+    assert_matches!(
+        eval("%contains% 1 [2, %blame% 0, 1]"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn sum() {
+    assert_matches!(
+        eval("lists.sum [1, \"a\"]"),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+    assert_matches!(
+        eval("%sum% {}"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%sum% [1 + 1, \"a\" ++ \"b\"]"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    // integers whose sum can't be represented exactly
+    assert_matches!(
+        eval("lists.sum [9007199254740992, 1]"),
+        Err(Error::EvalError(EvalError::IntegerOverflow(op, _))) if op == "sum"
+    );
+    assert_matches!(
+        eval("lists.sum [9007199254740994, -2]"),
+        Err(Error::EvalError(EvalError::IntegerOverflow(op, _))) if op.contains("element")
+    );
+    assert_matches!(
+        eval("lists.sum [-9007199254740992, -1]"),
+        Err(Error::EvalError(EvalError::IntegerOverflow(..)))
+    );
+}
+
+#[test]
+fn concat_laziness() {
+    // Elements are forced when accessed, not when the lists are concatenated.
+    assert_matches!(
+        eval("lists.elem_at 0 ([1/0] @ [2])"),
+        Err(Error::EvalError(EvalError::DivisionByZero(..)))
+    );
+    assert_matches!(
+        eval("lists.elem_at 1 ([1] @ [2/0])"),
+        Err(Error::EvalError(EvalError::DivisionByZero(..)))
+    );
+}
@@ -0,0 +1,195 @@
+use assert_matches::assert_matches;
+use nickel::error::{Error, EvalError};
+
+use utilities::eval;
+
+#[test]
+fn to_enum_checked() {
+    assert_matches!(
+        eval("strings.to_enum_checked [\"Debug\", \"Release\"] \"Profile\""),
+        Err(Error::EvalError(EvalError::Other(msg, ..)))
+            if msg.contains("`Profile`") && msg.contains("Debug, Release")
+    );
+    assert_matches!(
+        eval("strings.to_enum_checked [] \"Debug\""),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%enum_from_str_checked% \"Debug\" {}"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%enum_from_str_checked% \"Debug\" [\"Release\", 1]"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%enum_from_str_checked% \"Debug\" [\"Rel\" ++ \"ease\"]"),
+        Err(Error::EvalError(EvalError::Other(msg, ..))) if msg.contains("Release")
+    );
+    assert_matches!(
+        eval("strings.to_enum_checked [1, 2] \"Debug\""),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+}
+
+#[test]
+fn substring() {
+    assert_matches!(
+        eval("%str_substr% \"abc\" 0.5 2"),
+        Err(Error::EvalError(EvalError::NotAnInteger(_, n, _))) if n == 0.5
+    );
+    assert_matches!(
+        eval("%str_substr% \"abc\" 4 5"),
+        Err(Error::EvalError(EvalError::IndexOutOfBounds(_, n, range, _)))
+            if n == 4.0 && range == (0..4)
+    );
+    assert_matches!(
+        eval("%str_substr% \"abc\" 1 1"),
+        Err(Error::EvalError(EvalError::IndexOutOfBounds(_, n, range, _)))
+            if n == 1.0 && range == (2..4)
+    );
+    assert_matches!(
+        eval("%char_from_code% 65.5"),
+        Err(Error::EvalError(EvalError::NotAnInteger(..)))
+    );
+}
+
+#[test]
+fn decoding() {
+    assert_matches!(
+        eval("%base64_decode% \"Zm9vYmE\""),
+        Err(Error::EvalError(EvalError::Other(msg, pos, _)))
+            if msg.contains("base64") && pos.as_opt_ref().is_some()
+    );
+    assert_matches!(
+        eval("%base64_decode% \"Zm9*\""),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%base64_decode% \"Zg==Zg==\""),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    // the decoded bytes aren't valid UTF-8
+    assert_matches!(
+        eval("%base64_decode% \"/w==\""),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%hex_decode% \"486\""),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%hex_decode% \"4g\""),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%hex_decode% \"ff\""),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+}
+
+#[test]
+fn split_n() {
+    assert_matches!(
+        eval("%str_split_n% \"a,b\" \",\" 0"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%str_split_n% \"a,b\" \",\" 1.5"),
+        Err(Error::EvalError(EvalError::NotAnInteger(..)))
+    );
+    assert_matches!(
+        eval("%str_split_n% \"a,b\" 1 2"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn join() {
+    assert_matches!(
+        eval("%str_join% \",\" [\"a\", 1]"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%str_join% \",\" [\"a\" ++ \"b\", 1 + 1]"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%str_join% \",\" \"a\""),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("strings.join \",\" [\"a\", 1]"),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+}
+
+#[test]
+fn replace_n() {
+    assert_matches!(
+        eval("%str_replace_n% \"aaa\" \"a\" \"b\" (-1)"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%str_replace_n% \"aaa\" \"a\" \"b\" 0.5"),
+        Err(Error::EvalError(EvalError::NotAnInteger(..)))
+    );
+    assert_matches!(
+        eval("%str_replace_n% \"aaa\" \"a\" \"b\" \"1\""),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%str_replace_n% \"aaa\" \"a\" 1 1"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn case_insensitive() {
+    assert_matches!(
+        eval("%str_eq_ci% \"a\" 1"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%str_contains_ci% 1 \"a\""),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn trim() {
+    assert_matches!(
+        eval("%str_trim_start% 1"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%str_trim_end% 1"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%str_trim_chars% \"a\" 1"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn match_all() {
+    assert_matches!(
+        eval("%str_match_all% \"a\" \"(\""),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%str_match_all% \"a\" 1"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
+
+#[test]
+fn invalid_regex_is_not_cached() {
+    for _ in 0..2 {
+        assert_matches!(
+            eval("strings.is_match \"(\" \"a\""),
+            Err(Error::EvalError(EvalError::Other(..)))
+        );
+    }
+}
@@ -2,6 +2,7 @@ use assert_matches::assert_matches;
 use codespan::Files;
 use nickel::cache::resolvers::DummyResolver;
 use nickel::error::TypecheckError;
+use nickel::identifier::Ident;
 use nickel::parser::{grammar, lexer};
 use nickel::term::RichTerm;
 use nickel::typecheck::{type_check_in_env, Environment};
@@ -35,6 +36,17 @@ fn unbound_variable_always_throws() {
     )
 }
 
+#[test]
+fn unbound_variable_suggestions() {
+    match type_check_expr("let value = 1 in let other = 2 in valeu + other") {
+        Err(TypecheckError::UnboundIdentifier(id, suggestions, _)) => {
+            assert_eq!(id, Ident::from("valeu"));
+            assert_eq!(suggestions, vec![Ident::from("value")]);
+        }
+        res => panic!("expected an unbound identifier error, got {:?}", res),
+    }
+}
+
 #[test]
 fn promise_simple_checks() {
     assert_typecheck_fails!("true : Num");
@@ -113,6 +125,10 @@ fn static_record_simple() {
 
     assert_typecheck_fails!("{blo = 1}.blo : Bool");
 
+    assert_typecheck_fails!("(%record_insert% blo {bla = 1} true).blo : Num");
+    assert_typecheck_fails!("(%record_insert% bla {bla = 1} 2) : {bla : Num}");
+    assert_typecheck_fails!("(%record_insert% blo {bla = 1} 2).ble : Num");
+
     assert_typecheck_fails!(
         "let f : forall a. (forall r. {bla : Bool, blo : a, ble : a | r} -> a) =
             fun r => if r.bla then r.blo else r.ble in
@@ -230,3 +246,48 @@ fn piecewise_signature() {
         Err(TypecheckError::TypeMismatch(..))
     );
 }
+
+#[test]
+fn is_null_returns_bool() {
+    assert_matches!(
+        type_check_expr("let n : Num = %is_null% null in n"),
+        Err(TypecheckError::TypeMismatch(..))
+    );
+}
+
+#[test]
+fn enum_tag_takes_an_enum() {
+    assert_matches!(
+        type_check_expr("let s : Str = %enum_tag% \"a\" in s"),
+        Err(TypecheckError::TypeMismatch(..))
+    );
+    assert_matches!(
+        type_check_expr("let n : Num = %enum_tag% `a in n"),
+        Err(TypecheckError::TypeMismatch(..))
+    );
+}
+
+#[test]
+fn match_field_handlers_are_functions() {
+    assert_matches!(
+        type_check_expr("let x : Dyn = %match_field% ({a = 1} | Dyn) {a = 2} in x"),
+        Err(TypecheckError::TypeMismatch(..))
+    );
+}
+
+#[test]
+fn to_str_returns_str() {
+    assert_matches!(
+        type_check_expr("let n : Num = %to_str% (5 | Dyn) in n"),
+        Err(TypecheckError::TypeMismatch(..))
+    );
+}
+
+#[test]
+fn infinite_types() {
+    // Self-application would require an infinite type, which used to make the reporting of the
+    // inferred types loop forever
+    assert_typecheck_fails!("(fun x => (x x : Num)) : Dyn -> Num");
+    assert_typecheck_fails!("(let f = fun x => let y = (x x : Num) in x in f) : Num");
+    assert_typecheck_fails!("(fun l => [l] == l) : Dyn");
+}
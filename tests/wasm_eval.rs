@@ -0,0 +1,41 @@
+#![cfg(feature = "wasm-eval")]
+use nickel::serialize::ExportFormat;
+use nickel::wasm_eval::eval_with_imports;
+use std::collections::HashMap;
+
+#[test]
+fn eval_with_one_import() {
+    let mut imports = HashMap::new();
+    imports.insert(
+        String::from("lib/greet.ncl"),
+        String::from(r#"{greet = fun name => "hello, " ++ name}"#),
+    );
+
+    let source = r#"
+        let lib = import "lib/greet.ncl" in
+        {message = lib.greet "world", count : Num = 1 + 1}
+    "#;
+
+    let json = eval_with_imports(source, imports.clone(), ExportFormat::Json).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({"message": "hello, world", "count": 2})
+    );
+
+    let err = eval_with_imports(
+        r#"import "missing.ncl""#,
+        imports.clone(),
+        ExportFormat::Json,
+    )
+    .unwrap_err();
+    assert!(err.contains("missing.ncl"));
+
+    let err = eval_with_imports(
+        r#"(import "lib/greet.ncl").greet 1"#,
+        imports,
+        ExportFormat::Json,
+    )
+    .unwrap_err();
+    assert!(err.contains("error"));
+}